@@ -14,6 +14,18 @@ fn build_optimizer() -> Optimizer {
         pattern_direction: PatternDirection::ParallelToWidth,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -21,6 +33,18 @@ fn build_optimizer() -> Optimizer {
         pattern_direction: PatternDirection::ParallelToLength,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -28,6 +52,18 @@ fn build_optimizer() -> Optimizer {
         pattern_direction: PatternDirection::ParallelToWidth,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -35,6 +71,18 @@ fn build_optimizer() -> Optimizer {
         pattern_direction: PatternDirection::ParallelToLength,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 20;
@@ -51,6 +99,16 @@ fn build_optimizer() -> Optimizer {
                 PatternDirection::ParallelToLength
             },
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         });
     }
 
@@ -0,0 +1,92 @@
+//! Log of the random choices the optimizer makes, for diagnosing reports of nondeterminism. The
+//! entries are only sent when the `audit-log` feature is enabled and a callback is set with
+//! `Optimizer::set_audit_log_callback`.
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// A random choice made by the optimizer, or a marker of where in the optimization the choices
+/// that follow it were made. Two runs with the same inputs and random seed produce the same
+/// entries in the same order, so comparing the logs of two runs shows where they diverged.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuditEntry {
+    /// The optimizer started a run of the genetic algorithm for a phase of the optimization.
+    RunStarted {
+        /// Index of the phase, starting at 0.
+        phase: usize,
+    },
+
+    /// A heuristic was chosen at random to place a cut piece.
+    HeuristicChosen {
+        /// Index of the cut piece in the order they were added, counting each copy.
+        cut_piece: usize,
+
+        /// Name of the heuristic, as used by `HeuristicProfile::Custom`.
+        heuristic: String,
+    },
+
+    /// A stock piece was chosen at random to place a cut piece that didn't fit in the stock
+    /// pieces already in use.
+    StockPieceChosen {
+        /// Index of the cut piece in the order they were added, counting each copy.
+        cut_piece: usize,
+
+        /// Width of the stock piece.
+        width: usize,
+
+        /// Length of the stock piece.
+        length: usize,
+
+        /// External ID of the stock piece.
+        external_id: Option<usize>,
+    },
+
+    /// A generation of the genetic algorithm was completed.
+    EpochCompleted {
+        /// Fitness of the best solution in the current run.
+        best: f64,
+    },
+}
+
+/// Writes the entry on a single line, in a compact form that's easy to compare between runs.
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditEntry::RunStarted { phase } => write!(f, "run {}", phase),
+            AuditEntry::HeuristicChosen {
+                cut_piece,
+                heuristic,
+            } => write!(f, "heuristic {} {}", cut_piece, heuristic),
+            AuditEntry::StockPieceChosen {
+                cut_piece,
+                width,
+                length,
+                external_id,
+            } => {
+                write!(f, "stock {} {}x{}", cut_piece, width, length)?;
+                if let Some(external_id) = external_id {
+                    write!(f, " #{}", external_id)?;
+                }
+                Ok(())
+            }
+            AuditEntry::EpochCompleted { best } => write!(f, "epoch {}", best),
+        }
+    }
+}
+
+pub(crate) type AuditLogCallback = Arc<dyn Fn(AuditEntry) + Send + Sync>;
+
+/// Sends the entry built by `entry` to the callback, if there is one. The entry is only built
+/// when there's a callback to send it to.
+pub(crate) fn log<F>(callback: &Option<AuditLogCallback>, entry: F)
+where
+    F: FnOnce() -> AuditEntry,
+{
+    if let Some(callback) = callback {
+        callback(entry());
+    }
+}
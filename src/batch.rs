@@ -0,0 +1,153 @@
+//! Threads that `Optimizer::optimize_batch` runs its optimizations on. They're started the first
+//! time they're needed and kept for later batches, so each batch doesn't start its own. There are
+//! never more of them than the available parallelism of the system.
+use std::any::Any;
+use std::cmp;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{mpsc, Arc, Mutex, OnceLock, PoisonError};
+use std::thread;
+
+use crate::{Error, Result};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Workers {
+    sender: Mutex<mpsc::Sender<Job>>,
+    receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+    // Number of workers that aren't running a job or about to run one that was sent to them.
+    idle: Arc<Mutex<usize>>,
+    // Number of workers that were started, which is at most `max_workers`.
+    started: Mutex<usize>,
+    max_workers: usize,
+}
+
+impl Workers {
+    fn get() -> &'static Workers {
+        static WORKERS: OnceLock<Workers> = OnceLock::new();
+        WORKERS.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel();
+            Workers {
+                sender: Mutex::new(sender),
+                receiver: Arc::new(Mutex::new(receiver)),
+                idle: Arc::new(Mutex::new(0)),
+                started: Mutex::new(0),
+                max_workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            }
+        })
+    }
+
+    /// Sets aside up to `count` idle workers for jobs that are about to be sent, starting more
+    /// workers if there aren't enough and there's room for them, and returns how many were set
+    /// aside.
+    fn reserve(&self, count: usize) -> usize {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut started = self.started.lock().unwrap_or_else(PoisonError::into_inner);
+        while *idle < count && *started < self.max_workers {
+            let receiver = self.receiver.clone();
+            let idle_workers = self.idle.clone();
+            thread::spawn(move || loop {
+                let job = receiver
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv();
+                let Ok(job) = job else {
+                    break;
+                };
+                job();
+                *idle_workers.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+            });
+            *idle += 1;
+            *started += 1;
+        }
+        let reserved = cmp::min(*idle, count);
+        *idle -= reserved;
+        reserved
+    }
+
+    fn send(&self, job: Job) {
+        // The workers keep the receiver, so sending can't fail.
+        let _ = self
+            .sender
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(job);
+    }
+}
+
+/// Runs `job` with each index below `len`, on up to `parallelism` threads at a time, and returns
+/// the results in the order of their indices. A job that panics gives an `Error::Internal`
+/// instead.
+///
+/// The calling thread runs jobs too, along with the workers that are idle, so a job that runs a
+/// batch of its own doesn't wait for workers that are busy.
+pub(crate) fn run<T, F>(len: usize, parallelism: usize, job: F) -> Vec<Result<T>>
+where
+    T: Send + 'static,
+    F: Fn(usize) -> Result<T> + Send + Sync + 'static,
+{
+    let workers = Workers::get();
+    let num_workers = workers.reserve(cmp::min(parallelism, len).saturating_sub(1));
+
+    let job = Arc::new(job);
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..num_workers {
+        let (job, next, sender) = (job.clone(), next.clone(), sender.clone());
+        workers.send(Box::new(move || run_jobs(len, &next, &*job, &sender)));
+    }
+    run_jobs(len, &next, &*job, &sender);
+    drop(sender);
+
+    let mut results: Vec<Option<Result<T>>> = (0..len).map(|_| None).collect();
+    for (i, result) in receiver {
+        results[i] = Some(result);
+    }
+    results
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| {
+                Err(Error::Internal(
+                    "batch optimization didn't finish".to_string(),
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Runs `job` with the next index that no thread has taken until there are none left, and sends
+/// the results.
+fn run_jobs<T, F>(
+    len: usize,
+    next: &AtomicUsize,
+    job: &F,
+    sender: &mpsc::Sender<(usize, Result<T>)>,
+) where
+    F: Fn(usize) -> Result<T>,
+{
+    loop {
+        let i = next.fetch_add(1, atomic::Ordering::Relaxed);
+        if i >= len {
+            break;
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| job(i))).unwrap_or_else(|payload| {
+            Err(Error::Internal(format!(
+                "batch optimization panicked: {}",
+                panic_message(payload.as_ref())
+            )))
+        });
+        if sender.send((i, result)).is_err() {
+            break;
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
@@ -0,0 +1,1290 @@
+//! Reconstruction of guillotine cuts from the placement of cut pieces on a stock piece.
+use super::*;
+
+/// Returns the indices of `rects` in the order they come off the stock piece when it's cut up
+/// with guillotine cuts, starting with cuts parallel to the length. Rects that can't be
+/// separated with guillotine cuts are ordered by y location, then by x location.
+pub(crate) fn cutting_order(rects: &[Rect]) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..rects.len()).collect();
+    let mut order = Vec::with_capacity(rects.len());
+    order_group(rects, &mut indices, true, &mut order);
+    order
+}
+
+fn order_group(rects: &[Rect], indices: &mut [usize], along_length: bool, order: &mut Vec<usize>) {
+    if indices.len() <= 1 {
+        order.extend_from_slice(indices);
+        return;
+    }
+
+    for along_length in [along_length, !along_length] {
+        let groups = split_groups(rects, indices, along_length);
+        if groups.len() > 1 {
+            let mut start = 0;
+            for end in groups {
+                order_group(rects, &mut indices[start..end], !along_length, order);
+                start = end;
+            }
+            return;
+        }
+    }
+
+    // There's no guillotine cut that separates these rects.
+    indices.sort_by_key(|&i| (rects[i].y, rects[i].x));
+    order.extend_from_slice(indices);
+}
+
+/// Returns the number of cuts needed to cut `rects` out of a stock piece of `width` and `length`
+/// with guillotine cuts, starting with cuts parallel to the length. A single cut separates rects
+/// that are at most `blade_width` apart. Rects that can't be separated with guillotine cuts are
+/// counted as needing a cut on each of their sides.
+pub(crate) fn count_cuts(
+    rects: &[Rect],
+    width: usize,
+    length: usize,
+    blade_width: BladeWidth,
+) -> usize {
+    let mut indices: Vec<usize> = (0..rects.len()).collect();
+    let region = Rect {
+        x: 0,
+        y: 0,
+        width,
+        length,
+    };
+    let mut cuts = 0;
+    group_cuts(rects, &mut indices, region, true, blade_width, &mut |_| {
+        cuts += 1
+    });
+    cuts
+}
+
+/// Returns the total length of the guillotine cuts needed to cut `rects` out of a stock piece of
+/// `width` and `length`, including the cuts along its `seams` and the cuts that `trim` it. The
+/// cuts are the ones counted by `count_cuts`, within each region between the seams, and each one
+/// runs across the whole region it splits.
+pub(crate) fn guillotine_cut_length(
+    rects: &[Rect],
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+) -> u64 {
+    let mut cut_length = seams_length(width, length, seams, trim);
+    for region in free_rects_between_seams(width, length, seams, trim, blade_width) {
+        let mut indices: Vec<usize> = (0..rects.len())
+            .filter(|&i| region.contains(&rects[i]))
+            .collect();
+        group_cuts(rects, &mut indices, region, true, blade_width, &mut |cut| {
+            cut_length += cut as u64
+        });
+    }
+    cut_length
+}
+
+/// Returns the number of stages of guillotine cuts needed to cut `rects` out of a stock piece of
+/// `width` and `length`. The cuts of a stage are all in the same direction, and split up the
+/// pieces that were cut off by the stage before it, so cutting strips and then cutting the strips
+/// into cut pieces is two stages. Cutting the waste off the end of a cut piece counts as a stage.
+/// The cuts along the `seams` and the cuts that `trim` the stock piece don't count. Rects that
+/// can't be separated with guillotine cuts need `usize::MAX` stages.
+pub(crate) fn guillotine_stages(
+    rects: &[Rect],
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+) -> usize {
+    free_rects_between_seams(width, length, seams, trim, blade_width)
+        .into_iter()
+        .map(|region| {
+            let mut indices: Vec<usize> = (0..rects.len())
+                .filter(|&i| region.contains(&rects[i]))
+                .collect();
+            // The first stage can cut in either direction.
+            cmp::min(
+                group_stages(rects, &mut indices, region, true),
+                group_stages(rects, &mut indices, region, false),
+            )
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn group_stages(rects: &[Rect], indices: &mut [usize], region: Rect, along_length: bool) -> usize {
+    match indices {
+        [] => return 0,
+        [i] if rects[*i] == region => return 0,
+        _ => (),
+    }
+
+    for along_length in [along_length, !along_length] {
+        let group_spans = group_spans(rects, indices, along_length);
+        let (region_start, region_end) = span(&region, along_length);
+
+        if let [(_, _, group_start, group_end)] = group_spans[..] {
+            if group_start == region_start && group_end == region_end {
+                // There's no cut along this axis, so try the other one.
+                continue;
+            }
+        }
+
+        let stages = group_spans
+            .into_iter()
+            .map(|(start, end, group_start, group_end)| {
+                group_stages(
+                    rects,
+                    &mut indices[start..end],
+                    sub_region(&region, group_start, group_end, along_length),
+                    !along_length,
+                )
+            })
+            .max()
+            .unwrap_or(0);
+        return stages.saturating_add(1);
+    }
+
+    // There's no guillotine cut that separates these rects.
+    usize::MAX
+}
+
+/// Returns the total length of the cuts needed to cut `rects` out of a stock piece of `width` and
+/// `length` by cutting around each of them, including the cuts along its `seams` and the cuts
+/// that `trim` it. The sides of the rects that lie on an edge of the trimmed stock piece, or of a
+/// region between the seams, don't need to be cut. Where two rects are a cut apart, the one cut
+/// between them is only counted once.
+pub(crate) fn nested_cut_length(
+    rects: &[Rect],
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+) -> u64 {
+    let mut cut_length = seams_length(width, length, seams, trim);
+    for region in free_rects_between_seams(width, length, seams, trim, blade_width) {
+        let rects: Vec<Rect> = rects
+            .iter()
+            .filter(|rect| region.contains(rect))
+            .copied()
+            .collect();
+        for rect in &rects {
+            if rect.x > region.x {
+                cut_length += rect.length as u64;
+            }
+            if rect.x + rect.width < region.x + region.width {
+                cut_length += rect.length as u64;
+            }
+            if rect.y > region.y {
+                cut_length += rect.width as u64;
+            }
+            if rect.y + rect.length < region.y + region.length {
+                cut_length += rect.width as u64;
+            }
+        }
+        cut_length -= shared_cut_length(&rects, blade_width);
+    }
+    cut_length
+}
+
+/// Returns the length of the sides of `rects` that face the side of another one across a cut of
+/// `blade_width`, so a single cut separates them.
+fn shared_cut_length(rects: &[Rect], blade_width: BladeWidth) -> u64 {
+    let overlap = |start1: usize, end1: usize, start2: usize, end2: usize| {
+        cmp::min(end1, end2).saturating_sub(cmp::max(start1, start2)) as u64
+    };
+
+    let mut shared = 0;
+    for a in rects {
+        for b in rects {
+            if a.x + a.width + blade_width.rip == b.x {
+                shared += overlap(a.y, a.y + a.length, b.y, b.y + b.length);
+            }
+            if a.y + a.length + blade_width.crosscut == b.y {
+                shared += overlap(a.x, a.x + a.width, b.x, b.x + b.width);
+            }
+        }
+    }
+    shared
+}
+
+/// Returns the total length of the seams that cross a stock piece of `width` and `length`, and of
+/// the cuts that `trim` it.
+fn seams_length(width: usize, length: usize, seams: &[Seam], trim: Trim) -> u64 {
+    let trim_length = [
+        (trim.left, width, length),
+        (trim.right, width, length),
+        (trim.bottom, length, width),
+        (trim.top, length, width),
+    ]
+    .into_iter()
+    .map(|(margin, size, cut)| {
+        if margin > 0 && margin < size {
+            cut as u64
+        } else {
+            0
+        }
+    })
+    .sum::<u64>();
+
+    trim_length
+        + seams
+            .iter()
+            .map(|seam| match *seam {
+                Seam::ParallelToLength(x) if x > 0 && x < width => length as u64,
+                Seam::ParallelToWidth(y) if y > 0 && y < length => width as u64,
+                _ => 0,
+            })
+            .sum::<u64>()
+}
+
+/// Calls `on_cut` with the length of each guillotine cut needed to cut the rects at `indices` out
+/// of `region`.
+fn group_cuts(
+    rects: &[Rect],
+    indices: &mut [usize],
+    region: Rect,
+    along_length: bool,
+    blade_width: BladeWidth,
+    on_cut: &mut dyn FnMut(usize),
+) {
+    match indices {
+        [] => return,
+        [i] if rects[*i] == region => return,
+        _ => (),
+    }
+
+    for along_length in [along_length, !along_length] {
+        let group_spans = group_spans(rects, indices, along_length);
+        let (region_start, region_end) = span(&region, along_length);
+
+        if let [(_, _, group_start, group_end)] = group_spans[..] {
+            if group_start == region_start && group_end == region_end {
+                // There's no cut along this axis, so try the other one.
+                continue;
+            }
+        }
+
+        // Each cut runs across the whole region.
+        let cut = if along_length {
+            region.length
+        } else {
+            region.width
+        };
+        let mut prev_end = None;
+        for (start, end, group_start, group_end) in group_spans {
+            let cuts = match prev_end {
+                None if group_start > region_start => 1,
+                None => 0,
+                Some(prev_end) if group_start - prev_end <= blade_width.along(along_length) => 1,
+                Some(_) => 2,
+            };
+            for _ in 0..cuts {
+                on_cut(cut);
+            }
+
+            group_cuts(
+                rects,
+                &mut indices[start..end],
+                sub_region(&region, group_start, group_end, along_length),
+                !along_length,
+                blade_width,
+                on_cut,
+            );
+            prev_end = Some(group_end);
+        }
+        if prev_end.is_some_and(|prev_end| prev_end < region_end) {
+            on_cut(cut);
+        }
+
+        return;
+    }
+
+    // There's no guillotine cut that separates these rects, so cut each of their sides.
+    for &i in indices.iter() {
+        let rect = &rects[i];
+        for cut in [rect.length, rect.length, rect.width, rect.width] {
+            on_cut(cut);
+        }
+    }
+}
+
+/// Returns the free space around `rects` on a stock piece of `width` and `length`, split into
+/// disjoint rectangles along the guillotine cuts that separate the rects, starting with cuts
+/// parallel to the length. The stock piece is first trimmed by `trim` and split along its
+/// `seams`. Each cut is as wide as the `blade_width` in its direction. The free space of a region
+/// whose rects can't be separated with guillotine cuts is split into strips parallel to the
+/// width.
+pub(crate) fn guillotine_waste(
+    rects: &[Rect],
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+) -> Vec<Rect> {
+    let mut waste = Vec::new();
+    for region in free_rects_between_seams(width, length, seams, trim, blade_width) {
+        let mut indices: Vec<usize> = (0..rects.len())
+            .filter(|&i| region.contains(&rects[i]))
+            .collect();
+        group_waste(rects, &mut indices, region, true, blade_width, &mut waste);
+    }
+    waste
+}
+
+fn group_waste(
+    rects: &[Rect],
+    indices: &mut [usize],
+    region: Rect,
+    along_length: bool,
+    blade_width: BladeWidth,
+    waste: &mut Vec<Rect>,
+) {
+    match indices {
+        [] => {
+            if region.width > 0 && region.length > 0 {
+                waste.push(region);
+            }
+            return;
+        }
+        [i] if rects[*i] == region => return,
+        _ => (),
+    }
+
+    for along_length in [along_length, !along_length] {
+        let group_spans = group_spans(rects, indices, along_length);
+        let (region_start, region_end) = span(&region, along_length);
+
+        if let [(_, _, group_start, group_end)] = group_spans[..] {
+            if group_start == region_start && group_end == region_end {
+                // There's no cut along this axis, so try the other one.
+                continue;
+            }
+        }
+
+        let cut_width = blade_width.along(along_length);
+        let mut prev_end: Option<usize> = None;
+        for (start, end, group_start, group_end) in group_spans {
+            let waste_start =
+                prev_end.map_or(region_start, |prev_end| prev_end.saturating_add(cut_width));
+            push_strip(
+                waste,
+                &region,
+                waste_start,
+                group_start.saturating_sub(cut_width),
+                along_length,
+            );
+
+            group_waste(
+                rects,
+                &mut indices[start..end],
+                sub_region(&region, group_start, group_end, along_length),
+                !along_length,
+                blade_width,
+                waste,
+            );
+            prev_end = Some(group_end);
+        }
+        if let Some(prev_end) = prev_end {
+            push_strip(
+                waste,
+                &region,
+                prev_end.saturating_add(cut_width),
+                region_end,
+                along_length,
+            );
+        }
+
+        return;
+    }
+
+    // There's no guillotine cut that separates these rects.
+    strip_waste(rects, indices, region, blade_width, waste);
+}
+
+/// Splits the free space of `region` around `rects` into strips parallel to the width, leaving
+/// room for cuts of `blade_width` around each rect. Strips of the same width and x location that
+/// are next to each other are merged.
+fn strip_waste(
+    rects: &[Rect],
+    indices: &[usize],
+    region: Rect,
+    blade_width: BladeWidth,
+    waste: &mut Vec<Rect>,
+) {
+    let region_x_end = region.x + region.width;
+    let region_y_end = region.y + region.length;
+    // Each rect with the cuts around it, clipped to the region.
+    let occupied: Vec<Rect> = indices
+        .iter()
+        .map(|&i| {
+            let rect = &rects[i];
+            let x = cmp::max(rect.x.saturating_sub(blade_width.rip), region.x);
+            let y = cmp::max(rect.y.saturating_sub(blade_width.crosscut), region.y);
+            let x_end = cmp::min(
+                (rect.x + rect.width).saturating_add(blade_width.rip),
+                region_x_end,
+            );
+            let y_end = cmp::min(
+                (rect.y + rect.length).saturating_add(blade_width.crosscut),
+                region_y_end,
+            );
+            Rect {
+                x,
+                y,
+                width: x_end - x,
+                length: y_end - y,
+            }
+        })
+        .collect();
+
+    let mut ys: Vec<usize> = occupied
+        .iter()
+        .flat_map(|rect| [rect.y, rect.y + rect.length])
+        .chain([region.y, region_y_end])
+        .collect();
+    ys.sort_unstable();
+    ys.dedup();
+
+    // Strips that can still be extended by the next row.
+    let mut open: Vec<Rect> = Vec::new();
+    for row in ys.windows(2) {
+        let (y, y_end) = (row[0], row[1]);
+        let mut spans: Vec<(usize, usize)> = occupied
+            .iter()
+            .filter(|rect| rect.y < y_end && rect.y + rect.length > y)
+            .map(|rect| (rect.x, rect.x + rect.width))
+            .collect();
+        spans.sort_unstable();
+
+        let mut row_strips = Vec::new();
+        let mut x = region.x;
+        for (start, end) in spans.into_iter().chain([(region_x_end, region_x_end)]) {
+            if start > x {
+                row_strips.push((x, start));
+            }
+            x = cmp::max(x, end);
+        }
+
+        let mut next_open = Vec::with_capacity(row_strips.len());
+        for (x, x_end) in row_strips {
+            let width = x_end - x;
+            match open
+                .iter()
+                .position(|strip| strip.x == x && strip.width == width)
+            {
+                Some(i) => {
+                    let mut strip = open.swap_remove(i);
+                    strip.length += y_end - y;
+                    next_open.push(strip);
+                }
+                None => next_open.push(Rect {
+                    x,
+                    y,
+                    width,
+                    length: y_end - y,
+                }),
+            }
+        }
+        waste.append(&mut open);
+        open = next_open;
+    }
+    waste.append(&mut open);
+}
+
+/// Pushes the part of `region` between `start` and `end` along the axis perpendicular to the
+/// cuts onto `waste`, if it isn't empty.
+fn push_strip(waste: &mut Vec<Rect>, region: &Rect, start: usize, end: usize, along_length: bool) {
+    if start < end {
+        waste.push(sub_region(region, start, end, along_length));
+    }
+}
+
+/// Returns the tree of guillotine cuts that cut the cut pieces and fixed pieces out of
+/// `stock_piece`, or `None` if they can't be separated with guillotine cuts. The stock piece is
+/// first trimmed and cut along its seams, and then each region between the seams is cut up
+/// starting with cuts parallel to the length. Each cut is as wide as the `blade_width` in its
+/// direction.
+pub(crate) fn cut_tree(stock_piece: &ResultStockPiece, blade_width: BladeWidth) -> Option<CutNode> {
+    let rects: Vec<Rect> = stock_piece
+        .cut_pieces
+        .iter()
+        .map(Into::into)
+        .chain(stock_piece.fixed_pieces.iter().map(Into::into))
+        .collect();
+    let regions = free_rects_between_seams(
+        stock_piece.width,
+        stock_piece.length,
+        &stock_piece.seams,
+        stock_piece.trim,
+        blade_width,
+    );
+    let mut columns: Vec<(usize, usize)> = regions
+        .iter()
+        .map(|region| (region.x, region.x + region.width))
+        .collect();
+    columns.sort_unstable();
+    columns.dedup();
+    let mut rows: Vec<(usize, usize)> = regions
+        .iter()
+        .map(|region| (region.y, region.y + region.length))
+        .collect();
+    rows.sort_unstable();
+    rows.dedup();
+
+    let column_nodes = columns
+        .iter()
+        .map(|&(x, x_end)| {
+            let row_nodes = rows
+                .iter()
+                .map(|&(y, y_end)| {
+                    let region = Rect {
+                        x,
+                        y,
+                        width: x_end - x,
+                        length: y_end - y,
+                    };
+                    let mut indices: Vec<usize> = (0..rects.len())
+                        .filter(|&i| region.contains(&rects[i]))
+                        .collect();
+                    let node = group_cut_tree(
+                        &rects,
+                        &mut indices,
+                        region,
+                        true,
+                        blade_width,
+                        stock_piece,
+                    )?;
+                    Some((y_end, node))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let node = chain_cuts(row_nodes, CutDirection::ParallelToWidth)?;
+            Some((x_end, node))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    chain_cuts(column_nodes, CutDirection::ParallelToLength)
+}
+
+/// Returns the tree of guillotine cuts that cut the `rects` at `indices` out of `region` of
+/// `stock_piece`. The rects are its cut pieces, followed by its fixed pieces.
+fn group_cut_tree(
+    rects: &[Rect],
+    indices: &mut [usize],
+    region: Rect,
+    along_length: bool,
+    blade_width: BladeWidth,
+    stock_piece: &ResultStockPiece,
+) -> Option<CutNode> {
+    let waste = |rect: Rect| {
+        CutNode::Waste(WastePiece {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            length: rect.length,
+            pattern_direction: stock_piece.pattern_direction,
+        })
+    };
+    let num_cut_pieces = stock_piece.cut_pieces.len();
+    match indices {
+        [] => return Some(waste(region)),
+        [i] if rects[*i] == region => {
+            return Some(if *i < num_cut_pieces {
+                CutNode::CutPiece(*i)
+            } else {
+                CutNode::FixedPiece(*i - num_cut_pieces)
+            })
+        }
+        _ => (),
+    }
+
+    for along_length in [along_length, !along_length] {
+        let group_spans = group_spans(rects, indices, along_length);
+        let (region_start, region_end) = span(&region, along_length);
+
+        if let [(_, _, group_start, group_end)] = group_spans[..] {
+            if group_start == region_start && group_end == region_end {
+                // There's no cut along this axis, so try the other one.
+                continue;
+            }
+        }
+
+        let (direction, kerf) = if along_length {
+            (CutDirection::ParallelToLength, blade_width.rip)
+        } else {
+            (CutDirection::ParallelToWidth, blade_width.crosscut)
+        };
+
+        // The parts of the region between the cuts, with the end of each part, where the cut
+        // after it starts.
+        let mut parts = Vec::with_capacity(group_spans.len() * 2 + 1);
+        let push_waste = |parts: &mut Vec<(usize, CutNode)>, start: usize, end: usize| {
+            if start < end {
+                parts.push((end, waste(sub_region(&region, start, end, along_length))));
+            }
+        };
+
+        let mut prev_end: Option<usize> = None;
+        for (start, end, group_start, group_end) in group_spans {
+            let waste_start =
+                prev_end.map_or(region_start, |prev_end| prev_end.saturating_add(kerf));
+            push_waste(&mut parts, waste_start, group_start.saturating_sub(kerf));
+
+            let node = group_cut_tree(
+                rects,
+                &mut indices[start..end],
+                sub_region(&region, group_start, group_end, along_length),
+                !along_length,
+                blade_width,
+                stock_piece,
+            )?;
+            parts.push((group_end, node));
+            prev_end = Some(group_end);
+        }
+        if let Some(prev_end) = prev_end {
+            push_waste(&mut parts, prev_end.saturating_add(kerf), region_end);
+        }
+
+        return chain_cuts(parts, direction);
+    }
+
+    // There's no guillotine cut that separates these rects.
+    None
+}
+
+/// Joins the parts of a region, in order, with a cut in `direction` after each of them but the
+/// last. Each part has the position where the cut after it starts.
+fn chain_cuts(parts: Vec<(usize, CutNode)>, direction: CutDirection) -> Option<CutNode> {
+    let mut parts = parts.into_iter().rev();
+    let (_, mut node) = parts.next()?;
+    for (position, first) in parts {
+        node = CutNode::Cut {
+            direction,
+            position,
+            first: Box::new(first),
+            second: Box::new(node),
+        };
+    }
+    Some(node)
+}
+
+/// Slides `rects` toward the origin of a stock piece of `width` and `length`, down and then to
+/// the left, for as long as they move, leaving room for cuts of `blade_width` between them and
+/// the `obstacles`. Each rect stays within the `trim`, and in the region between the `seams` it's
+/// in. A move is only kept if it doesn't take the rect farther from the nearest edge of the stock
+/// piece, and doesn't add guillotine stages, so a layout that could be cut with guillotine cuts
+/// still can be. Returns whether any of the rects moved.
+pub(crate) fn compact(
+    rects: &mut [Rect],
+    obstacles: &[Rect],
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+) -> bool {
+    let regions = free_rects_between_seams(width, length, seams, trim, blade_width);
+    let rect_regions: Vec<Option<Rect>> = rects
+        .iter()
+        .map(|rect| regions.iter().find(|region| region.contains(rect)).copied())
+        .collect();
+    let stages = |rects: &[Rect]| {
+        guillotine_stages(
+            &[rects, obstacles].concat(),
+            width,
+            length,
+            seams,
+            trim,
+            blade_width,
+        )
+    };
+    let edge_distance = |rect: &Rect| {
+        cmp::min(
+            cmp::min(rect.x, width.saturating_sub(rect.x + rect.width)),
+            cmp::min(rect.y, length.saturating_sub(rect.y + rect.length)),
+        )
+    };
+    let max_stages = stages(rects);
+
+    let mut compacted = false;
+    loop {
+        let mut order: Vec<usize> = (0..rects.len()).collect();
+        order.sort_by_key(|&i| (rects[i].y, rects[i].x));
+
+        let mut moved = false;
+        for i in order {
+            let Some(region) = rect_regions[i] else {
+                continue;
+            };
+            for along_length in [true, false] {
+                let rect = rects[i];
+                let moved_rect = slide(rects, obstacles, i, region, along_length, blade_width);
+                if moved_rect == rect || edge_distance(&moved_rect) > edge_distance(&rect) {
+                    continue;
+                }
+                rects[i] = moved_rect;
+                if max_stages != usize::MAX && stages(rects) > max_stages {
+                    rects[i] = rect;
+                    continue;
+                }
+                moved = true;
+            }
+        }
+
+        if !moved {
+            return compacted;
+        }
+        compacted = true;
+    }
+}
+
+/// Returns rect `i` of `rects` slid as far down, if `along_length`, or as far to the left as it
+/// goes within `region` without coming closer than `blade_width` to the other rects and the
+/// `obstacles`.
+fn slide(
+    rects: &[Rect],
+    obstacles: &[Rect],
+    i: usize,
+    region: Rect,
+    along_length: bool,
+    blade_width: BladeWidth,
+) -> Rect {
+    let rect = rects[i];
+    let others = rects
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .map(|(_, other)| other)
+        .chain(obstacles);
+
+    let mut moved_rect = rect;
+    if along_length {
+        let mut y = region.y;
+        for other in others {
+            if other.x < rect.x + rect.width + blade_width.rip
+                && rect.x < other.x + other.width + blade_width.rip
+                && other.y < rect.y
+            {
+                y = cmp::max(y, other.y + other.length + blade_width.crosscut);
+            }
+        }
+        moved_rect.y = cmp::min(y, rect.y);
+    } else {
+        let mut x = region.x;
+        for other in others {
+            if other.y < rect.y + rect.length + blade_width.crosscut
+                && rect.y < other.y + other.length + blade_width.crosscut
+                && other.x < rect.x
+            {
+                x = cmp::max(x, other.x + other.width + blade_width.rip);
+            }
+        }
+        moved_rect.x = cmp::min(x, rect.x);
+    }
+    moved_rect
+}
+
+/// Returns the part of `region` between `start` and `end` along the axis perpendicular to the
+/// cuts.
+pub(crate) fn sub_region(region: &Rect, start: usize, end: usize, along_length: bool) -> Rect {
+    if along_length {
+        Rect {
+            x: start,
+            width: end - start,
+            ..*region
+        }
+    } else {
+        Rect {
+            y: start,
+            length: end - start,
+            ..*region
+        }
+    }
+}
+
+/// Returns the start and end of the rect along the axis perpendicular to the cuts.
+pub(crate) fn span(rect: &Rect, along_length: bool) -> (usize, usize) {
+    if along_length {
+        (rect.x, rect.x + rect.width)
+    } else {
+        (rect.y, rect.y + rect.length)
+    }
+}
+
+/// Sorts `indices` like `split_groups`, and returns the start and end index of each group in
+/// `indices`, with the start and end of the group along the axis perpendicular to the cuts.
+pub(crate) fn group_spans(
+    rects: &[Rect],
+    indices: &mut [usize],
+    along_length: bool,
+) -> Vec<(usize, usize, usize, usize)> {
+    let groups = split_groups(rects, indices, along_length);
+
+    let mut group_spans = Vec::with_capacity(groups.len());
+    let mut start = 0;
+    for end in groups {
+        let group_start = span(&rects[indices[start]], along_length).0;
+        let group_end = indices[start..end]
+            .iter()
+            .map(|&i| span(&rects[i], along_length).1)
+            .max()
+            .unwrap_or(group_start);
+        group_spans.push((start, end, group_start, group_end));
+        start = end;
+    }
+    group_spans
+}
+
+/// Sorts `indices` by position along the axis perpendicular to the cuts, and returns the end
+/// index of each group of rects that can be separated from the others by a single cut that
+/// spans the whole group.
+fn split_groups(rects: &[Rect], indices: &mut [usize], along_length: bool) -> Vec<usize> {
+    indices.sort_by_key(|&i| span(&rects[i], along_length));
+
+    let mut groups = Vec::new();
+    let mut group_end = 0;
+    for (n, &i) in indices.iter().enumerate() {
+        let (start, end) = span(&rects[i], along_length);
+        if n > 0 && start >= group_end {
+            groups.push(n);
+        }
+        group_end = cmp::max(group_end, end);
+    }
+    groups.push(indices.len());
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: usize, y: usize, width: usize, length: usize) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            length,
+        }
+    }
+
+    fn waste(x: usize, y: usize, width: usize, length: usize) -> CutNode {
+        CutNode::Waste(WastePiece {
+            x,
+            y,
+            width,
+            length,
+            pattern_direction: PatternDirection::None,
+        })
+    }
+
+    /// Returns a stock piece of `width` and `length` with cut pieces at `cut_pieces` and fixed
+    /// pieces at `fixed_pieces`.
+    fn stock_piece(
+        width: usize,
+        length: usize,
+        cut_pieces: &[Rect],
+        fixed_pieces: &[Rect],
+    ) -> ResultStockPiece {
+        let cut_pieces = cut_pieces
+            .iter()
+            .map(|rect| ResultCutPiece {
+                external_id: None,
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                length: rect.length,
+                pattern_direction: PatternDirection::None,
+                is_rotated: false,
+                rotated_to_match_pattern: false,
+                tag: None,
+                edge_banding: Default::default(),
+                pattern_direction_effective: PatternDirection::None,
+                pattern_mismatch: false,
+            })
+            .collect();
+        let mut stock_piece = ResultStockPiece::new(
+            None,
+            width,
+            length,
+            PatternDirection::None,
+            cut_pieces,
+            Vec::new(),
+            0,
+        );
+        stock_piece.fixed_pieces = fixed_pieces
+            .iter()
+            .map(|rect| FixedPiece {
+                external_id: None,
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                length: rect.length,
+            })
+            .collect();
+        stock_piece
+    }
+
+    #[test]
+    fn cutting_order_strips() {
+        // Two strips along the length. The left strip has two pieces stacked on top of each
+        // other, so they come off the left strip before the right strip is cut.
+        let rects = [rect(11, 0, 10, 30), rect(0, 11, 10, 10), rect(0, 0, 10, 10)];
+
+        assert_eq!(cutting_order(&rects), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn cutting_order_non_guillotine() {
+        // A pinwheel layout can't be separated with guillotine cuts.
+        let rects = [
+            rect(0, 0, 20, 10),
+            rect(20, 0, 10, 20),
+            rect(10, 20, 20, 10),
+            rect(0, 10, 10, 20),
+            rect(10, 10, 10, 10),
+        ];
+
+        assert_eq!(cutting_order(&rects), vec![0, 1, 3, 4, 2]);
+    }
+
+    #[test]
+    fn count_cuts_strips() {
+        // One cut between the strips, and one to separate the two pieces in the left strip.
+        let rects = [rect(11, 0, 10, 30), rect(0, 11, 10, 19), rect(0, 0, 10, 10)];
+        assert_eq!(count_cuts(&rects, 21, 30, BladeWidth::uniform(1)), 2);
+
+        // Another cut is needed to trim the waste from the right strip.
+        assert_eq!(count_cuts(&rects, 25, 30, BladeWidth::uniform(1)), 3);
+
+        // Two cuts are needed between strips that have waste between them.
+        let rects = [rect(15, 0, 10, 30), rect(0, 0, 10, 30)];
+        assert_eq!(count_cuts(&rects, 25, 30, BladeWidth::uniform(1)), 2);
+    }
+
+    #[test]
+    fn guillotine_cut_length_strips() {
+        // One cut across the whole length between the strips, and one across the width of the
+        // left strip.
+        let rects = [rect(11, 0, 10, 30), rect(0, 11, 10, 19), rect(0, 0, 10, 10)];
+        assert_eq!(
+            guillotine_cut_length(&rects, 21, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            40
+        );
+
+        // Another cut across the whole length trims the waste from the right strip.
+        assert_eq!(
+            guillotine_cut_length(&rects, 25, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            70
+        );
+
+        // The seam between the strips is the cut between them.
+        let seams = [Seam::ParallelToLength(10)];
+        assert_eq!(
+            guillotine_cut_length(
+                &rects,
+                21,
+                30,
+                &seams,
+                Trim::default(),
+                BladeWidth::uniform(1)
+            ),
+            40
+        );
+
+        // So is trimming the right edge.
+        let trim = Trim {
+            right: 4,
+            ..Trim::default()
+        };
+        assert_eq!(
+            guillotine_cut_length(&rects, 25, 30, &[], trim, BladeWidth::uniform(1)),
+            70
+        );
+    }
+
+    #[test]
+    fn guillotine_stages_strips() {
+        // Strips along the length, then cut pieces cut off the left strip.
+        let rects = [rect(11, 0, 10, 30), rect(0, 11, 10, 19), rect(0, 0, 10, 10)];
+        assert_eq!(
+            guillotine_stages(&rects, 21, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            2
+        );
+
+        // Cutting the waste off the end of the right strip happens along with the cuts in the
+        // left strip, but cutting the waste off the side of a cut piece is a third stage.
+        let rects = [rect(11, 0, 10, 25), rect(0, 11, 10, 19), rect(0, 0, 10, 10)];
+        assert_eq!(
+            guillotine_stages(&rects, 21, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            2
+        );
+        let rects = [rect(11, 0, 10, 30), rect(0, 11, 10, 19), rect(0, 0, 8, 10)];
+        assert_eq!(
+            guillotine_stages(&rects, 21, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            3
+        );
+
+        // The cuts that trim the stock piece aren't a stage.
+        let rects = [rect(0, 0, 21, 25)];
+        assert_eq!(
+            guillotine_stages(&rects, 21, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            1
+        );
+        let trim = Trim {
+            top: 5,
+            ..Trim::default()
+        };
+        assert_eq!(
+            guillotine_stages(&rects, 21, 30, &[], trim, BladeWidth::uniform(1)),
+            0
+        );
+
+        // A pinwheel layout can't be separated with guillotine cuts.
+        let rects = [
+            rect(0, 0, 30, 10),
+            rect(30, 0, 10, 30),
+            rect(10, 30, 30, 10),
+            rect(0, 10, 10, 30),
+        ];
+        assert_eq!(
+            guillotine_stages(&rects, 40, 40, &[], Trim::default(), BladeWidth::uniform(0)),
+            usize::MAX
+        );
+    }
+
+    #[test]
+    fn nested_cut_length_strips() {
+        // Each side of a piece that isn't on an edge of the stock piece is cut, once for the
+        // sides of pieces that are a cut apart.
+        let rects = [rect(11, 0, 10, 30), rect(0, 11, 10, 19), rect(0, 0, 10, 10)];
+        assert_eq!(
+            nested_cut_length(&rects, 21, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            30 + 10
+        );
+
+        // Sides that are further apart than a cut are cut separately.
+        assert_eq!(
+            nested_cut_length(&rects, 21, 30, &[], Trim::default(), BladeWidth::uniform(0)),
+            79
+        );
+
+        // The sides next to the seam are cut by the seam.
+        let seams = [Seam::ParallelToLength(10)];
+        assert_eq!(
+            nested_cut_length(
+                &rects,
+                21,
+                30,
+                &seams,
+                Trim::default(),
+                BladeWidth::uniform(1)
+            ),
+            30 + 10
+        );
+    }
+
+    #[test]
+    fn guillotine_waste_strips() {
+        // The waste to the right of the left strip is cut off before the pieces in it are
+        // separated, and the waste below the right strip is what's left of it.
+        let rects = [rect(0, 0, 10, 10), rect(0, 11, 10, 19), rect(11, 0, 10, 20)];
+
+        assert_eq!(
+            guillotine_waste(&rects, 25, 30, &[], Trim::default(), BladeWidth::uniform(1)),
+            vec![rect(11, 21, 10, 9), rect(22, 0, 3, 30)]
+        );
+    }
+
+    #[test]
+    fn guillotine_waste_non_guillotine() {
+        // The hole in the middle of a pinwheel layout can't be reached with guillotine cuts, so
+        // it's the only free space in the strips.
+        let rects = [
+            rect(0, 0, 20, 10),
+            rect(20, 0, 10, 20),
+            rect(10, 20, 20, 10),
+            rect(0, 10, 10, 20),
+        ];
+
+        assert_eq!(
+            guillotine_waste(&rects, 30, 30, &[], Trim::default(), BladeWidth::uniform(0)),
+            vec![rect(10, 10, 10, 10)]
+        );
+    }
+
+    #[test]
+    fn cut_tree_strips() {
+        // A strip along the length that's a cut piece, and one that's cut into two cut pieces
+        // and the waste at its end.
+        let stock_piece = stock_piece(
+            48,
+            96,
+            &[
+                rect(21, 41, 27, 40),
+                rect(0, 0, 20, 96),
+                rect(21, 0, 27, 40),
+            ],
+            &[],
+        );
+        let cut = |direction, position, first, second| CutNode::Cut {
+            direction,
+            position,
+            first: Box::new(first),
+            second: Box::new(second),
+        };
+
+        assert_eq!(
+            cut_tree(&stock_piece, BladeWidth::uniform(1)),
+            Some(cut(
+                CutDirection::ParallelToLength,
+                20,
+                CutNode::CutPiece(1),
+                cut(
+                    CutDirection::ParallelToWidth,
+                    40,
+                    CutNode::CutPiece(2),
+                    cut(
+                        CutDirection::ParallelToWidth,
+                        81,
+                        CutNode::CutPiece(0),
+                        waste(21, 82, 27, 14),
+                    ),
+                ),
+            ))
+        );
+    }
+
+    #[test]
+    fn cut_tree_seams_and_fixed_pieces() {
+        // The seam is cut first, and the fixed piece is a leaf like the cut pieces.
+        let mut stock_piece = stock_piece(30, 30, &[rect(0, 0, 14, 30)], &[rect(15, 0, 15, 10)]);
+        stock_piece.seams = vec![Seam::ParallelToLength(14)];
+        let tree = cut_tree(&stock_piece, BladeWidth::uniform(1)).unwrap();
+
+        assert_eq!(
+            tree.cuts(),
+            vec![
+                (CutDirection::ParallelToLength, 14),
+                (CutDirection::ParallelToWidth, 10)
+            ]
+        );
+        let CutNode::Cut { first, second, .. } = tree else {
+            panic!("should have cut along the seam");
+        };
+        assert_eq!(*first, CutNode::CutPiece(0));
+        assert_eq!(
+            *second,
+            CutNode::Cut {
+                direction: CutDirection::ParallelToWidth,
+                position: 10,
+                first: Box::new(CutNode::FixedPiece(0)),
+                second: Box::new(waste(15, 11, 15, 19)),
+            }
+        );
+    }
+
+    #[test]
+    fn cut_tree_not_guillotine() {
+        // A pinwheel layout can't be separated with guillotine cuts.
+        let stock_piece = stock_piece(
+            40,
+            40,
+            &[
+                rect(0, 0, 30, 10),
+                rect(30, 0, 10, 30),
+                rect(10, 30, 30, 10),
+                rect(0, 10, 10, 30),
+            ],
+            &[],
+        );
+
+        assert_eq!(cut_tree(&stock_piece, BladeWidth::uniform(0)), None);
+    }
+
+    #[test]
+    fn compact_slides_toward_origin() {
+        // The first rect slides down and to the left into the corner, and the second one
+        // slides down and then to the left up to the cut after the first one.
+        let mut rects = [rect(5, 5, 10, 10), rect(20, 8, 10, 10)];
+        assert!(compact(
+            &mut rects,
+            &[],
+            30,
+            30,
+            &[],
+            Trim::default(),
+            BladeWidth::uniform(1)
+        ));
+        assert_eq!(rects, [rect(0, 0, 10, 10), rect(11, 0, 10, 10)]);
+
+        // Nothing moves once the rects are compacted.
+        assert!(!compact(
+            &mut rects,
+            &[],
+            30,
+            30,
+            &[],
+            Trim::default(),
+            BladeWidth::uniform(1)
+        ));
+    }
+
+    #[test]
+    fn compact_keeps_edge_distance() {
+        // The rect is against the right edge, and sliding it to the left would take it away
+        // from every edge, so it stays.
+        let obstacles = [rect(0, 0, 50, 20), rect(0, 20, 20, 10)];
+        let mut rects = [rect(40, 20, 10, 10)];
+        assert!(!compact(
+            &mut rects,
+            &obstacles,
+            50,
+            50,
+            &[],
+            Trim::default(),
+            BladeWidth::uniform(0)
+        ));
+        assert_eq!(rects, [rect(40, 20, 10, 10)]);
+    }
+
+    #[test]
+    fn compact_keeps_guillotine_stages() {
+        // Sliding the first two rects straight down would need more stages of cuts, so they
+        // only move down as far as the last rect once it's slid into the corner.
+        let stages = |rects: &[Rect]| {
+            guillotine_stages(rects, 40, 40, &[], Trim::default(), BladeWidth::uniform(0))
+        };
+        let mut rects = [rect(11, 11, 11, 5), rect(11, 21, 5, 14), rect(20, 1, 7, 7)];
+        let max_stages = stages(&rects);
+        assert!(compact(
+            &mut rects,
+            &[],
+            40,
+            40,
+            &[],
+            Trim::default(),
+            BladeWidth::uniform(0)
+        ));
+        assert_eq!(
+            rects,
+            [rect(0, 7, 11, 5), rect(0, 12, 5, 14), rect(0, 0, 7, 7)]
+        );
+        assert!(stages(&rects) <= max_stages);
+    }
+
+    #[test]
+    fn compact_stays_between_seams() {
+        let mut rects = [rect(20, 5, 5, 5)];
+        compact(
+            &mut rects,
+            &[],
+            30,
+            30,
+            &[Seam::ParallelToLength(14)],
+            Trim::uniform(2),
+            BladeWidth::uniform(1),
+        );
+        assert_eq!(rects, [rect(15, 2, 5, 5)]);
+    }
+}
@@ -0,0 +1,374 @@
+//! Export of layouts as G-code for CNC routers, enabled by the `gcode-export` feature.
+//!
+//! `write_program` writes a program that cuts the profile of each cut piece of a solution with a
+//! router, one stock piece after the other. It's meant for layouts from nested optimization,
+//! where the cut pieces can't all be cut apart with guillotine cuts, but it works with any
+//! layout. The profiles are simple rectangles, offset by the radius of the tool so the cut
+//! pieces come out at their size, so the solution should be optimized with a cut width of at
+//! least the diameter of the tool.
+
+use crate::{ResultCutPiece, ResultStockPiece, Solution};
+
+use std::io;
+
+/// Options for the G-code written by `write_program`. Distances are in the units of the
+/// solution, which should match `inches`, and rates are in those units per minute.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GcodeOptions {
+    /// Rate the tool moves at while cutting.
+    pub feed_rate: f64,
+
+    /// Rate the tool moves down into the stock piece at.
+    pub plunge_rate: f64,
+
+    /// Speed of the spindle, in revolutions per minute.
+    pub spindle_speed: f64,
+
+    /// Diameter of the tool. The profiles are offset by half of it, so the cut pieces come out at
+    /// their size.
+    pub tool_diameter: f64,
+
+    /// Total depth of the profiles, which is usually a little more than the thickness of the
+    /// stock pieces.
+    pub depth: f64,
+
+    /// Deepest the tool cuts in one pass around a profile. The profiles are cut in as many passes
+    /// as it takes to reach `depth`.
+    pub depth_per_pass: f64,
+
+    /// Height above the stock pieces the tool moves between profiles at.
+    pub safe_height: f64,
+
+    /// Where the tabs that hold a cut piece in place are on each side of its profile, as
+    /// fractions of the length of the side from the corner it's cut from. Empty for no tabs.
+    pub tab_positions: Vec<f64>,
+
+    /// Width of each tab, along the side of the cut piece.
+    pub tab_width: f64,
+
+    /// Height of each tab, from the bottom of the profile.
+    pub tab_height: f64,
+
+    /// Whether the distances are in inches, instead of millimeters.
+    pub inches: bool,
+}
+
+impl Default for GcodeOptions {
+    fn default() -> Self {
+        Self {
+            feed_rate: 3000.0,
+            plunge_rate: 1000.0,
+            spindle_speed: 18000.0,
+            tool_diameter: 6.0,
+            depth: 19.0,
+            depth_per_pass: 6.5,
+            safe_height: 5.0,
+            tab_positions: Vec::new(),
+            tab_width: 10.0,
+            tab_height: 3.0,
+            inches: false,
+        }
+    }
+}
+
+/// Writes a program that cuts the profile of each cut piece of `solution` to `writer`. The
+/// program pauses with `M0` between stock pieces, so the next one can be loaded.
+pub fn write_program(
+    solution: &Solution,
+    options: &GcodeOptions,
+    writer: &mut dyn io::Write,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "G90 G17 {}",
+        if options.inches { "G20" } else { "G21" }
+    )?;
+    for (i, stock_piece) in solution.stock_pieces.iter().enumerate() {
+        if i > 0 {
+            writeln!(writer, "M0 (load the next stock piece)")?;
+        }
+        write_stock_piece(stock_piece, options, writer)?;
+    }
+    writeln!(writer, "M30")
+}
+
+fn write_stock_piece(
+    stock_piece: &ResultStockPiece,
+    options: &GcodeOptions,
+    writer: &mut dyn io::Write,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "(stock piece {}: {} x {})",
+        comment(&stock_piece.sheet_id),
+        stock_piece.width,
+        stock_piece.length
+    )?;
+    writeln!(writer, "G0 Z{}", num(options.safe_height))?;
+    writeln!(writer, "M3 S{}", num(options.spindle_speed))?;
+    for cut_piece in &stock_piece.cut_pieces {
+        write_profile(cut_piece, options, writer)?;
+    }
+    writeln!(writer, "M5")
+}
+
+/// Writes the passes that cut the profile of the cut piece, clockwise from its bottom left
+/// corner, so the side of the tool that cuts moves against the rotation of the spindle.
+fn write_profile(
+    cut_piece: &ResultCutPiece,
+    options: &GcodeOptions,
+    writer: &mut dyn io::Write,
+) -> io::Result<()> {
+    let radius = options.tool_diameter / 2.0;
+    let left = cut_piece.x as f64 - radius;
+    let bottom = cut_piece.y as f64 - radius;
+    let right = (cut_piece.x + cut_piece.width) as f64 + radius;
+    let top = (cut_piece.y + cut_piece.length) as f64 + radius;
+    let corners = [(left, bottom), (left, top), (right, top), (right, bottom)];
+
+    match cut_piece.external_id {
+        Some(external_id) => writeln!(
+            writer,
+            "(cut piece {}: {} x {})",
+            external_id, cut_piece.width, cut_piece.length
+        )?,
+        None => writeln!(
+            writer,
+            "(cut piece: {} x {})",
+            cut_piece.width, cut_piece.length
+        )?,
+    }
+    writeln!(writer, "G0 X{} Y{}", num(left), num(bottom))?;
+
+    let tab_top = options.tab_height - options.depth;
+    for depth in pass_depths(options) {
+        let z = -depth;
+        writeln!(writer, "G1 Z{} F{}", num(z), num(options.plunge_rate))?;
+        let mut feed = Some(options.feed_rate);
+        for (i, &start) in corners.iter().enumerate() {
+            let end = corners[(i + 1) % corners.len()];
+            let tabs = if z < tab_top {
+                tab_spans(distance(start, end), radius, options)
+            } else {
+                Vec::new()
+            };
+            for (tab_start, tab_end) in tabs {
+                let (x, y) = along(start, end, tab_start);
+                write_move(writer, x, y, &mut feed)?;
+                writeln!(writer, "G1 Z{}", num(tab_top))?;
+                let (x, y) = along(start, end, tab_end);
+                write_move(writer, x, y, &mut feed)?;
+                writeln!(writer, "G1 Z{}", num(z))?;
+            }
+            write_move(writer, end.0, end.1, &mut feed)?;
+        }
+    }
+
+    writeln!(writer, "G0 Z{}", num(options.safe_height))
+}
+
+/// Writes a cutting move to `(x, y)`, with the feed rate if it wasn't written yet.
+fn write_move(
+    writer: &mut dyn io::Write,
+    x: f64,
+    y: f64,
+    feed: &mut Option<f64>,
+) -> io::Result<()> {
+    match feed.take() {
+        Some(feed) => writeln!(writer, "G1 X{} Y{} F{}", num(x), num(y), num(feed)),
+        None => writeln!(writer, "G1 X{} Y{}", num(x), num(y)),
+    }
+}
+
+/// Returns the depth of each pass around a profile, ending with the full depth.
+fn pass_depths(options: &GcodeOptions) -> Vec<f64> {
+    if options.depth_per_pass <= 0.0 || options.depth_per_pass >= options.depth {
+        return vec![options.depth];
+    }
+
+    let passes = (options.depth / options.depth_per_pass).ceil() as usize;
+    (1..=passes)
+        .map(|pass| (pass as f64 * options.depth_per_pass).min(options.depth))
+        .collect()
+}
+
+/// Returns where the tool moves over the tabs on a side of a profile that's `length` long, as
+/// distances from the start of the side, in order. The tool stays clear of each tab by its
+/// `radius`, and tabs that overlap are merged.
+fn tab_spans(length: f64, radius: f64, options: &GcodeOptions) -> Vec<(f64, f64)> {
+    let mut centers: Vec<f64> = options
+        .tab_positions
+        .iter()
+        .map(|position| position.clamp(0.0, 1.0) * length)
+        .collect();
+    centers.sort_by(f64::total_cmp);
+
+    let half_width = options.tab_width / 2.0 + radius;
+    let mut spans: Vec<(f64, f64)> = Vec::with_capacity(centers.len());
+    for center in centers {
+        let start = (center - half_width).max(0.0);
+        let end = (center + half_width).min(length);
+        match spans.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => spans.push((start, end)),
+        }
+    }
+    spans
+}
+
+fn distance(start: (f64, f64), end: (f64, f64)) -> f64 {
+    (end.0 - start.0).hypot(end.1 - start.1)
+}
+
+/// Returns the point that's `distance` from `start` toward `end`.
+fn along(start: (f64, f64), end: (f64, f64), distance: f64) -> (f64, f64) {
+    let length = self::distance(start, end);
+    if length == 0.0 {
+        return start;
+    }
+    let t = distance / length;
+    (
+        start.0 + (end.0 - start.0) * t,
+        start.1 + (end.1 - start.1) * t,
+    )
+}
+
+/// Formats a number with up to 3 decimal places, without trailing zeros.
+fn num(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// Returns the text without the parentheses that would end a comment.
+fn comment(text: &str) -> String {
+    text.replace(['(', ')'], "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{result_solution, result_stock_piece};
+
+    fn solution(cut_pieces: &[(usize, usize, usize, usize)]) -> Solution {
+        let mut stock_piece = result_stock_piece(Some(7), 100, 100, cut_pieces);
+        stock_piece.sheet_id = "S1".to_string();
+        result_solution(vec![stock_piece.clone(), stock_piece])
+    }
+
+    fn program(solution: &Solution, options: &GcodeOptions) -> String {
+        let mut program = Vec::new();
+        write_program(solution, options, &mut program).unwrap();
+        String::from_utf8(program).unwrap()
+    }
+
+    #[test]
+    fn write_program_profiles() {
+        let options = GcodeOptions {
+            tool_diameter: 4.0,
+            depth: 10.0,
+            depth_per_pass: 0.0,
+            ..Default::default()
+        };
+        let program = program(&solution(&[(10, 20, 30, 40)]), &options);
+
+        assert_eq!(
+            program.lines().collect::<Vec<_>>(),
+            [
+                "G90 G17 G21",
+                "(stock piece S1: 100 x 100)",
+                "G0 Z5",
+                "M3 S18000",
+                "(cut piece 0: 30 x 40)",
+                "G0 X8 Y18",
+                "G1 Z-10 F1000",
+                "G1 X8 Y62 F3000",
+                "G1 X42 Y62",
+                "G1 X42 Y18",
+                "G1 X8 Y18",
+                "G0 Z5",
+                "M5",
+                "M0 (load the next stock piece)",
+                "(stock piece S1: 100 x 100)",
+                "G0 Z5",
+                "M3 S18000",
+                "(cut piece 0: 30 x 40)",
+                "G0 X8 Y18",
+                "G1 Z-10 F1000",
+                "G1 X8 Y62 F3000",
+                "G1 X42 Y62",
+                "G1 X42 Y18",
+                "G1 X8 Y18",
+                "G0 Z5",
+                "M5",
+                "M30",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_program_passes_and_tabs() {
+        let options = GcodeOptions {
+            tool_diameter: 2.0,
+            depth: 10.0,
+            depth_per_pass: 5.0,
+            tab_positions: vec![0.5],
+            tab_width: 6.0,
+            tab_height: 3.0,
+            inches: true,
+            ..Default::default()
+        };
+        let mut solution = solution(&[(1, 1, 18, 18)]);
+        solution.stock_pieces.truncate(1);
+        let program = program(&solution, &options);
+        let lines: Vec<&str> = program.lines().collect();
+
+        assert_eq!(lines[0], "G90 G17 G20");
+        // The passes are 5 and 10 deep, and only the last one goes below the tabs.
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line.starts_with("G1 Z-") && line.contains(" F"))
+                .copied()
+                .collect::<Vec<_>>(),
+            ["G1 Z-5 F1000", "G1 Z-10 F1000"]
+        );
+        let last_pass = lines
+            .iter()
+            .position(|&line| line == "G1 Z-10 F1000")
+            .unwrap();
+        assert_eq!(
+            lines[last_pass + 1..last_pass + 6],
+            [
+                "G1 X0 Y6 F3000",
+                "G1 Z-7",
+                "G1 X0 Y14",
+                "G1 Z-10",
+                "G1 X0 Y20",
+            ]
+        );
+        assert_eq!(lines.iter().filter(|&&line| line == "G1 Z-7").count(), 4);
+    }
+
+    #[test]
+    fn tab_spans_merge_and_clamp() {
+        let options = GcodeOptions {
+            tab_positions: vec![0.9, 0.0, 0.95],
+            tab_width: 4.0,
+            ..Default::default()
+        };
+
+        assert_eq!(tab_spans(100.0, 1.0, &options), [(0.0, 3.0), (87.0, 98.0)]);
+    }
+
+    #[test]
+    fn num_format() {
+        assert_eq!(num(10.0), "10");
+        assert_eq!(num(-2.5), "-2.5");
+        assert_eq!(num(0.12345), "0.123");
+        assert_eq!(num(-0.0001), "0");
+    }
+}
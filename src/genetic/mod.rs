@@ -1,4 +1,5 @@
 pub mod population;
 pub mod unit;
 
+#[cfg(test)]
 mod test;
@@ -19,11 +19,14 @@
 // THE SOFTWARE.
 
 use super::unit::Unit;
+use crate::{new_rng, RngFactory};
 
 use rand::prelude::*;
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use std::cmp::Ordering;
-use std::mem;
+use std::time::{Duration, Instant};
 
 /// Wraps a unit within a struct that lazily evaluates its fitness to avoid
 /// duplicate work.
@@ -63,9 +66,13 @@ pub struct Population<T: Unit> {
     units: Vec<T>,
 
     seed: u64,
+    seed_per_unit: bool,
+    rng_factory: Option<RngFactory>,
     breed_factor: f64,
     survival_factor: f64,
     max_size: usize,
+    deadline: Option<Instant>,
+    measure_elapsed: bool,
 }
 
 impl<T: Unit> Population<T> {
@@ -74,9 +81,13 @@ impl<T: Unit> Population<T> {
         Population {
             units: init_pop,
             seed: 1,
+            seed_per_unit: false,
+            rng_factory: None,
             breed_factor: 0.5,
             survival_factor: 0.5,
             max_size: 100,
+            deadline: None,
+            measure_elapsed: false,
         }
     }
 
@@ -88,6 +99,26 @@ impl<T: Unit> Population<T> {
         self
     }
 
+    /// Sets whether each unit bred in an epoch gets its own random number
+    /// generator, seeded from the random seed, the epoch, and the index of the
+    /// unit, instead of sharing one generator with the whole population.
+    pub fn set_seed_per_unit(&mut self, seed_per_unit: bool) -> &mut Self {
+        self.seed_per_unit = seed_per_unit;
+        self
+    }
+
+    /// Sets the function that creates the random number generators of the
+    /// population from their seeds. Without one, `StdRng` is used.
+    pub fn set_rng_factory(&mut self, rng_factory: Option<RngFactory>) -> &mut Self {
+        self.rng_factory = rng_factory;
+        self
+    }
+
+    /// Creates a random number generator seeded from `seed`.
+    fn new_rng(&self, seed: u64) -> Box<dyn RngCore + Send> {
+        new_rng(&self.rng_factory, seed)
+    }
+
     /// Sets the maximum size of the population. If already populated with more
     /// than this amount a random section of the population is killed.
     pub fn set_size(&mut self, size: usize) -> &mut Self {
@@ -126,12 +157,27 @@ impl<T: Unit> Population<T> {
         self
     }
 
+    /// Sets a deadline after which no more epochs are run, even if fewer than
+    /// the requested number of epochs have been run.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) -> &mut Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Sets whether the time since the evolution was created is measured for
+    /// `EpochStats::elapsed`. The clock is only read if it's measured or a
+    /// deadline is set, since it isn't available on every platform.
+    pub fn set_measure_elapsed(&mut self, measure_elapsed: bool) -> &mut Self {
+        self.measure_elapsed = measure_elapsed;
+        self
+    }
+
     //--------------------------------------------------------------------------
 
     /// An epoch that allows units to breed and mutate without harsh culling.
     /// It's important to sometimes allow 'weak' units to produce generations
     /// that might escape local peaks in certain dimensions.
-    fn epoch(&self, units: &mut Vec<LazyUnit<T>>, mut rng: StdRng) -> StdRng {
+    fn epoch(&self, epoch: u32, units: &mut Vec<LazyUnit<T>>, rng: &mut dyn RngCore) {
         assert!(!units.is_empty());
 
         // breed_factor dicates how large a percentage of the population will be
@@ -151,82 +197,249 @@ impl<T: Unit> Population<T> {
         // least one.
         let surviving_parents = (breeders.len() as f64 * self.survival_factor).ceil() as usize;
 
-        for i in 0..self.max_size - surviving_parents {
-            let rs = rng.gen_range(0..breeders.len());
-            units.push(LazyUnit::from(
-                breeders[i % breeders.len()]
-                    .unit
-                    .breed_with(&breeders[rs].unit, &mut rng),
-            ));
+        let offspring = self.max_size - surviving_parents;
+
+        // Breed in parallel, with a random number generator for each unit
+        // seeded from `rng`, so the result doesn't depend on the order the
+        // units are bred in.
+        #[cfg(feature = "rayon")]
+        {
+            let seeds: Vec<u64> = (0..offspring)
+                .map(|i| {
+                    if self.seed_per_unit {
+                        unit_seed(self.seed, epoch, i)
+                    } else {
+                        rng.gen()
+                    }
+                })
+                .collect();
+            let children: Vec<LazyUnit<T>> = seeds
+                .into_par_iter()
+                .enumerate()
+                .map(|(i, seed)| Self::breed(&breeders, i, &mut self.new_rng(seed)))
+                .collect();
+            units.extend(children);
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        for i in 0..offspring {
+            let child = if self.seed_per_unit {
+                let mut unit_rng = self.new_rng(unit_seed(self.seed, epoch, i));
+                Self::breed(&breeders, i, &mut unit_rng)
+            } else {
+                Self::breed(&breeders, i, rng)
+            };
+            units.push(child);
         }
 
         // Move our survivors into the new generation.
         units.append(&mut breeders.drain(0..surviving_parents).collect());
+    }
+
+    /// Breeds the `i`th breeder with a random one.
+    fn breed<R>(breeders: &[LazyUnit<T>], i: usize, rng: &mut R) -> LazyUnit<T>
+    where
+        R: Rng + ?Sized,
+    {
+        let rs = rng.gen_range(0..breeders.len());
+        LazyUnit::from(
+            breeders[i % breeders.len()]
+                .unit
+                .breed_with(&breeders[rs].unit, rng),
+        )
+    }
 
-        rng
+    /// Moves the units of this population into an `Evolution` that runs a
+    /// number of epochs one at a time, or fewer if the deadline passes first.
+    pub fn evolve(&mut self, n_epochs: u32) -> Evolution<T> {
+        let deadline = self.deadline;
+        let mut evolution = self.epochs_until(move |stats| {
+            stats.epoch >= n_epochs || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        });
+        evolution.n_epochs = Some(n_epochs);
+        evolution
     }
 
-    /// Runs a number of epochs.
-    pub fn epochs<F>(&mut self, n_epochs: u32, progress_callback: &F) -> &mut Self
+    /// Moves the units of this population into an `Evolution` that runs
+    /// epochs one at a time until `stop` returns true for the statistics of an
+    /// epoch, which makes it the last one. This lets the caller decide when to
+    /// stop, such as when the fitness reaches a target or stops improving,
+    /// without the population knowing about every policy. Evolution also
+    /// stops as soon as a unit is perfect or optimal.
+    pub fn epochs_until<F>(&mut self, stop: F) -> Evolution<T>
     where
-        F: Fn(f64),
+        F: FnMut(&EpochStats) -> bool + Send + 'static,
     {
-        let mut processed_stack = Vec::new();
         let mut active_stack = Vec::new();
-
         while let Some(unit) = self.units.pop() {
             active_stack.push(LazyUnit::from(unit));
         }
 
-        let mut rng = SeedableRng::seed_from_u64(self.seed);
+        Evolution {
+            population: Population {
+                units: Vec::new(),
+                rng_factory: self.rng_factory.clone(),
+                ..*self
+            },
+            active_stack,
+            rng: self.new_rng(self.seed),
+            epoch: 0,
+            n_epochs: None,
+            stop: Box::new(stop),
+            stopping: false,
+            started: self.measure_elapsed.then(Instant::now),
+            stats: None,
+            done: false,
+        }
+    }
+}
 
-        for i in 0..=n_epochs {
-            while let Some(mut unit) = active_stack.pop() {
-                unit.fitness();
-                processed_stack.push(unit);
-            }
+/// Statistics about an epoch of an `Evolution`, after its units have been
+/// evaluated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EpochStats {
+    /// Index of the epoch, starting from 0, which is also the number of
+    /// generations that were bred before it.
+    pub epoch: u32,
 
-            // Swap the full processed_stack with the active stack.
-            mem::swap(&mut active_stack, &mut processed_stack);
+    /// Fitness of the strongest unit.
+    pub best_fitness: f64,
 
-            // We want to sort such that highest fitness units are at the
-            // end.
-            active_stack.sort_by(|a, b| {
-                a.lazy_fitness
-                    .unwrap_or(0.0)
-                    .partial_cmp(&b.lazy_fitness.unwrap_or(0.0))
-                    .unwrap_or(Ordering::Equal)
-            });
+    /// Number of epochs since the fitness of the strongest unit last improved.
+    pub stagnant_epochs: u32,
 
-            // If we have the perfect solution then break early.
-            if active_stack.last().unwrap().lazy_fitness.unwrap_or(0.0) >= 1.0 {
-                break;
-            }
+    /// Time since the evolution was created, if the population measures it.
+    /// See `Population::set_measure_elapsed`.
+    pub elapsed: Option<Duration>,
+}
 
-            if i != n_epochs {
-                rng = self.epoch(&mut active_stack, rng);
-            }
+/// Derives the seed for the unit at `index` bred in `epoch` from the seed of
+/// the population, by mixing them with the SplitMix64 finalizer.
+fn unit_seed(seed: u64, epoch: u32, index: usize) -> u64 {
+    let mut z = seed
+        ^ (epoch as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (index as u64).wrapping_mul(0xd1b5_4a32_d192_ed03);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A population that's evolving, which runs its epochs one at a time so that
+/// the caller can do other work in between.
+pub struct Evolution<T: Unit> {
+    population: Population<T>,
+    active_stack: Vec<LazyUnit<T>>,
+    rng: Box<dyn RngCore + Send>,
+    epoch: u32,
+    // Number of epochs the progress is measured against, if it's known.
+    n_epochs: Option<u32>,
+    stop: Box<dyn FnMut(&EpochStats) -> bool + Send>,
+    // Whether `stop` was called, so the next epoch is the last one.
+    stopping: bool,
+    started: Option<Instant>,
+    stats: Option<EpochStats>,
+    done: bool,
+}
 
-            progress_callback(i as f64 / n_epochs as f64);
+impl<T: Unit> Evolution<T> {
+    /// Runs the next epoch. Returns the fraction of the epochs that have been
+    /// run, which is 0.0 if the number of epochs isn't known, and the fitness
+    /// of the strongest unit, or `None` if evolution is done.
+    pub fn step(&mut self) -> Option<(f64, f64)> {
+        if self.done {
+            return None;
+        }
+        let i = self.epoch;
+        self.epoch += 1;
+
+        #[cfg(feature = "rayon")]
+        self.active_stack.par_iter_mut().for_each(|unit| {
+            unit.fitness();
+        });
+        #[cfg(not(feature = "rayon"))]
+        self.active_stack.iter_mut().for_each(|unit| {
+            unit.fitness();
+        });
+
+        // Reverse the units, like popping them onto another stack would, so
+        // that units with the same fitness keep their order when sorted.
+        self.active_stack.reverse();
+
+        // We want to sort such that highest fitness units are at the
+        // end.
+        self.active_stack.sort_by(|a, b| {
+            a.lazy_fitness
+                .unwrap_or(0.0)
+                .partial_cmp(&b.lazy_fitness.unwrap_or(0.0))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        // If we have the perfect solution then break early.
+        let best = self.active_stack.last().unwrap();
+        let best_fitness = best.lazy_fitness.unwrap_or(0.0);
+        if best_fitness >= 1.0 || best.unit.is_optimal() {
+            self.done = true;
+            return None;
         }
 
-        // Reverse the order of units such that the first unit is the
-        // strongest candidate.
-        while let Some(unit) = active_stack.pop() {
-            self.units.push(unit.unit);
+        let stagnant_epochs = match self.stats {
+            Some(stats) if best_fitness <= stats.best_fitness => stats.stagnant_epochs + 1,
+            _ => 0,
+        };
+        let stats = EpochStats {
+            epoch: i,
+            best_fitness,
+            stagnant_epochs,
+            elapsed: self.started.map(|started| started.elapsed()),
+        };
+        self.stats = Some(stats);
+
+        // The last epoch's units are kept as they are, without breeding them.
+        if self.stopping || (self.stop)(&stats) {
+            self.done = true;
+        } else {
+            self.population
+                .epoch(i, &mut self.active_stack, &mut self.rng);
         }
 
-        self
+        let progress = self
+            .n_epochs
+            .map_or(0.0, |n_epochs| i as f64 / n_epochs as f64);
+        Some((progress, best_fitness))
     }
 
-    //--------------------------------------------------------------------------
+    /// Stops evolving once the units of the current epoch have been
+    /// evaluated, so that `finish` returns without running the rest of the
+    /// epochs.
+    pub fn stop(&mut self) {
+        self.stopping = true;
+    }
 
-    /// Returns the full population of units, ordered such that the first
-    /// element is the strongest candidate. This collection can be used to
-    /// create a new population.
-    pub fn finish(&mut self) -> Vec<T> {
-        let mut empty_units: Vec<T> = Vec::new();
-        mem::swap(&mut empty_units, &mut self.units);
-        empty_units
+    /// Returns the strongest unit that has been evaluated, or `None` if no
+    /// epoch has been run yet.
+    pub fn best(&self) -> Option<&T> {
+        let mut best: Option<&LazyUnit<T>> = None;
+        for unit in &self.active_stack {
+            if let Some(fitness) = unit.lazy_fitness {
+                if best.is_none_or(|best| fitness > best.lazy_fitness.unwrap_or(0.0)) {
+                    best = Some(unit);
+                }
+            }
+        }
+        best.map(|best| &best.unit)
+    }
+
+    /// Runs the rest of the epochs, and returns the units ordered such that
+    /// the first element is the strongest candidate.
+    pub fn finish(mut self) -> Vec<T> {
+        while self.step().is_some() {}
+
+        // Reverse the order of units such that the first unit is the
+        // strongest candidate.
+        let mut units = Vec::with_capacity(self.active_stack.len());
+        while let Some(unit) = self.active_stack.pop() {
+            units.push(unit.unit);
+        }
+        units
     }
 }
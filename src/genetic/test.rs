@@ -87,6 +87,7 @@ impl Unit for TendUnit {
 mod tests {
     use super::super::population::Population;
     use super::{FloatyUnit, MockUnit, TendUnit};
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn simple_compilation_test() {
@@ -95,7 +96,7 @@ mod tests {
             Population::new(vec![MockUnit { fitness: 0.2 }, MockUnit { fitness: 0.1 }])
                 .set_size(10)
                 .set_breed_factor(1.0)
-                .epochs(100, &|_| {})
+                .evolve(100)
                 .finish();
 
         assert_eq!(best_units.len(), 10);
@@ -116,9 +117,9 @@ mod tests {
         let best_unit = Population::new(test_vec.clone())
             .set_size(100)
             .set_breed_factor(0.25)
-            .epochs(100, &|_| {})
+            .evolve(100)
             .finish()
-            .get(0)
+            .first()
             .unwrap()
             .clone();
 
@@ -134,15 +135,53 @@ mod tests {
             .set_size(100)
             .set_breed_factor(0.5)
             .set_survival_factor(0.0)
-            .epochs(500, &|_| {})
+            .evolve(500)
             .finish()
-            .get(0)
+            .first()
             .unwrap()
             .clone();
 
         assert_eq!(best_unit.x.round(), towards);
     }
 
+    #[test]
+    fn epochs_until_test() {
+        let test_vec = vec![TendUnit {
+            x: 0.3,
+            towards: 10.0,
+        }];
+        let epochs = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = Arc::clone(&epochs);
+        let best_units = Population::new(test_vec.clone())
+            .set_size(10)
+            .epochs_until(move |stats| {
+                assert!(stats.elapsed.is_none());
+                recorded.lock().unwrap().push(stats.epoch);
+                stats.epoch >= 5
+            })
+            .finish();
+
+        assert_eq!(best_units.len(), 10);
+        assert_eq!(*epochs.lock().unwrap(), vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn epochs_until_plateau_test() {
+        // Units at the origin only breed units at the origin, so the fitness
+        // never improves after the first epoch.
+        let best_units = Population::new(vec![FloatyUnit { x: 0.0, y: 0.0 }])
+            .set_size(10)
+            .epochs_until(|stats| {
+                assert_eq!(stats.best_fitness, 0.0);
+                assert_eq!(stats.stagnant_epochs, stats.epoch);
+                stats.stagnant_epochs >= 3
+            })
+            .finish();
+
+        assert_eq!(best_units.len(), 10);
+    }
+
     #[test]
     fn seeding_test() {
         let test_vec = vec![
@@ -157,9 +196,9 @@ mod tests {
             .set_size(200)
             .set_rand_seed(10)
             .set_breed_factor(0.3)
-            .epochs(200, &|_| {})
+            .evolve(200)
             .finish()
-            .get(0)
+            .first()
             .unwrap()
             .clone();
 
@@ -167,9 +206,9 @@ mod tests {
             .set_size(200)
             .set_rand_seed(10)
             .set_breed_factor(0.3)
-            .epochs(200, &|_| {})
+            .evolve(200)
             .finish()
-            .get(0)
+            .first()
             .unwrap()
             .clone();
 
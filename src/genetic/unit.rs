@@ -23,12 +23,18 @@ use rand::prelude::*;
 /// Unit is an abstraction for representing a discrete set of variables to test
 /// against a fitness function, and producing children by mutating those
 /// variables.
-pub trait Unit: Send {
+pub trait Unit: Send + Sync {
     /// Calculate the relative fitness of this Unit by performing a task using
     /// its variables. Should return a value between 0 and 1, where 1 would be
     /// the maximum success and 0 is utter failure.
     fn fitness(&self) -> f64;
 
+    /// Returns whether this Unit is known to be an optimal solution, in which
+    /// case there's no need to keep evolving the population.
+    fn is_optimal(&self) -> bool {
+        false
+    }
+
     /// Create a new unit by merging variable qualities from this and one other
     /// unit. The offspring should occasionally experience mutation in random
     /// dimensions.
@@ -1,27 +1,86 @@
-/// Implementation of the Guillotine Algorithms for bin packing.
-/// [A Thousand Ways to Pack the Bin](http://pds25.egloos.com/pds/201504/21/98/RectangleBinPack.pdf)
+//! Implementation of the Guillotine Algorithms for bin packing.
+//! [A Thousand Ways to Pack the Bin](http://pds25.egloos.com/pds/201504/21/98/RectangleBinPack.pdf)
+//!
+//! The heuristics in this module are the ones `Optimizer::optimize_guillotine` tries. Each one is
+//! a combination of a `FreeRectChoiceHeuristic`, a `SplitHeuristic`, and a
+//! `RotateCutPieceHeuristic`, and its name is their names separated by spaces, such as
+//! `"BestAreaFit ShorterLeftoverAxis PreferUpright"`, which is the name used by
+//! `HeuristicProfile::Custom` and `StockPieceDiagnostics::heuristics`.
+
 use super::*;
 
 use rand::distributions::{Distribution, Standard};
 use rand::prelude::*;
-use smallvec::{smallvec, SmallVec};
+use smallvec::SmallVec;
 
 use std::borrow::Borrow;
 use std::cmp;
+use std::iter;
 
 /// Heuristics for deciding which of the free rectangles to place the demand piece in.
-#[allow(dead_code)]
-#[derive(Copy, Clone)]
-pub(crate) enum FreeRectChoiceHeuristic {
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum FreeRectChoiceHeuristic {
+    /// The free rectangle with the least area left over.
     BestAreaFit,
+
+    /// The free rectangle with the least left over along its shorter side.
     BestShortSideFit,
+
+    /// The free rectangle with the least left over along its longer side.
     BestLongSideFit,
+
+    /// The free rectangle with the most area left over. Only used with
+    /// `Optimizer::set_worst_fit_heuristics`.
     WorstAreaFit,
+
+    /// The free rectangle with the most left over along its shorter side. Only used with
+    /// `Optimizer::set_worst_fit_heuristics`.
     WorstShortSideFit,
+
+    /// The free rectangle with the most left over along its longer side. Only used with
+    /// `Optimizer::set_worst_fit_heuristics`.
     WorstLongSideFit,
+
+    /// The free rectangle closest to the bottom of the stock piece. Only used with
+    /// `Optimizer::set_smallest_y_heuristic`.
     SmallestY,
 }
 
+impl FreeRectChoiceHeuristic {
+    /// All of the free rectangle choice heuristics.
+    pub const ALL: [FreeRectChoiceHeuristic; 7] = [
+        FreeRectChoiceHeuristic::BestAreaFit,
+        FreeRectChoiceHeuristic::BestShortSideFit,
+        FreeRectChoiceHeuristic::BestLongSideFit,
+        FreeRectChoiceHeuristic::WorstAreaFit,
+        FreeRectChoiceHeuristic::WorstShortSideFit,
+        FreeRectChoiceHeuristic::WorstLongSideFit,
+        FreeRectChoiceHeuristic::SmallestY,
+    ];
+
+    /// Heuristics that choose the free rectangle the demand piece fits worst in.
+    pub(crate) const WORST_FIT: [FreeRectChoiceHeuristic; 3] = [
+        FreeRectChoiceHeuristic::WorstAreaFit,
+        FreeRectChoiceHeuristic::WorstShortSideFit,
+        FreeRectChoiceHeuristic::WorstLongSideFit,
+    ];
+
+    /// Returns the heuristics that are only used when enabled in `options`, in addition to the
+    /// ones that are always used.
+    fn extra(options: BinOptions) -> SmallVec<[FreeRectChoiceHeuristic; 4]> {
+        let mut heuristics = SmallVec::new();
+        if options.worst_fit_heuristics {
+            heuristics.extend_from_slice(&Self::WORST_FIT);
+        }
+        if options.smallest_y_heuristic {
+            heuristics.push(FreeRectChoiceHeuristic::SmallestY);
+        }
+        heuristics
+    }
+}
+
 impl Distribution<FreeRectChoiceHeuristic> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> FreeRectChoiceHeuristic {
         // Don't include the "worst fit" heuristics here since they tend to give worse results.
@@ -34,16 +93,41 @@ impl Distribution<FreeRectChoiceHeuristic> for Standard {
 }
 
 /// Heuristic for determining how to subdivide the free space that remains after placing a demand piece.
-#[derive(Copy, Clone)]
-pub(crate) enum SplitHeuristic {
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum SplitHeuristic {
+    /// Split along the shorter of the sides left over next to the demand piece.
     ShorterLeftoverAxis,
+
+    /// Split along the longer of the sides left over next to the demand piece.
     LongerLeftoverAxis,
+
+    /// Split so the smaller of the two free rectangles is as small as possible.
     MinimizeArea,
+
+    /// Split so the smaller of the two free rectangles is as large as possible.
     MaximizeArea,
+
+    /// Split along the shorter side of the free rectangle.
     ShorterAxis,
+
+    /// Split along the longer side of the free rectangle.
     LongerAxis,
 }
 
+impl SplitHeuristic {
+    /// All of the split heuristics.
+    pub const ALL: [SplitHeuristic; 6] = [
+        SplitHeuristic::ShorterLeftoverAxis,
+        SplitHeuristic::LongerLeftoverAxis,
+        SplitHeuristic::MinimizeArea,
+        SplitHeuristic::MaximizeArea,
+        SplitHeuristic::ShorterAxis,
+        SplitHeuristic::LongerAxis,
+    ];
+}
+
 impl Distribution<SplitHeuristic> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> SplitHeuristic {
         match rng.gen_range(0..6) {
@@ -58,12 +142,43 @@ impl Distribution<SplitHeuristic> for Standard {
 }
 
 /// Heuristic for determining whether to prefer rotating cut pieces.
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub(crate) enum RotateCutPieceHeuristic {
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum RotateCutPieceHeuristic {
+    /// Place cut pieces the way they were given, if they fit that way.
     PreferUpright,
+
+    /// Place cut pieces rotated, if they can be rotated and fit that way.
     PreferRotated,
 }
 
+impl RotateCutPieceHeuristic {
+    /// All of the rotation heuristics.
+    pub const ALL: [RotateCutPieceHeuristic; 2] = [
+        RotateCutPieceHeuristic::PreferUpright,
+        RotateCutPieceHeuristic::PreferRotated,
+    ];
+}
+
+impl fmt::Display for FreeRectChoiceHeuristic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::Display for SplitHeuristic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::Display for RotateCutPieceHeuristic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 impl Distribution<RotateCutPieceHeuristic> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> RotateCutPieceHeuristic {
         match rng.gen_range(0..2) {
@@ -77,11 +192,34 @@ impl Distribution<RotateCutPieceHeuristic> for Standard {
 pub(crate) struct GuillotineBin {
     width: usize,
     length: usize,
-    blade_width: usize,
+    blade_width: BladeWidth,
     pattern_direction: PatternDirection,
     cut_pieces: SmallVec<[UsedCutPiece; 8]>,
     free_rects: SmallVec<[Rect; 8]>,
     price: usize,
+    area_price: Option<AreaPrice>,
+    external_id: Option<usize>,
+    seams: Vec<Seam>,
+    trim: Trim,
+    min_leftover_area: Option<u64>,
+    grade: Option<char>,
+    material: Option<usize>,
+    thickness: Option<usize>,
+    source: Option<OffcutSource>,
+    fixed_pieces: Vec<FixedPiece>,
+    nominal_size: Option<(usize, usize)>,
+    must_use: bool,
+    max_stages: Option<usize>,
+    first_cut_direction: Option<CutDirection>,
+    extra_rect_choices: SmallVec<[FreeRectChoiceHeuristic; 4]>,
+    diagnostics: Option<BinDiagnostics>,
+}
+
+/// Diagnostics collected while inserting cut pieces into a `GuillotineBin`.
+#[derive(Clone, Debug, Default)]
+struct BinDiagnostics {
+    heuristics: SmallVec<[<GuillotineBin as Bin>::Heuristic; 4]>,
+    rejected_placements: usize,
 }
 
 impl Bin for GuillotineBin {
@@ -91,51 +229,91 @@ impl Bin for GuillotineBin {
         RotateCutPieceHeuristic,
     );
 
-    fn new(
-        width: usize,
-        length: usize,
-        blade_width: usize,
-        pattern_direction: PatternDirection,
-        price: usize,
-    ) -> Self {
-        // We start with a single big free rectangle that spans the whole bin.
-        let free_rect = Rect {
-            x: 0,
-            y: 0,
-            width,
-            length,
-        };
-
-        let free_rects = smallvec![free_rect];
+    fn new(stock_piece: &StockPiece, options: BinOptions) -> Self {
+        // We start with a single big free rectangle that spans the whole bin, unless it's split
+        // up by seams.
+        let free_rects = stock_piece.free_rects(options.blade_width).into();
 
         GuillotineBin {
-            width,
-            length,
+            width: stock_piece.width,
+            length: stock_piece.length,
             free_rects,
-            blade_width,
-            pattern_direction,
+            blade_width: options.blade_width,
+            pattern_direction: stock_piece.pattern_direction,
             cut_pieces: Default::default(),
-            price,
+            price: stock_piece.price,
+            area_price: stock_piece.area_price,
+            external_id: stock_piece.external_id,
+            seams: stock_piece.seams.clone(),
+            trim: stock_piece.trim,
+            min_leftover_area: stock_piece.min_leftover_area,
+            grade: stock_piece.grade,
+            material: stock_piece.material,
+            thickness: stock_piece.thickness,
+            source: stock_piece.source,
+            fixed_pieces: stock_piece.fixed_pieces.clone(),
+            nominal_size: stock_piece.nominal_size,
+            must_use: stock_piece.must_use,
+            max_stages: options.max_guillotine_stages,
+            first_cut_direction: options.first_cut_direction,
+            extra_rect_choices: FreeRectChoiceHeuristic::extra(options),
+            diagnostics: options.diagnostics.then(Default::default),
         }
     }
 
-    fn fitness(&self) -> f64 {
+    fn fitness(&self, include_optional: bool) -> f64 {
+        let counted = |p: &&UsedCutPiece| include_optional || !p.optional;
         let used_area = self
             .cut_pieces
             .iter()
+            .filter(counted)
             .fold(0, |acc, p| acc + p.rect.width as u64 * p.rect.length as u64)
             as f64;
 
+        // The space taken by the cut pieces that aren't counted is free, as if they were removed.
+        let uncounted = self.cut_pieces.iter().filter(|p| !counted(p));
         let free_area =
             self.free_rects
                 .iter()
+                .chain(uncounted.clone().map(|p| &p.rect))
                 .fold(0, |acc, fr| acc + fr.width as u64 * fr.length as u64) as f64;
+        let num_free_rects = self.free_rects.len() + uncounted.count();
 
-        (used_area / (used_area + free_area)).powf(2.0 + self.free_rects.len() as f64 * 0.01)
+        (used_area / (used_area + free_area)).powf(2.0 + num_free_rects as f64 * 0.01)
     }
 
-    fn price(&self) -> usize {
+    fn price(&self, include_optional: bool) -> usize {
+        let used_area = self
+            .cut_pieces
+            .iter()
+            .filter(|cut_piece| include_optional || !cut_piece.optional)
+            .map(|cut_piece| cut_piece.rect.width as u64 * cut_piece.rect.length as u64)
+            .sum();
         self.price
+            + self
+                .area_price
+                .map_or(0, |area_price| area_price.price_of(used_area))
+    }
+
+    fn area(&self) -> u64 {
+        self.width as u64 * self.length as u64
+    }
+
+    fn cut_length(&self, include_optional: bool) -> u64 {
+        let rects: Vec<Rect> = self
+            .cut_pieces
+            .iter()
+            .filter(|p| include_optional || !p.optional)
+            .map(|p| p.rect)
+            .collect();
+        cuts::guillotine_cut_length(
+            &rects,
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
+            self.blade_width,
+        )
     }
 
     fn remove_cut_pieces<I>(&mut self, cut_pieces: I) -> usize
@@ -160,8 +338,12 @@ impl Bin for GuillotineBin {
         self.cut_pieces.iter()
     }
 
-    fn possible_heuristics() -> Vec<Self::Heuristic> {
-        vec![
+    fn pattern_direction(&self) -> PatternDirection {
+        self.pattern_direction
+    }
+
+    fn possible_heuristics(options: BinOptions) -> Vec<Self::Heuristic> {
+        let mut heuristics = vec![
             (
                 FreeRectChoiceHeuristic::BestAreaFit,
                 SplitHeuristic::ShorterLeftoverAxis,
@@ -342,7 +524,39 @@ impl Bin for GuillotineBin {
                 SplitHeuristic::LongerAxis,
                 RotateCutPieceHeuristic::PreferRotated,
             ),
-        ]
+        ];
+
+        let extra_rect_choices = FreeRectChoiceHeuristic::extra(options);
+        if !extra_rect_choices.is_empty() {
+            for rotate_preference in [
+                RotateCutPieceHeuristic::PreferUpright,
+                RotateCutPieceHeuristic::PreferRotated,
+            ] {
+                for &rect_choice in &extra_rect_choices {
+                    for split_method in SplitHeuristic::ALL {
+                        heuristics.push((rect_choice, split_method, rotate_preference));
+                    }
+                }
+            }
+        }
+
+        heuristics
+    }
+
+    fn heuristic_name(heuristic: &Self::Heuristic) -> String {
+        format!("{} {} {}", heuristic.0, heuristic.1, heuristic.2)
+    }
+
+    fn in_named_profile(heuristic: &Self::Heuristic, profile: &HeuristicProfile) -> bool {
+        match profile {
+            // Splitting along the shorter leftover axis, or into even sized rects, keeps the
+            // leftover pieces from becoming narrow strips.
+            HeuristicProfile::PanelSaw => matches!(
+                heuristic.1,
+                SplitHeuristic::ShorterLeftoverAxis | SplitHeuristic::MaximizeArea
+            ),
+            HeuristicProfile::All | HeuristicProfile::Cnc | HeuristicProfile::Custom(_) => true,
+        }
     }
 
     fn insert_cut_piece_with_heuristic(
@@ -350,18 +564,56 @@ impl Bin for GuillotineBin {
         cut_piece: &CutPieceWithId,
         heuristic: &Self::Heuristic,
     ) -> bool {
-        self.insert_with_heuristics(cut_piece, true, heuristic.0, heuristic.1, heuristic.2)
+        if !cut_piece.allows_stock(self.external_id, self.grade, self.material, self.thickness) {
+            return false;
+        }
+
+        let used_area = self
+            .cut_pieces
+            .iter()
+            .map(|p| p.rect.width as u64 * p.rect.length as u64)
+            .sum::<u64>()
+            + fixed_area(&self.fixed_pieces);
+        if !leaves_min_leftover_area(
+            self.width,
+            self.length,
+            self.trim,
+            self.min_leftover_area,
+            used_area,
+            cut_piece,
+        ) {
+            return false;
+        }
+
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.rejected_placements += Rect::count_rejected_placements(
+                &self.free_rects,
+                self.pattern_direction,
+                cut_piece,
+            );
+        }
+
+        let inserted =
+            self.insert_with_heuristics(cut_piece, true, heuristic.0, heuristic.1, heuristic.2);
+
+        if let (true, Some(ref mut diagnostics)) = (inserted, &mut self.diagnostics) {
+            if !diagnostics.heuristics.contains(heuristic) {
+                diagnostics.heuristics.push(*heuristic);
+            }
+        }
+
+        inserted
     }
 
-    fn insert_cut_piece_random_heuristic<R>(
-        &mut self,
-        cut_piece: &CutPieceWithId,
-        rng: &mut R,
-    ) -> bool
+    fn random_heuristic<R>(&self, rng: &mut R) -> Self::Heuristic
     where
         R: Rng + ?Sized,
     {
-        self.insert_cut_piece_with_heuristic(cut_piece, &rng.gen())
+        let mut heuristic: Self::Heuristic = rng.gen();
+        if !self.extra_rect_choices.is_empty() && rng.gen_bool(0.5) {
+            heuristic.0 = *self.extra_rect_choices.choose(rng).unwrap();
+        }
+        heuristic
     }
 
     fn matches_stock_piece(&self, stock_piece: &StockPiece) -> bool {
@@ -369,6 +621,18 @@ impl Bin for GuillotineBin {
             && self.length == stock_piece.length
             && self.pattern_direction == stock_piece.pattern_direction
             && self.price == stock_piece.price
+            && self.area_price == stock_piece.area_price
+            && self.external_id == stock_piece.external_id
+            && self.seams == stock_piece.seams
+            && self.trim == stock_piece.trim
+            && self.min_leftover_area == stock_piece.min_leftover_area
+            && self.grade == stock_piece.grade
+            && self.material == stock_piece.material
+            && self.thickness == stock_piece.thickness
+            && self.source == stock_piece.source
+            && self.fixed_pieces == stock_piece.fixed_pieces
+            && self.nominal_size == stock_piece.nominal_size
+            && self.must_use == stock_piece.must_use
     }
 }
 
@@ -384,9 +648,16 @@ impl GuillotineBin {
     ) -> bool {
         let prefer_rotated = rotate_preference == RotateCutPieceHeuristic::PreferRotated;
 
-        if let Some((used_piece, free_index)) =
-            self.find_placement_for_cut_piece(cut_piece, rect_choice, prefer_rotated)
+        // Free rectangles where the cut piece would take too many stages to cut out.
+        let mut rejected = SmallVec::<[usize; 8]>::new();
+        while let Some((used_piece, free_index)) =
+            self.find_placement_for_cut_piece(cut_piece, rect_choice, prefer_rotated, &rejected)
         {
+            if !self.within_max_stages(&used_piece.rect) {
+                rejected.push(free_index);
+                continue;
+            }
+
             let free_rect = self.free_rects.swap_remove(free_index);
             self.split_free_rect_by_heuristic(&free_rect, &used_piece.rect, split_method);
 
@@ -396,79 +667,87 @@ impl GuillotineBin {
 
             self.cut_pieces.push(used_piece);
 
-            true
-        } else {
-            false
+            return true;
         }
+
+        false
     }
 
+    /// Returns whether the cut pieces can still be cut out in `max_stages` stages of guillotine
+    /// cuts after adding a cut piece at `rect`.
+    fn within_max_stages(&self, rect: &Rect) -> bool {
+        let Some(max_stages) = self.max_stages else {
+            return true;
+        };
+
+        let rects: Vec<Rect> = self
+            .cut_pieces
+            .iter()
+            .map(|p| p.rect)
+            .chain(iter::once(*rect))
+            .collect();
+        cuts::guillotine_stages(
+            &rects,
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
+            self.blade_width,
+        ) <= max_stages
+    }
+
+    /// Finds the free rectangle to place the cut piece in, skipping the indices in `rejected`.
+    /// Exact fits always win. Ties in score are broken by the lowest `y`, then the lowest `x`,
+    /// then the preferred orientation, so the choice doesn't depend on the order of `free_rects`.
     fn find_placement_for_cut_piece(
         &self,
         cut_piece: &CutPieceWithId,
         rect_choice: FreeRectChoiceHeuristic,
         prefer_rotated: bool,
+        rejected: &[usize],
     ) -> Option<(UsedCutPiece, usize)> {
         let mut best_rect = Rect::default();
-        let mut best_score = std::isize::MAX;
+        let mut best_key = (isize::MAX, usize::MAX, usize::MAX, true);
         let mut best_fit = Fit::None;
         let mut free_index = None;
 
         for (i, free_rect) in self.free_rects.iter().enumerate() {
-            let fit = free_rect.fit_cut_piece(self.pattern_direction, cut_piece, prefer_rotated);
-            match fit {
-                Fit::UprightExact => {
-                    best_rect.x = free_rect.x;
-                    best_rect.y = free_rect.y;
-                    best_rect.width = cut_piece.width;
-                    best_rect.length = cut_piece.length;
-                    best_fit = fit;
-                    free_index = Some(i);
-                    break;
-                }
-                Fit::RotatedExact => {
-                    best_rect.x = free_rect.x;
-                    best_rect.y = free_rect.y;
-                    best_rect.width = cut_piece.length;
-                    best_rect.length = cut_piece.width;
-                    best_fit = fit;
-                    free_index = Some(i);
-                    break;
-                }
-                Fit::Upright => {
-                    let score = score_by_heuristic(
-                        cut_piece.width,
-                        cut_piece.length,
-                        free_rect,
-                        rect_choice,
-                    );
-                    if score < best_score {
-                        best_rect.x = free_rect.x;
-                        best_rect.y = free_rect.y;
-                        best_rect.width = cut_piece.width;
-                        best_rect.length = cut_piece.length;
-                        best_score = score;
-                        best_fit = fit;
-                        free_index = Some(i);
-                    }
-                }
-                Fit::Rotated => {
-                    let score = score_by_heuristic(
-                        cut_piece.length,
-                        cut_piece.width,
-                        free_rect,
-                        rect_choice,
-                    );
-                    if score < best_score {
-                        best_rect.x = free_rect.x;
-                        best_rect.y = free_rect.y;
-                        best_rect.width = cut_piece.length;
-                        best_rect.length = cut_piece.width;
-                        best_score = score;
-                        best_fit = fit;
-                        free_index = Some(i);
-                    }
+            if rejected.contains(&i) {
+                continue;
+            }
+            let fit = free_rect.fit_cut_piece_in_stock(
+                self.width,
+                self.length,
+                self.pattern_direction,
+                cut_piece,
+                prefer_rotated,
+            );
+            let (width, length) = if fit.is_rotated() {
+                (cut_piece.length, cut_piece.width)
+            } else {
+                (cut_piece.width, cut_piece.length)
+            };
+            let score = match fit {
+                Fit::UprightExact | Fit::RotatedExact => isize::MIN,
+                Fit::Upright | Fit::Rotated => {
+                    score_by_heuristic(width, length, free_rect, rect_choice)
                 }
-                Fit::None => (),
+                Fit::None => continue,
+            };
+            let key = (
+                score,
+                free_rect.y,
+                free_rect.x,
+                fit.is_rotated() != prefer_rotated,
+            );
+            if key < best_key {
+                best_rect.x = free_rect.x;
+                best_rect.y = free_rect.y;
+                best_rect.width = width;
+                best_rect.length = length;
+                best_key = key;
+                best_fit = fit;
+                free_index = Some(i);
             }
         }
 
@@ -487,6 +766,17 @@ impl GuillotineBin {
                     can_rotate: cut_piece.can_rotate,
                     pattern_direction,
                     is_rotated,
+                    tag: cut_piece.tag.clone(),
+                    max_edge_distance: cut_piece.max_edge_distance,
+                    stock_filter: cut_piece.stock_filter.clone(),
+                    min_grade: cut_piece.min_grade,
+                    edge_banding: cut_piece.edge_banding,
+                    match_group: cut_piece.match_group,
+                    members: cut_piece.members.clone(),
+                    material: cut_piece.material,
+                    thickness: cut_piece.thickness,
+                    allow_pattern_mismatch: cut_piece.allow_pattern_mismatch,
+                    optional: cut_piece.optional,
                 },
                 index,
             ))
@@ -509,14 +799,19 @@ impl GuillotineBin {
         // two disjoint rectangles. This can be achieved with by splitting the L-shape using a single line.
         // We have two choices: horizontal or vertical.
 
-        // Use the given heuristic to decide which choice to make.
-        let split_horizontal = match method {
-            SplitHeuristic::ShorterLeftoverAxis => w <= h,
-            SplitHeuristic::LongerLeftoverAxis => w > h,
-            SplitHeuristic::MinimizeArea => rect.width as u64 * h > w * rect.length as u64,
-            SplitHeuristic::MaximizeArea => rect.width as u64 * h <= w * rect.length as u64,
-            SplitHeuristic::ShorterAxis => free_rect.width as u64 <= free_rect.length as u64,
-            SplitHeuristic::LongerAxis => free_rect.width as u64 > free_rect.length as u64,
+        // Use the given heuristic to decide which choice to make, unless this is one of the first
+        // cuts across the stock piece and their direction is forced.
+        let split_horizontal = match self.forced_first_cut_direction(free_rect) {
+            Some(CutDirection::ParallelToLength) => false,
+            Some(CutDirection::ParallelToWidth) => true,
+            None => match method {
+                SplitHeuristic::ShorterLeftoverAxis => w <= h,
+                SplitHeuristic::LongerLeftoverAxis => w > h,
+                SplitHeuristic::MinimizeArea => rect.width as u64 * h > w * rect.length as u64,
+                SplitHeuristic::MaximizeArea => rect.width as u64 * h <= w * rect.length as u64,
+                SplitHeuristic::ShorterAxis => free_rect.width as u64 <= free_rect.length as u64,
+                SplitHeuristic::LongerAxis => free_rect.width as u64 > free_rect.length as u64,
+            },
         };
 
         let split_axis = if split_horizontal {
@@ -527,6 +822,27 @@ impl GuillotineBin {
         self.split_free_rect_along_axis(free_rect, rect, split_axis);
     }
 
+    /// Returns the direction of the first cuts if it's forced and `free_rect` is the part of the
+    /// stock piece that hasn't been cut into strips yet, because it spans the whole region
+    /// between the seams in that direction.
+    fn forced_first_cut_direction(&self, free_rect: &Rect) -> Option<CutDirection> {
+        let direction = self.first_cut_direction?;
+        let along_length = direction == CutDirection::ParallelToLength;
+        let (start, end) = cuts::span(free_rect, !along_length);
+        free_rects_between_seams(
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
+            self.blade_width,
+        )
+        .into_iter()
+        .any(|region| {
+            region.contains(free_rect) && cuts::span(&region, !along_length) == (start, end)
+        })
+        .then_some(direction)
+    }
+
     fn split_free_rect_along_axis(&mut self, free_rect: &Rect, rect: &Rect, split_axis: SplitAxis) {
         let (bottom_width, right_length) = match split_axis {
             SplitAxis::Horizontal => (free_rect.width, rect.length),
@@ -534,12 +850,12 @@ impl GuillotineBin {
         };
 
         let bottom_length = match free_rect.length - rect.length {
-            h if h > self.blade_width => h - self.blade_width,
+            h if h > self.blade_width.crosscut => h - self.blade_width.crosscut,
             _ => 0,
         };
 
         let right_width = match free_rect.width - rect.width {
-            w if w > self.blade_width => w - self.blade_width,
+            w if w > self.blade_width.rip => w - self.blade_width.rip,
             _ => 0,
         };
 
@@ -547,7 +863,7 @@ impl GuillotineBin {
         if bottom_width > 0 && bottom_length > 0 {
             let bottom = Rect {
                 x: free_rect.x,
-                y: free_rect.y + rect.length + self.blade_width,
+                y: free_rect.y + rect.length + self.blade_width.crosscut,
                 width: bottom_width,
                 length: bottom_length,
             };
@@ -555,7 +871,7 @@ impl GuillotineBin {
         }
         if right_width > 0 && right_length > 0 {
             let right = Rect {
-                x: free_rect.x + rect.width + self.blade_width,
+                x: free_rect.x + rect.width + self.blade_width.rip,
                 y: free_rect.y,
                 width: right_width,
                 length: right_length,
@@ -564,38 +880,56 @@ impl GuillotineBin {
         }
     }
 
+    /// Returns whether there is a seam along the given line, which free rectangles can't be merged
+    /// across.
+    fn is_seam(&self, seam: Seam) -> bool {
+        self.seams.contains(&seam)
+    }
+
     /// Merge adjacent free rectangles
     fn merge_free_rects(&mut self) {
+        let BladeWidth { rip, crosscut } = self.blade_width;
         for i in (0..self.free_rects.len()).rev() {
             for j in (i + 1..self.free_rects.len()).rev() {
                 if self.free_rects[i].width == self.free_rects[j].width
                     && self.free_rects[i].x == self.free_rects[j].x
                 {
                     if self.free_rects[i].y
-                        == self.free_rects[j].y + self.free_rects[j].length + self.blade_width
+                        == self.free_rects[j].y + self.free_rects[j].length + crosscut
+                        && !self.is_seam(Seam::ParallelToWidth(
+                            self.free_rects[j].y + self.free_rects[j].length,
+                        ))
                     {
-                        self.free_rects[i].y -= self.free_rects[j].length + self.blade_width;
-                        self.free_rects[i].length += self.free_rects[j].length + self.blade_width;
+                        self.free_rects[i].y -= self.free_rects[j].length + crosscut;
+                        self.free_rects[i].length += self.free_rects[j].length + crosscut;
                         self.free_rects.swap_remove(j);
-                    } else if self.free_rects[i].y + self.free_rects[i].length + self.blade_width
+                    } else if self.free_rects[i].y + self.free_rects[i].length + crosscut
                         == self.free_rects[j].y
+                        && !self.is_seam(Seam::ParallelToWidth(
+                            self.free_rects[i].y + self.free_rects[i].length,
+                        ))
                     {
-                        self.free_rects[i].length += self.free_rects[j].length + self.blade_width;
+                        self.free_rects[i].length += self.free_rects[j].length + crosscut;
                         self.free_rects.swap_remove(j);
                     }
                 } else if self.free_rects[i].length == self.free_rects[j].length
                     && self.free_rects[i].y == self.free_rects[j].y
                 {
-                    if self.free_rects[i].x
-                        == self.free_rects[j].x + self.free_rects[j].width + self.blade_width
+                    if self.free_rects[i].x == self.free_rects[j].x + self.free_rects[j].width + rip
+                        && !self.is_seam(Seam::ParallelToLength(
+                            self.free_rects[j].x + self.free_rects[j].width,
+                        ))
                     {
-                        self.free_rects[i].x -= self.free_rects[j].width + self.blade_width;
-                        self.free_rects[i].width += self.free_rects[j].width + self.blade_width;
+                        self.free_rects[i].x -= self.free_rects[j].width + rip;
+                        self.free_rects[i].width += self.free_rects[j].width + rip;
                         self.free_rects.swap_remove(j);
-                    } else if self.free_rects[i].x + self.free_rects[i].width + self.blade_width
+                    } else if self.free_rects[i].x + self.free_rects[i].width + rip
                         == self.free_rects[j].x
+                        && !self.is_seam(Seam::ParallelToLength(
+                            self.free_rects[i].x + self.free_rects[i].width,
+                        ))
                     {
-                        self.free_rects[i].width += self.free_rects[j].width + self.blade_width;
+                        self.free_rects[i].width += self.free_rects[j].width + rip;
                         self.free_rects.swap_remove(j);
                     }
                 }
@@ -606,13 +940,43 @@ impl GuillotineBin {
 
 impl From<GuillotineBin> for ResultStockPiece {
     fn from(bin: GuillotineBin) -> Self {
-        Self {
-            width: bin.width,
-            length: bin.length,
-            pattern_direction: bin.pattern_direction,
-            cut_pieces: bin.cut_pieces.iter().map(Into::into).collect(),
-            waste_pieces: bin.free_rects.into_vec(),
-            price: bin.price,
+        let fitness = bin.fitness(true);
+        let price = bin.price(true);
+        let stock_piece = Self::new(
+            bin.external_id,
+            bin.width,
+            bin.length,
+            bin.pattern_direction,
+            bin.cut_pieces
+                .iter()
+                .flat_map(|cut_piece| cut_piece.result_cut_pieces(bin.blade_width.max()))
+                .collect(),
+            bin.free_rects.into_vec(),
+            price,
+        )
+        .with_fitness(fitness)
+        .with_seams(bin.seams)
+        .with_trim(bin.trim)
+        .with_grade(bin.grade)
+        .with_material(bin.material)
+        .with_thickness(bin.thickness)
+        .with_fixed_pieces(bin.fixed_pieces)
+        .with_nominal_size(bin.nominal_size)
+        .with_source(bin.source)
+        .with_waste_areas()
+        .with_maximal_free_rects(bin.blade_width)
+        .with_guillotine_cut_length(bin.blade_width);
+
+        match bin.diagnostics {
+            Some(diagnostics) => stock_piece.with_diagnostics(
+                diagnostics
+                    .heuristics
+                    .iter()
+                    .map(GuillotineBin::heuristic_name)
+                    .collect(),
+                diagnostics.rejected_placements,
+            ),
+            None => stock_piece,
         }
     }
 }
@@ -680,6 +1044,38 @@ fn score_worst_long_side_fit(width: usize, length: usize, free_rect: &Rect) -> i
 mod tests {
     use super::*;
 
+    #[test]
+    fn possible_heuristics_worst_fit() {
+        let is_worst_fit = |heuristic: &<GuillotineBin as Bin>::Heuristic| {
+            FreeRectChoiceHeuristic::WORST_FIT.contains(&heuristic.0)
+        };
+
+        let heuristics = GuillotineBin::possible_heuristics(Default::default());
+        assert!(!heuristics.iter().any(is_worst_fit));
+
+        let heuristics = GuillotineBin::possible_heuristics(BinOptions {
+            worst_fit_heuristics: true,
+            ..Default::default()
+        });
+        assert_eq!(heuristics.iter().filter(|h| is_worst_fit(h)).count(), 36);
+    }
+
+    #[test]
+    fn possible_heuristics_smallest_y() {
+        let is_smallest_y = |heuristic: &<GuillotineBin as Bin>::Heuristic| {
+            heuristic.0 == FreeRectChoiceHeuristic::SmallestY
+        };
+
+        let heuristics = GuillotineBin::possible_heuristics(Default::default());
+        assert!(!heuristics.iter().any(is_smallest_y));
+
+        let heuristics = GuillotineBin::possible_heuristics(BinOptions {
+            smallest_y_heuristic: true,
+            ..Default::default()
+        });
+        assert_eq!(heuristics.iter().filter(|h| is_smallest_y(h)).count(), 12);
+    }
+
     #[test]
     fn remove_cut_pieces() {
         let cut_pieces = &[
@@ -690,6 +1086,17 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             CutPieceWithId {
                 id: 1,
@@ -698,6 +1105,17 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             CutPieceWithId {
                 id: 2,
@@ -706,6 +1124,17 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             CutPieceWithId {
                 id: 3,
@@ -714,12 +1143,53 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
         ];
 
-        let heuristic = GuillotineBin::possible_heuristics()[0];
+        let heuristic = GuillotineBin::possible_heuristics(Default::default())[0];
 
-        let mut bin = GuillotineBin::new(48, 96, 1, PatternDirection::None, 0);
+        let mut bin = GuillotineBin::new(
+            &StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: None,
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            BinOptions {
+                blade_width: BladeWidth::uniform(1),
+                diagnostics: false,
+                worst_fit_heuristics: false,
+                smallest_y_heuristic: false,
+                guillotine_waste_pieces: false,
+                max_guillotine_stages: None,
+                first_cut_direction: None,
+                nested_strip_direction: None,
+            },
+        );
         cut_pieces.iter().for_each(|cut_piece| {
             bin.insert_cut_piece_with_heuristic(cut_piece, &heuristic);
         });
@@ -734,6 +1204,17 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 is_rotated: false,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             UsedCutPiece {
                 id: 3,
@@ -742,6 +1223,17 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 is_rotated: false,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
         ];
 
@@ -757,11 +1249,27 @@ mod tests {
         let bin = GuillotineBin {
             width: 48,
             length: 96,
-            blade_width: 1,
+            blade_width: BladeWidth::uniform(1),
             pattern_direction: PatternDirection::None,
             cut_pieces: Default::default(),
             free_rects: Default::default(),
             price: 0,
+            external_id: None,
+            extra_rect_choices: Default::default(),
+            diagnostics: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            max_stages: None,
+            first_cut_direction: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            area_price: None,
+            must_use: false,
         };
 
         let stock_piece = StockPiece {
@@ -770,6 +1278,18 @@ mod tests {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(20),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         };
 
         assert!(bin.matches_stock_piece(&stock_piece));
@@ -780,11 +1300,27 @@ mod tests {
         let bin = GuillotineBin {
             width: 48,
             length: 96,
-            blade_width: 1,
+            blade_width: BladeWidth::uniform(1),
             pattern_direction: PatternDirection::None,
             cut_pieces: Default::default(),
             free_rects: Default::default(),
             price: 0,
+            external_id: None,
+            extra_rect_choices: Default::default(),
+            diagnostics: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            max_stages: None,
+            first_cut_direction: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            area_price: None,
+            must_use: false,
         };
 
         let stock_pieces = &[
@@ -794,6 +1330,18 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 price: 0,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
             },
             StockPiece {
                 width: 48,
@@ -801,6 +1349,18 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 price: 0,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
             },
             StockPiece {
                 width: 48,
@@ -808,6 +1368,18 @@ mod tests {
                 pattern_direction: PatternDirection::ParallelToLength,
                 price: 0,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
             },
             StockPiece {
                 width: 48,
@@ -815,6 +1387,56 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 price: 10,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: Some(20),
+                external_id: Some(1),
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: true,
+                area_price: None,
             },
         ];
 
@@ -822,4 +1444,95 @@ mod tests {
             .iter()
             .for_each(|stock_piece| assert!(!bin.matches_stock_piece(stock_piece)))
     }
+
+    #[test]
+    fn placement_ties_ignore_free_rect_order() {
+        let cut_piece = CutPieceWithId {
+            id: 0,
+            external_id: None,
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            members: None,
+            allow_pattern_mismatch: false,
+            optional: false,
+            material: None,
+            thickness: None,
+        };
+        let free_rects = [
+            Rect {
+                x: 30,
+                y: 30,
+                width: 12,
+                length: 12,
+            },
+            Rect {
+                x: 30,
+                y: 0,
+                width: 12,
+                length: 12,
+            },
+            Rect {
+                x: 0,
+                y: 30,
+                width: 10,
+                length: 10,
+            },
+            Rect {
+                x: 0,
+                y: 50,
+                width: 10,
+                length: 10,
+            },
+        ];
+
+        for rect_choice in [
+            FreeRectChoiceHeuristic::BestAreaFit,
+            FreeRectChoiceHeuristic::BestShortSideFit,
+            FreeRectChoiceHeuristic::BestLongSideFit,
+            FreeRectChoiceHeuristic::SmallestY,
+        ] {
+            let mut bin = GuillotineBin::new(
+                &StockPiece {
+                    width: 100,
+                    length: 100,
+                    pattern_direction: PatternDirection::None,
+                    price: 0,
+                    quantity: None,
+                    external_id: None,
+                    seams: Vec::new(),
+                    trim: Trim::default(),
+                    min_leftover_area: None,
+                    source: None,
+                    grade: None,
+                    fixed_pieces: Vec::new(),
+                    nominal_size: None,
+                    material: None,
+                    thickness: None,
+                    must_use: false,
+                    area_price: None,
+                },
+                Default::default(),
+            );
+            bin.free_rects = free_rects.iter().copied().collect();
+            let (forward, _) = bin
+                .find_placement_for_cut_piece(&cut_piece, rect_choice, false, &[])
+                .unwrap();
+            bin.free_rects = free_rects.iter().rev().copied().collect();
+            let (reversed, _) = bin
+                .find_placement_for_cut_piece(&cut_piece, rect_choice, false, &[])
+                .unwrap();
+
+            // The exact fit with the lowest y always wins.
+            assert_eq!((forward.rect.x, forward.rect.y), (0, 30));
+            assert_eq!(forward.rect, reversed.rect);
+        }
+    }
 }
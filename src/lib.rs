@@ -1,27 +1,76 @@
-//! cut-optimizer-2d is an optimizer library that attempts layout rectangular cut pieces from stock pieces in a
-//! way that gives the least waste. It uses genetic algorithms and multiple heuristics to solve the problem.
+//! cut-optimizer-2d is an optimizer library that attempts layout rectangular cut pieces from stock
+//! pieces in a way that gives the least waste. It uses genetic algorithms and multiple heuristics
+//! to solve the problem.
+//!
+//! The `pipeline` module has the stages of an optimization, for running them one at a time with
+//! custom steps in between, such as a local search that polishes the best layout.
+//!
+//! With the `rayon` feature, each generation of the genetic algorithm is evaluated and bred in
+//! parallel. Results are still deterministic for a given random seed, but they differ from the
+//! results without the feature, unless `EpochSeedStrategy::PerUnit` is used.
+//!
+//! The `audit-log` feature adds `Optimizer::set_audit_log_callback`, which logs the random choices
+//! the optimizer makes, for diagnosing reports of nondeterminism.
+//!
+//! The `saw-export` feature adds the `saw` module, which writes guillotine layouts as programs for
+//! beam saws, such as in the PTX format.
+//!
+//! The `server` feature adds the `server` module, with [axum] handlers for running the optimizer as
+//! an HTTP service that takes a JSON `Problem` and returns a JSON `Solution`, with progress sent as
+//! server-sent events.
+//!
+//! The `testing` feature adds the `testing` module, with a corpus of cases that have been hard for
+//! the optimizer, and functions for checking that a configured `Optimizer` still solves them.
+//!
+//! The `units` feature adds the `units` module, with `UnitOptimizer`, which takes the sizes of the
+//! stock pieces and cut pieces in one unit of length, such as millimeters or inches, so they
+//! can't be mixed.
 
 #![deny(missing_docs)]
 
+mod audit;
+mod batch;
+mod cuts;
+#[cfg(feature = "gcode-export")]
+pub mod gcode;
 mod genetic;
-mod guillotine;
-mod maxrects;
+pub mod guillotine;
+pub mod maxrects;
+pub mod pipeline;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "saw-export")]
+pub mod saw;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "units")]
+pub mod units;
 
 #[cfg(test)]
 mod tests;
 
-use genetic::population::Population;
+use audit::AuditLogCallback;
+use genetic::population::{Evolution, Population};
 use genetic::unit::Unit;
 use guillotine::GuillotineBin;
 use maxrects::MaxRectsBin;
 
-use fnv::FnvHashSet;
+use fnv::{FnvHashSet, FnvHasher};
 use rand::prelude::*;
 use rand::seq::SliceRandom;
 
 use std::borrow::Borrow;
 use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::{self, ControlFlow};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
@@ -40,6 +89,12 @@ pub enum PatternDirection {
 
     /// Linear pattern that runs parallel to the length
     ParallelToLength,
+
+    /// A cut piece that can be cut from a stock piece with any pattern direction, unlike `None`,
+    /// which can only be cut from stock pieces without a pattern. It's only rotated to make it
+    /// fit, and its result cut pieces have the pattern direction of their stock piece. A stock
+    /// piece with it can only be used for cut pieces that have it too.
+    Either,
 }
 
 impl PatternDirection {
@@ -49,6 +104,123 @@ impl PatternDirection {
             PatternDirection::None => PatternDirection::None,
             PatternDirection::ParallelToWidth => PatternDirection::ParallelToLength,
             PatternDirection::ParallelToLength => PatternDirection::ParallelToWidth,
+            PatternDirection::Either => PatternDirection::Either,
+        }
+    }
+
+    /// Returns whether a cut piece with this pattern direction can be placed on a stock piece
+    /// with the `stock` pattern direction without rotating it.
+    fn matches(self, stock: PatternDirection) -> bool {
+        self == stock || self == PatternDirection::Either
+    }
+
+    /// Returns whether this is a linear pattern that a cut piece can be rotated to match.
+    fn is_linear(self) -> bool {
+        matches!(
+            self,
+            PatternDirection::ParallelToWidth | PatternDirection::ParallelToLength
+        )
+    }
+}
+
+/// How the random numbers used by the genetic algorithm in each epoch are derived from the random
+/// seed.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum EpochSeedStrategy {
+    /// Use a single stream of random numbers for the whole optimization, so every unit that's
+    /// bred changes the random numbers used by the units bred after it.
+    #[default]
+    Shared,
+
+    /// Use a separate stream of random numbers for each unit bred in each epoch, derived from the
+    /// random seed, the epoch, and the index of the unit. A change to how one unit is bred doesn't
+    /// change the random numbers used by any other unit, which makes it possible to compare
+    /// changes to the genetic algorithm while holding the rest of the randomness fixed.
+    PerUnit,
+}
+
+/// Policy for rotating cut pieces whose pattern direction only matches the stock piece when
+/// rotated.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum RotateToMatchPattern {
+    /// Always rotate cut pieces to match the pattern direction of the stock piece, even if
+    /// `can_rotate` is false.
+    Always,
+
+    /// Never rotate cut pieces to match the pattern direction of the stock piece, even if
+    /// `can_rotate` is true.
+    Never,
+
+    /// Only rotate cut pieces to match the pattern direction of the stock piece if `can_rotate`
+    /// is true.
+    #[default]
+    IfAllowed,
+}
+
+impl RotateToMatchPattern {
+    /// Returns whether the optimizer may rotate the cut piece.
+    fn can_rotate(self, cut_piece: &CutPieceWithId) -> bool {
+        if !cut_piece.pattern_direction.is_linear() {
+            // Cut pieces without a pattern to match are only rotated to make them fit.
+            return cut_piece.can_rotate;
+        }
+
+        match self {
+            RotateToMatchPattern::Always => true,
+            RotateToMatchPattern::Never => false,
+            RotateToMatchPattern::IfAllowed => cut_piece.can_rotate,
+        }
+    }
+}
+
+/// Set of heuristics the optimizer tries when laying out cut pieces for one `OptimizeMode`.
+/// Profiles are applied after the heuristics enabled by options such as
+/// `Optimizer::set_worst_fit_heuristics`, so they can only narrow that set down.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum HeuristicProfile {
+    /// Every heuristic.
+    #[default]
+    All,
+
+    /// Heuristics suited to cutting on a panel saw. For guillotine optimization, these split the
+    /// remaining space so that they don't leave narrow strips that are hard to support while
+    /// cutting. For nested optimization, this is the same as `All`.
+    PanelSaw,
+
+    /// Heuristics suited to cutting on a CNC. For nested optimization, these place cut pieces
+    /// against the edges of the stock piece and other cut pieces. For guillotine optimization,
+    /// this is the same as `All`.
+    Cnc,
+
+    /// Only the heuristics with these names. The names are the same ones reported in
+    /// `StockPieceDiagnostics::heuristics`, and are made of the names of the heuristic enums in
+    /// the `guillotine` and `maxrects` modules.
+    Custom(Vec<String>),
+}
+
+impl HeuristicProfile {
+    /// Returns the heuristics for bins created with `options` that are in this profile.
+    fn heuristics<B>(&self, options: BinOptions) -> Vec<B::Heuristic>
+    where
+        B: Bin,
+    {
+        let heuristics = B::possible_heuristics(options);
+        match self {
+            HeuristicProfile::All => heuristics,
+            HeuristicProfile::Custom(names) => heuristics
+                .into_iter()
+                .filter(|heuristic| names.contains(&B::heuristic_name(heuristic)))
+                .collect(),
+            profile => heuristics
+                .into_iter()
+                .filter(|heuristic| B::in_named_profile(heuristic, profile))
+                .collect(),
         }
     }
 }
@@ -77,6 +249,186 @@ pub struct CutPiece {
 
     /// Whether or not the optimizer is allowed to rotate this piece to make it fit.
     pub can_rotate: bool,
+
+    /// Arbitrary text to be carried through to the result cut pieces, such as an assembly phase
+    /// or room name, which can be used for grouping cut pieces in reports. This has no meaning to
+    /// the optimizer so it can be set to `None` if not needed.
+    pub tag: Option<String>,
+
+    /// Maximum distance from this cut piece to the nearest edge of the stock piece, such as for
+    /// long thin pieces that need to be cut from the stiffer outer strip of the stock piece.
+    /// `None` means the cut piece can be placed anywhere.
+    pub max_edge_distance: Option<usize>,
+
+    /// Which stock pieces this cut piece may be cut from, by their external IDs, such as to keep
+    /// a part off of remnants. Defaults to `StockFilter::Any`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub stock_filter: StockFilter,
+
+    /// Value of each copy of this cut piece, when the optimizer picks which cut pieces to cut with
+    /// `OptimizeGoal::MaximizeValue`, so the ones with a low value are left out first. `None`
+    /// means its area. The totals are reported in `Solution::placed_value` and
+    /// `Solution::unplaced_value`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub value: Option<usize>,
+
+    /// Lowest grade of stock piece this cut piece may be cut from, such as `'A'` for a visible
+    /// face, or `'C'` for a cabinet back. See `StockPiece::grade`. `None` means any stock piece.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub min_grade: Option<char>,
+
+    /// Edges of this cut piece that get edge banding. See
+    /// `Optimizer::set_prefer_banded_edges_outward`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub edge_banding: EdgeBanding,
+
+    /// Match group of this cut piece, such as for a waterfall of drawer fronts or sequenced
+    /// veneer, whose grain needs to run on from one cut piece to the next. `None` means it isn't
+    /// in a match group.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub match_group: Option<MatchGroup>,
+
+    /// Material this cut piece is cut from, such as plywood, MDF, or melamine, as a key chosen by
+    /// the caller. It's only cut from stock pieces with the same `StockPiece::material`, so cut
+    /// pieces of several materials can be optimized together. `None` only matches stock pieces
+    /// without a material.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub material: Option<usize>,
+
+    /// Thickness of this cut piece. Like `material`, it's only cut from stock pieces with the same
+    /// `StockPiece::thickness`, so jobs with several thicknesses can be optimized together, and it
+    /// has no other meaning to the optimizer. `None` only matches stock pieces without a
+    /// thickness.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub thickness: Option<usize>,
+
+    /// Most copies of this cut piece to cut, such as for shelf blanks that are cut if there's room
+    /// for them. The copies past `quantity` are optional, so they're only placed in the space left
+    /// on the stock pieces used for the other cut pieces. They never need another stock piece, or
+    /// take the place of a cut piece that isn't optional, and aren't reported as unplaced if they
+    /// don't fit. `None` means exactly `quantity` copies. Cut pieces in a match group don't have
+    /// optional copies.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub max_quantity: Option<usize>,
+}
+
+impl CutPiece {
+    /// Returns the number of copies of this cut piece, including the optional ones.
+    fn total_quantity(&self) -> usize {
+        match self.max_quantity {
+            Some(max_quantity) if self.match_group.is_none() => {
+                cmp::max(self.quantity, max_quantity)
+            }
+            _ => self.quantity,
+        }
+    }
+}
+
+/// Cut pieces in the same match group are placed next to each other on the same stock piece, in
+/// order of their `position` along the grain, with a cut between each one. The grain runs along the
+/// width of cut pieces with `PatternDirection::ParallelToWidth`, and along the length otherwise.
+/// The cut pieces of a match group need to have the same pattern direction, stock filter, material,
+/// thickness, and size across the grain, and the copies of a cut piece are placed one after the
+/// other. A match group is placed like one cut piece, so it's only rotated if all of its cut pieces
+/// can rotate.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct MatchGroup {
+    /// ID of the match group, which is the same for each cut piece in it.
+    pub id: usize,
+
+    /// Position of the cut piece along the grain within the match group, starting with the
+    /// smallest.
+    pub position: usize,
+}
+
+/// Edges of a cut piece that get edge banding. On a `CutPiece` the edges are in its own
+/// orientation, and on a `ResultCutPiece` they're where the banded edges ended up on the stock
+/// piece, after rotating and mirroring.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct EdgeBanding {
+    /// The edge along the length at the smallest x location.
+    pub left: bool,
+
+    /// The edge along the length at the largest x location.
+    pub right: bool,
+
+    /// The edge along the width at the smallest y location.
+    pub bottom: bool,
+
+    /// The edge along the width at the largest y location.
+    pub top: bool,
+}
+
+impl EdgeBanding {
+    /// No banded edges.
+    pub const NONE: Self = Self {
+        left: false,
+        right: false,
+        bottom: false,
+        top: false,
+    };
+
+    /// Returns whether any edge is banded.
+    pub fn any(&self) -> bool {
+        self.left || self.right || self.bottom || self.top
+    }
+
+    /// Returns the banded edges after a cut piece is rotated by the optimizer, which turns it a
+    /// quarter turn counterclockwise.
+    fn rotated(self) -> Self {
+        Self {
+            left: self.top,
+            right: self.bottom,
+            bottom: self.left,
+            top: self.right,
+        }
+    }
+
+    /// Returns the banded edges after a cut piece is mirrored.
+    fn mirrored(self, horizontal: bool, vertical: bool) -> Self {
+        let mut edge_banding = self;
+        if horizontal {
+            mem::swap(&mut edge_banding.left, &mut edge_banding.right);
+        }
+        if vertical {
+            mem::swap(&mut edge_banding.bottom, &mut edge_banding.top);
+        }
+        edge_banding
+    }
+}
+
+/// Stock pieces a cut piece may be cut from, matched by the external ID of the stock piece.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum StockFilter {
+    /// The cut piece may be cut from any stock piece.
+    #[default]
+    Any,
+
+    /// The cut piece may only be cut from stock pieces with one of these external IDs. Stock
+    /// pieces without an external ID aren't allowed.
+    Only(Vec<usize>),
+
+    /// The cut piece may be cut from any stock piece except the ones with one of these external
+    /// IDs.
+    Except(Vec<usize>),
+}
+
+impl StockFilter {
+    /// Returns whether a cut piece with this filter may be cut from a stock piece with the
+    /// external ID.
+    pub fn allows(&self, external_id: Option<usize>) -> bool {
+        match self {
+            StockFilter::Any => true,
+            StockFilter::Only(ids) => external_id.is_some_and(|id| ids.contains(&id)),
+            StockFilter::Except(ids) => external_id.is_none_or(|id| !ids.contains(&id)),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -87,6 +439,43 @@ pub(crate) struct CutPieceWithId {
     pub(crate) length: usize,
     pub(crate) pattern_direction: PatternDirection,
     pub(crate) can_rotate: bool,
+    pub(crate) tag: Option<Arc<str>>,
+    pub(crate) max_edge_distance: Option<usize>,
+    pub(crate) stock_filter: Option<Arc<StockFilter>>,
+    pub(crate) min_grade: Option<char>,
+    pub(crate) edge_banding: EdgeBanding,
+    pub(crate) match_group: Option<MatchGroup>,
+    // The cut pieces of the match group, in order along the grain, if this cut piece stands in
+    // for a whole match group.
+    pub(crate) members: Option<Arc<[CutPieceWithId]>>,
+    // Whether this cut piece may be placed against the pattern of the stock piece when it can't
+    // be placed with it.
+    pub(crate) allow_pattern_mismatch: bool,
+    // Whether this is a copy past the required quantity, which is only placed if it fits.
+    pub(crate) optional: bool,
+    pub(crate) material: Option<usize>,
+    pub(crate) thickness: Option<usize>,
+}
+
+impl CutPieceWithId {
+    /// Returns whether this cut piece may be cut from a stock piece with the external ID, grade,
+    /// material, and thickness.
+    fn allows_stock(
+        &self,
+        external_id: Option<usize>,
+        grade: Option<char>,
+        material: Option<usize>,
+        thickness: Option<usize>,
+    ) -> bool {
+        self.stock_filter
+            .as_ref()
+            .is_none_or(|filter| filter.allows(external_id))
+            && self
+                .min_grade
+                .is_none_or(|min_grade| grade.is_some_and(|grade| grade <= min_grade))
+            && self.material == material
+            && self.thickness == thickness
+    }
 }
 
 impl Hash for CutPieceWithId {
@@ -109,6 +498,152 @@ pub(crate) struct UsedCutPiece {
     pub(crate) pattern_direction: PatternDirection,
     pub(crate) is_rotated: bool,
     pub(crate) can_rotate: bool,
+    pub(crate) tag: Option<Arc<str>>,
+    pub(crate) max_edge_distance: Option<usize>,
+    pub(crate) stock_filter: Option<Arc<StockFilter>>,
+    pub(crate) min_grade: Option<char>,
+    pub(crate) edge_banding: EdgeBanding,
+    pub(crate) match_group: Option<MatchGroup>,
+    pub(crate) members: Option<Arc<[CutPieceWithId]>>,
+    pub(crate) allow_pattern_mismatch: bool,
+    pub(crate) optional: bool,
+    pub(crate) material: Option<usize>,
+    pub(crate) thickness: Option<usize>,
+}
+
+impl UsedCutPiece {
+    /// Returns the result cut pieces for this cut piece, which are the cut pieces of its match
+    /// group if it stands in for one. They're `gap` apart along the grain, and rotated along with
+    /// this cut piece.
+    fn result_cut_pieces(&self, gap: usize) -> Vec<ResultCutPiece> {
+        let Some(ref members) = self.members else {
+            return vec![self.into()];
+        };
+
+        let mut offset = 0;
+        members
+            .iter()
+            .map(|member| {
+                // The location of the member within this cut piece before it's rotated.
+                let (x, y) = if member.pattern_direction == PatternDirection::ParallelToWidth {
+                    (offset, 0)
+                } else {
+                    (0, offset)
+                };
+                offset += match member.pattern_direction {
+                    PatternDirection::ParallelToWidth => member.width,
+                    _ => member.length,
+                } + gap;
+
+                // Rotating turns this cut piece a quarter turn counterclockwise, like
+                // `EdgeBanding::rotated`.
+                let rect = if self.is_rotated {
+                    Rect {
+                        x: self.rect.x + self.rect.width - y - member.length,
+                        y: self.rect.y + x,
+                        width: member.length,
+                        length: member.width,
+                    }
+                } else {
+                    Rect {
+                        x: self.rect.x + x,
+                        y: self.rect.y + y,
+                        width: member.width,
+                        length: member.length,
+                    }
+                };
+                let used_cut_piece = UsedCutPiece {
+                    id: member.id,
+                    external_id: member.external_id,
+                    rect,
+                    pattern_direction: if self.is_rotated {
+                        member.pattern_direction.rotated()
+                    } else {
+                        member.pattern_direction
+                    },
+                    is_rotated: self.is_rotated,
+                    can_rotate: member.can_rotate,
+                    tag: member.tag.clone(),
+                    max_edge_distance: member.max_edge_distance,
+                    stock_filter: member.stock_filter.clone(),
+                    min_grade: member.min_grade,
+                    edge_banding: member.edge_banding,
+                    match_group: member.match_group,
+                    members: None,
+                    allow_pattern_mismatch: member.allow_pattern_mismatch,
+                    optional: member.optional,
+                    material: member.material,
+                    thickness: member.thickness,
+                };
+                (&used_cut_piece).into()
+            })
+            .collect()
+    }
+
+    /// Returns the banded edges of this cut piece where they are on the stock piece.
+    fn placed_edge_banding(&self) -> EdgeBanding {
+        if self.is_rotated {
+            self.edge_banding.rotated()
+        } else {
+            self.edge_banding
+        }
+    }
+
+    /// Returns how many edges of this cut piece are banded, and how many of those face outward,
+    /// toward the edge of the stock piece or waste instead of one of the `other` cut pieces.
+    fn banded_edges_outward<'a, I>(&self, others: I, blade_width: BladeWidth) -> (usize, usize)
+    where
+        I: Iterator<Item = &'a UsedCutPiece> + Clone,
+    {
+        let edge_banding = self.placed_edge_banding();
+        if !edge_banding.any() {
+            return (0, 0);
+        }
+
+        let (rect, rip, crosscut) = (&self.rect, blade_width.rip, blade_width.crosscut);
+        let overlaps_x = |o: &Rect| o.x < rect.x + rect.width && rect.x < o.x + o.width;
+        let overlaps_y = |o: &Rect| o.y < rect.y + rect.length && rect.y < o.y + o.length;
+        let faces_cut_piece = |faces: &dyn Fn(&Rect) -> bool| {
+            others
+                .clone()
+                .any(|other| other.id != self.id && faces(&other.rect))
+        };
+        let edges = [
+            (
+                edge_banding.left,
+                faces_cut_piece(&|o| {
+                    o.x + o.width <= rect.x && rect.x <= o.x + o.width + rip && overlaps_y(o)
+                }),
+            ),
+            (
+                edge_banding.right,
+                faces_cut_piece(&|o| {
+                    rect.x + rect.width <= o.x && o.x <= rect.x + rect.width + rip && overlaps_y(o)
+                }),
+            ),
+            (
+                edge_banding.bottom,
+                faces_cut_piece(&|o| {
+                    o.y + o.length <= rect.y && rect.y <= o.y + o.length + crosscut && overlaps_x(o)
+                }),
+            ),
+            (
+                edge_banding.top,
+                faces_cut_piece(&|o| {
+                    rect.y + rect.length <= o.y
+                        && o.y <= rect.y + rect.length + crosscut
+                        && overlaps_x(o)
+                }),
+            ),
+        ];
+
+        edges.into_iter().filter(|(banded, _)| *banded).fold(
+            (0, 0),
+            |(banded, outward), (_, faces_cut_piece)| {
+                (banded + 1, outward + usize::from(!faces_cut_piece))
+            },
+        )
+    }
 }
 
 impl PartialEq for UsedCutPiece {
@@ -141,6 +676,17 @@ impl From<&UsedCutPiece> for CutPieceWithId {
             length,
             can_rotate: used_cut_piece.can_rotate,
             pattern_direction,
+            tag: used_cut_piece.tag.clone(),
+            max_edge_distance: used_cut_piece.max_edge_distance,
+            stock_filter: used_cut_piece.stock_filter.clone(),
+            min_grade: used_cut_piece.min_grade,
+            edge_banding: used_cut_piece.edge_banding,
+            match_group: used_cut_piece.match_group,
+            members: used_cut_piece.members.clone(),
+            allow_pattern_mismatch: used_cut_piece.allow_pattern_mismatch,
+            optional: used_cut_piece.optional,
+            material: used_cut_piece.material,
+            thickness: used_cut_piece.thickness,
         }
     }
 }
@@ -155,6 +701,16 @@ impl From<&UsedCutPiece> for ResultCutPiece {
             length: used_cut_piece.rect.length,
             pattern_direction: used_cut_piece.pattern_direction,
             is_rotated: used_cut_piece.is_rotated,
+            rotated_to_match_pattern: used_cut_piece.is_rotated
+                && used_cut_piece.pattern_direction.is_linear(),
+            tag: used_cut_piece.tag.as_deref().map(Into::into),
+            edge_banding: used_cut_piece.placed_edge_banding(),
+            pattern_direction_effective: if used_cut_piece.is_rotated {
+                used_cut_piece.pattern_direction.rotated()
+            } else {
+                used_cut_piece.pattern_direction
+            },
+            pattern_mismatch: false,
         }
     }
 }
@@ -185,13 +741,266 @@ pub struct ResultCutPiece {
     /// Whether or not this cut piece was rotated 90 degrees by the optimizer from it's original
     /// oriorientation.
     pub is_rotated: bool,
+
+    /// Whether this cut piece was rotated so its pattern direction would match the stock piece,
+    /// as opposed to being rotated to make it fit.
+    pub rotated_to_match_pattern: bool,
+
+    /// Tag that matches the one on the cut piece that was passed to the optimizer.
+    pub tag: Option<String>,
+
+    /// Banded edges of this cut piece, where they ended up on the stock piece after rotating and
+    /// mirroring.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub edge_banding: EdgeBanding,
+
+    /// Pattern direction of this cut piece relative to its own width and length as they were
+    /// passed to the optimizer, which is the direction a label should show the grain in. Unlike
+    /// `pattern_direction`, which is relative to the stock piece, it doesn't change when the cut
+    /// piece is rotated, except for a cut piece with `PatternDirection::Either`, which gets the
+    /// pattern of the stock piece, turned with the cut piece.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub pattern_direction_effective: PatternDirection,
+
+    /// Whether this cut piece was placed against the pattern of the stock piece, which only
+    /// happens when `Optimizer::set_pattern_mismatch_penalty` allows it.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub pattern_mismatch: bool,
+}
+
+/// A piece of a stock piece that remains after cutting the cut pieces.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WastePiece {
+    /// X location of the left side of this waste piece within the stock piece.
+    pub x: usize,
+
+    /// Y location of the (bottom or top) side of this waste piece within the stock piece.
+    pub y: usize,
+
+    /// Width of this waste piece.
+    pub width: usize,
+
+    /// Length of this waste piece.
+    pub length: usize,
+
+    /// Pattern direction of this waste piece, which is the same as the stock piece it remains
+    /// from.
+    pub pattern_direction: PatternDirection,
+}
+
+impl WastePiece {
+    /// Creates a stock piece from this waste piece, so it can be used as stock for another
+    /// optimization. The stock piece keeps the pattern direction of this waste piece.
+    pub fn to_stock_piece(&self, price: usize, quantity: Option<usize>) -> StockPiece {
+        StockPiece {
+            width: self.width,
+            length: self.length,
+            pattern_direction: self.pattern_direction,
+            price,
+            quantity,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        }
+    }
+}
+
+/// A line across a stock piece, such as where panels were joined, that cut pieces can't be placed
+/// across. The stock piece is always cut along its seams.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Seam {
+    /// Seam parallel to the length of the stock piece, at this x location.
+    ParallelToLength(usize),
+
+    /// Seam parallel to the width of the stock piece, at this y location.
+    ParallelToWidth(usize),
+}
+
+/// Returns the total area of the fixed pieces.
+fn fixed_area(fixed_pieces: &[FixedPiece]) -> u64 {
+    fixed_pieces
+        .iter()
+        .map(|fixed_piece| fixed_piece.width as u64 * fixed_piece.length as u64)
+        .sum()
+}
+
+/// Returns whether placing the cut piece on a stock piece of `width` and `length`, which already
+/// has cut pieces with a total area of `used_area`, leaves at least `min_leftover_area` unused
+/// within the `trim`.
+fn leaves_min_leftover_area(
+    width: usize,
+    length: usize,
+    trim: Trim,
+    min_leftover_area: Option<u64>,
+    used_area: u64,
+    cut_piece: &CutPieceWithId,
+) -> bool {
+    match min_leftover_area {
+        Some(min_leftover_area) => {
+            let area = width.saturating_sub(trim.left + trim.right) as u64
+                * length.saturating_sub(trim.bottom + trim.top) as u64;
+            let cut_piece_area = cut_piece.width as u64 * cut_piece.length as u64;
+            used_area + cut_piece_area + min_leftover_area <= area
+        }
+        None => true,
+    }
+}
+
+/// Margins trimmed off the edges of a stock piece before cut pieces are placed, such as for the
+/// edges of a sheet that aren't square or clean. Each margin includes the width of the cut that
+/// trims it. The sides are independent, so a clean factory edge can be kept with a margin of 0
+/// while the other sides are trimmed, and layouts are scored against the area inside the margins.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Hash, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Trim {
+    /// Margin trimmed off the side of the stock piece at x location 0.
+    pub left: usize,
+
+    /// Margin trimmed off the side of the stock piece at x location `width`.
+    pub right: usize,
+
+    /// Margin trimmed off the side of the stock piece at y location 0.
+    pub bottom: usize,
+
+    /// Margin trimmed off the side of the stock piece at y location `length`.
+    pub top: usize,
+}
+
+impl Trim {
+    /// Returns a trim with the same margin on every side.
+    pub const fn uniform(margin: usize) -> Self {
+        Trim {
+            left: margin,
+            right: margin,
+            bottom: margin,
+            top: margin,
+        }
+    }
+}
+
+/// Widths of the cuts in each direction. Rip cuts are parallel to the length, so they separate
+/// pieces along the x axis, and crosscuts are parallel to the width, so they separate pieces
+/// along the y axis.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct BladeWidth {
+    /// Width of the cuts parallel to the length.
+    pub(crate) rip: usize,
+
+    /// Width of the cuts parallel to the width.
+    pub(crate) crosscut: usize,
+}
+
+impl BladeWidth {
+    /// Returns a blade width that's the same in both directions.
+    pub(crate) const fn uniform(width: usize) -> Self {
+        BladeWidth {
+            rip: width,
+            crosscut: width,
+        }
+    }
+
+    /// Returns the width of the cuts parallel to the length if `along_length`, or parallel to the
+    /// width otherwise.
+    pub(crate) fn along(self, along_length: bool) -> usize {
+        if along_length {
+            self.rip
+        } else {
+            self.crosscut
+        }
+    }
+
+    /// Returns the width of the wider cuts.
+    pub(crate) fn max(self) -> usize {
+        cmp::max(self.rip, self.crosscut)
+    }
+}
+
+/// Returns the free space of a stock piece before any cut pieces are placed, which is the whole
+/// stock piece split along its seams, within its `trim`. The cut along each seam starts at the
+/// seam and is as wide as the `blade_width` in its direction.
+fn free_rects_between_seams(
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+) -> Vec<Rect> {
+    let intervals = |mut start: usize, end: usize, mut offsets: Vec<usize>, blade_width: usize| {
+        offsets.retain(|&offset| offset > start && offset < end);
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        let mut intervals = Vec::with_capacity(offsets.len() + 1);
+        for offset in offsets {
+            if offset > start {
+                intervals.push((start, offset));
+            }
+            start = cmp::max(start, offset.saturating_add(blade_width));
+        }
+        if start < end {
+            intervals.push((start, end));
+        }
+        intervals
+    };
+
+    let xs = intervals(
+        trim.left,
+        width.saturating_sub(trim.right),
+        seams
+            .iter()
+            .filter_map(|seam| match *seam {
+                Seam::ParallelToLength(x) => Some(x),
+                Seam::ParallelToWidth(_) => None,
+            })
+            .collect(),
+        blade_width.rip,
+    );
+    let ys = intervals(
+        trim.bottom,
+        length.saturating_sub(trim.top),
+        seams
+            .iter()
+            .filter_map(|seam| match *seam {
+                Seam::ParallelToLength(_) => None,
+                Seam::ParallelToWidth(y) => Some(y),
+            })
+            .collect(),
+        blade_width.crosscut,
+    );
+
+    let mut rects = Vec::with_capacity(xs.len() * ys.len());
+    for &(y, y_end) in &ys {
+        for &(x, x_end) in &xs {
+            rects.push(Rect {
+                x,
+                y,
+                width: x_end - x,
+                length: y_end - y,
+            });
+        }
+    }
+    rects
 }
 
 /// A rectangular stock piece that is available to cut one or more
 /// cut pieces from.
 #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
-#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Hash, Clone, Debug, Eq, PartialEq)]
 pub struct StockPiece {
     /// Width of rectangular stock piece.
     pub width: usize,
@@ -204,23 +1013,206 @@ pub struct StockPiece {
 
     /// Price to use to optimize for price when not all stock pieces are the same price per unit
     /// area. If optimizing for less waste instead, price can be set to 0 for all stock pieces.
+    /// This is the flat price of each stock piece that's used, including any handling cost, and
+    /// `area_price` can add to it.
     pub price: usize,
 
+    /// Price of the area of this stock piece that the cut pieces use, which is added to `price`,
+    /// such as for a remnant that's charged by the area taken from it. `None` means only `price`
+    /// is charged.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub area_price: Option<AreaPrice>,
+
     /// Quantity of this stock piece available for optimization. `None` means infinite quantity.
     pub quantity: Option<usize>,
+
+    /// ID to be used by the caller to identify this stock piece, such as a SKU. It's passed
+    /// through to the result stock pieces and has no meaning to the optimizer, so it can be set
+    /// to `None` if not needed.
+    pub external_id: Option<usize>,
+
+    /// Seams across this stock piece that cut pieces can't be placed across.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub seams: Vec<Seam>,
+
+    /// Margins trimmed off the edges of this stock piece, which cut pieces can't be placed in.
+    /// The dimensions of the stock piece, and of its result stock pieces, include the margins.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub trim: Trim,
+
+    /// Minimum area of this stock piece that's left over after cutting the cut pieces, such as
+    /// to keep the offcut big enough to clamp again. The optimizer won't place a cut piece on the
+    /// stock piece if it would leave less than this area unused. The area that's trimmed off with
+    /// `trim` isn't counted as left over, so a maximum utilization `u` is a minimum leftover area
+    /// of `(1.0 - u)` times the area within the trim. `None` means there's no minimum.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub min_leftover_area: Option<u64>,
+
+    /// Where this stock piece came from, if it's an offcut of a stock piece in an earlier
+    /// solution, such as from `Solution::offcuts`. It's passed through to the result stock pieces
+    /// and has no meaning to the optimizer.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub source: Option<OffcutSource>,
+
+    /// Grade of this stock piece, such as the quality of its faces, where `'A'` is the best
+    /// grade, `'B'` is the next best, and so on. Grades are compared by their order in the
+    /// alphabet. Cut pieces are only cut from stock pieces that are at least their
+    /// `CutPiece::min_grade`, so `None` only suits cut pieces without a minimum grade.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub grade: Option<char>,
+
+    /// Material of this stock piece, as a key chosen by the caller. Only cut pieces with the same
+    /// `CutPiece::material` are cut from it.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub material: Option<usize>,
+
+    /// Thickness of this stock piece. Only cut pieces with the same `CutPiece::thickness` are cut
+    /// from it.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub thickness: Option<usize>,
+
+    /// Pieces at fixed locations on this stock piece that are already cut or reserved, which the
+    /// optimizer places the cut pieces around, leaving room for a cut between them. Since the
+    /// pieces are at the same location on each copy of the stock piece, a partly used stock piece
+    /// usually has a quantity of `Some(1)`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub fixed_pieces: Vec<FixedPiece>,
+
+    /// Nominal width and length this stock piece is sold by, such as a 1220 by 2440 sheet, when
+    /// the `width` and `length` the optimizer packs are its actual usable size, such as 1250 by
+    /// 2500. It's passed through to the result stock pieces, so they can be matched up with the
+    /// nominal sizes they were priced and bought by, and has no meaning to the optimizer. `None`
+    /// means the nominal size is the same as the usable size.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub nominal_size: Option<(usize, usize)>,
+
+    /// Whether this stock piece needs to be used up before any stock piece that doesn't, such as
+    /// an offcut in inventory that should be consumed before breaking into a full sheet, even if
+    /// the layout is a little worse. It's also used when optimizing each stock size separately,
+    /// so it usually has a `quantity`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub must_use: bool,
+}
+
+/// Price of the area of a stock piece that's used, as `price` for each `area`. See
+/// `StockPiece::area_price`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct AreaPrice {
+    /// Price of each `area` that's used.
+    pub price: usize,
+
+    /// Area the price is for, such as 1,000,000 for a price per square meter when the sizes are
+    /// in millimeters.
+    pub area: u64,
+}
+
+impl AreaPrice {
+    /// Returns the price of `used_area`, rounded up.
+    fn price_of(self, used_area: u64) -> usize {
+        (used_area * self.price as u64).div_ceil(cmp::max(self.area, 1)) as usize
+    }
+
+    /// Returns the price of each unit of area.
+    fn price_per_unit_area(self) -> f64 {
+        self.price as f64 / cmp::max(self.area, 1) as f64
+    }
+}
+
+/// A piece at a fixed location on a stock piece, which is already cut or reserved.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FixedPiece {
+    /// ID to be used by the caller to identify this piece. It's passed through to the result
+    /// stock pieces and has no meaning to the optimizer, so it can be set to `None` if not needed.
+    pub external_id: Option<usize>,
+
+    /// X location of the left side of this piece within the stock piece.
+    pub x: usize,
+
+    /// Y location of the (bottom or top) side of this piece within the stock piece.
+    pub y: usize,
+
+    /// Width of this piece.
+    pub width: usize,
+
+    /// Length of this piece.
+    pub length: usize,
+}
+
+/// Where an offcut that's used as a stock piece came from.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct OffcutSource {
+    /// Serial number of the stock piece the offcut remains from, in the earlier solution.
+    pub serial_number: usize,
+
+    /// ID of the stock piece the offcut remains from.
+    pub external_id: Option<usize>,
+
+    /// X location of the left side of the offcut within the stock piece it remains from.
+    pub x: usize,
+
+    /// Y location of the (bottom or top) side of the offcut within the stock piece it remains
+    /// from.
+    pub y: usize,
 }
 
 impl StockPiece {
-    /// Checks whether of not the cut piece fits within the bounds of this stock piece.
-    fn fits_cut_piece(&self, cut_piece: &CutPieceWithId) -> bool {
-        let rect = Rect {
-            x: 0,
-            y: 0,
-            width: self.width,
-            length: self.length,
-        };
+    /// Checks whether of not the cut piece fits within the bounds of this stock piece, without
+    /// crossing any of its seams, and whether its stock filter allows this stock piece.
+    fn fits_cut_piece(&self, cut_piece: &CutPieceWithId, blade_width: BladeWidth) -> bool {
+        if !cut_piece.allows_stock(self.external_id, self.grade, self.material, self.thickness)
+            || !leaves_min_leftover_area(
+                self.width,
+                self.length,
+                self.trim,
+                self.min_leftover_area,
+                self.fixed_area(),
+                cut_piece,
+            )
+        {
+            return false;
+        }
+
+        let fits =
+            |rect: &Rect| rect.fit_cut_piece(self.pattern_direction, cut_piece, false) != Fit::None;
+
+        if self.seams.is_empty() && self.fixed_pieces.is_empty() {
+            fits(&Rect {
+                x: self.trim.left,
+                y: self.trim.bottom,
+                width: self.width.saturating_sub(self.trim.left + self.trim.right),
+                length: self.length.saturating_sub(self.trim.bottom + self.trim.top),
+            })
+        } else {
+            self.free_rects(blade_width).iter().any(fits)
+        }
+    }
+
+    /// Returns the free space of this stock piece before any cut pieces are placed, which is
+    /// split with guillotine cuts around its fixed pieces.
+    fn free_rects(&self, blade_width: BladeWidth) -> Vec<Rect> {
+        let mut free_rects =
+            free_rects_between_seams(self.width, self.length, &self.seams, self.trim, blade_width);
+        for fixed_piece in &self.fixed_pieces {
+            free_rects = free_rects
+                .iter()
+                .flat_map(|free_rect| free_rect.split_around(&fixed_piece.into(), blade_width))
+                .collect();
+        }
+        free_rects
+    }
 
-        rect.fit_cut_piece(self.pattern_direction, cut_piece, false) != Fit::None
+    /// Returns the total area of the fixed pieces of this stock piece.
+    fn fixed_area(&self) -> u64 {
+        fixed_area(&self.fixed_pieces)
     }
 
     /// Decrement the quantity of this stock piece. If quantity is `None` it will remain `None`.
@@ -243,6 +1235,22 @@ impl StockPiece {
 #[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
 #[derive(Clone, Debug)]
 pub struct ResultStockPiece {
+    /// ID that matches the one on the stock piece that was passed to the optimizer.
+    pub external_id: Option<usize>,
+
+    /// Identifier the optimizer gave this stock piece, which is unique within the solution. The
+    /// stock pieces of a solution are numbered from 1 in the order they're returned, so the same
+    /// input gives the same serial numbers.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub serial_number: usize,
+
+    /// Identifier for this stock piece that's unique across solutions, for sheet labels, cut
+    /// programs, and other systems that need to refer to the same stock piece. It's made from
+    /// `Solution::fingerprint` and `serial_number`, so it stays the same as long as the layout
+    /// does, however the solution is exported.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub sheet_id: String,
+
     /// Width of this stock piece.
     pub width: usize,
 
@@ -252,864 +1260,6508 @@ pub struct ResultStockPiece {
     /// Pattern direction of this stock piece.
     pub pattern_direction: PatternDirection,
 
-    /// Cut pieces to cut from this stock piece.
+    /// Cut pieces to cut from this stock piece. It's empty if the solution only has a summary of
+    /// each stock piece. See `Optimizer::set_result_detail`.
+    #[cfg_attr(feature = "serialize", serde(default))]
     pub cut_pieces: Vec<ResultCutPiece>,
 
-    /// Waste pieces that remain after cutting the cut pieces.
-    pub waste_pieces: Vec<Rect>,
-
-    /// Price of stock piece.
+    /// Waste pieces that remain after cutting the cut pieces. It's empty if the solution doesn't
+    /// include the details of the layout. See `Optimizer::set_result_detail`.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub waste_pieces: Vec<WastePiece>,
+
+    /// The waste pieces that are at least the minimum size set with
+    /// `Optimizer::set_min_reusable_waste`, in either orientation, which are worth keeping as
+    /// inventory.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub reusable_offcuts: Vec<WastePiece>,
+
+    /// The waste pieces that are smaller than the minimum size set with
+    /// `Optimizer::set_min_reusable_waste`.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub scrap: Vec<WastePiece>,
+
+    /// Seams across this stock piece, which are always cut along.
+    pub seams: Vec<Seam>,
+
+    /// Margins trimmed off the edges of this stock piece, which matches the one on the stock
+    /// piece that was passed to the optimizer.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub trim: Trim,
+
+    /// Grade of this stock piece, which matches the one on the stock piece that was passed to
+    /// the optimizer.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub grade: Option<char>,
+
+    /// Material of this stock piece, which matches the one on the stock piece that was passed to
+    /// the optimizer.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub material: Option<usize>,
+
+    /// Thickness of this stock piece, which matches the one on the stock piece that was passed to
+    /// the optimizer.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub thickness: Option<usize>,
+
+    /// Pieces at fixed locations on this stock piece, which match the ones on the stock piece
+    /// that was passed to the optimizer.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub fixed_pieces: Vec<FixedPiece>,
+
+    /// Nominal width and length of this stock piece, which matches the one on the stock piece
+    /// that was passed to the optimizer. The `width` and `length` are the usable size the cut
+    /// pieces were packed in.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub nominal_size: Option<(usize, usize)>,
+
+    /// Where this stock piece came from, which matches the one on the stock piece that was
+    /// passed to the optimizer.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub source: Option<OffcutSource>,
+
+    /// The free space that remains after cutting the cut pieces, as the set of maximal
+    /// rectangles that don't overlap any cut piece or the cuts around it. Unlike `waste_pieces`,
+    /// these rectangles can overlap each other, and each one is as large as possible, so they're
+    /// suitable for laying out another job on the remainder of this stock piece. They have the
+    /// pattern direction of this stock piece.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub maximal_free_rects: Vec<WastePiece>,
+
+    /// The tree of guillotine cuts that cut up the area of this stock piece within the trim,
+    /// starting with the cuts along the seams, then preferring cuts parallel to the length. It's
+    /// `None` if the layout can't be cut with guillotine cuts, such as some layouts from nested
+    /// optimization.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub cut_tree: Option<CutNode>,
+
+    /// Price of stock piece, including the per-area price of the area used by cut pieces.
     pub price: usize,
+
+    /// Cost of the area of this stock piece that isn't used by cut pieces, based on the price
+    /// per unit area of the stock piece.
+    pub waste_cost: f64,
+
+    /// Fitness score the optimizer gave the layout of this stock piece.
+    /// Ranges between 0.0 and 1.0 inclusive, with 1.0 being a layout with no waste.
+    pub fitness: f64,
+
+    /// Fraction of the area of this stock piece that is used by cut pieces.
+    pub utilization: f64,
+
+    /// Total length of the cuts needed to cut the cut pieces out of this stock piece, including
+    /// the cuts along its seams. For guillotine optimization, this is the length of each
+    /// guillotine cut across the part of the stock piece it splits. For nested optimization, it's
+    /// the perimeter of each cut piece, except for the sides on an edge of the stock piece, and
+    /// with the sides of cut pieces that are a cut apart counted once, since one cut separates
+    /// them.
+    pub cut_length: u64,
+
+    /// Area of this stock piece lost to the width of the cuts, which can't be avoided. The
+    /// margins trimmed off its edges and its fixed pieces aren't counted.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub kerf_area: u64,
+
+    /// Area of the waste pieces of this stock piece, both the reusable offcuts and the scrap,
+    /// which is the waste that a better layout can reduce.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub waste_area: u64,
+
+    /// Explanation of how the layout of this stock piece was produced. This is only set when
+    /// diagnostics are enabled with `Optimizer::set_diagnostics`.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub diagnostics: Option<StockPieceDiagnostics>,
+
+    /// Annotations attached to this stock piece with `ResultStockPiece::annotate`, such as the
+    /// machine or operator that will cut it.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub annotations: BTreeMap<String, String>,
 }
 
-/// A rectangle
+/// Machine-readable explanation of how the layout of a stock piece was produced.
 #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
-#[derive(Copy, Clone, Debug, Default)]
-pub struct Rect {
-    /// X location of this rectangle.
-    x: usize,
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StockPieceDiagnostics {
+    /// Names of the distinct heuristics used to place cut pieces on this stock piece, in the
+    /// order they were first used.
+    pub heuristics: Vec<String>,
+
+    /// Number of candidate placements that were rejected because the cut piece would only fit
+    /// with a different pattern direction or rotation than was allowed.
+    pub rejected_placements: usize,
+
+    /// Area of this stock piece lost to the width of the cuts.
+    pub kerf_area: u64,
+}
 
-    /// Y location of this rectangle.
-    y: usize,
+/// How the heuristics did in one pass of the optimizer, which is a run with one set of stock
+/// pieces, such as every stock size mixed or a single stock size. See `Solution::pass_stats`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PassStats {
+    /// Index of the pass, which matches the phase of `ProgressEvent::PhaseStarted`.
+    pub phase: usize,
 
-    /// Width of this rectangle.
-    width: usize,
+    /// Fitness of the best solution of the pass.
+    pub best_fitness: f64,
 
-    /// Length of this rectangle.
-    length: usize,
+    /// How each heuristic that seeded layouts in the pass did, in the order they were tried.
+    pub heuristics: Vec<HeuristicStats>,
 }
 
-impl Rect {
-    fn fit_cut_piece(
-        &self,
-        pattern_direction: PatternDirection,
-        cut_piece: &CutPieceWithId,
-        prefer_rotated: bool,
-    ) -> Fit {
-        let upright_fit = if cut_piece.pattern_direction == pattern_direction {
-            if cut_piece.width == self.width && cut_piece.length == self.length {
-                Some(Fit::UprightExact)
-            } else if cut_piece.width <= self.width && cut_piece.length <= self.length {
-                Some(Fit::Upright)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+/// How one heuristic did in a pass of the optimizer.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeuristicStats {
+    /// Name of the heuristic, as used by `HeuristicProfile::Custom`.
+    pub name: String,
 
-        let rotated_fit =
-            if cut_piece.can_rotate && cut_piece.pattern_direction.rotated() == pattern_direction {
-                if cut_piece.length == self.width && cut_piece.width == self.length {
-                    Some(Fit::RotatedExact)
-                } else if cut_piece.length <= self.width && cut_piece.width <= self.length {
-                    Some(Fit::Rotated)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
+    /// Number of layouts in the initial population whose cut pieces were all placed with this
+    /// heuristic.
+    pub layouts: usize,
 
-        match (upright_fit, rotated_fit) {
-            (Some(upright_fit), Some(rotated_fit)) => {
-                if prefer_rotated {
-                    rotated_fit
+    /// Average fitness of the layouts that were placed with this heuristic.
+    pub mean_fitness: f64,
+
+    /// Fitness of the best layout that was placed with this heuristic.
+    pub best_fitness: f64,
+
+    /// Number of stock pieces in the best solution of the pass that this heuristic placed cut
+    /// pieces on.
+    pub winning_stock_pieces: usize,
+}
+
+impl ResultStockPiece {
+    fn new(
+        external_id: Option<usize>,
+        width: usize,
+        length: usize,
+        pattern_direction: PatternDirection,
+        mut cut_pieces: Vec<ResultCutPiece>,
+        waste_pieces: Vec<Rect>,
+        price: usize,
+    ) -> Self {
+        // Cut pieces that can be cut from any pattern direction take on the one of the stock piece.
+        for cut_piece in &mut cut_pieces {
+            if cut_piece.pattern_direction == PatternDirection::Either {
+                cut_piece.pattern_direction = pattern_direction;
+                cut_piece.pattern_direction_effective = if cut_piece.is_rotated {
+                    pattern_direction.rotated()
                 } else {
-                    upright_fit
-                }
+                    pattern_direction
+                };
+            }
+            if cut_piece.pattern_direction != pattern_direction {
+                cut_piece.pattern_mismatch = true;
+                cut_piece.rotated_to_match_pattern = false;
             }
-            (Some(upright_fit), None) => upright_fit,
-            (None, Some(rotated_fit)) => rotated_fit,
-            (None, None) => Fit::None,
         }
-    }
 
-    fn contains(&self, rect: &Rect) -> bool {
-        rect.x >= self.x
-            && rect.x + rect.width <= self.x + self.width
-            && rect.y >= self.y
-            && rect.y + rect.length <= self.y + self.length
-    }
-}
+        let stock_area = width as u64 * length as u64;
+        let used_area = cut_pieces
+            .iter()
+            .map(|cp| cp.width as u64 * cp.length as u64)
+            .sum::<u64>();
+        let (waste_cost, utilization) = if stock_area == 0 {
+            (0.0, 0.0)
+        } else {
+            (
+                (stock_area - used_area) as f64 * price as f64 / stock_area as f64,
+                used_area as f64 / stock_area as f64,
+            )
+        };
 
-impl From<&ResultCutPiece> for Rect {
-    fn from(cut_piece: &ResultCutPiece) -> Self {
         Self {
-            x: cut_piece.x,
-            y: cut_piece.y,
-            width: cut_piece.width,
-            length: cut_piece.length,
+            external_id,
+            serial_number: 0,
+            sheet_id: String::new(),
+            width,
+            length,
+            pattern_direction,
+            cut_pieces,
+            waste_pieces: waste_pieces
+                .into_iter()
+                .map(|rect| WastePiece {
+                    x: rect.x,
+                    y: rect.y,
+                    width: rect.width,
+                    length: rect.length,
+                    pattern_direction,
+                })
+                .collect(),
+            reusable_offcuts: Vec::new(),
+            scrap: Vec::new(),
+            seams: Vec::new(),
+            trim: Trim::default(),
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            source: None,
+            maximal_free_rects: Vec::new(),
+            cut_tree: None,
+            price,
+            waste_cost,
+            fitness: 0.0,
+            utilization,
+            cut_length: 0,
+            kerf_area: 0,
+            waste_area: 0,
+            diagnostics: None,
+            annotations: BTreeMap::new(),
+            material: None,
+            thickness: None,
         }
     }
-}
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-enum Fit {
-    None,
-    UprightExact,
-    RotatedExact,
-    Upright,
-    Rotated,
-}
+    /// Attaches an annotation to this stock piece, replacing any annotation with the same `key`.
+    pub fn annotate(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.annotations.insert(key.into(), value.into());
+        self
+    }
 
-impl Fit {
-    fn is_none(self) -> bool {
-        self == Fit::None
+    /// Returns the annotation attached to this stock piece with `key`.
+    pub fn annotation(&self, key: &str) -> Option<&str> {
+        self.annotations.get(key).map(String::as_str)
     }
 
-    fn is_upright(self) -> bool {
-        self == Fit::Upright || self == Fit::UprightExact
+    /// Returns the largest empty rectangle of this stock piece, which is the largest of its
+    /// `maximal_free_rects`, regardless of how the waste pieces were split by the cuts. This is the
+    /// largest offcut that could be cut from the remainder of this stock piece, so it can be used
+    /// to decide whether the remainder is worth keeping. Ties are broken by the lowest `y`, then
+    /// the lowest `x`. Returns `None` if there's no free space left.
+    pub fn largest_empty_rect(&self) -> Option<WastePiece> {
+        self.maximal_free_rects
+            .iter()
+            .max_by_key(|rect| {
+                (
+                    rect.width as u64 * rect.length as u64,
+                    cmp::Reverse((rect.y, rect.x)),
+                )
+            })
+            .copied()
     }
 
-    fn is_rotated(self) -> bool {
-        self == Fit::Rotated || self == Fit::RotatedExact
+    /// Sets the fitness score that the bin of this stock piece computed.
+    fn with_fitness(mut self, fitness: f64) -> Self {
+        self.fitness = fitness;
+        self
     }
-}
 
-/// Represents a bin used for bin-packing.
-trait Bin {
-    /// Heuristic used for inserting `CutPiece`s.
-    type Heuristic;
+    /// Sets the seams of this stock piece.
+    fn with_seams(mut self, seams: Vec<Seam>) -> Self {
+        self.seams = seams;
+        self
+    }
 
-    /// Creates a new `Bin`.
-    fn new(
-        width: usize,
-        length: usize,
-        blade_width: usize,
-        pattern_direction: PatternDirection,
-        price: usize,
-    ) -> Self;
+    /// Sets the margins trimmed off the edges of this stock piece.
+    fn with_trim(mut self, trim: Trim) -> Self {
+        self.trim = trim;
+        self
+    }
 
-    /// Computes the fitness of this `Bin` on a scale of 0.0 to 1.0, with 1.0 being the most fit.
-    fn fitness(&self) -> f64;
+    /// Sets the grade of this stock piece.
+    fn with_grade(mut self, grade: Option<char>) -> Self {
+        self.grade = grade;
+        self
+    }
 
-    fn price(&self) -> usize;
+    /// Sets the thickness of this stock piece.
+    fn with_thickness(mut self, thickness: Option<usize>) -> Self {
+        self.thickness = thickness;
+        self
+    }
 
-    /// Removes `UsedCutPiece`s from this `Bin` and returns how many were removed.
-    fn remove_cut_pieces<I>(&mut self, cut_pieces: I) -> usize
-    where
-        I: Iterator,
-        I::Item: Borrow<UsedCutPiece>;
+    /// Sets the material of this stock piece.
+    fn with_material(mut self, material: Option<usize>) -> Self {
+        self.material = material;
+        self
+    }
 
-    /// Returns an iterator over the `UsedCutPiece`s in this `Bin`.
-    fn cut_pieces(&self) -> std::slice::Iter<'_, UsedCutPiece>;
+    /// Sets the nominal size of this stock piece.
+    fn with_nominal_size(mut self, nominal_size: Option<(usize, usize)>) -> Self {
+        self.nominal_size = nominal_size;
+        self
+    }
 
-    /// Returns the possible heuristics that can be passed to `insert_cut_piece_with_heuristic`.
-    fn possible_heuristics() -> Vec<Self::Heuristic>;
+    /// Sets the pieces at fixed locations on this stock piece.
+    fn with_fixed_pieces(mut self, fixed_pieces: Vec<FixedPiece>) -> Self {
+        self.fixed_pieces = fixed_pieces;
+        self
+    }
 
-    /// Inserts the `CutPieceWithId` into this `Bin` using the specified heuristic. Returns whether
-    /// the insert succeeded.
-    fn insert_cut_piece_with_heuristic(
-        &mut self,
-        cut_piece: &CutPieceWithId,
-        heuristic: &Self::Heuristic,
-    ) -> bool;
+    /// Sets where this stock piece came from.
+    fn with_source(mut self, source: Option<OffcutSource>) -> Self {
+        self.source = source;
+        self
+    }
 
-    /// Inserts the `CutPieceWithId` into this `Bin` using a random heuristic. Returns whether
-    /// the insert succeeded.
-    fn insert_cut_piece_random_heuristic<R>(
-        &mut self,
-        cut_piece: &CutPieceWithId,
-        rng: &mut R,
-    ) -> bool
-    where
-        R: Rng + ?Sized;
+    /// Computes the maximal free rectangles of this stock piece, leaving room for cuts of
+    /// `blade_width` around the cut pieces and the fixed pieces.
+    fn with_maximal_free_rects(mut self, blade_width: BladeWidth) -> Self {
+        let occupied: Vec<Rect> = self
+            .cut_pieces
+            .iter()
+            .map(Into::into)
+            .chain(self.fixed_pieces.iter().map(Into::into))
+            .collect();
+        self.maximal_free_rects = maxrects::maximal_free_rects(
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
+            blade_width,
+            &occupied,
+        )
+        .into_iter()
+        .map(|rect| WastePiece {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            length: rect.length,
+            pattern_direction: self.pattern_direction,
+        })
+        .collect();
+        self
+    }
 
-    /// Returns whether the `StockPiece` is equivalent to this `Bin`.
-    fn matches_stock_piece(&self, stock_piece: &StockPiece) -> bool;
-}
+    /// Computes the length of the guillotine cuts that cut the cut pieces out of this stock
+    /// piece, with cuts of `blade_width`.
+    fn with_guillotine_cut_length(mut self, blade_width: BladeWidth) -> Self {
+        let rects: Vec<Rect> = self.cut_pieces.iter().map(Into::into).collect();
+        self.cut_length = cuts::guillotine_cut_length(
+            &rects,
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
+            blade_width,
+        );
+        self
+    }
 
-struct OptimizerUnit<'a, B>
-where
-    B: Bin,
-{
-    bins: Vec<B>,
+    /// Computes the length of the cuts around the cut pieces of this stock piece, with cuts of
+    /// `blade_width`.
+    fn with_nested_cut_length(mut self, blade_width: BladeWidth) -> Self {
+        let rects: Vec<Rect> = self.cut_pieces.iter().map(Into::into).collect();
+        self.cut_length = cuts::nested_cut_length(
+            &rects,
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
+            blade_width,
+        );
+        self
+    }
 
-    // All of the possible stock pieces. It remains constant.
-    possible_stock_pieces: &'a [StockPiece],
+    /// Computes the area of this stock piece that's lost to the width of the cuts, and the area
+    /// of its waste pieces. The trim and fixed pieces need to be set first.
+    fn with_waste_areas(mut self) -> Self {
+        let trimmed_area = self.width.saturating_sub(self.trim.left + self.trim.right) as u64
+            * self.length.saturating_sub(self.trim.bottom + self.trim.top) as u64;
+        self.waste_area = self
+            .waste_pieces
+            .iter()
+            .map(|wp| wp.width as u64 * wp.length as u64)
+            .sum();
+        self.kerf_area = trimmed_area
+            .saturating_sub(self.used_area() + self.waste_area + fixed_area(&self.fixed_pieces));
+        self
+    }
 
-    // Stock pieces that are currently available to use for new bins.
-    available_stock_pieces: Vec<StockPiece>,
+    /// Builds the diagnostics for this stock piece from what was collected by its bin.
+    fn with_diagnostics(mut self, heuristics: Vec<String>, rejected_placements: usize) -> Self {
+        self.diagnostics = Some(StockPieceDiagnostics {
+            heuristics,
+            rejected_placements,
+            kerf_area: self.kerf_area,
+        });
+        self
+    }
 
-    // Cut pieces that couldn't be added to bins.
-    unused_cut_pieces: FnvHashSet<CutPieceWithId>,
+    /// Creates a stock piece from this result stock piece, with its cut pieces as fixed pieces, so
+    /// another optimization can place more cut pieces in its waste without moving the ones already
+    /// on it, such as to freeze a stock piece that's already cut, try inserting more cut pieces, or
+    /// resume a job. The fixed pieces keep the external IDs of the cut pieces, and are reported in
+    /// `ResultStockPiece::fixed_pieces` of the new solution along with the fixed pieces this stock
+    /// piece already had. The stock piece has a quantity of `Some(1)`, and keeps the external ID,
+    /// pattern direction, seams, trim, grade, material, thickness, source, and nominal size of this
+    /// stock piece. Its layout is in the same coordinates as this stock piece, so if this stock
+    /// piece was mirrored by `Optimizer::set_mirror`, the new optimization shouldn't mirror it
+    /// again.
+    pub fn to_stock_piece(&self, price: usize) -> StockPiece {
+        StockPiece {
+            width: self.width,
+            length: self.length,
+            pattern_direction: self.pattern_direction,
+            price,
+            quantity: Some(1),
+            external_id: self.external_id,
+            seams: self.seams.clone(),
+            trim: self.trim,
+            min_leftover_area: None,
+            source: self.source,
+            grade: self.grade,
+            material: self.material,
+            thickness: self.thickness,
+            fixed_pieces: self
+                .fixed_pieces
+                .iter()
+                .copied()
+                .chain(self.cut_pieces.iter().map(|cut_piece| FixedPiece {
+                    external_id: cut_piece.external_id,
+                    x: cut_piece.x,
+                    y: cut_piece.y,
+                    width: cut_piece.width,
+                    length: cut_piece.length,
+                }))
+                .collect(),
+            nominal_size: self.nominal_size,
+            must_use: false,
+            area_price: None,
+        }
+    }
 
-    blade_width: usize,
-}
+    /// Area of this stock piece that is used by cut pieces.
+    fn used_area(&self) -> u64 {
+        self.cut_pieces
+            .iter()
+            .map(|cp| cp.width as u64 * cp.length as u64)
+            .sum()
+    }
 
-impl<'a, B> Clone for OptimizerUnit<'a, B>
-where
-    B: Bin + Clone,
-{
-    fn clone(&self) -> Self {
-        Self {
-            bins: self.bins.clone(),
-            possible_stock_pieces: self.possible_stock_pieces,
-            available_stock_pieces: self.available_stock_pieces.clone(),
-            unused_cut_pieces: self.unused_cut_pieces.clone(),
-            blade_width: self.blade_width,
+    /// Mirrors the layout of this stock piece, where the cuts along its seams are as wide as the
+    /// `blade_width` in their direction. The source of the stock piece isn't mirrored, since it's
+    /// a location on another stock piece.
+    fn mirror(&mut self, mirror: Mirror, blade_width: BladeWidth) {
+        let (horizontal, vertical) = match mirror {
+            Mirror::None => return,
+            Mirror::Horizontal => (true, false),
+            Mirror::Vertical => (false, true),
+            Mirror::Both => (true, true),
+        };
+        let (width, length) = (self.width, self.length);
+        let flip_x = |x: usize, w: usize| if horizontal { width - x - w } else { x };
+        let flip_y = |y: usize, l: usize| if vertical { length - y - l } else { y };
+
+        for cut_piece in &mut self.cut_pieces {
+            cut_piece.x = flip_x(cut_piece.x, cut_piece.width);
+            cut_piece.y = flip_y(cut_piece.y, cut_piece.length);
+            cut_piece.edge_banding = cut_piece.edge_banding.mirrored(horizontal, vertical);
+        }
+        for waste_piece in &mut self.waste_pieces {
+            waste_piece.x = flip_x(waste_piece.x, waste_piece.width);
+            waste_piece.y = flip_y(waste_piece.y, waste_piece.length);
+        }
+        for rect in &mut self.maximal_free_rects {
+            rect.x = flip_x(rect.x, rect.width);
+            rect.y = flip_y(rect.y, rect.length);
+        }
+        for fixed_piece in &mut self.fixed_pieces {
+            fixed_piece.x = flip_x(fixed_piece.x, fixed_piece.width);
+            fixed_piece.y = flip_y(fixed_piece.y, fixed_piece.length);
+        }
+        for seam in &mut self.seams {
+            *seam = match *seam {
+                Seam::ParallelToLength(x) if horizontal => {
+                    Seam::ParallelToLength(width.saturating_sub(x + blade_width.rip))
+                }
+                Seam::ParallelToWidth(y) if vertical => {
+                    Seam::ParallelToWidth(length.saturating_sub(y + blade_width.crosscut))
+                }
+                seam => seam,
+            };
+        }
+        if horizontal {
+            mem::swap(&mut self.trim.left, &mut self.trim.right);
+        }
+        if vertical {
+            mem::swap(&mut self.trim.bottom, &mut self.trim.top);
         }
     }
-}
 
-impl<'a, B> OptimizerUnit<'a, B>
-where
-    B: Bin,
-{
-    fn with_random_heuristics<R>(
-        possible_stock_pieces: &'a [StockPiece],
-        cut_pieces: &[&CutPieceWithId],
-        blade_width: usize,
-        rng: &mut R,
-    ) -> Result<OptimizerUnit<'a, B>>
-    where
-        R: Rng + ?Sized,
-    {
-        let mut unit = OptimizerUnit {
-            bins: Vec::new(),
-            possible_stock_pieces,
-            available_stock_pieces: possible_stock_pieces.to_vec(),
-            unused_cut_pieces: Default::default(),
+    /// Returns this stock piece with its cut pieces slid toward its origin, and the waste pieces,
+    /// maximal free rectangles, cut length, and kerf and waste areas updated to match. See
+    /// `Solution::compact`. The reusable offcuts and scrap need to be split again.
+    fn compacted(mut self, blade_width: BladeWidth) -> Self {
+        let mut rects: Vec<Rect> = self.cut_pieces.iter().map(Into::into).collect();
+        let obstacles: Vec<Rect> = self.fixed_pieces.iter().map(Into::into).collect();
+        if !cuts::compact(
+            &mut rects,
+            &obstacles,
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
             blade_width,
+        ) {
+            return self;
+        }
+
+        for (cut_piece, rect) in self.cut_pieces.iter_mut().zip(&rects) {
+            cut_piece.x = rect.x;
+            cut_piece.y = rect.y;
+        }
+        rects.extend_from_slice(&obstacles);
+        let (width, length, seams, trim) = (self.width, self.length, &self.seams, self.trim);
+        let guillotine =
+            cuts::guillotine_stages(&rects, width, length, seams, trim, blade_width) != usize::MAX;
+        let waste_pieces = if guillotine {
+            cuts::guillotine_waste(&rects, width, length, seams, trim, blade_width)
+        } else {
+            maxrects::disjoint_free_rects(width, length, seams, trim, blade_width, &rects)
         };
+        let pattern_direction = self.pattern_direction;
+        self.waste_pieces = waste_pieces
+            .into_iter()
+            .map(|rect| WastePiece {
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                length: rect.length,
+                pattern_direction,
+            })
+            .collect();
+        self.reusable_offcuts = Vec::new();
+        self.scrap = Vec::new();
 
-        for cut_piece in cut_pieces {
-            if !unit.first_fit_random_heuristics(cut_piece, rng) {
-                unit.unused_cut_pieces.insert((*cut_piece).clone());
-            }
+        let mut stock_piece = self.with_waste_areas().with_maximal_free_rects(blade_width);
+        stock_piece = if guillotine {
+            stock_piece.with_guillotine_cut_length(blade_width)
+        } else {
+            stock_piece.with_nested_cut_length(blade_width)
+        };
+        if let Some(diagnostics) = &mut stock_piece.diagnostics {
+            diagnostics.kerf_area = stock_piece.kerf_area;
         }
+        stock_piece
+    }
 
-        Ok(unit)
+    /// Removes the details of this stock piece that aren't included at the `detail` level.
+    fn reduce_detail(&mut self, detail: ResultDetail) {
+        if detail == ResultDetail::Full {
+            return;
+        }
+        self.waste_pieces = Vec::new();
+        self.reusable_offcuts = Vec::new();
+        self.scrap = Vec::new();
+        self.maximal_free_rects = Vec::new();
+        self.cut_tree = None;
+        self.diagnostics = None;
+        if detail == ResultDetail::Summary {
+            self.cut_pieces = Vec::new();
+        }
     }
 
-    fn with_heuristic<R>(
-        possible_stock_pieces: &'a [StockPiece],
-        cut_pieces: &[&CutPieceWithId],
-        blade_width: usize,
-        heuristic: &B::Heuristic,
-        rng: &mut R,
-    ) -> Result<OptimizerUnit<'a, B>>
-    where
-        R: Rng + ?Sized,
-    {
-        let mut unit = OptimizerUnit {
-            bins: Vec::new(),
-            possible_stock_pieces,
-            available_stock_pieces: possible_stock_pieces.to_vec(),
-            unused_cut_pieces: Default::default(),
-            blade_width,
-        };
+    /// Builds the tree of guillotine cuts that cut up this stock piece, with cuts of
+    /// `blade_width`. This needs to be done after the cut pieces are sorted, since the tree
+    /// refers to them by index.
+    fn build_cut_tree(&mut self, blade_width: BladeWidth) {
+        self.cut_tree = cuts::cut_tree(self, blade_width);
+    }
 
-        for cut_piece in cut_pieces {
-            if !unit.first_fit_with_heuristic(cut_piece, heuristic, rng) {
-                unit.unused_cut_pieces.insert((*cut_piece).clone());
+    /// Splits the waste pieces of this stock piece into the reusable offcuts that are at least
+    /// `min_width` by `min_length`, in either orientation, and the scrap.
+    fn split_waste(&mut self, (min_width, min_length): (usize, usize)) {
+        (self.reusable_offcuts, self.scrap) = self.waste_pieces.iter().partition(|waste_piece| {
+            (waste_piece.width >= min_width && waste_piece.length >= min_length)
+                || (waste_piece.width >= min_length && waste_piece.length >= min_width)
+        });
+    }
+
+    /// Sorts the cut pieces of this stock piece by the given order.
+    fn sort_cut_pieces(&mut self, order: CutPieceOrder) {
+        match order {
+            CutPieceOrder::Placement => (),
+            CutPieceOrder::YThenX => self.cut_pieces.sort_by_key(|cp| (cp.y, cp.x)),
+            CutPieceOrder::XThenY => self.cut_pieces.sort_by_key(|cp| (cp.x, cp.y)),
+            CutPieceOrder::Cutting => {
+                let rects: Vec<Rect> = self.cut_pieces.iter().map(Into::into).collect();
+                let mut cut_pieces: Vec<Option<ResultCutPiece>> =
+                    self.cut_pieces.drain(..).map(Some).collect();
+                self.cut_pieces = cuts::cutting_order(&rects)
+                    .into_iter()
+                    .filter_map(|i| cut_pieces[i].take())
+                    .collect();
             }
         }
-
-        Ok(unit)
     }
+}
 
-    pub(crate) fn generate_initial_units(
-        possible_stock_pieces: &'a [StockPiece],
-        mut cut_pieces: Vec<&CutPieceWithId>,
-        blade_width: usize,
-        random_seed: u64,
-    ) -> Result<Vec<OptimizerUnit<'a, B>>> {
-        let mut set = FnvHashSet::default();
-        for cut_piece in &cut_pieces {
-            set.insert((
-                cut_piece.width,
-                cut_piece.length,
-                cut_piece.can_rotate,
-                cut_piece.pattern_direction,
-            ));
-        }
-        let unique_cut_pieces = set.len();
+/// Order of the stock pieces in a `Solution`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum StockPieceOrder {
+    /// Largest stock pieces first.
+    #[default]
+    SizeDescending,
+
+    /// Stock pieces with the most used area first.
+    UtilizationDescending,
+
+    /// Stock pieces with the least used area first.
+    UtilizationAscending,
+
+    /// Ordered by the stock piece `external_id`, with `None` last. Stock pieces with the same
+    /// `external_id` are ordered largest first.
+    ExternalId,
+}
+
+/// Order of the cut pieces within each stock piece of a `Solution`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum CutPieceOrder {
+    /// The order the optimizer placed the cut pieces.
+    #[default]
+    Placement,
+
+    /// Ordered by y location, then by x location.
+    YThenX,
+
+    /// Ordered by x location, then by y location.
+    XThenY,
+
+    /// The order the cut pieces come off the stock piece when cutting it up with guillotine cuts,
+    /// starting with cuts parallel to the length. Cut pieces that can't be separated with
+    /// guillotine cuts are ordered by y location, then by x location.
+    Cutting,
+}
+
+/// How the layouts of a `Solution` are mirrored, such as for a machine that measures from the
+/// right edge of a stock piece instead of the left.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum Mirror {
+    /// The layouts aren't mirrored.
+    #[default]
+    None,
+
+    /// The x locations are mirrored, so the left side of each stock piece becomes the right side.
+    Horizontal,
+
+    /// The y locations are mirrored, so the side of each stock piece at y location 0 becomes the
+    /// side at its length.
+    Vertical,
+
+    /// The layouts are flipped both horizontally and vertically.
+    Both,
+}
+
+/// How much of the detail of each stock piece a `Solution` includes, which can be reduced to
+/// make large solutions smaller, such as when they're serialized.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResultDetail {
+    /// Everything, including the waste pieces, free rectangles and cut tree of each stock piece.
+    #[default]
+    Full,
+
+    /// The cut pieces of each stock piece, without its waste pieces, reusable offcuts, scrap,
+    /// maximal free rectangles, cut tree, or diagnostics.
+    PiecesOnly,
+
+    /// Only the totals of each stock piece, such as its price, utilization and areas, without
+    /// its cut pieces either.
+    Summary,
+}
+
+/// Direction of a cut across a stock piece, or of the cuts that split a region of a stock piece
+/// into strips.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum CutDirection {
+    /// Cuts parallel to the length, which split the region into strips along its width. These are
+    /// rip cuts when the length is along the grain.
+    ParallelToLength,
+
+    /// Cuts parallel to the width, which split the region into strips along its length. These are
+    /// crosscuts when the length is along the grain.
+    ParallelToWidth,
+}
+
+/// A node of the tree of guillotine cuts that cut up a stock piece, which is
+/// `ResultStockPiece::cut_tree`. Each node is a region of the stock piece, which is either cut in
+/// two by a cut all the way across it, or is a cut piece, a fixed piece, or waste.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CutNode {
+    /// A cut all the way across the region, which splits it in two.
+    Cut {
+        /// Direction of the cut.
+        direction: CutDirection,
+
+        /// Where the cut starts, which is its x location for cuts parallel to the length, and its
+        /// y location for cuts parallel to the width. The cut is as wide as the blade, or as the
+        /// cut along a seam.
+        position: usize,
+
+        /// The part of the region to the left of or below the cut.
+        first: Box<CutNode>,
+
+        /// The part of the region to the right of or above the cut.
+        second: Box<CutNode>,
+    },
+
+    /// The region is the cut piece at this index of `ResultStockPiece::cut_pieces`.
+    CutPiece(usize),
+
+    /// The region is the fixed piece at this index of `ResultStockPiece::fixed_pieces`.
+    FixedPiece(usize),
+
+    /// The region is waste, which is this waste piece.
+    Waste(WastePiece),
+}
+
+impl CutNode {
+    /// Returns the cuts of this tree in an order they can be made in, with each cut coming after
+    /// the cut that split off the region it's in, as the direction and position of each cut.
+    pub fn cuts(&self) -> Vec<(CutDirection, usize)> {
+        let mut cuts = Vec::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if let CutNode::Cut {
+                direction,
+                position,
+                first,
+                second,
+            } = node
+            {
+                cuts.push((*direction, *position));
+                stack.push(second);
+                stack.push(first);
+            }
+        }
+        cuts
+    }
+}
+
+/// A rectangle
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    /// X location of this rectangle.
+    x: usize,
+
+    /// Y location of this rectangle.
+    y: usize,
+
+    /// Width of this rectangle.
+    width: usize,
+
+    /// Length of this rectangle.
+    length: usize,
+}
+
+impl Rect {
+    fn fit_cut_piece(
+        &self,
+        pattern_direction: PatternDirection,
+        cut_piece: &CutPieceWithId,
+        prefer_rotated: bool,
+    ) -> Fit {
+        let upright_fit = if cut_piece.width == self.width && cut_piece.length == self.length {
+            Some(Fit::UprightExact)
+        } else if cut_piece.width <= self.width && cut_piece.length <= self.length {
+            Some(Fit::Upright)
+        } else {
+            None
+        };
+
+        let rotated_fit = if !cut_piece.can_rotate {
+            None
+        } else if cut_piece.length == self.width && cut_piece.width == self.length {
+            Some(Fit::RotatedExact)
+        } else if cut_piece.length <= self.width && cut_piece.width <= self.length {
+            Some(Fit::Rotated)
+        } else {
+            None
+        };
+
+        let upright_matches = cut_piece.pattern_direction.matches(pattern_direction);
+        let rotated_matches = cut_piece
+            .pattern_direction
+            .rotated()
+            .matches(pattern_direction);
+
+        // A cut piece that's allowed to go against the pattern only does when it doesn't fit
+        // with the pattern.
+        let (upright_fit, rotated_fit) = if cut_piece.allow_pattern_mismatch
+            && !(upright_matches && upright_fit.is_some())
+            && !(rotated_matches && rotated_fit.is_some())
+        {
+            (upright_fit, rotated_fit)
+        } else {
+            (
+                upright_fit.filter(|_| upright_matches),
+                rotated_fit.filter(|_| rotated_matches),
+            )
+        };
+
+        match (upright_fit, rotated_fit) {
+            (Some(upright_fit), Some(rotated_fit)) => {
+                if prefer_rotated {
+                    rotated_fit
+                } else {
+                    upright_fit
+                }
+            }
+            (Some(upright_fit), None) => upright_fit,
+            (None, Some(rotated_fit)) => rotated_fit,
+            (None, None) => Fit::None,
+        }
+    }
+
+    /// Like `fit_cut_piece`, but for a cut piece placed in the corner of this rectangle within a
+    /// stock piece of `width` and `length`. If the placement would be farther from the edges of
+    /// the stock piece than the `max_edge_distance` of the cut piece allows, it tries the other
+    /// orientation.
+    ///
+    /// Only the placement at the corner at `x` and `y` is checked, since that's the only place in
+    /// a free rectangle that the bins put cut pieces. A bin that placed them anywhere else would
+    /// need to check `max_edge_distance` for that placement itself.
+    fn fit_cut_piece_in_stock(
+        &self,
+        width: usize,
+        length: usize,
+        pattern_direction: PatternDirection,
+        cut_piece: &CutPieceWithId,
+        prefer_rotated: bool,
+    ) -> Fit {
+        let max_edge_distance = match cut_piece.max_edge_distance {
+            Some(max_edge_distance) => max_edge_distance,
+            None => return self.fit_cut_piece(pattern_direction, cut_piece, prefer_rotated),
+        };
+
+        let near_edge = |fit: Fit| {
+            let (piece_width, piece_length) = if fit.is_rotated() {
+                (cut_piece.length, cut_piece.width)
+            } else {
+                (cut_piece.width, cut_piece.length)
+            };
+            let edge_distance = cmp::min(
+                cmp::min(self.x, width - (self.x + piece_width)),
+                cmp::min(self.y, length - (self.y + piece_length)),
+            );
+            edge_distance <= max_edge_distance
+        };
+
+        for prefer_rotated in [prefer_rotated, !prefer_rotated] {
+            let fit = self.fit_cut_piece(pattern_direction, cut_piece, prefer_rotated);
+            if fit.is_none() || near_edge(fit) {
+                return fit;
+            }
+        }
+
+        Fit::None
+    }
+
+    /// Checks whether the cut piece would fit within this rectangle in either orientation if
+    /// pattern direction and rotation were ignored.
+    fn fits_dimensions(&self, cut_piece: &CutPieceWithId) -> bool {
+        (cut_piece.width <= self.width && cut_piece.length <= self.length)
+            || (cut_piece.length <= self.width && cut_piece.width <= self.length)
+    }
+
+    /// Counts the rectangles that the cut piece would fit in if pattern direction and rotation
+    /// were ignored, but doesn't fit in with them.
+    fn count_rejected_placements<'r, I>(
+        rects: I,
+        pattern_direction: PatternDirection,
+        cut_piece: &CutPieceWithId,
+    ) -> usize
+    where
+        I: IntoIterator<Item = &'r Rect>,
+    {
+        rects
+            .into_iter()
+            .filter(|rect| {
+                rect.fits_dimensions(cut_piece)
+                    && rect
+                        .fit_cut_piece(pattern_direction, cut_piece, false)
+                        .is_none()
+            })
+            .count()
+    }
+
+    /// Returns the parts of this rectangle that are outside `rect`, with room for cuts of
+    /// `blade_width` around it. The parts are split with guillotine cuts, first along the sides
+    /// of `rect` that are parallel to the length, and then across the strip that `rect` is in.
+    fn split_around(&self, rect: &Rect, blade_width: BladeWidth) -> Vec<Rect> {
+        let x = rect.x.saturating_sub(blade_width.rip);
+        let y = rect.y.saturating_sub(blade_width.crosscut);
+        let x_end = (rect.x + rect.width).saturating_add(blade_width.rip);
+        let y_end = (rect.y + rect.length).saturating_add(blade_width.crosscut);
+        let self_x_end = self.x + self.width;
+        let self_y_end = self.y + self.length;
+
+        if x >= self_x_end || x_end <= self.x || y >= self_y_end || y_end <= self.y {
+            return vec![*self];
+        }
+
+        let mut rects = Vec::with_capacity(4);
+        if x > self.x {
+            rects.push(Rect {
+                width: x - self.x,
+                ..*self
+            });
+        }
+        if x_end < self_x_end {
+            rects.push(Rect {
+                x: x_end,
+                width: self_x_end - x_end,
+                ..*self
+            });
+        }
+        let strip_x = cmp::max(self.x, x);
+        let strip_width = cmp::min(self_x_end, x_end) - strip_x;
+        if y > self.y {
+            rects.push(Rect {
+                x: strip_x,
+                y: self.y,
+                width: strip_width,
+                length: y - self.y,
+            });
+        }
+        if y_end < self_y_end {
+            rects.push(Rect {
+                x: strip_x,
+                y: y_end,
+                width: strip_width,
+                length: self_y_end - y_end,
+            });
+        }
+        rects
+    }
+
+    fn contains(&self, rect: &Rect) -> bool {
+        rect.x >= self.x
+            && rect.x + rect.width <= self.x + self.width
+            && rect.y >= self.y
+            && rect.y + rect.length <= self.y + self.length
+    }
+}
+
+impl From<&ResultCutPiece> for Rect {
+    fn from(cut_piece: &ResultCutPiece) -> Self {
+        Self {
+            x: cut_piece.x,
+            y: cut_piece.y,
+            width: cut_piece.width,
+            length: cut_piece.length,
+        }
+    }
+}
+
+impl From<&FixedPiece> for Rect {
+    fn from(fixed_piece: &FixedPiece) -> Self {
+        Self {
+            x: fixed_piece.x,
+            y: fixed_piece.y,
+            width: fixed_piece.width,
+            length: fixed_piece.length,
+        }
+    }
+}
+
+impl From<&WastePiece> for Rect {
+    fn from(waste_piece: &WastePiece) -> Self {
+        Self {
+            x: waste_piece.x,
+            y: waste_piece.y,
+            width: waste_piece.width,
+            length: waste_piece.length,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Fit {
+    None,
+    UprightExact,
+    RotatedExact,
+    Upright,
+    Rotated,
+}
+
+impl Fit {
+    fn is_none(self) -> bool {
+        self == Fit::None
+    }
+
+    fn is_upright(self) -> bool {
+        self == Fit::Upright || self == Fit::UprightExact
+    }
+
+    fn is_rotated(self) -> bool {
+        self == Fit::Rotated || self == Fit::RotatedExact
+    }
+}
+
+/// Options that are the same for every `Bin` in an optimization.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct BinOptions {
+    /// Widths of the cuts between cut pieces.
+    pub(crate) blade_width: BladeWidth,
+
+    /// Whether to collect diagnostics about how cut pieces were placed.
+    pub(crate) diagnostics: bool,
+
+    /// Whether to also use heuristics that choose the free rectangle that fits worst.
+    pub(crate) worst_fit_heuristics: bool,
+
+    /// Whether to also use the heuristic that chooses the free rectangle nearest the top.
+    pub(crate) smallest_y_heuristic: bool,
+
+    /// Whether to split the waste of nested layouts along guillotine cuts.
+    pub(crate) guillotine_waste_pieces: bool,
+
+    /// Maximum number of stages of guillotine cuts in guillotine layouts.
+    pub(crate) max_guillotine_stages: Option<usize>,
+
+    /// Direction of the first cuts across each stock piece in guillotine layouts.
+    pub(crate) first_cut_direction: Option<CutDirection>,
+
+    /// Direction of the guillotine cuts that split nested layouts into strips.
+    pub(crate) nested_strip_direction: Option<CutDirection>,
+}
+
+/// Represents a bin used for bin-packing.
+trait Bin: fmt::Debug + Send + Sync + 'static {
+    /// Heuristic used for inserting `CutPiece`s.
+    type Heuristic: Clone + Send + Sync;
+
+    /// Creates a new empty `Bin` for the `StockPiece`.
+    fn new(stock_piece: &StockPiece, options: BinOptions) -> Self;
+
+    /// Computes the fitness of this `Bin` on a scale of 0.0 to 1.0, with 1.0 being the most fit.
+    /// Unless `include_optional`, the space taken by optional cut pieces is counted as free.
+    fn fitness(&self, include_optional: bool) -> f64;
+
+    /// Returns the price of this `Bin`. Unless `include_optional`, the area price doesn't count
+    /// the optional cut pieces.
+    fn price(&self, include_optional: bool) -> usize;
+
+    /// Returns the area of the stock piece of this `Bin`.
+    fn area(&self) -> u64;
+
+    /// Returns the total length of the cuts needed to cut the cut pieces out of this `Bin`,
+    /// leaving out the optional cut pieces unless `include_optional`.
+    fn cut_length(&self, include_optional: bool) -> u64;
+
+    /// Removes `UsedCutPiece`s from this `Bin` and returns how many were removed.
+    fn remove_cut_pieces<I>(&mut self, cut_pieces: I) -> usize
+    where
+        I: Iterator,
+        I::Item: Borrow<UsedCutPiece>;
+
+    /// Returns an iterator over the `UsedCutPiece`s in this `Bin`.
+    fn cut_pieces(&self) -> std::slice::Iter<'_, UsedCutPiece>;
+
+    /// Returns the pattern direction of the stock piece of this `Bin`.
+    fn pattern_direction(&self) -> PatternDirection;
+
+    /// Returns the possible heuristics that can be passed to `insert_cut_piece_with_heuristic`
+    /// for bins created with `options`.
+    fn possible_heuristics(options: BinOptions) -> Vec<Self::Heuristic>;
+
+    /// Returns the name of the heuristic, as shown in diagnostics and used by custom heuristic
+    /// profiles.
+    fn heuristic_name(heuristic: &Self::Heuristic) -> String;
+
+    /// Returns whether the heuristic is in one of the named heuristic profiles, such as
+    /// `HeuristicProfile::PanelSaw`.
+    fn in_named_profile(heuristic: &Self::Heuristic, profile: &HeuristicProfile) -> bool;
+
+    /// Inserts the `CutPieceWithId` into this `Bin` using the specified heuristic. Returns whether
+    /// the insert succeeded.
+    fn insert_cut_piece_with_heuristic(
+        &mut self,
+        cut_piece: &CutPieceWithId,
+        heuristic: &Self::Heuristic,
+    ) -> bool;
+
+    /// Returns a random heuristic for inserting a `CutPieceWithId` into this `Bin`.
+    fn random_heuristic<R>(&self, rng: &mut R) -> Self::Heuristic
+    where
+        R: Rng + ?Sized;
+
+    /// Returns whether the `StockPiece` is equivalent to this `Bin`.
+    fn matches_stock_piece(&self, stock_piece: &StockPiece) -> bool;
+}
+
+/// State that is shared by all of the units of an optimization.
+struct UnitContext<B>
+where
+    B: Bin,
+{
+    // All of the possible stock pieces. It remains constant.
+    possible_stock_pieces: Vec<StockPiece>,
+
+    bin_options: BinOptions,
+
+    // Heuristics that are allowed by the heuristic profile. It remains constant.
+    heuristics: Vec<B::Heuristic>,
+
+    // Whether the heuristic profile restricts the heuristics, in which case random heuristics
+    // are chosen from `heuristics` instead of being sampled by the bins.
+    restricted_heuristics: bool,
+
+    // Maximum number of units in the population, to bound memory usage.
+    max_units: usize,
+
+    // Fewest stock pieces the cut pieces could fit in, based on their area.
+    min_stock_pieces: usize,
+
+    // Utilization a unit that uses `min_stock_pieces` needs to stop evolution early, or `None`
+    // if evolution shouldn't stop early.
+    early_stop_utilization: Option<f64>,
+
+    // The first internal error that happened while evolving the units. Units can't return
+    // errors from `Unit::breed_with`, so they record them here instead.
+    internal_error: OnceLock<String>,
+
+    // Callback that receives the random choices made by the units, if the audit log is enabled.
+    audit_log_callback: Option<AuditLogCallback>,
+
+    // What the fitness of the units measures.
+    fitness_objective: FitnessObjective,
+
+    // Whether the units need to place every cut piece.
+    goal: OptimizeGoal,
+
+    // Value of each cut piece as given by `CutPiece::value`, indexed by its ID.
+    cut_piece_values: Vec<Option<usize>>,
+
+    // Pairs of external IDs, smallest first, of cut pieces that must be on different stock
+    // pieces.
+    separations: FnvHashSet<(usize, usize)>,
+
+    // Whether the fitness favors layouts where banded edges face outward.
+    prefer_banded_edges_outward: bool,
+
+    // Fraction of the fitness lost when every cut piece is placed against the pattern, or `None`
+    // if cut pieces can't be placed against the pattern.
+    pattern_mismatch_penalty: Option<f64>,
+
+    // Most stock pieces a unit can use, or `None` if there's no limit.
+    max_stock_pieces: Option<usize>,
+}
+
+impl<B> UnitContext<B>
+where
+    B: Bin,
+{
+    /// Returns the value of the cut piece, which is its area if it wasn't given one.
+    fn cut_piece_value(&self, id: usize, width: usize, length: usize) -> u64 {
+        match self.cut_piece_values.get(id).copied().flatten() {
+            Some(value) => value as u64,
+            None => width as u64 * length as u64,
+        }
+    }
+
+    /// Returns whether the cut piece can be placed on the same stock piece as the cut pieces that
+    /// are already in `bin`, without breaking a separation.
+    fn allows_same_bin(&self, bin: &B, cut_piece: &CutPieceWithId) -> bool {
+        let Some(external_id) = cut_piece.external_id else {
+            return true;
+        };
+        self.separations.is_empty()
+            || bin.cut_pieces().all(|other| {
+                other.external_id.is_none_or(|other_id| {
+                    !self
+                        .separations
+                        .contains(&separation(external_id, other_id))
+                })
+            })
+    }
+
+    /// Inserts the `CutPieceWithId` into `bin` using a random heuristic that is allowed by the
+    /// heuristic profile. Returns whether the insert succeeded.
+    fn insert_cut_piece_random_heuristic<R>(
+        &self,
+        bin: &mut B,
+        cut_piece: &CutPieceWithId,
+        rng: &mut R,
+    ) -> bool
+    where
+        R: Rng + ?Sized,
+    {
+        let heuristic = if self.restricted_heuristics {
+            match self.heuristics.choose(rng) {
+                Some(heuristic) => heuristic.clone(),
+                None => return false,
+            }
+        } else {
+            bin.random_heuristic(rng)
+        };
+        audit::log(&self.audit_log_callback, || AuditEntry::HeuristicChosen {
+            cut_piece: cut_piece.id,
+            heuristic: B::heuristic_name(&heuristic),
+        });
+        bin.insert_cut_piece_with_heuristic(cut_piece, &heuristic)
+    }
+}
+
+struct OptimizerUnit<B>
+where
+    B: Bin,
+{
+    bins: Vec<B>,
+
+    context: Arc<UnitContext<B>>,
+
+    // Stock pieces that are currently available to use for new bins.
+    available_stock_pieces: Vec<StockPiece>,
+
+    // Cut pieces that couldn't be added to bins.
+    unused_cut_pieces: FnvHashSet<CutPieceWithId>,
+}
+
+impl<B> Clone for OptimizerUnit<B>
+where
+    B: Bin + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            bins: self.bins.clone(),
+            context: self.context.clone(),
+            available_stock_pieces: self.available_stock_pieces.clone(),
+            unused_cut_pieces: self.unused_cut_pieces.clone(),
+        }
+    }
+}
+
+impl<B> OptimizerUnit<B>
+where
+    B: Bin,
+{
+    fn with_random_heuristics<R>(
+        context: &Arc<UnitContext<B>>,
+        cut_pieces: &[&CutPieceWithId],
+        rng: &mut R,
+    ) -> Result<OptimizerUnit<B>>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut unit = OptimizerUnit {
+            bins: Vec::new(),
+            context: context.clone(),
+            available_stock_pieces: context.possible_stock_pieces.clone(),
+            unused_cut_pieces: Default::default(),
+        };
+
+        for cut_piece in required_first(cut_pieces) {
+            if !unit.first_fit_random_heuristics(cut_piece, rng) {
+                unit.unused_cut_pieces.insert(cut_piece.clone());
+            }
+        }
+
+        Ok(unit)
+    }
+
+    fn with_heuristic<R>(
+        context: &Arc<UnitContext<B>>,
+        cut_pieces: &[&CutPieceWithId],
+        heuristic: &B::Heuristic,
+        rng: &mut R,
+    ) -> Result<OptimizerUnit<B>>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut unit = OptimizerUnit {
+            bins: Vec::new(),
+            context: context.clone(),
+            available_stock_pieces: context.possible_stock_pieces.clone(),
+            unused_cut_pieces: Default::default(),
+        };
+
+        for cut_piece in required_first(cut_pieces) {
+            if !unit.first_fit_with_heuristic(cut_piece, heuristic, rng) {
+                unit.unused_cut_pieces.insert(cut_piece.clone());
+            }
+        }
+
+        Ok(unit)
+    }
+
+    /// Creates a unit that keeps the layout of `solution` as much as it can. Each stock piece of
+    /// the solution that's still available is used again, for the cut pieces it had that are still
+    /// wanted, and then the rest of the cut pieces are added like in `with_heuristic`. The cut
+    /// pieces within each stock piece are placed again with `heuristic`, so they can move.
+    fn from_solution<R>(
+        context: &Arc<UnitContext<B>>,
+        cut_pieces: &[&CutPieceWithId],
+        solution: &Solution,
+        heuristic: &B::Heuristic,
+        rng: &mut R,
+    ) -> OptimizerUnit<B>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut unit = OptimizerUnit {
+            bins: Vec::new(),
+            context: context.clone(),
+            available_stock_pieces: context.possible_stock_pieces.clone(),
+            unused_cut_pieces: Default::default(),
+        };
+        let mut cut_pieces = cut_pieces.to_vec();
+
+        for result_stock_piece in &solution.stock_pieces {
+            if !unit.has_room_for_bin() {
+                break;
+            }
+            let Some(stock_piece) = unit.available_stock_pieces.iter_mut().find(|stock_piece| {
+                stock_piece.quantity != Some(0)
+                    && stock_piece.width == result_stock_piece.width
+                    && stock_piece.length == result_stock_piece.length
+                    && stock_piece.pattern_direction == result_stock_piece.pattern_direction
+                    && stock_piece.external_id == result_stock_piece.external_id
+                    && stock_piece.material == result_stock_piece.material
+                    && stock_piece.thickness == result_stock_piece.thickness
+                    && stock_piece.source == result_stock_piece.source
+                    && stock_piece.fixed_pieces == result_stock_piece.fixed_pieces
+                    && stock_piece.nominal_size == result_stock_piece.nominal_size
+            }) else {
+                continue;
+            };
+
+            // The cut pieces that were on this stock piece, largest first like the initial units.
+            let mut bin_cut_pieces: Vec<&CutPieceWithId> = Vec::new();
+            for result_cut_piece in &result_stock_piece.cut_pieces {
+                let (width, length) = if result_cut_piece.is_rotated {
+                    (result_cut_piece.length, result_cut_piece.width)
+                } else {
+                    (result_cut_piece.width, result_cut_piece.length)
+                };
+                if let Some(i) = cut_pieces.iter().position(|cut_piece| {
+                    cut_piece.external_id == result_cut_piece.external_id
+                        && cut_piece.width == width
+                        && cut_piece.length == length
+                }) {
+                    bin_cut_pieces.push(cut_pieces.remove(i));
+                }
+            }
+            bin_cut_pieces.sort_by_key(|p| cmp::Reverse((p.width, p.length)));
+
+            let mut bin = B::new(stock_piece, context.bin_options);
+            for cut_piece in bin_cut_pieces {
+                if !context.allows_same_bin(&bin, cut_piece)
+                    || !bin.insert_cut_piece_with_heuristic(cut_piece, heuristic)
+                {
+                    cut_pieces.push(cut_piece);
+                }
+            }
+            if bin.cut_pieces().next().is_some() {
+                stock_piece.dec_quantity();
+                unit.bins.push(bin);
+            }
+        }
+
+        for cut_piece in required_first(&cut_pieces) {
+            if !unit.first_fit_with_heuristic(cut_piece, heuristic, rng) {
+                unit.unused_cut_pieces.insert(cut_piece.clone());
+            }
+        }
+
+        unit
+    }
+
+    /// Generates the initial units, each with the index in `context.heuristics` of the heuristic
+    /// its cut pieces were all placed with, or `None` if they were placed with random heuristics.
+    pub(crate) fn generate_initial_units<R>(
+        context: &Arc<UnitContext<B>>,
+        mut cut_pieces: Vec<&CutPieceWithId>,
+        rng: &mut R,
+    ) -> Result<Vec<(Option<usize>, OptimizerUnit<B>)>>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut set = FnvHashSet::default();
+        for cut_piece in &cut_pieces {
+            set.insert((
+                cut_piece.width,
+                cut_piece.length,
+                cut_piece.can_rotate,
+                cut_piece.pattern_direction,
+            ));
+        }
+        let unique_cut_pieces = set.len();
+
+        let possible_heuristics = &context.heuristics;
+
+        let num_units = if cut_pieces.len() < 3 {
+            possible_heuristics.len()
+        } else {
+            let denom = if cut_pieces.len() > 1 {
+                (cut_pieces.len() as f64).log10()
+            } else {
+                1.0
+            };
+
+            cmp::max(
+                possible_heuristics.len() * 3,
+                (cut_pieces.len() as f64 / denom + ((unique_cut_pieces - 1) * 10) as f64) as usize,
+            )
+        };
+        let num_units = cmp::min(num_units, context.max_units);
+        let mut units = Vec::with_capacity(num_units);
+
+        cut_pieces.sort_by_key(|p| cmp::Reverse((p.width, p.length)));
+        for (i, heuristic) in possible_heuristics.iter().enumerate().take(num_units) {
+            units.push((
+                Some(i),
+                OptimizerUnit::with_heuristic(context, &cut_pieces, heuristic, rng)?,
+            ));
+        }
+
+        if cut_pieces.len() > 2 {
+            for (i, heuristic) in possible_heuristics
+                .iter()
+                .enumerate()
+                .take(num_units - units.len())
+            {
+                cut_pieces.shuffle(rng);
+                units.push((
+                    Some(i),
+                    OptimizerUnit::with_heuristic(context, &cut_pieces, heuristic, rng)?,
+                ));
+            }
+
+            for _ in 0..num_units - units.len() {
+                cut_pieces.shuffle(rng);
+                units.push((
+                    None,
+                    OptimizerUnit::with_random_heuristics(context, &cut_pieces, rng)?,
+                ));
+            }
+        }
+        Ok(units)
+    }
+
+    /// Returns the error for a cut piece of this unit that couldn't be placed.
+    fn no_fit_error(&self, cut_piece: &CutPieceWithId) -> Error {
+        let placed = self
+            .bins
+            .iter()
+            .flat_map(Bin::cut_pieces)
+            .filter(|used_cut_piece| is_copy_of(&(*used_cut_piece).into(), cut_piece))
+            .count();
+        let unplaced = self
+            .unused_cut_pieces
+            .iter()
+            .filter(|unused_cut_piece| is_copy_of(unused_cut_piece, cut_piece))
+            .count();
+        let stock_exhausted = self
+            .context
+            .possible_stock_pieces
+            .iter()
+            .any(|stock_piece| {
+                stock_piece.fits_cut_piece(cut_piece, self.context.bin_options.blade_width)
+            });
+
+        no_fit_for_cut_piece_error(
+            cut_piece,
+            PlacementSummary {
+                placed,
+                quantity: placed + unplaced,
+                stock_exhausted,
+            },
+        )
+    }
+
+    /// Returns the fewest stock pieces that the cut pieces could possibly fit in, based on their
+    /// area and the area of the largest stock piece. Optional cut pieces don't need any.
+    fn min_stock_pieces(stock_pieces: &[StockPiece], cut_pieces: &[&CutPieceWithId]) -> usize {
+        let cut_area: u64 = cut_pieces
+            .iter()
+            .filter(|cut_piece| !cut_piece.optional)
+            .map(|cut_piece| cut_piece.width as u64 * cut_piece.length as u64)
+            .sum();
+        let max_stock_area = stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.width as u64 * stock_piece.length as u64)
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        cmp::max(cut_area.div_ceil(max_stock_area) as usize, 1)
+    }
+
+    /// Returns how well this unit meets the objective, on a scale of 0.0 to 1.0, with 1.0 being
+    /// the best possible. Each measure compares the unit to a lower bound for the cut pieces it
+    /// placed, such as the fewest stock pieces they could fit in.
+    fn objective_fitness(&self, objective: FitnessObjective) -> f64 {
+        let [waste, sheet_count, price, cut_length] = objective.weights();
+        let total_weight = waste + sheet_count + price + cut_length;
+        if self.bins.is_empty() || total_weight <= 0.0 {
+            return 0.0;
+        }
+
+        let used_area = self.required_area();
+        let mut fitness = 0.0;
+        if waste > 0.0 {
+            let stock_area: u64 = self.bins.iter().map(Bin::area).sum();
+            fitness += waste * used_area as f64 / cmp::max(stock_area, 1) as f64;
+        }
+        if sheet_count > 0.0 {
+            fitness += sheet_count
+                * (self.context.min_stock_pieces as f64 / self.bins.len() as f64).min(1.0);
+        }
+        if price > 0.0 {
+            let total_price: usize = self.bins.iter().map(|bin| bin.price(false)).sum();
+            let min_price_per_area = self
+                .context
+                .possible_stock_pieces
+                .iter()
+                .map(|stock_piece| {
+                    stock_piece.price as f64
+                        / cmp::max(stock_piece.width as u64 * stock_piece.length as u64, 1) as f64
+                        + stock_piece
+                            .area_price
+                            .map_or(0.0, AreaPrice::price_per_unit_area)
+                })
+                .fold(f64::INFINITY, f64::min);
+            fitness += price
+                * if total_price == 0 {
+                    1.0
+                } else {
+                    (used_area as f64 * min_price_per_area / total_price as f64).min(1.0)
+                };
+        }
+        if cut_length > 0.0 {
+            let total_cut_length: u64 = self.bins.iter().map(|bin| bin.cut_length(false)).sum();
+            // Each cut piece needs at least two of its sides cut, unless it shares cuts with
+            // other cut pieces.
+            let min_cut_length: u64 = self
+                .required_cut_pieces()
+                .map(|cut_piece| (cut_piece.rect.width + cut_piece.rect.length) as u64)
+                .sum();
+            fitness += cut_length
+                * if total_cut_length == 0 {
+                    1.0
+                } else {
+                    (min_cut_length as f64 / total_cut_length as f64).min(1.0)
+                };
+        }
+
+        fitness / total_weight
+    }
+
+    /// Returns whether this unit can use another stock piece without going over the maximum.
+    fn has_room_for_bin(&self) -> bool {
+        self.context
+            .max_stock_pieces
+            .is_none_or(|max| self.bins.len() < max)
+    }
+
+    /// Returns whether this unit is a solution, which places every cut piece unless the goal is
+    /// to maximize the value of the cut pieces that are placed.
+    fn is_complete(&self) -> bool {
+        !self.has_unused_required_cut_pieces() || self.context.goal == OptimizeGoal::MaximizeValue
+    }
+
+    /// Returns the fitness of the layout of this unit, before it's adjusted for the optional
+    /// cut pieces and the goal. The layout is measured as if the optional cut pieces weren't
+    /// placed.
+    fn layout_fitness(&self) -> f64 {
+        let packing_fitness = if self.bins.is_empty() {
+            0.0
+        } else {
+            self.bins.iter().fold(0.0, |acc, b| acc + b.fitness(false)) / self.bins.len() as f64
+        };
+
+        // Other than for price, which is compared separately, the fitness mostly measures the
+        // objective, with how tightly the bins are packed breaking ties.
+        let fitness = match self.context.fitness_objective {
+            FitnessObjective::MinimizePrice => packing_fitness,
+            objective => 0.9 * self.objective_fitness(objective) + 0.1 * packing_fitness,
+        };
+        let fitness = if self.context.prefer_banded_edges_outward {
+            0.95 * fitness + 0.05 * self.banded_edges_outward_fraction()
+        } else {
+            fitness
+        };
+        // Using up the stock pieces that must be used matters more than how well the rest of the
+        // stock pieces are used.
+        let fitness = match self.must_use_fraction() {
+            Some(fraction) => 0.5 * fitness + 0.5 * fraction,
+            None => fitness,
+        };
+        match self.context.pattern_mismatch_penalty {
+            Some(penalty) => fitness * (1.0 - penalty * self.pattern_mismatch_fraction()),
+            None => fitness,
+        }
+    }
+
+    /// Returns the cut pieces placed in the bins that aren't optional.
+    fn required_cut_pieces(&self) -> impl Iterator<Item = &UsedCutPiece> {
+        self.bins
+            .iter()
+            .flat_map(Bin::cut_pieces)
+            .filter(|cut_piece| !cut_piece.optional)
+    }
+
+    /// Returns the area of the cut pieces placed in the bins that aren't optional.
+    fn required_area(&self) -> u64 {
+        self.required_cut_pieces()
+            .map(|cut_piece| cut_piece.rect.width as u64 * cut_piece.rect.length as u64)
+            .sum()
+    }
+
+    /// Returns whether any of the cut pieces that aren't optional weren't placed.
+    fn has_unused_required_cut_pieces(&self) -> bool {
+        self.unused_cut_pieces
+            .iter()
+            .any(|cut_piece| !cut_piece.optional)
+    }
+
+    /// Returns the fraction of the area of the optional cut pieces that's placed, or `None` if
+    /// there aren't any.
+    fn optional_fraction(&self) -> Option<f64> {
+        let area = |width: usize, length: usize| width as u64 * length as u64;
+        let placed: u64 = self
+            .bins
+            .iter()
+            .flat_map(Bin::cut_pieces)
+            .filter(|cut_piece| cut_piece.optional)
+            .map(|cut_piece| area(cut_piece.rect.width, cut_piece.rect.length))
+            .sum();
+        let unused: u64 = self
+            .unused_cut_pieces
+            .iter()
+            .filter(|cut_piece| cut_piece.optional)
+            .map(|cut_piece| area(cut_piece.width, cut_piece.length))
+            .sum();
+        if placed + unused == 0 {
+            None
+        } else {
+            Some(placed as f64 / (placed + unused) as f64)
+        }
+    }
+
+    /// Returns the fraction of the banded edges of the placed cut pieces that face outward,
+    /// toward the edge of the stock piece or waste instead of another cut piece.
+    fn banded_edges_outward_fraction(&self) -> f64 {
+        let blade_width = self.context.bin_options.blade_width;
+        let (banded, outward) = self
+            .bins
+            .iter()
+            .flat_map(|bin| {
+                let required = bin.cut_pieces().filter(|cut_piece| !cut_piece.optional);
+                required.clone().map(move |cut_piece| {
+                    cut_piece.banded_edges_outward(required.clone(), blade_width)
+                })
+            })
+            .fold((0, 0), |(banded, outward), (b, o)| {
+                (banded + b, outward + o)
+            });
+        if banded == 0 {
+            1.0
+        } else {
+            outward as f64 / banded as f64
+        }
+    }
+
+    /// Fraction of the stock pieces that must be used that were used, or `None` if there aren't
+    /// any with a limited quantity.
+    fn must_use_fraction(&self) -> Option<f64> {
+        let must_use_quantity = |stock_pieces: &[StockPiece]| -> usize {
+            stock_pieces
+                .iter()
+                .filter(|stock_piece| stock_piece.must_use)
+                .filter_map(|stock_piece| stock_piece.quantity)
+                .sum()
+        };
+        let total = must_use_quantity(&self.context.possible_stock_pieces);
+        if total == 0 {
+            return None;
+        }
+        let unused = must_use_quantity(&self.available_stock_pieces);
+        Some((total - unused) as f64 / total as f64)
+    }
+
+    /// Fraction of the cut pieces placed in the bins that go against the pattern of their stock
+    /// piece.
+    fn pattern_mismatch_fraction(&self) -> f64 {
+        let (placed, mismatched) = self
+            .bins
+            .iter()
+            .flat_map(|bin| {
+                bin.cut_pieces()
+                    .filter(|cut_piece| !cut_piece.optional)
+                    .map(move |cut_piece| {
+                        !cut_piece.pattern_direction.matches(bin.pattern_direction())
+                    })
+            })
+            .fold((0, 0), |(placed, mismatched), is_mismatched| {
+                (placed + 1, mismatched + usize::from(is_mismatched))
+            });
+        if placed == 0 {
+            0.0
+        } else {
+            mismatched as f64 / placed as f64
+        }
+    }
+
+    /// Fraction of the total value of the cut pieces that's placed in the bins.
+    fn placed_value_fraction(&self) -> f64 {
+        let (placed_value, unused_value) = self.placed_and_unused_value();
+        if placed_value + unused_value == 0 {
+            return 1.0;
+        }
+        placed_value as f64 / (placed_value + unused_value) as f64
+    }
+
+    /// Total value of the cut pieces that are placed in the bins, and of the ones that aren't.
+    fn placed_and_unused_value(&self) -> (u64, u64) {
+        let unused_value = self
+            .unused_cut_pieces
+            .iter()
+            .filter(|cut_piece| !cut_piece.optional)
+            .map(|cut_piece| {
+                self.context
+                    .cut_piece_value(cut_piece.id, cut_piece.width, cut_piece.length)
+            })
+            .sum();
+        let placed_value = self
+            .bins
+            .iter()
+            .flat_map(Bin::cut_pieces)
+            .map(|cut_piece| {
+                self.context.cut_piece_value(
+                    cut_piece.id,
+                    cut_piece.rect.width,
+                    cut_piece.rect.length,
+                )
+            })
+            .sum();
+        (placed_value, unused_value)
+    }
+
+    /// Area of the bins that is used by cut pieces.
+    fn used_area(&self) -> u64 {
+        self.bins
+            .iter()
+            .flat_map(Bin::cut_pieces)
+            .map(|cut_piece| cut_piece.rect.width as u64 * cut_piece.rect.length as u64)
+            .sum()
+    }
+
+    /// Fraction of the area of the bins that is used by cut pieces.
+    fn utilization(&self) -> f64 {
+        let stock_area: u64 = self.bins.iter().map(Bin::area).sum();
+        if stock_area == 0 {
+            return 0.0;
+        }
+        self.used_area() as f64 / stock_area as f64
+    }
+
+    /// Returns the optimality gap of this unit, as described by `Solution::optimality_gap`.
+    fn optimality_gap(&self) -> f64 {
+        let stock_area: u64 = self.bins.iter().map(Bin::area).sum();
+        if stock_area == 0 {
+            return 0.0;
+        }
+
+        let cut_area = self.used_area();
+        let (min_stock_area, max_stock_area) = self
+            .context
+            .possible_stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.width as u64 * stock_piece.length as u64)
+            .fold((u64::MAX, 1), |(min, max), area| {
+                (cmp::min(min, area), cmp::max(max, area))
+            });
+        let min_stock_pieces = cut_area.div_ceil(max_stock_area);
+        let lower_bound = cmp::max(cut_area, min_stock_pieces.saturating_mul(min_stock_area));
+
+        stock_area.saturating_sub(lower_bound) as f64 / stock_area as f64
+    }
+
+    /// Estimates the number of bytes a unit uses for the cut pieces and stock pieces. Units with
+    /// more bins than this assumes can use more.
+    fn estimated_size(stock_pieces: &[StockPiece], cut_pieces: &[&CutPieceWithId]) -> usize {
+        let cut_area: u64 = cut_pieces
+            .iter()
+            .map(|cut_piece| cut_piece.width as u64 * cut_piece.length as u64)
+            .sum();
+        let min_stock_area = stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.width as u64 * stock_piece.length as u64)
+            .min()
+            .unwrap_or(1)
+            .max(1);
+        // Assume bins are about half full.
+        let num_bins = cmp::min(
+            cut_pieces.len(),
+            (cut_area * 2 / min_stock_area) as usize + 1,
+        );
+
+        mem::size_of::<Self>()
+            + num_bins * mem::size_of::<B>()
+            + cut_pieces.len() * estimated_cut_piece_size()
+            + mem::size_of_val(stock_pieces)
+    }
+
+    /// Places a few cut pieces by trying them in every order, with every heuristic, on every
+    /// stock piece already in the layout or every stock piece that could be added for each of
+    /// them, instead of relying on the genetic algorithm. The heuristics cover each orientation
+    /// and position a cut piece can be placed in. Returns the unit with the lowest price, or the
+    /// highest fitness if prices are the same, or `None` if the cut pieces can't all be placed,
+    /// or there are more than `MAX_ENUMERATED_PLACEMENTS` ways to try placing them.
+    fn best_for_few_cut_pieces(
+        context: &Arc<UnitContext<B>>,
+        cut_pieces: &[&CutPieceWithId],
+    ) -> Option<OptimizerUnit<B>>
+    where
+        B: Send + Clone,
+    {
+        if cut_pieces.iter().any(|cut_piece| cut_piece.optional)
+            || context.max_stock_pieces == Some(0)
+        {
+            return None;
+        }
+
+        // Each cut piece can go on each stock piece before it, or on a new one of any size.
+        let num_stock_pieces = context.possible_stock_pieces.len();
+        let num_placements = (1..=cut_pieces.len()).zip(0..).try_fold(
+            context.heuristics.len(),
+            |count, (i, bins_before)| {
+                count
+                    .checked_mul(i)?
+                    .checked_mul(bins_before + num_stock_pieces)
+            },
+        );
+        if cut_pieces.len() > 1 && num_placements.is_none_or(|n| n > MAX_ENUMERATED_PLACEMENTS) {
+            return None;
+        }
+
+        // Orders that only differ by swapping copies of the same cut piece are only tried once.
+        let copy_of: Vec<usize> = (0..cut_pieces.len())
+            .map(|i| {
+                (0..i)
+                    .find(|&j| is_identical_copy(cut_pieces[i], cut_pieces[j]))
+                    .unwrap_or(i)
+            })
+            .collect();
+        let mut tried = BTreeSet::new();
+        let orders = permutations(cut_pieces.len()).into_iter().filter(|order| {
+            tried.insert(order.iter().map(|&i| copy_of[i]).collect::<Vec<usize>>())
+        });
+
+        let unit = OptimizerUnit {
+            bins: Vec::new(),
+            context: context.clone(),
+            available_stock_pieces: context.possible_stock_pieces.clone(),
+            unused_cut_pieces: Default::default(),
+        };
+        let mut best_unit = None;
+        for order in orders {
+            let cut_pieces: Vec<&CutPieceWithId> = order.iter().map(|&i| cut_pieces[i]).collect();
+            for heuristic in &context.heuristics {
+                unit.place_every_way(&cut_pieces, heuristic, &mut best_unit);
+            }
+        }
+        best_unit
+    }
+
+    /// Places the first of the cut pieces on each stock piece of this unit that it fits on, and
+    /// on each stock piece that can be added for it, and then the rest of the cut pieces on each
+    /// of those units the same way. The best unit that places all of them is kept in
+    /// `best_unit`. See `best_for_few_cut_pieces`.
+    fn place_every_way(
+        &self,
+        cut_pieces: &[&CutPieceWithId],
+        heuristic: &B::Heuristic,
+        best_unit: &mut Option<OptimizerUnit<B>>,
+    ) where
+        B: Send + Clone,
+    {
+        let Some((cut_piece, rest)) = cut_pieces.split_first() else {
+            let price = |unit: &Self| unit.bins.iter().map(|bin| bin.price(true)).sum::<usize>();
+            let is_better = match best_unit {
+                Some(ref best_unit) => {
+                    let (price, best_price) = (price(self), price(best_unit));
+                    price < best_price
+                        || (price == best_price && self.fitness() > best_unit.fitness())
+                }
+                None => true,
+            };
+            if is_better {
+                *best_unit = Some(self.clone());
+            }
+            return;
+        };
+
+        for i in 0..self.bins.len() {
+            if !self.context.allows_same_bin(&self.bins[i], cut_piece) {
+                continue;
+            }
+            let mut unit = self.clone();
+            if unit.bins[i].insert_cut_piece_with_heuristic(cut_piece, heuristic) {
+                unit.place_every_way(rest, heuristic, best_unit);
+            }
+        }
+
+        if !self.has_room_for_bin() {
+            return;
+        }
+        let fits = |stock_piece: &StockPiece| {
+            stock_piece.quantity != Some(0)
+                && stock_piece.fits_cut_piece(cut_piece, self.context.bin_options.blade_width)
+        };
+        let must_use = self
+            .available_stock_pieces
+            .iter()
+            .any(|stock_piece| stock_piece.must_use && fits(stock_piece));
+        for (i, stock_piece) in self.available_stock_pieces.iter().enumerate() {
+            if !fits(stock_piece) || (must_use && !stock_piece.must_use) {
+                continue;
+            }
+
+            let mut bin = B::new(stock_piece, self.context.bin_options);
+            if !bin.insert_cut_piece_with_heuristic(cut_piece, heuristic) {
+                continue;
+            }
+            let mut unit = self.clone();
+            unit.bins.push(bin);
+            unit.available_stock_pieces[i].dec_quantity();
+            unit.place_every_way(rest, heuristic, best_unit);
+        }
+    }
+
+    fn first_fit_random_heuristics<R>(&mut self, cut_piece: &CutPieceWithId, rng: &mut R) -> bool
+    where
+        R: Rng + ?Sized,
+    {
+        self.first_fit(cut_piece, rng, |context, bin, rng| {
+            context.insert_cut_piece_random_heuristic(bin, cut_piece, rng)
+        })
+    }
+
+    fn first_fit_with_heuristic<R>(
+        &mut self,
+        cut_piece: &CutPieceWithId,
+        heuristic: &B::Heuristic,
+        rng: &mut R,
+    ) -> bool
+    where
+        R: Rng + ?Sized,
+    {
+        self.first_fit(cut_piece, rng, |_, bin, _| {
+            bin.insert_cut_piece_with_heuristic(cut_piece, heuristic)
+        })
+    }
+
+    /// Inserts the cut piece with `insert` into the first bin that it fits in, or a new bin if it
+    /// doesn't fit in any of them. The bins of stock pieces that must be used come first, and a
+    /// new one of those is started before trying the other bins, so they're used up first.
+    fn first_fit<R, F>(&mut self, cut_piece: &CutPieceWithId, rng: &mut R, mut insert: F) -> bool
+    where
+        R: Rng + ?Sized,
+        F: FnMut(&UnitContext<B>, &mut B, &mut R) -> bool,
+    {
+        let context = self.context.clone();
+        let is_must_use = |bin: &B| {
+            context
+                .possible_stock_pieces
+                .iter()
+                .any(|stock_piece| stock_piece.must_use && bin.matches_stock_piece(stock_piece))
+        };
+        let has_must_use = context
+            .possible_stock_pieces
+            .iter()
+            .any(|stock_piece| stock_piece.must_use);
+
+        if has_must_use {
+            for bin in self.bins.iter_mut().filter(|bin| is_must_use(bin)) {
+                if context.allows_same_bin(bin, cut_piece) && insert(&context, bin, rng) {
+                    return true;
+                }
+            }
+            if self.add_to_new_bin(cut_piece, true, rng) {
+                return true;
+            }
+        }
+
+        for bin in self.bins.iter_mut() {
+            if (!has_must_use || !is_must_use(bin))
+                && context.allows_same_bin(bin, cut_piece)
+                && insert(&context, bin, rng)
+            {
+                return true;
+            }
+        }
+
+        self.add_to_new_bin(cut_piece, false, rng)
+    }
+
+    /// Inserts the cut piece into a new bin for a random stock piece that it fits on, choosing
+    /// from the stock pieces that must be used if it fits on any of them. With `must_use_only`,
+    /// no other stock pieces are tried. Optional cut pieces never get a new bin.
+    fn add_to_new_bin<R>(
+        &mut self,
+        cut_piece: &CutPieceWithId,
+        must_use_only: bool,
+        rng: &mut R,
+    ) -> bool
+    where
+        R: Rng + ?Sized,
+    {
+        if cut_piece.optional || !self.has_room_for_bin() {
+            return false;
+        }
+
+        let blade_width = self.context.bin_options.blade_width;
+        let fits = |stock_piece: &StockPiece| {
+            stock_piece.quantity != Some(0) && stock_piece.fits_cut_piece(cut_piece, blade_width)
+        };
+        // Stock pieces that must be used are used up before any others.
+        let must_use = self
+            .available_stock_pieces
+            .iter()
+            .any(|stock_piece| stock_piece.must_use && fits(stock_piece));
+        if must_use_only && !must_use {
+            return false;
+        }
+        let stock_pieces = self
+            .available_stock_pieces
+            .iter_mut()
+            .filter(|stock_piece| fits(stock_piece) && (stock_piece.must_use || !must_use));
+
+        match stock_pieces.choose(rng) {
+            Some(stock_piece) => {
+                audit::log(&self.context.audit_log_callback, || {
+                    AuditEntry::StockPieceChosen {
+                        cut_piece: cut_piece.id,
+                        width: stock_piece.width,
+                        length: stock_piece.length,
+                        external_id: stock_piece.external_id,
+                    }
+                });
+                stock_piece.dec_quantity();
+
+                let mut bin = B::new(stock_piece, self.context.bin_options);
+                if !self
+                    .context
+                    .insert_cut_piece_random_heuristic(&mut bin, cut_piece, rng)
+                {
+                    return false;
+                }
+                self.bins.push(bin);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Creates a new unit by injecting some of the bins of `other` into this unit. Returns an
+    /// error describing the problem if the bookkeeping of the units is inconsistent.
+    fn crossover<R>(
+        &self,
+        other: &OptimizerUnit<B>,
+        rng: &mut R,
+    ) -> std::result::Result<OptimizerUnit<B>, String>
+    where
+        R: Rng + ?Sized,
+        B: Clone,
+    {
+        // If there aren't multiple bins we can't do a crossover,
+        // so just return a clone of this unit.
+        if self.bins.len() < 2 && other.bins.len() < 2 {
+            return Ok(self.clone());
+        }
+
+        // Randomly select insertion point and range of bins to inject into the new unit.
+        let cross_dest = rng.gen_range(0..=self.bins.len());
+        let cross_src_start = rng.gen_range(0..other.bins.len());
+        let cross_src_end = rng.gen_range(cross_src_start + 1..=other.bins.len());
+
+        // Create a new unit with the bins of `self` and some of the bins of `other` injected in.
+        let mut new_unit = OptimizerUnit {
+            bins: (self.bins[..cross_dest])
+                .iter()
+                .chain((other.bins[cross_src_start..cross_src_end]).iter())
+                .chain((self.bins[cross_dest..]).iter())
+                .cloned()
+                .collect(),
+            context: self.context.clone(),
+            // Make all possible stock pieces available initially. Quantities will be updated below.
+            available_stock_pieces: self.context.possible_stock_pieces.clone(),
+            // Start with no unused cut pieces, and update below.
+            unused_cut_pieces: Default::default(),
+        };
+
+        let mut unused_cut_pieces = self.unused_cut_pieces.clone();
+
+        // Update available stock piece quantities based on the injected bins.
+        for bin in &other.bins[cross_src_start..cross_src_end] {
+            if let Some(ref mut stock_piece) = new_unit
+                .available_stock_pieces
+                .iter_mut()
+                .find(|sp| bin.matches_stock_piece(sp))
+            {
+                // Remove the injected cut pieces from the unused set.
+                for cut_piece in bin.cut_pieces() {
+                    unused_cut_pieces.remove(&cut_piece.into());
+                }
+                stock_piece.dec_quantity();
+            } else {
+                return Err(format!(
+                    "Attempt to inject invalid bin in crossover operation. This shouldn't happen, \
+                     and means there is a bug in the code. Bin: {:?}",
+                    bin
+                ));
+            }
+        }
+
+        // Remove injected cut pieces from all non-injected bins,
+        // and update the available stock piece quantities.
+        for i in (0..cross_dest)
+            .chain((cross_dest + cross_src_end - cross_src_start)..new_unit.bins.len())
+            .rev()
+        {
+            let bin = &mut new_unit.bins[i];
+            let stock_piece = new_unit
+                .available_stock_pieces
+                .iter_mut()
+                .find(|sp| sp.quantity != Some(0) && bin.matches_stock_piece(sp));
+            let injected_cut_pieces = (other.bins[cross_src_start..cross_src_end])
+                .iter()
+                .flat_map(Bin::cut_pieces);
+            let num_removed_cut_pieces = bin.remove_cut_pieces(injected_cut_pieces);
+
+            if let (0, Some(stock_piece)) = (num_removed_cut_pieces, stock_piece) {
+                // No cut pieces were removed from this bin, so we'll keep it
+                // and decrement the quantity of the available stock piece.
+                stock_piece.dec_quantity();
+            } else {
+                // Either there were no available stock pieces, or there
+                // were cut pieces removed from this bin so we'll remove it
+                // and add its cut pieces to the unused set.
+                for cut_piece in bin.cut_pieces() {
+                    unused_cut_pieces.insert(cut_piece.into());
+                }
+                new_unit.bins.remove(i);
+            }
+        }
+
+        // Take the optional cut pieces out of the bins, so they're placed again after the others,
+        // and never take the space a cut piece that isn't optional needs.
+        for bin in &mut new_unit.bins {
+            let optional_cut_pieces: Vec<UsedCutPiece> = bin
+                .cut_pieces()
+                .filter(|cut_piece| cut_piece.optional)
+                .cloned()
+                .collect();
+            if !optional_cut_pieces.is_empty() {
+                bin.remove_cut_pieces(optional_cut_pieces.iter());
+                for cut_piece in &optional_cut_pieces {
+                    unused_cut_pieces.insert(cut_piece.into());
+                }
+            }
+        }
+
+        // Remove the bins past the maximum number of stock pieces, and add their cut pieces to the
+        // unused set.
+        if let Some(max) = self.context.max_stock_pieces {
+            if new_unit.bins.len() > max {
+                for bin in new_unit.bins.drain(max..) {
+                    if let Some(stock_piece) = new_unit
+                        .available_stock_pieces
+                        .iter_mut()
+                        .find(|sp| bin.matches_stock_piece(sp))
+                    {
+                        stock_piece.inc_quantity();
+                    }
+                    for cut_piece in bin.cut_pieces() {
+                        unused_cut_pieces.insert(cut_piece.into());
+                    }
+                }
+            }
+        }
+
+        // Try to add all unused cut pieces, with the optional ones last so they only fill the space
+        // that's left.
+        unused_cut_pieces.retain(|cut_piece| {
+            cut_piece.optional || !new_unit.first_fit_random_heuristics(cut_piece, rng)
+        });
+        unused_cut_pieces.retain(|cut_piece| {
+            !cut_piece.optional || !new_unit.first_fit_random_heuristics(cut_piece, rng)
+        });
+        new_unit.unused_cut_pieces = unused_cut_pieces;
+
+        // Remove bins that don't have cut pieces.
+        for i in (0..new_unit.bins.len()).rev() {
+            if new_unit.bins[i].cut_pieces().next().is_none() {
+                let bin = &mut new_unit.bins[i];
+                if let Some(ref mut stock_piece) = new_unit
+                    .available_stock_pieces
+                    .iter_mut()
+                    .find(|sp| sp.quantity != Some(0) && bin.matches_stock_piece(sp))
+                {
+                    // Make this stock piece available to use again.
+                    stock_piece.inc_quantity();
+                }
+                new_unit.bins.remove(i);
+            }
+        }
+
+        Ok(new_unit)
+    }
+
+    // Randomly apply a mutation to this unit.
+    fn mutate<R>(&mut self, rng: &mut R)
+    where
+        R: Rng + ?Sized,
+    {
+        if !self.bins.is_empty() && rng.gen_range(0..20) == 1 {
+            self.inversion(rng)
+        }
+    }
+
+    // Reverse order of a random range of bins.
+    fn inversion<R>(&mut self, rng: &mut R)
+    where
+        R: Rng + ?Sized,
+    {
+        let start = rng.gen_range(0..self.bins.len());
+        let end = rng.gen_range(start..self.bins.len());
+        self.bins[start..end].reverse();
+    }
+}
+
+impl<B> Unit for OptimizerUnit<B>
+where
+    B: Bin + Send + Clone,
+{
+    fn fitness(&self) -> f64 {
+        // The layout is measured without the optional cut pieces, so they can't make up for using
+        // more stock pieces. Filling the waste with them is only worth a little.
+        let fitness = self.layout_fitness();
+        let fitness = match self.optional_fraction() {
+            Some(fraction) => 0.95 * fitness + 0.05 * fraction,
+            None => fitness,
+        };
+
+        if self.context.goal == OptimizeGoal::MaximizeValue {
+            // The value of the cut pieces that were placed matters most, and the layout of them
+            // breaks ties.
+            0.9 * self.placed_value_fraction() + 0.1 * fitness
+        } else if !self.has_unused_required_cut_pieces() {
+            fitness
+        } else {
+            // If there are unused cut pieces, the fitness is below 0 because it's not a valid
+            // solution.
+            fitness - 1.0
+        }
+    }
+
+    fn is_optimal(&self) -> bool {
+        match self.context.early_stop_utilization {
+            Some(early_stop_utilization) => {
+                !self.has_unused_required_cut_pieces()
+                    && self.bins.len() <= self.context.min_stock_pieces
+                    && self.utilization() >= early_stop_utilization
+            }
+            None => false,
+        }
+    }
+
+    fn breed_with<R>(&self, other: &OptimizerUnit<B>, rng: &mut R) -> OptimizerUnit<B>
+    where
+        R: Rng + ?Sized,
+    {
+        match self.crossover(other, rng) {
+            Ok(mut new_unit) => {
+                new_unit.mutate(rng);
+                new_unit
+            }
+            Err(error) => {
+                // Record the error so it can be returned once evolution is done, and keep going
+                // with a copy of this unit.
+                let _ = self.context.internal_error.set(error);
+                self.clone()
+            }
+        }
+    }
+}
+
+/// Error while optimizing.
+#[derive(Debug)]
+pub enum Error {
+    /// There was no stock piece that could contain this demand piece. The summary tells how many
+    /// copies of it were placed before there was no room left for the rest.
+    NoFitForCutPiece(Box<CutPiece>, PlacementSummary),
+
+    /// The optimizer's internal bookkeeping became inconsistent. This means there is a bug in the
+    /// optimizer. The message describes the problem, and may include a dump of the internal state
+    /// involved.
+    Internal(String),
+
+    /// This demand piece was placed more than once in the solution. This means there is a bug in
+    /// the optimizer, and the solution was discarded instead of being returned with duplicated
+    /// placements.
+    DuplicateCutPiece(Box<CutPiece>),
+
+    /// The heuristic profile for this optimize mode doesn't include any heuristics, such as when
+    /// none of the names in a `HeuristicProfile::Custom` match a heuristic.
+    EmptyHeuristicProfile(OptimizeMode),
+
+    /// The cut width is larger than a side of a cut piece, or at least as large as the shorter
+    /// side of every stock piece. Layouts with a cut width like this aren't useful, so this
+    /// usually means the cut width is in the wrong unit. With different widths for rips and
+    /// crosscuts, this is the wider one.
+    CutWidthTooLarge(usize),
+
+    /// The progress callback stopped the optimization before any solution was found.
+    Cancelled,
+
+    /// The inputs checked by `OptimizerBuilder::build` have these problems.
+    InvalidInput(Vec<InputError>),
+
+    /// The optimizer would lay out this many cut pieces, counting each group of copies once,
+    /// which is more than the maximum set with `Optimizer::set_max_cut_pieces`.
+    TooManyCutPieces(usize),
+
+    /// There are no stock pieces to cut the cut pieces from, because none were added or they all
+    /// have a quantity of 0. This is how many of them have a quantity of 0.
+    NoStockAvailable(usize),
+}
+
+/// A problem with the inputs of an optimizer, found by `OptimizerBuilder::build` before any
+/// optimizing is done.
+#[derive(Clone, Debug)]
+pub enum InputError {
+    /// The stock piece has a width or length of 0.
+    ZeroSizeStockPiece(StockPiece),
+
+    /// The cut piece has a width or length of 0.
+    ZeroSizeCutPiece(CutPiece),
+
+    /// The cut width is larger than a side of a cut piece, or at least as large as the shorter
+    /// side of every stock piece. See `Error::CutWidthTooLarge`.
+    CutWidthTooLarge(usize),
+
+    /// No stock piece can contain the cut piece, taking its pattern direction, whether it can
+    /// rotate, and its stock filter into account. This isn't a problem with
+    /// `OptimizeGoal::MaximizeValue`.
+    NoStockForCutPiece(CutPiece),
+
+    /// The optimizer would lay out more cut pieces than the maximum. See
+    /// `Error::TooManyCutPieces`.
+    TooManyCutPieces(usize),
+
+    /// The stock piece has a quantity of 0, so the optimizer can't use it.
+    ZeroQuantityStockPiece(StockPiece),
+
+    /// The cut pieces of the match group with this ID don't all have the same pattern direction,
+    /// stock filter, material, thickness, and size across the grain. See `MatchGroup`.
+    MismatchedMatchGroup(usize),
+}
+
+/// How many copies of a cut piece were placed, for `Error::NoFitForCutPiece`. Copies are the cut
+/// pieces with the same external ID and size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PlacementSummary {
+    /// Number of copies of the cut piece that were placed.
+    pub placed: usize,
+
+    /// Number of copies of the cut piece that were requested.
+    pub quantity: usize,
+
+    /// Whether the rest of the copies didn't fit because the stock pieces that could contain them
+    /// ran out, rather than because no stock piece could contain them.
+    pub stock_exhausted: bool,
+}
+
+impl fmt::Display for PlacementSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} placed", self.placed, self.quantity)?;
+        if self.stock_exhausted {
+            write!(f, ", stock exhausted")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the pair of external IDs of a separation, smallest first, so the pair is the same
+/// whichever order the IDs are given in.
+fn separation(external_id_a: usize, external_id_b: usize) -> (usize, usize) {
+    (
+        cmp::min(external_id_a, external_id_b),
+        cmp::max(external_id_a, external_id_b),
+    )
+}
+
+/// Returns the cut pieces with the optional ones last, so they only fill the space that's left by
+/// the others.
+fn required_first<'a>(
+    cut_pieces: &[&'a CutPieceWithId],
+) -> impl Iterator<Item = &'a CutPieceWithId> {
+    let (required, optional): (Vec<_>, Vec<_>) = cut_pieces
+        .iter()
+        .copied()
+        .partition(|cut_piece| !cut_piece.optional);
+    required.into_iter().chain(optional)
+}
+
+/// Returns whether the cut pieces are copies of each other, as counted by `PlacementSummary`.
+fn is_copy_of(cut_piece: &CutPieceWithId, other: &CutPieceWithId) -> bool {
+    cut_piece.external_id == other.external_id
+        && cut_piece.width == other.width
+        && cut_piece.length == other.length
+}
+
+/// Estimates the number of bytes a unit uses for each of its cut pieces.
+fn estimated_cut_piece_size() -> usize {
+    mem::size_of::<UsedCutPiece>() + mem::size_of::<Rect>() + mem::size_of::<CutPieceWithId>()
+}
+
+/// Returns every order of the indices below `n`.
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    permutations(n - 1)
+        .into_iter()
+        .flat_map(|order| {
+            (0..n).map(move |i| {
+                let mut order = order.clone();
+                order.insert(i, n - 1);
+                order
+            })
+        })
+        .collect()
+}
+
+/// Returns whether the cut pieces are copies of the same cut piece in every way but their IDs.
+fn is_identical_copy(cut_piece: &CutPieceWithId, other: &CutPieceWithId) -> bool {
+    is_copy_of(cut_piece, other)
+        && cut_piece.pattern_direction == other.pattern_direction
+        && cut_piece.can_rotate == other.can_rotate
+        && cut_piece.tag == other.tag
+        && cut_piece.max_edge_distance == other.max_edge_distance
+        && cut_piece.stock_filter == other.stock_filter
+        && cut_piece.min_grade == other.min_grade
+        && cut_piece.edge_banding == other.edge_banding
+        && cut_piece.match_group == other.match_group
+        && cut_piece.allow_pattern_mismatch == other.allow_pattern_mismatch
+        && cut_piece.optional == other.optional
+        && cut_piece.material == other.material
+        && cut_piece.thickness == other.thickness
+}
+
+fn error_cut_piece(cut_piece: &CutPieceWithId) -> CutPiece {
+    CutPiece {
+        quantity: 1,
+        external_id: cut_piece.external_id,
+        width: cut_piece.width,
+        length: cut_piece.length,
+        can_rotate: cut_piece.can_rotate,
+        pattern_direction: cut_piece.pattern_direction,
+        tag: cut_piece.tag.as_deref().map(Into::into),
+        max_edge_distance: cut_piece.max_edge_distance,
+        stock_filter: cut_piece
+            .stock_filter
+            .as_deref()
+            .cloned()
+            .unwrap_or_default(),
+        value: None,
+        min_grade: cut_piece.min_grade,
+        edge_banding: cut_piece.edge_banding,
+        match_group: cut_piece.match_group,
+        material: cut_piece.material,
+        thickness: cut_piece.thickness,
+        max_quantity: None,
+    }
+}
+
+/// Returns a cut piece that stands in for the cut pieces of a match group, which are `gap` apart
+/// along the grain in the order given. It's as wide across the grain as the widest of them, and
+/// is only as constrained as the most constrained of them.
+fn match_group_cut_piece(id: usize, members: Vec<CutPieceWithId>, gap: usize) -> CutPieceWithId {
+    let first = &members[0];
+    let along_width = first.pattern_direction == PatternDirection::ParallelToWidth;
+    let along_grain: usize = members
+        .iter()
+        .map(|cut_piece| {
+            if along_width {
+                cut_piece.width
+            } else {
+                cut_piece.length
+            }
+        })
+        .sum::<usize>()
+        + gap * (members.len() - 1);
+    let across_grain = members
+        .iter()
+        .map(|cut_piece| {
+            if along_width {
+                cut_piece.length
+            } else {
+                cut_piece.width
+            }
+        })
+        .max()
+        .unwrap_or(0);
+    let (width, length) = if along_width {
+        (along_grain, across_grain)
+    } else {
+        (across_grain, along_grain)
+    };
+
+    CutPieceWithId {
+        id,
+        external_id: None,
+        width,
+        length,
+        pattern_direction: first.pattern_direction,
+        can_rotate: members.iter().all(|cut_piece| cut_piece.can_rotate),
+        tag: None,
+        max_edge_distance: members
+            .iter()
+            .filter_map(|cut_piece| cut_piece.max_edge_distance)
+            .min(),
+        stock_filter: first.stock_filter.clone(),
+        min_grade: members
+            .iter()
+            .filter_map(|cut_piece| cut_piece.min_grade)
+            .min(),
+        edge_banding: EdgeBanding::NONE,
+        match_group: None,
+        allow_pattern_mismatch: first.allow_pattern_mismatch,
+        optional: false,
+        material: first.material,
+        thickness: first.thickness,
+        members: Some(members.into()),
+    }
+}
+
+/// Returns a cut piece that stands in for copies of the same cut piece, which are `gap` apart
+/// along the grain. It keeps the external ID and tag of the copies, so separations still apply.
+fn quantity_group_cut_piece(id: usize, copies: Vec<CutPieceWithId>, gap: usize) -> CutPieceWithId {
+    let (external_id, tag) = (copies[0].external_id, copies[0].tag.clone());
+    CutPieceWithId {
+        external_id,
+        tag,
+        ..match_group_cut_piece(id, copies, gap)
+    }
+}
+
+/// Returns the cut pieces that weren't placed, with the copies of a cut piece combined into one
+/// with their total quantity.
+fn unplaced_cut_pieces<'a, I>(cut_pieces: I, values: &[Option<usize>]) -> Vec<CutPiece>
+where
+    I: IntoIterator<Item = &'a CutPieceWithId>,
+{
+    let mut cut_pieces: Vec<&CutPieceWithId> = cut_pieces
+        .into_iter()
+        .flat_map(|cut_piece| match cut_piece.members {
+            Some(ref members) => members.iter().collect(),
+            None => vec![cut_piece],
+        })
+        .collect();
+    cut_pieces.sort_by_key(|cut_piece| cut_piece.id);
+
+    let mut unplaced: Vec<CutPiece> = Vec::new();
+    let mut previous: Option<&CutPieceWithId> = None;
+    for cut_piece in cut_pieces {
+        match (previous, unplaced.last_mut()) {
+            (Some(previous), Some(last)) if is_copy_of(cut_piece, previous) => {
+                last.quantity += 1;
+            }
+            _ => unplaced.push(CutPiece {
+                value: values.get(cut_piece.id).copied().flatten(),
+                ..error_cut_piece(cut_piece)
+            }),
+        }
+        previous = Some(cut_piece);
+    }
+    unplaced
+}
+
+fn no_fit_for_cut_piece_error(cut_piece: &CutPieceWithId, summary: PlacementSummary) -> Error {
+    Error::NoFitForCutPiece(Box::new(error_cut_piece(cut_piece)), summary)
+}
+fn duplicate_cut_piece_error(cut_piece: &UsedCutPiece) -> Error {
+    Error::DuplicateCutPiece(Box::new(error_cut_piece(&cut_piece.into())))
+}
+type Result<T> = std::result::Result<T, Error>;
+
+/// A valid solution to an optimization.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug)]
+pub struct Solution {
+    /// Fitness score for this solution.
+    /// Ranges between 0.0 and 1.0 inclusive, with 1.0 being a perfect solution with no waste.
+    pub fitness: f64,
+
+    /// The stock pieces that were used for this solution, each containing the demand piece layout.
+    pub stock_pieces: Vec<ResultStockPiece>,
+
+    /// The objective this solution is best at, for solutions returned by
+    /// `Optimizer::optimize_guillotine_pareto` and `Optimizer::optimize_nested_pareto`.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub objective: Option<Objective>,
+
+    /// The cut pieces that couldn't be placed, which is only ever non-empty with
+    /// `OptimizeGoal::MaximizeValue`, such as with `Optimizer::set_allow_partial`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub unplaced_cut_pieces: Vec<CutPiece>,
+
+    /// Total value of the cut pieces in `stock_pieces`. See `CutPiece::value`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub placed_value: u64,
+
+    /// Total value of the cut pieces in `unplaced_cut_pieces`. See `CutPiece::value`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub unplaced_value: u64,
+
+    /// Annotations attached to this solution with `Solution::annotate`, such as the job number.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub annotations: BTreeMap<String, String>,
+
+    /// Estimate of how far this solution could be from the best possible one, as the fraction of
+    /// the area of its stock pieces that might be saved. It compares the area of the stock pieces
+    /// with a lower bound on the area the cut pieces need, which is the larger of the area of the
+    /// cut pieces, and the fewest stock pieces they could fit in times the area of the smallest
+    /// stock piece. A gap of 0.0 means no solution can use less stock area. The lower bound
+    /// ignores how the cut pieces fit together, so a larger gap doesn't always mean a better
+    /// solution exists, but it's worth optimizing again with more time if it is.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub optimality_gap: f64,
+
+    /// Random seed the optimizer used to find this solution, which is the one derived from the
+    /// input with `Optimizer::set_derive_random_seed` if no seed was set. Optimizing the same
+    /// input with this seed gives the same solution.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub random_seed: u64,
+
+    /// How the heuristics did in each pass of the optimizer that evolved layouts, when
+    /// diagnostics are enabled with `Optimizer::set_diagnostics`. Passes that are skipped, or
+    /// that place a few cut pieces without evolving layouts, aren't included.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub pass_stats: Vec<PassStats>,
+
+    /// Smallest width and length, in either orientation, that a waste piece needs to be one of
+    /// the `ResultStockPiece::reusable_offcuts`, which was set with
+    /// `Optimizer::set_min_reusable_waste`. `Solution::compact` splits the waste pieces by it
+    /// again.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub min_reusable_waste: (usize, usize),
+
+    /// How much of the detail of each stock piece this solution includes, which was set with
+    /// `Optimizer::set_result_detail`. `Solution::compact` keeps it.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub result_detail: ResultDetail,
+
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    price: usize,
+}
+
+impl Solution {
+    /// Returns whether this solution cuts the same cut pieces out of the same stock pieces in the
+    /// same places as `other`. Both solutions need to be sorted with `Optimizer::sort_solution`.
+    fn has_same_layout(&self, other: &Solution) -> bool {
+        self.stock_pieces.len() == other.stock_pieces.len()
+            && self
+                .stock_pieces
+                .iter()
+                .zip(&other.stock_pieces)
+                .all(|(a, b)| {
+                    a.width == b.width
+                        && a.length == b.length
+                        && a.external_id == b.external_id
+                        && a.cut_pieces == b.cut_pieces
+                })
+    }
+
+    /// Removes the details of the stock pieces that aren't included at the `detail` level.
+    fn reduce_detail(&mut self, detail: ResultDetail) {
+        self.result_detail = cmp::max(self.result_detail, detail);
+        for stock_piece in &mut self.stock_pieces {
+            stock_piece.reduce_detail(detail);
+        }
+    }
+
+    /// Returns the key that decides between solutions that are equally good otherwise, where
+    /// lower is preferred: fewer stock pieces, then fewer distinct stock sizes, then larger stock
+    /// pieces first.
+    fn tie_break_key(&self) -> (usize, usize, Vec<cmp::Reverse<u64>>) {
+        let distinct_sizes: FnvHashSet<(usize, usize)> = self
+            .stock_pieces
+            .iter()
+            .map(|stock_piece| (stock_piece.width, stock_piece.length))
+            .collect();
+        let mut areas: Vec<cmp::Reverse<u64>> = self
+            .stock_pieces
+            .iter()
+            .map(|stock_piece| cmp::Reverse(stock_piece.width as u64 * stock_piece.length as u64))
+            .collect();
+        areas.sort();
+        (self.stock_pieces.len(), distinct_sizes.len(), areas)
+    }
+
+    /// Returns a hash of the layout of this solution, which is the width, length, and external ID
+    /// of each of its stock pieces, and the external ID, location, size, and rotation of their
+    /// cut pieces. It only changes when the layout does, and is the same on every platform and
+    /// run, so it can be used to tell layouts apart across exports.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = FingerprintHasher::default();
+        for stock_piece in &self.stock_pieces {
+            hasher.write_usize(stock_piece.width);
+            hasher.write_usize(stock_piece.length);
+            hasher.write_option(stock_piece.external_id, FingerprintHasher::write_usize);
+            hasher.write_usize(stock_piece.cut_pieces.len());
+            for cut_piece in &stock_piece.cut_pieces {
+                hasher.write_option(cut_piece.external_id, FingerprintHasher::write_usize);
+                hasher.write_usize(cut_piece.x);
+                hasher.write_usize(cut_piece.y);
+                hasher.write_usize(cut_piece.width);
+                hasher.write_usize(cut_piece.length);
+                hasher.write_bool(cut_piece.is_rotated);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Gives each stock piece of this solution its `ResultStockPiece::sheet_id`, from the
+    /// fingerprint of the layout and the serial number of the stock piece.
+    fn assign_sheet_ids(&mut self) {
+        let fingerprint = self.fingerprint();
+        for stock_piece in &mut self.stock_pieces {
+            stock_piece.sheet_id = format!("{fingerprint:016x}-{}", stock_piece.serial_number);
+        }
+    }
+
+    /// Compacts the layout of each stock piece of this solution, by sliding its cut pieces down
+    /// and then to the left, toward the origin of the stock piece, for as long as they move. This
+    /// closes the gaps the optimizer left between cut pieces, so the free space is gathered
+    /// toward the top and right of the stock piece, such as to improve a solution that was saved
+    /// before `Optimizer::set_compact` was used. The cuts between the cut pieces are `cut_width`
+    /// wide, which should be the cut width the solution was optimized with.
+    ///
+    /// Cut pieces stay within the trim, on their side of the seams, and clear of the fixed
+    /// pieces. A cut piece is only moved if that doesn't take it farther from the nearest edge of
+    /// the stock piece, so `CutPiece::max_edge_distance` still holds, and a layout that could be
+    /// cut with guillotine cuts still can be, with no more stages. The waste pieces, maximal free
+    /// rectangles, cut length, sheet IDs, and kerf and waste areas are updated to match, and the
+    /// waste pieces are split into reusable offcuts and scrap again by
+    /// `Solution::min_reusable_waste`. The details that the `Solution::result_detail` level
+    /// leaves out aren't added back. Layouts that were mirrored with `Optimizer::set_mirror` are
+    /// still compacted toward the origin.
+    ///
+    /// The rip cuts, which are parallel to the length, are `rip_width` wide, and the crosscuts,
+    /// which are parallel to the width, are `crosscut_width` wide, which should be the cut widths
+    /// the solution was optimized with. See `Optimizer::set_cut_widths`.
+    pub fn compact(&mut self, rip_width: usize, crosscut_width: usize) -> &mut Self {
+        let blade_width = BladeWidth {
+            rip: rip_width,
+            crosscut: crosscut_width,
+        };
+        let (min_reusable_waste, result_detail) = (self.min_reusable_waste, self.result_detail);
+        self.stock_pieces = mem::take(&mut self.stock_pieces)
+            .into_iter()
+            .map(|stock_piece| {
+                let mut stock_piece = stock_piece.compacted(blade_width);
+                if result_detail == ResultDetail::Full {
+                    stock_piece.split_waste(min_reusable_waste);
+                    stock_piece.build_cut_tree(blade_width);
+                } else {
+                    stock_piece.reduce_detail(result_detail);
+                }
+                stock_piece
+            })
+            .collect();
+        self.assign_sheet_ids();
+        self
+    }
+
+    /// Attaches an annotation to this solution, replacing any annotation with the same `key`.
+    /// Annotations are kept when the solution is serialized, so downstream systems can keep
+    /// their own data with it. Use `ResultStockPiece::annotate` to annotate a stock piece.
+    pub fn annotate(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.annotations.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns the annotation attached to this solution with `key`.
+    pub fn annotation(&self, key: &str) -> Option<&str> {
+        self.annotations.get(key).map(String::as_str)
+    }
+
+    /// Returns the total length of the cuts needed to cut the cut pieces out of the stock pieces
+    /// of this solution. See `ResultStockPiece::cut_length`.
+    pub fn cut_length(&self) -> u64 {
+        self.stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.cut_length)
+            .sum()
+    }
+
+    /// Returns the total area of the stock pieces of this solution that's lost to the width of the
+    /// cuts. See `ResultStockPiece::kerf_area`.
+    pub fn kerf_area(&self) -> u64 {
+        self.stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.kerf_area)
+            .sum()
+    }
+
+    /// Returns the total area of the waste pieces of this solution. See
+    /// `ResultStockPiece::waste_area`.
+    pub fn waste_area(&self) -> u64 {
+        self.stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.waste_area)
+            .sum()
+    }
+
+    /// Returns the totals of the areas and prices of the stock pieces of this solution, and the
+    /// utilization of each of them. They're the same at every `ResultDetail` level.
+    pub fn stats(&self) -> SolutionStats {
+        let mut stats = SolutionStats {
+            stock_pieces: self.stock_pieces.len(),
+            sheets: Vec::with_capacity(self.stock_pieces.len()),
+            ..Default::default()
+        };
+        for stock_piece in &self.stock_pieces {
+            let stock_area = stock_piece.width as u64 * stock_piece.length as u64;
+            // The utilization is kept when the cut pieces are left out of a summary, and the used
+            // area is recovered from it exactly.
+            let used_area = (stock_piece.utilization * stock_area as f64).round() as u64;
+            stats.stock_area += stock_area;
+            stats.used_area += used_area;
+            stats.waste_area += stock_piece.waste_area;
+            stats.kerf_area += stock_piece.kerf_area;
+            stats.cut_length += stock_piece.cut_length;
+            stats.price += stock_piece.price;
+            stats.sheets.push(SheetStats {
+                serial_number: stock_piece.serial_number,
+                used_area,
+                utilization_percent: stock_piece.utilization * 100.0,
+                cut_length: stock_piece.cut_length,
+            });
+        }
+        if stats.stock_area > 0 {
+            stats.utilization_percent = stats.used_area as f64 * 100.0 / stats.stock_area as f64;
+        }
+        stats
+    }
+
+    /// Returns the waste pieces of this solution that are at least `min_width` by `min_length`, in
+    /// either orientation, as stock pieces for another optimization. Each one has a quantity of 1,
+    /// a price of 0, and the pattern direction, `external_id`, grade, material, and thickness of
+    /// the stock piece it remains from. Its `source` links it back to that stock piece, which is
+    /// passed through to the solution of the other optimization.
+    pub fn offcuts(&self, min_width: usize, min_length: usize) -> Vec<StockPiece> {
+        self.stock_pieces
+            .iter()
+            .flat_map(|stock_piece| {
+                stock_piece
+                    .waste_pieces
+                    .iter()
+                    .filter(move |waste_piece| {
+                        (waste_piece.width >= min_width && waste_piece.length >= min_length)
+                            || (waste_piece.width >= min_length && waste_piece.length >= min_width)
+                    })
+                    .map(move |waste_piece| StockPiece {
+                        external_id: stock_piece.external_id,
+                        grade: stock_piece.grade,
+                        material: stock_piece.material,
+                        thickness: stock_piece.thickness,
+                        source: Some(OffcutSource {
+                            serial_number: stock_piece.serial_number,
+                            external_id: stock_piece.external_id,
+                            x: waste_piece.x,
+                            y: waste_piece.y,
+                        }),
+                        ..waste_piece.to_stock_piece(0, Some(1))
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns which stock pieces the cut pieces with each external ID were placed on, and how
+    /// many were placed on each, for tracking consumption without the layout. The assignments
+    /// are ordered by external ID, with `None` last, and the stock pieces of each are ordered by
+    /// serial number.
+    pub fn cut_piece_assignments(&self) -> Vec<CutPieceAssignment> {
+        let mut assignments: Vec<CutPieceAssignment> = Vec::new();
+        for stock_piece in &self.stock_pieces {
+            for cut_piece in &stock_piece.cut_pieces {
+                let i = match assignments
+                    .iter()
+                    .position(|assignment| assignment.external_id == cut_piece.external_id)
+                {
+                    Some(i) => i,
+                    None => {
+                        assignments.push(CutPieceAssignment {
+                            external_id: cut_piece.external_id,
+                            stock_pieces: Vec::new(),
+                        });
+                        assignments.len() - 1
+                    }
+                };
+
+                let stock_pieces = &mut assignments[i].stock_pieces;
+                match stock_pieces
+                    .iter_mut()
+                    .find(|count| count.serial_number == stock_piece.serial_number)
+                {
+                    Some(count) => count.count += 1,
+                    None => stock_pieces.push(StockPieceCount {
+                        serial_number: stock_piece.serial_number,
+                        count: 1,
+                    }),
+                }
+            }
+        }
+
+        assignments
+            .sort_by_key(|assignment| (assignment.external_id.is_none(), assignment.external_id));
+        for assignment in &mut assignments {
+            assignment
+                .stock_pieces
+                .sort_by_key(|count| count.serial_number);
+        }
+        assignments
+    }
+
+    /// Returns a cut list of this solution in CSV format, with a section for the cut pieces and a
+    /// section for the reusable offcuts, separated by an empty line. Each section starts with a
+    /// header row, and has a row for each piece, in the order of the stock pieces and of the
+    /// pieces on them. The columns of the cut pieces are:
+    ///
+    /// `sheet,sheet_id,external_id,tag,x,y,width,length,rotated`
+    ///
+    /// and the columns of the reusable offcuts are:
+    ///
+    /// `sheet,sheet_id,x,y,width,length`
+    ///
+    /// where `sheet` is the serial number of the stock piece, and `rotated` is `true` or `false`.
+    /// An `external_id` or `tag` that the cut piece doesn't have is empty. Fields with a comma,
+    /// double quote, or line break are quoted, with their double quotes doubled, and rows end with
+    /// `\n`. The sections only have a header row if the solution doesn't include the layout. See
+    /// `Optimizer::set_result_detail`.
+    pub fn to_csv(&self) -> String {
+        use std::fmt::Write;
+
+        let mut csv = String::from("sheet,sheet_id,external_id,tag,x,y,width,length,rotated\n");
+        for stock_piece in &self.stock_pieces {
+            for cut_piece in &stock_piece.cut_pieces {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{},{}",
+                    stock_piece.serial_number,
+                    csv_field(&stock_piece.sheet_id),
+                    cut_piece
+                        .external_id
+                        .map(|external_id| external_id.to_string())
+                        .unwrap_or_default(),
+                    csv_field(cut_piece.tag.as_deref().unwrap_or_default()),
+                    cut_piece.x,
+                    cut_piece.y,
+                    cut_piece.width,
+                    cut_piece.length,
+                    cut_piece.is_rotated,
+                );
+            }
+        }
+
+        csv.push_str("\nsheet,sheet_id,x,y,width,length\n");
+        for stock_piece in &self.stock_pieces {
+            for offcut in &stock_piece.reusable_offcuts {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{}",
+                    stock_piece.serial_number,
+                    csv_field(&stock_piece.sheet_id),
+                    offcut.x,
+                    offcut.y,
+                    offcut.width,
+                    offcut.length,
+                );
+            }
+        }
+        csv
+    }
+}
+
+/// Returns `text` as a CSV field, quoted if it has a comma, double quote, or line break.
+fn csv_field(text: &str) -> std::borrow::Cow<'_, str> {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\"")).into()
+    } else {
+        text.into()
+    }
+}
+
+/// Totals of the stock pieces of a solution, from `Solution::stats`. The area of the stock
+/// pieces is made up of the used area, the waste area, the kerf area, and the area of their trim
+/// and fixed pieces.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SolutionStats {
+    /// Number of stock pieces.
+    pub stock_pieces: usize,
+
+    /// Total area of the stock pieces.
+    pub stock_area: u64,
+
+    /// Total area of the stock pieces that is used by cut pieces.
+    pub used_area: u64,
+
+    /// Total area of the waste pieces. See `ResultStockPiece::waste_area`.
+    pub waste_area: u64,
+
+    /// Total area lost to the width of the cuts. See `ResultStockPiece::kerf_area`.
+    pub kerf_area: u64,
+
+    /// Total length of the cuts. See `ResultStockPiece::cut_length`.
+    pub cut_length: u64,
+
+    /// Percentage of the area of the stock pieces that is used by cut pieces, from 0.0 to 100.0.
+    pub utilization_percent: f64,
+
+    /// Total price of the stock pieces.
+    pub price: usize,
+
+    /// The stock pieces, in the order of the solution.
+    pub sheets: Vec<SheetStats>,
+}
+
+/// Totals of one stock piece of a solution, for `SolutionStats`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SheetStats {
+    /// Serial number of the stock piece in the solution.
+    pub serial_number: usize,
+
+    /// Area of the stock piece that is used by cut pieces.
+    pub used_area: u64,
+
+    /// Percentage of the area of the stock piece that is used by cut pieces, from 0.0 to 100.0.
+    pub utilization_percent: f64,
+
+    /// Length of the cuts on the stock piece. See `ResultStockPiece::cut_length`.
+    pub cut_length: u64,
+}
+
+/// The stock pieces that the cut pieces with an external ID were placed on, from
+/// `Solution::cut_piece_assignments`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CutPieceAssignment {
+    /// ID that matches the one on the cut pieces that were passed to the optimizer.
+    pub external_id: Option<usize>,
+
+    /// The stock pieces the cut pieces were placed on.
+    pub stock_pieces: Vec<StockPieceCount>,
+}
+
+/// Number of cut pieces placed on a stock piece, for a `CutPieceAssignment`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StockPieceCount {
+    /// Serial number of the stock piece in the solution.
+    pub serial_number: usize,
+
+    /// Number of cut pieces placed on the stock piece.
+    pub count: usize,
+}
+
+/// A rough prediction of the stock pieces needed for the cut pieces, from `Optimizer::estimate`.
+/// It comes from a single greedy pass instead of a full optimization, so an optimized solution
+/// often needs fewer stock pieces.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Estimate {
+    /// Estimated number of stock pieces needed.
+    pub stock_pieces: usize,
+
+    /// Estimated fraction of the area of the stock pieces that is used by cut pieces.
+    pub utilization: f64,
+
+    /// Estimated total price of the stock pieces.
+    pub price: usize,
+
+    /// Number of cut pieces that didn't fit in any available stock piece.
+    pub unplaced_cut_pieces: usize,
+}
+
+/// Which stock pieces to buy for the cut pieces, from `Optimizer::recommend_stock`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug)]
+pub struct StockRecommendation {
+    /// The ways to buy stock pieces for the cut pieces, ordered from best to worst. There's
+    /// always at least one.
+    pub options: Vec<StockPurchaseOption>,
+}
+
+impl StockRecommendation {
+    /// Returns the recommended option, which is the first one.
+    pub fn recommended(&self) -> &StockPurchaseOption {
+        &self.options[0]
+    }
+}
+
+/// One way to buy stock pieces for the cut pieces, for a `StockRecommendation`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Clone, Debug)]
+pub struct StockPurchaseOption {
+    /// The stock pieces to buy, with one entry for each external ID and size.
+    pub purchases: Vec<StockPurchase>,
+
+    /// Total price of the stock pieces.
+    pub price: usize,
+
+    /// Total number of stock pieces.
+    pub stock_pieces: usize,
+
+    /// Fraction of the area of the stock pieces that is used by cut pieces.
+    pub utilization: f64,
+
+    /// The solution that lays out the cut pieces on the stock pieces.
+    pub solution: Solution,
+}
+
+impl StockPurchaseOption {
+    /// Creates an option that buys the stock pieces of `solution`.
+    fn from_solution(solution: Solution) -> Self {
+        let mut purchases: Vec<StockPurchase> = Vec::new();
+        for stock_piece in &solution.stock_pieces {
+            let (width, length) = stock_piece
+                .nominal_size
+                .unwrap_or((stock_piece.width, stock_piece.length));
+            match purchases.iter_mut().find(|purchase| {
+                purchase.external_id == stock_piece.external_id
+                    && purchase.width == width
+                    && purchase.length == length
+            }) {
+                Some(purchase) => {
+                    purchase.quantity += 1;
+                    purchase.price += stock_piece.price;
+                }
+                None => purchases.push(StockPurchase {
+                    external_id: stock_piece.external_id,
+                    width,
+                    length,
+                    quantity: 1,
+                    price: stock_piece.price,
+                }),
+            }
+        }
+        purchases.sort_by_key(|purchase| (purchase.width, purchase.length, purchase.external_id));
+
+        let stock_area: u64 = solution
+            .stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.width as u64 * stock_piece.length as u64)
+            .sum();
+        let used_area: u64 = solution
+            .stock_pieces
+            .iter()
+            .map(ResultStockPiece::used_area)
+            .sum();
+
+        Self {
+            purchases,
+            price: solution.stock_pieces.iter().map(|sp| sp.price).sum(),
+            stock_pieces: solution.stock_pieces.len(),
+            utilization: if stock_area == 0 {
+                0.0
+            } else {
+                used_area as f64 / stock_area as f64
+            },
+            solution,
+        }
+    }
+}
+
+/// A number of stock pieces of the same external ID and size to buy, for a
+/// `StockPurchaseOption`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StockPurchase {
+    /// ID that matches the one on the stock pieces that were passed to the optimizer.
+    pub external_id: Option<usize>,
+
+    /// Width of the stock pieces, which is their nominal size if they have one.
+    pub width: usize,
+
+    /// Length of the stock pieces, which is their nominal size if they have one.
+    pub length: usize,
+
+    /// Number of stock pieces to buy.
+    pub quantity: usize,
+
+    /// Total price of the stock pieces.
+    pub price: usize,
+}
+
+/// What the optimizer looks for in a solution, which is how it scores the layouts it tries and
+/// picks the best one. See `Optimizer::set_fitness_objective`. Unlike `Objective`, which picks
+/// solutions from the ones found with the default scoring, this changes what the genetic
+/// algorithm optimizes for.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum FitnessObjective {
+    /// Waste as little of the area of the stock pieces as possible.
+    MinimizeWaste,
+
+    /// Use as few stock pieces as possible.
+    MinimizeSheetCount,
+
+    /// Use the lowest-priced stock pieces, with the cut pieces packed as tightly as possible
+    /// among solutions with the same price. Each stock size is also optimized on its own, in case
+    /// it's cheaper than mixing sizes.
+    #[default]
+    MinimizePrice,
+
+    /// Cut the cut pieces out with the shortest total length of cuts. See
+    /// `ResultStockPiece::cut_length`.
+    MinimizeCutLength,
+
+    /// Balance the other objectives by these weights, which are relative to each other.
+    #[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+    Weighted {
+        /// Weight of `MinimizeWaste`.
+        waste: f64,
+
+        /// Weight of `MinimizeSheetCount`.
+        sheet_count: f64,
+
+        /// Weight of `MinimizePrice`.
+        price: f64,
+
+        /// Weight of `MinimizeCutLength`.
+        cut_length: f64,
+    },
+}
+
+impl FitnessObjective {
+    /// Returns the weights of waste, sheet count, price and cut length for this objective.
+    fn weights(self) -> [f64; 4] {
+        match self {
+            FitnessObjective::MinimizeWaste => [1.0, 0.0, 0.0, 0.0],
+            FitnessObjective::MinimizeSheetCount => [0.0, 1.0, 0.0, 0.0],
+            FitnessObjective::MinimizePrice => [0.0, 0.0, 1.0, 0.0],
+            FitnessObjective::MinimizeCutLength => [0.0, 0.0, 0.0, 1.0],
+            FitnessObjective::Weighted {
+                waste,
+                sheet_count,
+                price,
+                cut_length,
+            } => [waste, sheet_count, price, cut_length],
+        }
+    }
+}
+
+/// What a solution needs to do with the cut pieces. See `Optimizer::set_optimize_goal`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum OptimizeGoal {
+    /// Place every cut piece, using whichever stock pieces are best for the fitness objective.
+    /// Optimizing fails if a cut piece doesn't fit.
+    #[default]
+    PlaceAll,
+
+    /// Place the cut pieces with the most total value that fit in the stock pieces, such as to
+    /// pick which pieces of a wish list to cut from a few remnants. The cut pieces that don't fit
+    /// are left out of the solution instead of failing the optimization. The value of each cut
+    /// piece is `CutPiece::value`.
+    MaximizeValue,
+}
+
+/// Something the optimizer can minimize when looking for solutions.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Objective {
+    /// Total price of the stock pieces.
+    Price,
+
+    /// Total area of the stock pieces that isn't used by cut pieces.
+    Waste,
+
+    /// Total number of cuts needed to cut the cut pieces from the stock pieces, assuming
+    /// guillotine cuts.
+    Cuts,
+}
+
+/// All of the inputs and options for an optimization, as a plain value. This is an alternative to
+/// the setters on `Optimizer`, useful when the whole job is received at once, such as by a
+/// service.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase", default))]
+#[derive(Clone, Debug)]
+pub struct Problem {
+    /// Stock pieces that the optimizer can use to optimize cut pieces.
+    pub stock_pieces: Vec<StockPiece>,
+
+    /// Desired cut pieces that need to be cut from stock pieces.
+    pub cut_pieces: Vec<CutPiece>,
+
+    /// Width of the cut to use between cut pieces.
+    pub cut_width: usize,
+
+    /// Widths of the rip cuts and the crosscuts, which replace `cut_width` when they're set.
+    pub cut_widths: Option<(usize, usize)>,
+
+    /// Random seed used by the genetic algorithms in the optimizer.
+    pub random_seed: u64,
+
+    /// Whether to derive the random seed from the stock pieces and cut pieces instead of using
+    /// `random_seed`. See `Optimizer::set_derive_random_seed`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub derive_random_seed: bool,
+
+    /// How the random numbers used in each epoch are derived from the random seed.
+    pub epoch_seed_strategy: EpochSeedStrategy,
+
+    /// Whether the optimizer should allow mixed sized stock pieces in the results.
+    pub allow_mixed_stock_sizes: bool,
+
+    /// Order of the stock pieces in the solution.
+    pub stock_piece_order: StockPieceOrder,
+
+    /// Order of the cut pieces within each stock piece in the solution.
+    pub cut_piece_order: CutPieceOrder,
+
+    /// How the layouts of the solution are mirrored.
+    pub mirror: Mirror,
+
+    /// Whether the layouts of the solution are compacted. See `Optimizer::set_compact`.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub compact: bool,
+
+    /// How much of the detail of each stock piece the solution includes.
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub result_detail: ResultDetail,
+
+    /// Minimum width and length of the waste pieces that are reusable offcuts.
+    pub min_reusable_waste: Option<(usize, usize)>,
+
+    /// Whether each stock piece in the solution should include diagnostics.
+    pub diagnostics: bool,
+
+    /// Policy for rotating cut pieces to match the pattern direction of stock pieces.
+    pub rotate_to_match_pattern: RotateToMatchPattern,
+
+    /// Whether the optimizer should also try "worst fit" heuristics.
+    pub worst_fit_heuristics: bool,
+
+    /// Whether the optimizer should also try the "smallest y" heuristic.
+    pub smallest_y_heuristic: bool,
+
+    /// Whether nested optimization should split the waste along guillotine cuts.
+    pub guillotine_waste_pieces: bool,
+
+    /// Maximum number of stages of cuts in guillotine layouts.
+    pub max_guillotine_stages: Option<usize>,
+
+    /// Direction of the first cuts across each stock piece in guillotine layouts.
+    pub first_cut_direction: Option<CutDirection>,
+
+    /// Direction of the guillotine cuts that split nested layouts into strips.
+    pub nested_strip_direction: Option<CutDirection>,
+
+    /// Pairs of external IDs of cut pieces that must be placed on different stock pieces.
+    pub separations: Vec<(usize, usize)>,
+
+    /// Whether to prefer layouts where the banded edges of cut pieces face outward.
+    pub prefer_banded_edges_outward: bool,
+
+    /// Fraction of the fitness lost for cut pieces placed against the pattern, if they can be.
+    pub pattern_mismatch_penalty: Option<f64>,
+
+    /// Most stock pieces a solution can use.
+    pub max_stock_pieces: Option<usize>,
+
+    /// Heuristics the optimizer tries for guillotine optimization.
+    pub guillotine_heuristic_profile: HeuristicProfile,
+
+    /// Heuristics the optimizer tries for nested optimization.
+    pub nested_heuristic_profile: HeuristicProfile,
+
+    /// What the optimizer looks for in a solution.
+    pub fitness_objective: FitnessObjective,
+
+    /// What a solution needs to do with the cut pieces.
+    pub goal: OptimizeGoal,
+
+    /// Objectives to find the best solution for when optimizing for a Pareto front.
+    pub objectives: Vec<Objective>,
+
+    /// Approximate maximum number of bytes the optimizer should use.
+    pub max_memory: Option<usize>,
+
+    /// Maximum number of cut pieces the optimizer lays out, counting each group of copies once.
+    pub max_cut_pieces: Option<usize>,
+
+    /// Most copies of a cut piece the optimizer lays out together as one.
+    pub quantity_group_size: Option<usize>,
+
+    /// Utilization at which the optimizer stops early when a solution uses the fewest stock
+    /// pieces possible.
+    pub early_stop_utilization: Option<f64>,
+
+    /// Maximum time the optimizer should spend optimizing.
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for Problem {
+    fn default() -> Self {
+        Self {
+            stock_pieces: Default::default(),
+            cut_pieces: Default::default(),
+            cut_width: Default::default(),
+            cut_widths: None,
+            random_seed: Default::default(),
+            derive_random_seed: false,
+            epoch_seed_strategy: Default::default(),
+            allow_mixed_stock_sizes: true,
+            stock_piece_order: Default::default(),
+            cut_piece_order: Default::default(),
+            mirror: Default::default(),
+            compact: false,
+            result_detail: Default::default(),
+            min_reusable_waste: None,
+            diagnostics: false,
+            rotate_to_match_pattern: Default::default(),
+            worst_fit_heuristics: false,
+            smallest_y_heuristic: false,
+            guillotine_waste_pieces: false,
+            max_guillotine_stages: None,
+            first_cut_direction: None,
+            nested_strip_direction: None,
+            separations: Vec::new(),
+            prefer_banded_edges_outward: false,
+            pattern_mismatch_penalty: None,
+            max_stock_pieces: None,
+            guillotine_heuristic_profile: Default::default(),
+            nested_heuristic_profile: Default::default(),
+            fitness_objective: Default::default(),
+            goal: Default::default(),
+            objectives: Vec::new(),
+            max_memory: None,
+            max_cut_pieces: None,
+            quantity_group_size: None,
+            early_stop_utilization: None,
+            time_limit: None,
+        }
+    }
+}
+
+impl Problem {
+    /// Optimize this problem with guillotine cuts. See `Optimizer::optimize_guillotine`.
+    pub fn optimize_guillotine<F, R>(&self, progress_callback: F) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        Optimizer::from_problem(self.clone()).optimize_guillotine(progress_callback)
+    }
+
+    /// Optimize this problem without the requirement of guillotine cuts. See
+    /// `Optimizer::optimize_nested`.
+    pub fn optimize_nested<F, R>(&self, progress_callback: F) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        Optimizer::from_problem(self.clone()).optimize_nested(progress_callback)
+    }
+}
+
+/// Value returned from the progress callback passed to methods like
+/// `Optimizer::optimize_guillotine`, which tells the optimizer whether to keep going. A callback
+/// that returns `()` never stops the optimization. A callback that returns `ControlFlow<()>` can
+/// return `ControlFlow::Break(())` to stop it early, in which case the best solution found so far
+/// is returned.
+pub trait ProgressControl {
+    /// Returns whether the optimization should stop.
+    fn should_stop(self) -> bool;
+}
+
+impl ProgressControl for () {
+    fn should_stop(self) -> bool {
+        false
+    }
+}
+
+impl ProgressControl for ControlFlow<()> {
+    fn should_stop(self) -> bool {
+        self.is_break()
+    }
+}
+
+/// Event that's sent to the callback set with `Optimizer::set_progress_event_callback` while
+/// optimizing.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProgressEvent {
+    /// The optimizer started a phase. Each phase optimizes with a different set of stock sizes,
+    /// and phases that can't find a cheaper solution than an earlier one are skipped.
+    PhaseStarted {
+        /// Index of this phase, starting at 0.
+        phase: usize,
+
+        /// Total number of phases.
+        phases: usize,
+    },
+
+    /// The optimizer completed a generation of the current phase.
+    EpochCompleted {
+        /// Fitness of the best solution in the current phase.
+        best: f64,
+    },
+
+    /// The optimizer found a solution with a higher fitness than any it found before.
+    NewBestSolution {
+        /// Fitness of the solution. It's below 0.0 if not all cut pieces fit.
+        fitness: f64,
+    },
+
+    /// The optimizer finished, whether or not it found a solution. This is always the last event.
+    Finished,
+}
+
+type ProgressEventCallback = Box<dyn Fn(ProgressEvent) + Send + Sync>;
+
+/// Creates a random number generator from a seed. See `Optimizer::set_rng_factory`.
+pub(crate) type RngFactory = Arc<dyn Fn(u64) -> Box<dyn RngCore + Send> + Send + Sync>;
+
+/// Creates a random number generator from `seed` with `rng_factory`, or a `StdRng` if there's no
+/// factory.
+pub(crate) fn new_rng(rng_factory: &Option<RngFactory>, seed: u64) -> Box<dyn RngCore + Send> {
+    match rng_factory {
+        Some(rng_factory) => rng_factory(seed),
+        None => Box::new(StdRng::seed_from_u64(seed)),
+    }
+}
+
+/// Hasher for fingerprints, which hashes each value as a little-endian `u64`, so a fingerprint is
+/// the same on every platform, whatever the width and byte order of `usize` there.
+#[derive(Default)]
+struct FingerprintHasher(FnvHasher);
+
+impl FingerprintHasher {
+    fn write_u64(&mut self, value: u64) {
+        self.0.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_bool(&mut self, value: bool) {
+        self.write_u64(value as u64);
+    }
+
+    /// Hashes whether there's a value, and then the value with `write` if there is one.
+    fn write_option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        match value {
+            Some(value) => {
+                self.write_u64(1);
+                write(self, value);
+            }
+            None => self.write_u64(0),
+        }
+    }
+
+    fn write_pattern_direction(&mut self, pattern_direction: PatternDirection) {
+        self.write_u64(match pattern_direction {
+            PatternDirection::None => 0,
+            PatternDirection::ParallelToWidth => 1,
+            PatternDirection::ParallelToLength => 2,
+            PatternDirection::Either => 3,
+        });
+    }
+
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+/// Handle to the best solution found so far by an optimization, which can be read from another
+/// thread while the optimization runs, such as to show a live preview of the layout. Set it with
+/// `Optimizer::set_solution_snapshot`, and keep a clone of it to read from.
+///
+/// When the optimizer finds a better layout, it only stores a copy of the layout in the
+/// snapshot. The solution is built from the layout by the thread that reads it, so reading the
+/// snapshot doesn't slow down the optimization.
+#[derive(Clone, Default)]
+pub struct SolutionSnapshot {
+    state: Arc<Mutex<SnapshotState>>,
+}
+
+#[derive(Default)]
+struct SnapshotState {
+    // Number of times the snapshot was updated.
+    version: u64,
+
+    // Fitness of the best solution so far.
+    fitness: Option<f64>,
+
+    // Builds the best solution so far, until it's read.
+    pending: Option<Box<dyn FnOnce() -> Option<Solution> + Send>>,
+
+    // The best solution so far, once it's built.
+    solution: Option<Solution>,
+}
+
+impl SolutionSnapshot {
+    /// Create a new empty snapshot.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the best solution found so far, or `None` if the optimization hasn't found a
+    /// solution that places every cut piece yet. The solution is sorted like the solution that the
+    /// optimization returns.
+    pub fn latest(&self) -> Option<Solution> {
+        let (version, pending) = {
+            let mut state = self.lock();
+            match state.pending.take() {
+                Some(pending) => (state.version, pending),
+                None => return state.solution.clone(),
+            }
+        };
+
+        // Build the solution without holding the lock, so the optimizer isn't kept waiting.
+        let solution = pending();
+
+        let mut state = self.lock();
+        if state.version == version && solution.is_some() {
+            state.solution.clone_from(&solution);
+        }
+        solution.or_else(|| state.solution.clone())
+    }
+
+    /// Returns the fitness of the best solution found so far, without building the solution.
+    pub fn fitness(&self) -> Option<f64> {
+        self.lock().fitness
+    }
+
+    /// Returns the number of times the snapshot was updated with a better solution, which can be
+    /// compared with an earlier version to check whether there's a new solution to read.
+    pub fn version(&self) -> u64 {
+        self.lock().version
+    }
+
+    /// Replaces the solution with the one built by `build`, which is only called when the
+    /// snapshot is read.
+    fn update<F>(&self, fitness: f64, build: F)
+    where
+        F: FnOnce() -> Option<Solution> + Send + 'static,
+    {
+        let replaced = {
+            let mut state = self.lock();
+            state.version += 1;
+            state.fitness = Some(fitness);
+            state.pending.replace(Box::new(build))
+        };
+        // Drop the replaced layout after releasing the lock.
+        drop(replaced);
+    }
+
+    /// Removes the solution of an earlier optimization.
+    fn clear(&self) {
+        let mut state = self.lock();
+        state.fitness = None;
+        state.pending = None;
+        state.solution = None;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SnapshotState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for SolutionSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SolutionSnapshot")
+            .field("version", &self.version())
+            .field("fitness", &self.fitness())
+            .finish()
+    }
+}
+
+#[cfg(feature = "audit-log")]
+pub use audit::AuditEntry;
+#[cfg(not(feature = "audit-log"))]
+use audit::AuditEntry;
+
+/// The kind of cuts the optimizer lays out cut pieces for.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub enum OptimizeMode {
+    /// Only guillotine cuts. See `Optimizer::optimize_guillotine`.
+    #[default]
+    Guillotine,
+
+    /// Cuts that can start and stop in the middle of the stock piece. See
+    /// `Optimizer::optimize_nested`.
+    Nested,
+}
+
+impl From<Problem> for Optimizer {
+    fn from(problem: Problem) -> Self {
+        Optimizer::from_problem(problem)
+    }
+}
+
+/// Builder for an `Optimizer` that checks its inputs before optimizing, so that problems like a cut
+/// piece that doesn't fit any stock piece are all reported at once, instead of one at a time after
+/// optimizing. The inputs are added and the options are set with the setters of `Optimizer`,
+/// which the builder dereferences to.
+#[derive(Default)]
+pub struct OptimizerBuilder {
+    optimizer: Optimizer,
+}
+
+impl OptimizerBuilder {
+    /// Create a new optimizer builder.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the optimizer, or `Error::InvalidInput` with every problem found with its inputs.
+    pub fn build(self) -> Result<Optimizer> {
+        let errors = self.optimizer.input_errors();
+        if errors.is_empty() {
+            Ok(self.optimizer)
+        } else {
+            Err(Error::InvalidInput(errors))
+        }
+    }
+}
+
+impl ops::Deref for OptimizerBuilder {
+    type Target = Optimizer;
+
+    fn deref(&self) -> &Optimizer {
+        &self.optimizer
+    }
+}
+
+impl ops::DerefMut for OptimizerBuilder {
+    fn deref_mut(&mut self) -> &mut Optimizer {
+        &mut self.optimizer
+    }
+}
+
+/// Most cut pieces that are placed by trying every way of placing them, instead of with the
+/// genetic algorithm.
+const MAX_ENUMERATED_CUT_PIECES: usize = 3;
+
+/// Most placements that are tried when placing a few cut pieces every way they can be placed.
+const MAX_ENUMERATED_PLACEMENTS: usize = 100_000;
+
+/// Fewest layouts in a population before copies of the cut pieces are grouped to fit in the
+/// maximum memory. See `Optimizer::set_max_memory`.
+const MIN_UNITS_IN_MEMORY: usize = 10;
+
+/// Optimizer for optimizing rectangular cut pieces from rectangular
+/// stock pieces.
+pub struct Optimizer {
+    stock_pieces: Vec<StockPiece>,
+    cut_pieces: Vec<CutPieceWithId>,
+    // Value of each cut piece as given by `CutPiece::value`, indexed by its ID.
+    cut_piece_values: Vec<Option<usize>>,
+    cut_width: BladeWidth,
+    // Random seed set with `set_random_seed`.
+    random_seed: Option<u64>,
+    derive_random_seed: bool,
+    epoch_seed_strategy: EpochSeedStrategy,
+    allow_mixed_stock_sizes: bool,
+    stock_piece_order: StockPieceOrder,
+    cut_piece_order: CutPieceOrder,
+    mirror: Mirror,
+    compact: bool,
+    result_detail: ResultDetail,
+    min_reusable_waste: (usize, usize),
+    diagnostics: bool,
+    rotate_to_match_pattern: RotateToMatchPattern,
+    worst_fit_heuristics: bool,
+    smallest_y_heuristic: bool,
+    guillotine_waste_pieces: bool,
+    max_guillotine_stages: Option<usize>,
+    first_cut_direction: Option<CutDirection>,
+    nested_strip_direction: Option<CutDirection>,
+    // Pairs of external IDs, smallest first, of cut pieces that must be on different stock pieces.
+    separations: FnvHashSet<(usize, usize)>,
+    prefer_banded_edges_outward: bool,
+    pattern_mismatch_penalty: Option<f64>,
+    max_stock_pieces: Option<usize>,
+    guillotine_heuristic_profile: HeuristicProfile,
+    nested_heuristic_profile: HeuristicProfile,
+    fitness_objective: FitnessObjective,
+    goal: OptimizeGoal,
+    objectives: Vec<Objective>,
+    max_memory: Option<usize>,
+    early_stop_utilization: Option<f64>,
+    time_limit: Option<Duration>,
+    max_cut_pieces: Option<usize>,
+    quantity_group_size: usize,
+    // Number of cut pieces the optimizer lays out for the cut pieces that were added, counting
+    // each group of copies once.
+    num_layout_pieces: usize,
+    // Cut pieces that weren't added because they would go over `max_cut_pieces`.
+    pending_cut_pieces: Vec<CutPiece>,
+    progress_event_callback: Option<ProgressEventCallback>,
+    audit_log_callback: Option<AuditLogCallback>,
+    solution_snapshot: Option<SolutionSnapshot>,
+    rng_factory: Option<RngFactory>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self {
+            stock_pieces: Default::default(),
+            cut_pieces: Default::default(),
+            cut_piece_values: Default::default(),
+            cut_width: Default::default(),
+            random_seed: None,
+            derive_random_seed: false,
+            epoch_seed_strategy: Default::default(),
+            allow_mixed_stock_sizes: true,
+            stock_piece_order: Default::default(),
+            cut_piece_order: Default::default(),
+            mirror: Default::default(),
+            compact: false,
+            result_detail: Default::default(),
+            min_reusable_waste: (0, 0),
+            diagnostics: false,
+            rotate_to_match_pattern: Default::default(),
+            worst_fit_heuristics: false,
+            smallest_y_heuristic: false,
+            guillotine_waste_pieces: false,
+            max_guillotine_stages: None,
+            first_cut_direction: None,
+            nested_strip_direction: None,
+            separations: Default::default(),
+            prefer_banded_edges_outward: false,
+            pattern_mismatch_penalty: None,
+            max_stock_pieces: None,
+            guillotine_heuristic_profile: Default::default(),
+            nested_heuristic_profile: Default::default(),
+            fitness_objective: Default::default(),
+            goal: Default::default(),
+            objectives: Vec::new(),
+            max_memory: None,
+            early_stop_utilization: None,
+            time_limit: None,
+            max_cut_pieces: None,
+            quantity_group_size: 1,
+            num_layout_pieces: 0,
+            pending_cut_pieces: Vec::new(),
+            progress_event_callback: None,
+            audit_log_callback: None,
+            solution_snapshot: None,
+            rng_factory: None,
+        }
+    }
+}
+
+impl Optimizer {
+    /// Create a new optimizer.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a new optimizer with all of the inputs and options of the `Problem`.
+    pub fn from_problem(problem: Problem) -> Self {
+        let mut optimizer = Self::new();
+        if let Some(max_cut_pieces) = problem.max_cut_pieces {
+            optimizer.set_max_cut_pieces(max_cut_pieces);
+        }
+        if let Some(size) = problem.quantity_group_size {
+            optimizer.set_quantity_group_size(size);
+        }
+        optimizer
+            .add_stock_pieces(problem.stock_pieces)
+            .add_cut_pieces(problem.cut_pieces)
+            .set_cut_width(problem.cut_width)
+            .set_derive_random_seed(problem.derive_random_seed)
+            .set_epoch_seed_strategy(problem.epoch_seed_strategy)
+            .allow_mixed_stock_sizes(problem.allow_mixed_stock_sizes)
+            .set_stock_piece_order(problem.stock_piece_order)
+            .set_cut_piece_order(problem.cut_piece_order)
+            .set_mirror(problem.mirror)
+            .set_compact(problem.compact)
+            .set_result_detail(problem.result_detail)
+            .set_diagnostics(problem.diagnostics)
+            .set_rotate_to_match_pattern(problem.rotate_to_match_pattern)
+            .set_worst_fit_heuristics(problem.worst_fit_heuristics)
+            .set_smallest_y_heuristic(problem.smallest_y_heuristic)
+            .set_guillotine_waste_pieces(problem.guillotine_waste_pieces)
+            .set_heuristic_profile(
+                OptimizeMode::Guillotine,
+                problem.guillotine_heuristic_profile,
+            )
+            .set_heuristic_profile(OptimizeMode::Nested, problem.nested_heuristic_profile)
+            .set_fitness_objective(problem.fitness_objective)
+            .set_optimize_goal(problem.goal)
+            .set_prefer_banded_edges_outward(problem.prefer_banded_edges_outward)
+            .set_objectives(problem.objectives);
+        if !problem.derive_random_seed {
+            optimizer.set_random_seed(problem.random_seed);
+        }
+        if let Some((rip, crosscut)) = problem.cut_widths {
+            optimizer.set_cut_widths(rip, crosscut);
+        }
+        if let Some((width, length)) = problem.min_reusable_waste {
+            optimizer.set_min_reusable_waste(width, length);
+        }
+        if let Some(stages) = problem.max_guillotine_stages {
+            optimizer.set_max_guillotine_stages(stages);
+        }
+        if let Some(direction) = problem.first_cut_direction {
+            optimizer.set_first_cut_direction(direction);
+        }
+        if let Some(direction) = problem.nested_strip_direction {
+            optimizer.set_nested_strip_direction(direction);
+        }
+        for (external_id_a, external_id_b) in problem.separations {
+            optimizer.add_separation(external_id_a, external_id_b);
+        }
+        if let Some(penalty) = problem.pattern_mismatch_penalty {
+            optimizer.set_pattern_mismatch_penalty(penalty);
+        }
+        if let Some(max) = problem.max_stock_pieces {
+            optimizer.set_max_stock_pieces(max);
+        }
+        if let Some(max_memory) = problem.max_memory {
+            optimizer.set_max_memory(max_memory);
+        }
+        if let Some(utilization) = problem.early_stop_utilization {
+            optimizer.set_early_stop_utilization(utilization);
+        }
+        if let Some(time_limit) = problem.time_limit {
+            optimizer.set_time_limit(time_limit);
+        }
+        optimizer
+    }
+
+    /// Add a stock piece that the optimizer can use to optimize cut pieces.
+    /// If the same stock piece is added multiple times, the quantities will be
+    /// summed up. If any have a `None` quantity, the quantity on other equivalent
+    /// pieces will be ignored. A stock piece with a quantity of `Some(0)` is kept, but the
+    /// optimizer can't use it, and `OptimizerBuilder::build` reports it.
+    pub fn add_stock_piece(&mut self, stock_piece: StockPiece) -> &mut Self {
+        let mut existing_stock_piece = self.stock_pieces.iter_mut().find(|sp| {
+            sp.width == stock_piece.width
+                && sp.length == stock_piece.length
+                && sp.pattern_direction == stock_piece.pattern_direction
+                && sp.price == stock_piece.price
+                && sp.area_price == stock_piece.area_price
+                && sp.external_id == stock_piece.external_id
+                && sp.seams == stock_piece.seams
+                && sp.trim == stock_piece.trim
+                && sp.min_leftover_area == stock_piece.min_leftover_area
+                && sp.grade == stock_piece.grade
+                && sp.material == stock_piece.material
+                && sp.thickness == stock_piece.thickness
+                && sp.source == stock_piece.source
+                && sp.fixed_pieces == stock_piece.fixed_pieces
+                && sp.nominal_size == stock_piece.nominal_size
+                && sp.must_use == stock_piece.must_use
+        });
+
+        if let Some(ref mut existing_stock_piece) = existing_stock_piece {
+            match (&mut existing_stock_piece.quantity, stock_piece.quantity) {
+                (Some(ref mut existing_quantity), Some(quantity)) => {
+                    // If there is already a stock piece that is the same except the quantity, add
+                    // to the quantity.
+                    *existing_quantity += quantity;
+                }
+                _ => {
+                    // `None` quantity means infinite, so if either is `None` we want it to be
+                    // `None`.
+                    existing_stock_piece.quantity = None;
+                }
+            }
+        } else {
+            // A stock piece like this hasn't yet been added so let's do it.
+            self.stock_pieces.push(stock_piece);
+        }
+
+        self
+    }
+
+    /// Add a stock pieces that the optimizer can use to optimize cut pieces.
+    /// If the same stock piece is added multiple times, the quantities will be
+    /// summed up. If any have a `None` quantity, the quantity on other equivalent
+    /// pieces will be ignored.
+    pub fn add_stock_pieces<I>(&mut self, stock_pieces: I) -> &mut Self
+    where
+        I: IntoIterator<Item = StockPiece>,
+    {
+        stock_pieces.into_iter().for_each(|sp| {
+            self.add_stock_piece(sp);
+        });
+        self
+    }
+
+    /// Add a desired cut piece that you need cut from a stock piece. Each copy of the cut piece,
+    /// or each group of copies with `set_quantity_group_size`, counts toward the maximum set with
+    /// `set_max_cut_pieces`. Cut pieces that would go over it aren't added, and optimizing
+    /// returns `Error::TooManyCutPieces` instead.
+    pub fn add_cut_piece(&mut self, cut_piece: CutPiece) -> &mut Self {
+        let num_layout_pieces = self.num_layout_pieces_of(&cut_piece);
+        if self.pending_cut_pieces.is_empty()
+            && self
+                .max_cut_pieces
+                .is_none_or(|max| num_layout_pieces <= max.saturating_sub(self.num_layout_pieces))
+        {
+            self.num_layout_pieces += num_layout_pieces;
+            self.expand_cut_piece(cut_piece);
+        } else {
+            self.pending_cut_pieces.push(cut_piece);
+        }
+        self
+    }
+
+    /// Returns the number of cut pieces the optimizer lays out for the cut piece, which is one
+    /// for each group of its required copies if they're grouped, and one for each of its other
+    /// copies.
+    fn num_layout_pieces_of(&self, cut_piece: &CutPiece) -> usize {
+        let total_quantity = cut_piece.total_quantity();
+        if cut_piece.match_group.is_some() || cut_piece.value.is_some() {
+            return total_quantity;
+        }
+        cut_piece.quantity.div_ceil(self.quantity_group_size) + total_quantity - cut_piece.quantity
+    }
+
+    /// Adds a copy of the cut piece for each of its quantity, and an optional copy for each one
+    /// past that up to its maximum quantity.
+    fn expand_cut_piece(&mut self, cut_piece: CutPiece) {
+        let total_quantity = cut_piece.total_quantity();
+        let tag: Option<Arc<str>> = cut_piece.tag.map(Into::into);
+        let stock_filter = match cut_piece.stock_filter {
+            StockFilter::Any => None,
+            stock_filter => Some(Arc::new(stock_filter)),
+        };
+        let value = cut_piece.value;
+        let min_grade = cut_piece.min_grade;
+        let edge_banding = cut_piece.edge_banding;
+        let match_group = cut_piece.match_group;
+        let material = cut_piece.material;
+        let thickness = cut_piece.thickness;
+        for i in 0..total_quantity {
+            let cut_piece = CutPieceWithId {
+                id: self.cut_pieces.len(),
+                external_id: cut_piece.external_id,
+                width: cut_piece.width,
+                length: cut_piece.length,
+                pattern_direction: cut_piece.pattern_direction,
+                can_rotate: cut_piece.can_rotate,
+                tag: tag.clone(),
+                max_edge_distance: cut_piece.max_edge_distance,
+                stock_filter: stock_filter.clone(),
+                min_grade,
+                edge_banding,
+                match_group,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: i >= cut_piece.quantity,
+                material,
+                thickness,
+            };
+
+            self.cut_pieces.push(cut_piece);
+            self.cut_piece_values.push(value);
+        }
+    }
+
+    /// Add desired cut pieces that you need cut from a stock piece.
+    pub fn add_cut_pieces<I>(&mut self, cut_pieces: I) -> &mut Self
+    where
+        I: IntoIterator<Item = CutPiece>,
+    {
+        cut_pieces.into_iter().for_each(|dp| {
+            self.add_cut_piece(dp);
+        });
+        self
+    }
+
+    /// Set the width of the cut to use between cut pieces. This could
+    /// represent blade or kerf thickness.
+    pub fn set_cut_width(&mut self, cut_width: usize) -> &mut Self {
+        self.cut_width = BladeWidth::uniform(cut_width);
+        self
+    }
+
+    /// Set different widths for the rip cuts, which are parallel to the length, and the
+    /// crosscuts, which are parallel to the width, such as for a shop that uses a different blade
+    /// or machine for each. This replaces the width set with `set_cut_width`.
+    pub fn set_cut_widths(&mut self, rip: usize, crosscut: usize) -> &mut Self {
+        self.cut_width = BladeWidth { rip, crosscut };
+        self
+    }
+
+    /// Set the random seed used by the genetic algorithms in the optimizer. Using
+    /// the same random seed will give you the same result for the same input.
+    pub fn set_random_seed(&mut self, seed: u64) -> &mut Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Set whether to derive the random seed from `Optimizer::fingerprint` when none was set with
+    /// `set_random_seed`, instead of using 0. Each input then gets its own seed, which is
+    /// reported in `Solution::random_seed`, so a solution can be reproduced even if no seed was
+    /// set. Defaults to false.
+    pub fn set_derive_random_seed(&mut self, derive_random_seed: bool) -> &mut Self {
+        self.derive_random_seed = derive_random_seed;
+        self
+    }
+
+    /// Returns the random seed the genetic algorithms use, which is the one set with
+    /// `set_random_seed`, or the one derived from the input with `set_derive_random_seed`.
+    pub fn random_seed(&self) -> u64 {
+        match self.random_seed {
+            Some(seed) => seed,
+            None if self.derive_random_seed => self.fingerprint(),
+            None => 0,
+        }
+    }
+
+    /// Returns a hash of the stock pieces, cut pieces, and cut width added to this optimizer.
+    /// It's the same on every platform and run for the same input, since each value is hashed as
+    /// a little-endian `u64`. The hash is made from:
+    ///
+    /// - the width, length, pattern direction, price, area price, quantity, external ID, seams,
+    ///   trim, minimum leftover area, grade, material, thickness, fixed pieces, and `must_use` of
+    ///   each stock piece,
+    /// - the external ID, width, length, pattern direction, and `can_rotate` of each cut piece,
+    ///   and whether it's optional,
+    /// - and the rip and crosscut widths of the cuts.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = FingerprintHasher::default();
+        hasher.write_usize(self.stock_pieces.len());
+        for stock_piece in &self.stock_pieces {
+            hasher.write_usize(stock_piece.width);
+            hasher.write_usize(stock_piece.length);
+            hasher.write_pattern_direction(stock_piece.pattern_direction);
+            hasher.write_usize(stock_piece.price);
+            hasher.write_option(stock_piece.area_price, |hasher, area_price| {
+                hasher.write_usize(area_price.price);
+                hasher.write_u64(area_price.area);
+            });
+            hasher.write_option(stock_piece.quantity, FingerprintHasher::write_usize);
+            hasher.write_option(stock_piece.external_id, FingerprintHasher::write_usize);
+            hasher.write_usize(stock_piece.seams.len());
+            for seam in &stock_piece.seams {
+                let (direction, position) = match *seam {
+                    Seam::ParallelToLength(x) => (0, x),
+                    Seam::ParallelToWidth(y) => (1, y),
+                };
+                hasher.write_u64(direction);
+                hasher.write_usize(position);
+            }
+            let trim = stock_piece.trim;
+            for margin in [trim.left, trim.right, trim.bottom, trim.top] {
+                hasher.write_usize(margin);
+            }
+            hasher.write_option(stock_piece.min_leftover_area, FingerprintHasher::write_u64);
+            hasher.write_option(stock_piece.grade, |hasher, grade| {
+                hasher.write_u64(grade as u64)
+            });
+            hasher.write_option(stock_piece.material, FingerprintHasher::write_usize);
+            hasher.write_option(stock_piece.thickness, FingerprintHasher::write_usize);
+            hasher.write_usize(stock_piece.fixed_pieces.len());
+            for fixed_piece in &stock_piece.fixed_pieces {
+                hasher.write_usize(fixed_piece.x);
+                hasher.write_usize(fixed_piece.y);
+                hasher.write_usize(fixed_piece.width);
+                hasher.write_usize(fixed_piece.length);
+            }
+            hasher.write_bool(stock_piece.must_use);
+        }
+        hasher.write_usize(self.cut_pieces.len());
+        for cut_piece in &self.cut_pieces {
+            hasher.write_option(cut_piece.external_id, FingerprintHasher::write_usize);
+            hasher.write_usize(cut_piece.width);
+            hasher.write_usize(cut_piece.length);
+            hasher.write_pattern_direction(cut_piece.pattern_direction);
+            hasher.write_bool(cut_piece.can_rotate);
+            hasher.write_bool(cut_piece.optional);
+        }
+        hasher.write_usize(self.cut_width.rip);
+        hasher.write_usize(self.cut_width.crosscut);
+        hasher.finish()
+    }
+
+    /// Set how the random numbers used by the genetic algorithms in each epoch are derived from
+    /// the random seed. With `EpochSeedStrategy::PerUnit`, each unit bred in an epoch gets its own
+    /// random numbers, so changes to the genetic algorithm can be compared one at a time.
+    /// Defaults to `EpochSeedStrategy::Shared`.
+    pub fn set_epoch_seed_strategy(&mut self, strategy: EpochSeedStrategy) -> &mut Self {
+        self.epoch_seed_strategy = strategy;
+        self
+    }
+
+    /// Set the type of random number generator used by the genetic algorithms, which is seeded
+    /// from the random seed with `SeedableRng::seed_from_u64`, such as to use a generator that's
+    /// been vetted for a deployment. Defaults to `StdRng`.
+    pub fn set_rng<R>(&mut self) -> &mut Self
+    where
+        R: RngCore + SeedableRng + Send + 'static,
+    {
+        self.set_rng_factory(|seed| Box::new(R::seed_from_u64(seed)))
+    }
+
+    /// Set a function that creates the random number generators used by the genetic algorithms
+    /// from a seed, which is derived from the random seed, such as for a property test framework
+    /// that controls where the randomness comes from. The function is called with the same seeds
+    /// for the same random seed, so to keep the results reproducible, it should return the same
+    /// generator for the same seed. This replaces the type set with `set_rng`.
+    pub fn set_rng_factory<F>(&mut self, rng_factory: F) -> &mut Self
+    where
+        F: Fn(u64) -> Box<dyn RngCore + Send> + Send + Sync + 'static,
+    {
+        self.rng_factory = Some(Arc::new(rng_factory));
+        self
+    }
+
+    /// Set whether the optimizer should allow mixed sized stock pieces in the results.
+    /// If set to false, and multiple stock sizes are given, only one stock size will be used in
+    /// the results.
+    pub fn allow_mixed_stock_sizes(&mut self, allow: bool) -> &mut Self {
+        self.allow_mixed_stock_sizes = allow;
+        self
+    }
+
+    /// Set the order of the stock pieces in the solution. Defaults to
+    /// `StockPieceOrder::SizeDescending`.
+    pub fn set_stock_piece_order(&mut self, order: StockPieceOrder) -> &mut Self {
+        self.stock_piece_order = order;
+        self
+    }
+
+    /// Set the order of the cut pieces within each stock piece in the solution. Defaults to
+    /// `CutPieceOrder::Placement`.
+    pub fn set_cut_piece_order(&mut self, order: CutPieceOrder) -> &mut Self {
+        self.cut_piece_order = order;
+        self
+    }
+
+    /// Set how the layouts of the solution are mirrored, such as for a machine that measures from
+    /// the right edge of a stock piece. The locations of the cut pieces, waste pieces, seams and
+    /// trim are all mirrored, and the cut pieces are ordered by `set_cut_piece_order` after
+    /// they're mirrored. Defaults to `Mirror::None`.
+    pub fn set_mirror(&mut self, mirror: Mirror) -> &mut Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Set how much of the detail of each stock piece the solution includes. The waste pieces,
+    /// free rectangles and cut trees of large solutions can be left out to make them smaller,
+    /// and to skip building them. Defaults to `ResultDetail::Full`.
+    pub fn set_result_detail(&mut self, detail: ResultDetail) -> &mut Self {
+        self.result_detail = detail;
+        self
+    }
+
+    /// Set whether to compact the layouts of the solution once the optimizer is done with them,
+    /// by sliding the cut pieces toward the origin of their stock pieces to close the gaps left
+    /// between them. Layouts are compacted before they're mirrored. See `Solution::compact`.
+    /// Defaults to `false`.
+    pub fn set_compact(&mut self, compact: bool) -> &mut Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Set the minimum size of the waste pieces that are worth keeping as inventory. The waste
+    /// pieces of each stock piece in the solution that are at least `width` by `length`, in
+    /// either orientation, are its `reusable_offcuts`, and the rest are its `scrap`. Defaults to
+    /// 0 by 0, so every waste piece is a reusable offcut.
+    pub fn set_min_reusable_waste(&mut self, width: usize, length: usize) -> &mut Self {
+        self.min_reusable_waste = (width, length);
+        self
+    }
+
+    /// Set whether each stock piece in the solution should include diagnostics that explain how
+    /// its layout was produced, and the solution should include statistics about how each
+    /// heuristic did in `Solution::pass_stats`. This is useful for understanding why the
+    /// optimizer didn't find an expected layout, but it makes optimizing slightly slower.
+    /// Defaults to `false`.
+    pub fn set_diagnostics(&mut self, enabled: bool) -> &mut Self {
+        self.diagnostics = enabled;
+        self
+    }
+
+    /// Set the policy for rotating cut pieces whose pattern direction only matches a stock piece
+    /// when rotated. Defaults to `RotateToMatchPattern::IfAllowed`.
+    pub fn set_rotate_to_match_pattern(&mut self, policy: RotateToMatchPattern) -> &mut Self {
+        self.rotate_to_match_pattern = policy;
+        self
+    }
+
+    /// Set whether the optimizer should also try "worst fit" heuristics, which place each cut
+    /// piece in the free rectangle it fits worst. These usually give worse results, but can help
+    /// with unusual jobs, such as a few cut pieces that are almost as large as the stock pieces.
+    /// Enabling them makes optimizing slower. Only guillotine optimization has worst fit
+    /// heuristics. Defaults to `false`.
+    pub fn set_worst_fit_heuristics(&mut self, enabled: bool) -> &mut Self {
+        self.worst_fit_heuristics = enabled;
+        self
+    }
+
+    /// Set whether the optimizer should also try the "smallest y" heuristic, which places each cut
+    /// piece in the free rectangle nearest the top of the stock piece. It tends to produce
+    /// top-down layouts that are easy to cut on a panel saw, even when they don't waste less.
+    /// Only guillotine optimization uses it. Defaults to `false`.
+    pub fn set_smallest_y_heuristic(&mut self, enabled: bool) -> &mut Self {
+        self.smallest_y_heuristic = enabled;
+        self
+    }
+
+    /// Set whether nested optimization should split the waste of each stock piece into waste
+    /// pieces along guillotine cuts around the cut pieces, instead of reporting the free
+    /// rectangles left by the optimizer. The free rectangles are arbitrary slices of the unused
+    /// area, so they can be hard to follow on a diagram, while these waste pieces are the ones
+    /// left after cutting the stock piece up. Guillotine optimization always does this. Defaults
+    /// to `false`.
+    pub fn set_guillotine_waste_pieces(&mut self, enabled: bool) -> &mut Self {
+        self.guillotine_waste_pieces = enabled;
+        self
+    }
+
+    /// Set the maximum number of stages of cuts in guillotine layouts. The cuts of a stage all go
+    /// in the same direction across the pieces cut off by the stage before it, so with two stages
+    /// a panel saw cuts the stock piece into strips, then cuts the strips into cut pieces, and with
+    /// three stages it can also trim the waste off the cut pieces. Cut pieces are only placed where
+    /// the layout can still be cut in this many stages. The cuts along seams and the cuts that trim
+    /// the stock piece don't count. Only guillotine optimization uses it. There is no limit by
+    /// default.
+    pub fn set_max_guillotine_stages(&mut self, stages: usize) -> &mut Self {
+        self.max_guillotine_stages = Some(stages);
+        self
+    }
+
+    /// Set the direction of the first cuts across each stock piece in guillotine layouts, such as
+    /// `CutDirection::ParallelToLength` for a panel saw that has to rip large sheets into strips
+    /// before they can be crosscut. The first cuts run across the whole stock piece, or the whole
+    /// region between its seams, and the cuts within the strips they make can go either way. Only
+    /// guillotine optimization uses it. By default the first cuts can go either way.
+    pub fn set_first_cut_direction(&mut self, direction: CutDirection) -> &mut Self {
+        self.first_cut_direction = Some(direction);
+        self
+    }
+
+    /// Set the direction of guillotine cuts that split nested layouts into strips, for a hybrid
+    /// of guillotine and nested layouts, such as for a shop that rough-cuts stock pieces into
+    /// strips on a panel saw and finishes them on a CNC. The cuts run across the whole stock
+    /// piece, each one just past the cut piece that starts a new strip, and the cut pieces are
+    /// nested freely within the strips. Only nested optimization uses it. By default nested
+    /// layouts aren't split into strips.
+    pub fn set_nested_strip_direction(&mut self, direction: CutDirection) -> &mut Self {
+        self.nested_strip_direction = Some(direction);
+        self
+    }
+
+    /// Require the cut pieces with external ID `external_id_a` to be placed on different stock
+    /// pieces than the cut pieces with external ID `external_id_b`, such as to cut a pair of door
+    /// panels from different sheets, so a flaw in one sheet doesn't ruin both. If the IDs are the
+    /// same, each copy of the cut piece is placed on its own stock piece. Cut pieces without an
+    /// external ID can't be separated.
+    pub fn add_separation(&mut self, external_id_a: usize, external_id_b: usize) -> &mut Self {
+        self.separations
+            .insert(separation(external_id_a, external_id_b));
+        self
+    }
+
+    /// Set the heuristics the optimizer tries for the optimize mode. This makes it possible to
+    /// favor layouts that suit the machine that will cut the stock pieces, such as with
+    /// `HeuristicProfile::PanelSaw`. Defaults to `HeuristicProfile::All` for every mode.
+    pub fn set_heuristic_profile(
+        &mut self,
+        mode: OptimizeMode,
+        profile: HeuristicProfile,
+    ) -> &mut Self {
+        match mode {
+            OptimizeMode::Guillotine => self.guillotine_heuristic_profile = profile,
+            OptimizeMode::Nested => self.nested_heuristic_profile = profile,
+        }
+        self
+    }
+
+    /// Set what the optimizer looks for in a solution. By default it looks for the lowest-priced
+    /// solution. See `FitnessObjective`.
+    pub fn set_fitness_objective(&mut self, objective: FitnessObjective) -> &mut Self {
+        self.fitness_objective = objective;
+        self
+    }
+
+    /// Set what a solution needs to do with the cut pieces. By default it needs to place all of
+    /// them. See `OptimizeGoal`.
+    pub fn set_optimize_goal(&mut self, goal: OptimizeGoal) -> &mut Self {
+        self.goal = goal;
+        self
+    }
+
+    /// Set whether to prefer layouts where the banded edges of the cut pieces, given by
+    /// `CutPiece::edge_banding`, face outward toward the edge of the stock piece or waste rather
+    /// than another cut piece, so they're exposed for the edge bander in the order they come off
+    /// the saw. This only breaks ties between layouts that are otherwise about as good. Where the
+    /// banded edges ended up is reported in `ResultCutPiece::edge_banding`. Defaults to `false`.
+    pub fn set_prefer_banded_edges_outward(&mut self, prefer: bool) -> &mut Self {
+        self.prefer_banded_edges_outward = prefer;
+        self
+    }
+
+    /// Allow cut pieces to be placed against the pattern of the stock piece when they can't be
+    /// placed with it, instead of failing with `Error::NoFitForCutPiece`. This suits look-alike
+    /// materials without a real pattern, where going against the grain is only cosmetic. Each
+    /// cut piece is still placed with the pattern wherever it fits that way, and layouts are
+    /// penalized for the ones that aren't: `penalty` (between 0.0 and 1.0) is the fraction of the
+    /// fitness lost if every cut piece goes against the pattern. Cut pieces placed against the
+    /// pattern are reported by `ResultCutPiece::pattern_mismatch`. By default cut pieces are
+    /// only placed with the pattern.
+    pub fn set_pattern_mismatch_penalty(&mut self, penalty: f64) -> &mut Self {
+        self.pattern_mismatch_penalty = Some(penalty);
+        self
+    }
+
+    /// Set whether a solution can leave out cut pieces that don't fit on the stock pieces, instead
+    /// of failing with `Error::NoFitForCutPiece`. The cut pieces that are left out are listed in
+    /// `Solution::unplaced_cut_pieces`. This is the same as setting the goal to
+    /// `OptimizeGoal::MaximizeValue`, so the solution places as much of the cut pieces' value as it
+    /// can, which is their area unless they're given a value.
+    pub fn set_allow_partial(&mut self, allow: bool) -> &mut Self {
+        self.goal = if allow {
+            OptimizeGoal::MaximizeValue
+        } else {
+            OptimizeGoal::PlaceAll
+        };
+        self
+    }
 
-        let possible_heuristics = B::possible_heuristics();
+    /// Set the most stock pieces a solution can use in total, such as the number of sheets that
+    /// fit in a delivery or the job's budget. Layouts that would need more stock pieces don't place
+    /// the rest of the cut pieces, so they aren't solutions, and optimizing fails with
+    /// `Error::NoFitForCutPiece` if every layout needs more, unless partial solutions are allowed
+    /// with `set_allow_partial`. By default there's no maximum.
+    pub fn set_max_stock_pieces(&mut self, max: usize) -> &mut Self {
+        self.max_stock_pieces = Some(max);
+        self
+    }
 
-        let num_units = if cut_pieces.len() < 3 {
-            possible_heuristics.len()
-        } else {
-            let denom = if cut_pieces.len() > 1 {
-                (cut_pieces.len() as f64).log10()
-            } else {
-                1.0
-            };
+    /// Set the objectives that `optimize_guillotine_pareto` and `optimize_nested_pareto` find the
+    /// best solution for. Ties for an objective are broken by the other objectives, in order.
+    pub fn set_objectives(&mut self, objectives: Vec<Objective>) -> &mut Self {
+        self.objectives = objectives;
+        self
+    }
 
-            cmp::max(
-                possible_heuristics.len() * 3,
-                (cut_pieces.len() as f64 / denom + ((unique_cut_pieces - 1) * 10) as f64) as usize,
-            )
+    /// Set the maximum number of cut pieces the optimizer lays out, counting each copy of a cut
+    /// piece, or each group of copies with `set_quantity_group_size`. Each one is laid out on its
+    /// own, so a quantity that's too large by mistake, such as 10 million, would take too long to
+    /// optimize. Cut pieces past the maximum aren't added, and optimizing returns
+    /// `Error::TooManyCutPieces`. Raising the maximum adds the cut pieces that fit under it, but
+    /// lowering it doesn't remove cut pieces that were already added. By default there's no
+    /// maximum.
+    pub fn set_max_cut_pieces(&mut self, max: usize) -> &mut Self {
+        self.max_cut_pieces = Some(max);
+        let pending_cut_pieces = mem::take(&mut self.pending_cut_pieces);
+        self.add_cut_pieces(pending_cut_pieces)
+    }
+
+    /// Set the most copies of a cut piece that the optimizer lays out together as one piece, so
+    /// legitimately large quantities can be optimized in a reasonable time. The copies in a group
+    /// are side by side along the grain, or along the length without a pattern, with a cut
+    /// between each of them, and they're returned as separate cut pieces. Groups are only as
+    /// large as the stock pieces can fit, and only the required copies of cut pieces without a
+    /// `CutPiece::value` or `CutPiece::match_group` are grouped. Larger groups are faster to
+    /// optimize, but can leave more waste. Changing the size adds the cut pieces that now fit
+    /// under the maximum set with `set_max_cut_pieces`. By default the size is 1, so each copy is
+    /// laid out on its own.
+    pub fn set_quantity_group_size(&mut self, size: usize) -> &mut Self {
+        self.quantity_group_size = cmp::max(size, 1);
+        self.num_layout_pieces = self.num_added_layout_pieces();
+        let pending_cut_pieces = mem::take(&mut self.pending_cut_pieces);
+        self.add_cut_pieces(pending_cut_pieces)
+    }
+
+    /// Set the approximate maximum number of bytes the optimizer should use. For large jobs, the
+    /// optimizer uses a smaller population to stay within this, which can give worse results,
+    /// but avoids running out of memory. If even a population of a few layouts wouldn't fit, the
+    /// copies of each cut piece are also laid out in groups, like with `set_quantity_group_size`,
+    /// with the groups made larger until it fits or each cut piece is in as few groups as the
+    /// stock pieces allow. The optimizer always uses at least enough memory for one layout of the
+    /// cut pieces. By default there's no maximum.
+    pub fn set_max_memory(&mut self, bytes: usize) -> &mut Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    /// Stop optimizing as soon as a solution uses the fewest stock pieces that could possibly fit
+    /// the cut pieces, based on their area, and the cut pieces use at least `utilization`
+    /// (between 0.0 and 1.0) of the area of those stock pieces. Easy jobs often find such a
+    /// solution early, and this avoids spending the rest of the time polishing it. By default
+    /// the optimizer always runs every generation.
+    pub fn set_early_stop_utilization(&mut self, utilization: f64) -> &mut Self {
+        self.early_stop_utilization = Some(utilization);
+        self
+    }
+
+    /// Set the maximum time the optimizer should spend optimizing. When more than one stock size
+    /// is tried, the time is split between them, and the time that one doesn't use is given to
+    /// the rest. The optimizer returns the best solution found in that time, but it always lays out
+    /// the initial population for each stock size, so it can take longer for large jobs. By
+    /// default there's no time limit.
+    pub fn set_time_limit(&mut self, time_limit: Duration) -> &mut Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    /// Set a callback that receives detailed events while optimizing, in addition to the
+    /// fraction of progress passed to the progress callback of each optimize method. This is
+    /// useful for showing how the optimization is going, such as the fitness of the best
+    /// solution found so far. The callback can forward the events to another thread, such as
+    /// through a channel. By default there's no progress event callback.
+    pub fn set_progress_event_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(ProgressEvent) + Send + Sync + 'static,
+    {
+        self.progress_event_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Set a snapshot that's updated with the best solution found so far while optimizing, which
+    /// another thread can read without slowing down the optimization. The snapshot is cleared
+    /// when an optimization starts. By default there's no solution snapshot.
+    pub fn set_solution_snapshot(&mut self, snapshot: SolutionSnapshot) -> &mut Self {
+        self.solution_snapshot = Some(snapshot);
+        self
+    }
+
+    /// Set a callback that receives a log of the random choices the optimizer makes, such as the
+    /// heuristic used to place each cut piece, and the stock piece chosen when a new one is
+    /// needed. Optimizing the same inputs with the same random seed produces the same log, so
+    /// the log of a run that gave an unexpected result can be attached to a bug report and
+    /// compared with the log of a replay of the run. The log can be long, since it includes the
+    /// choices of every unit of every generation. By default there's no audit log callback.
+    #[cfg(feature = "audit-log")]
+    pub fn set_audit_log_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(AuditEntry) + Send + Sync + 'static,
+    {
+        self.audit_log_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Quickly estimate the stock pieces needed for the cut pieces, with a single greedy pass
+    /// that places the largest cut pieces first. This is much faster than optimizing, so it's
+    /// useful for things like quoting while the cut pieces are still being entered, but the
+    /// result is only an estimate. Mixed stock sizes are always allowed.
+    pub fn estimate(&self) -> Estimate {
+        let cut_pieces = self.rotatable_cut_pieces();
+        let mut cut_pieces: Vec<&CutPieceWithId> = cut_pieces.iter().collect();
+        cut_pieces.sort_by_key(|p| (p.optional, cmp::Reverse((p.width, p.length))));
+
+        let bin_options = BinOptions {
+            diagnostics: false,
+            ..self.bin_options()
         };
-        let mut units = Vec::with_capacity(num_units);
-        let mut rng: StdRng = SeedableRng::seed_from_u64(random_seed);
+        let context = Arc::new(UnitContext::<GuillotineBin> {
+            possible_stock_pieces: self.stock_pieces.clone(),
+            bin_options,
+            heuristics: GuillotineBin::possible_heuristics(bin_options),
+            restricted_heuristics: false,
+            max_units: 1,
+            min_stock_pieces: 1,
+            early_stop_utilization: None,
+            internal_error: OnceLock::new(),
+            audit_log_callback: None,
+            fitness_objective: self.fitness_objective,
+            goal: OptimizeGoal::PlaceAll,
+            cut_piece_values: Vec::new(),
+            separations: self.separations.clone(),
+            prefer_banded_edges_outward: self.prefer_banded_edges_outward,
+            pattern_mismatch_penalty: self.pattern_mismatch_penalty,
+            max_stock_pieces: self.max_stock_pieces,
+        });
 
-        cut_pieces.sort_by_key(|p| cmp::Reverse((p.width, p.length)));
-        for heuristic in &possible_heuristics {
-            units.push(OptimizerUnit::with_heuristic(
-                possible_stock_pieces,
-                &cut_pieces,
-                blade_width,
-                heuristic,
-                &mut rng,
-            )?);
+        let mut unit = OptimizerUnit {
+            bins: Vec::new(),
+            context: context.clone(),
+            available_stock_pieces: self.stock_pieces.clone(),
+            unused_cut_pieces: Default::default(),
+        };
+        let mut rng = new_rng(&self.rng_factory, self.random_seed());
+        for cut_piece in cut_pieces {
+            if !unit.first_fit_with_heuristic(cut_piece, &context.heuristics[0], &mut rng) {
+                unit.unused_cut_pieces.insert(cut_piece.clone());
+            }
         }
 
-        if cut_pieces.len() > 2 {
-            for heuristic in &possible_heuristics {
-                cut_pieces.shuffle(&mut rng);
-                units.push(OptimizerUnit::with_heuristic(
-                    possible_stock_pieces,
-                    &cut_pieces,
-                    blade_width,
-                    heuristic,
-                    &mut rng,
-                )?);
+        Estimate {
+            stock_pieces: unit.bins.len(),
+            utilization: unit.utilization(),
+            price: unit.bins.iter().map(|bin| bin.price(true)).sum(),
+            // Cut pieces over the maximum aren't placed either.
+            unplaced_cut_pieces: unit
+                .unused_cut_pieces
+                .iter()
+                .filter(|cut_piece| !cut_piece.optional)
+                .count()
+                + self
+                    .pending_cut_pieces
+                    .iter()
+                    .map(|cut_piece| cut_piece.quantity)
+                    .sum::<usize>(),
+        }
+    }
+
+    /// Optimize many independent problems, using up to `parallelism` threads at a time. If
+    /// `parallelism` is 0, the available parallelism of the system is used. The results are in
+    /// the same order as `problems`. A problem whose optimization panics gets an
+    /// `Error::Internal`, without affecting the others.
+    ///
+    /// What's shared between the problems is the threads, which are kept for later batches
+    /// rather than started for each one, and there are never more of them than the available
+    /// parallelism of the system. Each problem is optimized with its own random number generator,
+    /// seeded as it would be on its own, so its solution is the same as if it were optimized by
+    /// itself.
+    pub fn optimize_batch(
+        problems: Vec<Problem>,
+        mode: OptimizeMode,
+        parallelism: usize,
+    ) -> Vec<Result<Solution>> {
+        let parallelism = match parallelism {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let optimizers: Arc<[Optimizer]> =
+            problems.into_iter().map(Optimizer::from_problem).collect();
+        batch::run(optimizers.len(), parallelism, move |i| match mode {
+            OptimizeMode::Guillotine => optimizers[i].optimize_guillotine(|_| {}),
+            OptimizeMode::Nested => optimizers[i].optimize_nested(|_| {}),
+        })
+    }
+
+    /// Optimize in a way where each cut piece can be cut out using only guillotine cuts,
+    /// where each cut extends from one side to the other.
+    ///
+    /// This method is suitable for cutting with a panel saw.
+    ///
+    /// The progress callback is called with the fraction of the optimization that's done. It can
+    /// stop the optimization early by returning `ControlFlow::Break(())`. See `ProgressControl`.
+    pub fn optimize_guillotine<F, R>(&self, progress_callback: F) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimize::<GuillotineBin, F, R>(
+            OptimizeMode::Guillotine,
+            Selection::Best,
+            progress_callback,
+        )
+        .map(|mut solutions| solutions.remove(0))
+    }
+
+    /// Optimize with guillotine cuts, like `optimize_guillotine`, but return the best solution
+    /// for each of the objectives set with `set_objectives`, instead of a single compromise. Each
+    /// solution is labeled with the objective it's best at, and a solution that's best at more
+    /// than one objective is only returned once. If no objectives are set, this returns only the
+    /// same solution as `optimize_guillotine`.
+    pub fn optimize_guillotine_pareto<F, R>(&self, progress_callback: F) -> Result<Vec<Solution>>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimize::<GuillotineBin, F, R>(
+            OptimizeMode::Guillotine,
+            Selection::Pareto,
+            progress_callback,
+        )
+    }
+
+    /// Optimize with guillotine cuts, like `optimize_guillotine`, but return up to `n` of the
+    /// best distinct solutions, such as to let the end user choose between alternative layouts.
+    /// The first solution is the same one `optimize_guillotine` returns, and the rest are ordered
+    /// from best to worst, by price and then by fitness. Fewer than `n` solutions are returned
+    /// if the optimizer didn't find that many distinct layouts.
+    pub fn optimize_guillotine_top_n<F, R>(
+        &self,
+        n: usize,
+        progress_callback: F,
+    ) -> Result<Vec<Solution>>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimize::<GuillotineBin, F, R>(
+            OptimizeMode::Guillotine,
+            Selection::TopN(n),
+            progress_callback,
+        )
+    }
+
+    /// Optimize with guillotine cuts, like `optimize_guillotine`, but also call
+    /// `solution_callback` with the best solution so far each time a solution with a higher
+    /// fitness is found, such as to show the current best layout while the optimization runs.
+    /// The solutions passed to the callback are complete, and sorted like the returned solution,
+    /// which is the best of them by price and then by fitness.
+    pub fn optimize_guillotine_anytime<F, R, S>(
+        &self,
+        progress_callback: F,
+        solution_callback: S,
+    ) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+        S: Fn(&Solution),
+    {
+        Optimization::<GuillotineBin>::new(self, OptimizeMode::Guillotine, Selection::Best)
+            .with_solution_callback(&solution_callback)
+            .run(&progress_callback)
+            .map(|mut solutions| solutions.remove(0))
+    }
+
+    /// Optimize with guillotine cuts, like `optimize_guillotine`, but start from the layout of
+    /// `solution`, such as one from an earlier optimization of this job before some cut pieces
+    /// were added or removed. The cut pieces keep the stock pieces they had in `solution` when
+    /// they can, so the new solution is usually close to it, and it's found faster than by
+    /// optimizing from scratch.
+    pub fn optimize_guillotine_from<F, R>(
+        &self,
+        solution: &Solution,
+        progress_callback: F,
+    ) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        Optimization::<GuillotineBin>::new(self, OptimizeMode::Guillotine, Selection::Best)
+            .with_warm_start(solution)
+            .run(&progress_callback)
+            .map(|mut solutions| solutions.remove(0))
+    }
+
+    /// Optimize without the requirement of guillotine cuts. Cuts can start and stop in the middle
+    /// of the stock piece.
+    ///
+    /// This method is suitable for cutting on a CNC.
+    pub fn optimize_nested<F, R>(&self, progress_callback: F) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimize::<MaxRectsBin, F, R>(OptimizeMode::Nested, Selection::Best, progress_callback)
+            .map(|mut solutions| solutions.remove(0))
+    }
+
+    /// Optimize without the requirement of guillotine cuts, like `optimize_nested`, but return
+    /// the best solution for each of the objectives set with `set_objectives`. See
+    /// `optimize_guillotine_pareto`.
+    pub fn optimize_nested_pareto<F, R>(&self, progress_callback: F) -> Result<Vec<Solution>>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimize::<MaxRectsBin, F, R>(
+            OptimizeMode::Nested,
+            Selection::Pareto,
+            progress_callback,
+        )
+    }
+
+    /// Optimize without the requirement of guillotine cuts, like `optimize_nested`, but also call
+    /// `solution_callback` with each better solution as it's found. See
+    /// `optimize_guillotine_anytime`.
+    pub fn optimize_nested_anytime<F, R, S>(
+        &self,
+        progress_callback: F,
+        solution_callback: S,
+    ) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+        S: Fn(&Solution),
+    {
+        Optimization::<MaxRectsBin>::new(self, OptimizeMode::Nested, Selection::Best)
+            .with_solution_callback(&solution_callback)
+            .run(&progress_callback)
+            .map(|mut solutions| solutions.remove(0))
+    }
+
+    /// Optimize without the requirement of guillotine cuts, like `optimize_nested`, but start from
+    /// the layout of `solution`. See `optimize_guillotine_from`.
+    pub fn optimize_nested_from<F, R>(
+        &self,
+        solution: &Solution,
+        progress_callback: F,
+    ) -> Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        Optimization::<MaxRectsBin>::new(self, OptimizeMode::Nested, Selection::Best)
+            .with_warm_start(solution)
+            .run(&progress_callback)
+            .map(|mut solutions| solutions.remove(0))
+    }
+
+    /// Optimize without the requirement of guillotine cuts, like `optimize_nested`, but return up
+    /// to `n` of the best distinct solutions. See `optimize_guillotine_top_n`.
+    pub fn optimize_nested_top_n<F, R>(
+        &self,
+        n: usize,
+        progress_callback: F,
+    ) -> Result<Vec<Solution>>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimize::<MaxRectsBin, F, R>(
+            OptimizeMode::Nested,
+            Selection::TopN(n),
+            progress_callback,
+        )
+    }
+
+    /// Recommend which stock pieces to buy for the cut pieces, such as when the stock pieces are
+    /// the sizes a supplier sells, with their prices and usually no quantity. Each stock size is
+    /// optimized separately, along with all stock sizes together if mixed stock sizes are allowed
+    /// with `allow_mixed_stock_sizes`, and each of these runs is an option in the recommendation.
+    /// The options are ordered from best to worst, by price, then by the number of stock pieces,
+    /// then by fitness, so the first one is the recommended purchase. Runs that can't place the
+    /// cut pieces are left out, and an error is only returned if none of them can.
+    ///
+    /// The progress callback is called with the fraction of the optimization that's done. It can
+    /// stop the optimization early by returning `ControlFlow::Break(())`. See `ProgressControl`.
+    pub fn recommend_stock<F, R>(
+        &self,
+        mode: OptimizeMode,
+        progress_callback: F,
+    ) -> Result<StockRecommendation>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        let solutions = match mode {
+            OptimizeMode::Guillotine => {
+                self.optimize::<GuillotineBin, F, R>(mode, Selection::PerRun, progress_callback)
             }
+            OptimizeMode::Nested => {
+                self.optimize::<MaxRectsBin, F, R>(mode, Selection::PerRun, progress_callback)
+            }
+        }?;
 
-            for _ in 0..num_units - units.len() {
-                cut_pieces.shuffle(&mut rng);
-                units.push(OptimizerUnit::with_random_heuristics(
-                    possible_stock_pieces,
+        let mut options: Vec<StockPurchaseOption> = solutions
+            .into_iter()
+            .map(StockPurchaseOption::from_solution)
+            .collect();
+        options.sort_by(|a, b| {
+            a.price
+                .cmp(&b.price)
+                .then_with(|| a.stock_pieces.cmp(&b.stock_pieces))
+                .then_with(|| {
+                    b.solution
+                        .fitness
+                        .partial_cmp(&a.solution.fitness)
+                        .unwrap_or(cmp::Ordering::Equal)
+                })
+        });
+        // The run with all stock sizes often ends up with only one of them.
+        options.dedup_by(|a, b| a.purchases == b.purchases);
+
+        Ok(StockRecommendation { options })
+    }
+
+    /// Start an optimization that only runs when `OptimizerSession::step` is called, a little at
+    /// a time, instead of all at once. This is useful when the optimization can't block the
+    /// thread it runs on, such as the main thread of a browser, since the steps can be run
+    /// between other work, such as from `requestAnimationFrame`. The session finds the same
+    /// solution as `optimize_guillotine` or `optimize_nested` for the mode.
+    pub fn start_session(&self, mode: OptimizeMode) -> OptimizerSession<'_> {
+        let optimization = match mode {
+            OptimizeMode::Guillotine => SessionOptimization::Guillotine(Optimization::new(
+                self,
+                OptimizeMode::Guillotine,
+                Selection::Best,
+            )),
+            OptimizeMode::Nested => SessionOptimization::Nested(Optimization::new(
+                self,
+                OptimizeMode::Nested,
+                Selection::Best,
+            )),
+        };
+        OptimizerSession { optimization }
+    }
+
+    /// Runs the optimization, and returns the solutions picked by `selection`.
+    fn optimize<B, F, R>(
+        &self,
+        mode: OptimizeMode,
+        selection: Selection,
+        progress_callback: F,
+    ) -> Result<Vec<Solution>>
+    where
+        B: Bin + Clone + Send + Into<ResultStockPiece>,
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        Optimization::<B>::new(self, mode, selection).run(&progress_callback)
+    }
+
+    /// Returns the best of the `candidates` for each objective, labeled with the objective.
+    /// Ties are broken by the other objectives in order, so none of the returned solutions is
+    /// dominated by another candidate. Solutions that are best at several objectives are only
+    /// returned once, labeled with the first of them.
+    fn pareto_front(&self, candidates: Vec<Solution>) -> Vec<Solution> {
+        let scores: Vec<Vec<u64>> = candidates
+            .iter()
+            .map(|solution| {
+                self.objectives
+                    .iter()
+                    .map(|&objective| self.objective_score(solution, objective))
+                    .collect()
+            })
+            .collect();
+
+        let mut front: Vec<(usize, Objective)> = Vec::new();
+        for (n, &objective) in self.objectives.iter().enumerate() {
+            let best = (0..candidates.len()).min_by(|&a, &b| {
+                scores[a][n]
+                    .cmp(&scores[b][n])
+                    .then_with(|| scores[a].cmp(&scores[b]))
+            });
+            if let Some(best) = best {
+                if !front.iter().any(|&(i, _)| scores[i] == scores[best]) {
+                    front.push((best, objective));
+                }
+            }
+        }
+
+        front
+            .into_iter()
+            .map(|(i, objective)| {
+                let mut solution = candidates[i].clone();
+                solution.objective = Some(objective);
+                solution
+            })
+            .collect()
+    }
+
+    /// Returns the score of the solution for the objective, where lower is better.
+    fn objective_score(&self, solution: &Solution, objective: Objective) -> u64 {
+        match objective {
+            Objective::Price => solution.price as u64,
+            Objective::Waste => solution
+                .stock_pieces
+                .iter()
+                .map(|stock_piece| {
+                    stock_piece.width as u64 * stock_piece.length as u64 - stock_piece.used_area()
+                })
+                .sum(),
+            Objective::Cuts => solution
+                .stock_pieces
+                .iter()
+                .map(|stock_piece| {
+                    let rects: Vec<Rect> = stock_piece.cut_pieces.iter().map(Into::into).collect();
+                    cuts::count_cuts(
+                        &rects,
+                        stock_piece.width,
+                        stock_piece.length,
+                        self.cut_width,
+                    ) as u64
+                })
+                .sum(),
+        }
+    }
+
+    /// Puts the solution in the form it's returned in, by mirroring its layouts, ordering its
+    /// stock pieces and cut pieces, and numbering its stock pieces.
+    fn sort_solution(&self, solution: &mut Solution) {
+        self.solution_order().sort(solution);
+    }
+
+    fn solution_order(&self) -> SolutionOrder {
+        SolutionOrder {
+            stock_piece_order: self.stock_piece_order,
+            cut_piece_order: self.cut_piece_order,
+            mirror: self.mirror,
+            compact: self.compact,
+            result_detail: self.result_detail,
+            cut_width: self.cut_width,
+            min_reusable_waste: self.min_reusable_waste,
+            random_seed: self.random_seed(),
+        }
+    }
+
+    /// Starts a run that optimizes the cut pieces with only the stock pieces, which stops evolving
+    /// at the `deadline`.
+    fn start_run<B>(
+        &self,
+        mode: OptimizeMode,
+        stock_pieces: &[StockPiece],
+        cut_pieces: &[CutPieceWithId],
+        deadline: Option<Instant>,
+        warm_start: Option<&Solution>,
+    ) -> Result<Run<B>>
+    where
+        B: Bin + Clone + Send + Into<ResultStockPiece>,
+    {
+        let cut_pieces: Vec<&CutPieceWithId> = cut_pieces.iter().collect();
+        let context = self.unit_context::<B>(mode, stock_pieces, &cut_pieces);
+
+        // A few cut pieces can be placed by trying every way of placing them, so there's no need
+        // for the genetic algorithm.
+        if !cut_pieces.is_empty() && cut_pieces.len() <= MAX_ENUMERATED_CUT_PIECES {
+            if let Some(mut best_unit) =
+                OptimizerUnit::<B>::best_for_few_cut_pieces(&context, &cut_pieces)
+            {
+                return Ok(Run::Solved(Self::build_solution(&mut best_unit)?));
+            }
+            if let [cut_piece] = cut_pieces[..] {
+                let mut unit = OptimizerUnit {
+                    bins: Vec::new(),
+                    context: context.clone(),
+                    available_stock_pieces: Vec::new(),
+                    unused_cut_pieces: [cut_piece.clone()].into_iter().collect(),
+                };
+                if !unit.is_complete() {
+                    return Err(unit.no_fit_error(cut_piece));
+                }
+                return Ok(Run::Solved(Self::build_solution(&mut unit)?));
+            }
+        }
+
+        let random_seed = self.random_seed();
+        let mut rng = new_rng(&self.rng_factory, random_seed);
+        let units = OptimizerUnit::generate_initial_units(&context, cut_pieces.clone(), &mut rng)?;
+        let heuristic_stats = if self.diagnostics {
+            Self::heuristic_stats(&context, &units)
+        } else {
+            Vec::new()
+        };
+        let mut units: Vec<OptimizerUnit<B>> = units.into_iter().map(|(_, unit)| unit).collect();
+
+        // Replace the most random of the initial units with units that keep the layout of the
+        // solution to start from, up to half of the population.
+        if let Some(solution) = warm_start {
+            let mut rng = new_rng(&self.rng_factory, random_seed);
+            let num_seeded = cmp::min(context.heuristics.len(), units.len().div_ceil(2));
+            units.truncate(units.len() - num_seeded);
+            for heuristic in context.heuristics.iter().take(num_seeded) {
+                units.push(OptimizerUnit::from_solution(
+                    &context,
                     &cut_pieces,
-                    blade_width,
+                    solution,
+                    heuristic,
                     &mut rng,
-                )?);
+                ));
             }
         }
-        Ok(units)
+
+        Ok(Run::Evolving {
+            context,
+            evolution: Box::new(self.evolution(units, 100, deadline)),
+            heuristic_stats,
+        })
     }
 
-    fn first_fit_random_heuristics<R>(&mut self, cut_piece: &CutPieceWithId, rng: &mut R) -> bool
+    /// Checks that the cut pieces can be optimized in `mode`, which is the first part of
+    /// preparing an optimization.
+    fn check_optimizable<B>(&self, mode: OptimizeMode) -> Result<()>
     where
-        R: Rng + ?Sized,
+        B: Bin,
     {
-        for bin in self.bins.iter_mut() {
-            if bin.insert_cut_piece_random_heuristic(cut_piece, rng) {
-                return true;
-            }
+        if !self.pending_cut_pieces.is_empty() {
+            Err(Error::TooManyCutPieces(self.num_cut_pieces()))
+        } else if self.goal == OptimizeGoal::PlaceAll && !self.has_stock_available() {
+            Err(Error::NoStockAvailable(
+                self.stock_pieces
+                    .iter()
+                    .filter(|stock_piece| stock_piece.quantity == Some(0))
+                    .count(),
+            ))
+        } else if self
+            .heuristic_profile(mode)
+            .heuristics::<B>(self.bin_options())
+            .is_empty()
+        {
+            Err(Error::EmptyHeuristicProfile(mode))
+        } else if !self.is_cut_width_valid() {
+            Err(Error::CutWidthTooLarge(self.cut_width.max()))
+        } else {
+            Ok(())
         }
+    }
 
-        self.add_to_new_bin(cut_piece, rng)
+    /// Returns the context the units of a run share, for optimizing the cut pieces with the stock
+    /// pieces in `mode`. This is the stage that prepares a run.
+    fn unit_context<B>(
+        &self,
+        mode: OptimizeMode,
+        stock_pieces: &[StockPiece],
+        cut_pieces: &[&CutPieceWithId],
+    ) -> Arc<UnitContext<B>>
+    where
+        B: Bin + Clone + Send,
+    {
+        let profile = self.heuristic_profile(mode);
+        let bin_options = self.bin_options();
+        Arc::new(UnitContext {
+            possible_stock_pieces: stock_pieces.to_vec(),
+            bin_options,
+            heuristics: profile.heuristics::<B>(bin_options),
+            restricted_heuristics: *profile != HeuristicProfile::All,
+            max_units: match self.max_memory {
+                // The population grows by about half while breeding.
+                Some(max_memory) => cmp::max(
+                    max_memory
+                        / (OptimizerUnit::<B>::estimated_size(stock_pieces, cut_pieces) * 3 / 2),
+                    1,
+                ),
+                None => usize::MAX,
+            },
+            min_stock_pieces: OptimizerUnit::<B>::min_stock_pieces(stock_pieces, cut_pieces),
+            early_stop_utilization: self.early_stop_utilization,
+            internal_error: OnceLock::new(),
+            audit_log_callback: self.audit_log_callback.clone(),
+            fitness_objective: self.fitness_objective,
+            goal: self.goal,
+            cut_piece_values: self.cut_piece_values.clone(),
+            separations: self.separations.clone(),
+            prefer_banded_edges_outward: self.prefer_banded_edges_outward,
+            pattern_mismatch_penalty: self.pattern_mismatch_penalty,
+            max_stock_pieces: self.max_stock_pieces,
+        })
     }
 
-    fn first_fit_with_heuristic<R>(
-        &mut self,
-        cut_piece: &CutPieceWithId,
-        heuristic: &B::Heuristic,
-        rng: &mut R,
-    ) -> bool
+    /// Returns the evolution of the `units` for up to `generations` generations of the genetic
+    /// algorithm, which stops early at the `deadline`.
+    fn evolution<B>(
+        &self,
+        units: Vec<OptimizerUnit<B>>,
+        generations: u32,
+        deadline: Option<Instant>,
+    ) -> Evolution<OptimizerUnit<B>>
     where
-        R: Rng + ?Sized,
+        B: Bin + Clone + Send,
     {
-        for bin in self.bins.iter_mut() {
-            if bin.insert_cut_piece_with_heuristic(cut_piece, heuristic) {
-                return true;
+        self.population(units, deadline).evolve(generations)
+    }
+
+    /// Returns the population of the genetic algorithm for evolving the `units`, which stops
+    /// early at the `deadline`.
+    fn population<B>(
+        &self,
+        units: Vec<OptimizerUnit<B>>,
+        deadline: Option<Instant>,
+    ) -> Population<OptimizerUnit<B>>
+    where
+        B: Bin + Clone + Send,
+    {
+        let population_size = units.len();
+        let mut population = Population::new(units);
+        population
+            .set_size(population_size)
+            .set_rand_seed(self.random_seed())
+            .set_rng_factory(self.rng_factory.clone())
+            .set_seed_per_unit(self.epoch_seed_strategy == EpochSeedStrategy::PerUnit)
+            .set_breed_factor(0.5)
+            .set_survival_factor(0.6)
+            .set_deadline(deadline);
+        population
+    }
+
+    /// Returns how the layouts of the initial `units` did for each heuristic they were placed
+    /// with, in the order of `context.heuristics`.
+    fn heuristic_stats<B>(
+        context: &UnitContext<B>,
+        units: &[(Option<usize>, OptimizerUnit<B>)],
+    ) -> Vec<HeuristicStats>
+    where
+        B: Bin + Clone + Send,
+    {
+        let mut stats: Vec<HeuristicStats> = context
+            .heuristics
+            .iter()
+            .map(|heuristic| HeuristicStats {
+                name: B::heuristic_name(heuristic),
+                best_fitness: f64::NEG_INFINITY,
+                ..Default::default()
+            })
+            .collect();
+        for (i, unit) in units {
+            let Some(stats) = i.and_then(|i| stats.get_mut(i)) else {
+                continue;
+            };
+            let fitness = unit.fitness();
+            stats.layouts += 1;
+            stats.mean_fitness += fitness;
+            stats.best_fitness = stats.best_fitness.max(fitness);
+        }
+
+        stats.retain(|stats| stats.layouts > 0);
+        for stats in &mut stats {
+            stats.mean_fitness /= stats.layouts as f64;
+        }
+        stats
+    }
+
+    /// Returns the solutions of a run whose evolution is done. The best solution is first. If
+    /// `population` is true, it's followed by the solutions for the rest of the final population.
+    fn finish_run<B>(
+        context: &UnitContext<B>,
+        evolution: Evolution<OptimizerUnit<B>>,
+        population: bool,
+    ) -> Result<Vec<Solution>>
+    where
+        B: Bin + Clone + Send + Into<ResultStockPiece>,
+    {
+        let mut result_units = evolution.finish();
+        Self::check_best_unit(context, &result_units)?;
+
+        let best_unit = &mut result_units[0];
+        if !population {
+            return Ok(vec![Self::build_solution(best_unit)?]);
+        }
+
+        result_units
+            .iter_mut()
+            .filter(|unit| unit.is_complete())
+            .map(Self::build_solution)
+            .collect()
+    }
+
+    /// Checks that the best of the `units`, which is the first one, is a solution. This is the
+    /// stage that selects the result of a run.
+    fn check_best_unit<B>(context: &UnitContext<B>, units: &[OptimizerUnit<B>]) -> Result<()>
+    where
+        B: Bin + Clone + Send,
+    {
+        if let Some(error) = context.internal_error.get() {
+            return Err(Error::Internal(error.clone()));
+        }
+
+        let best_unit = &units[0];
+        if !best_unit.is_complete() {
+            let cut_piece = best_unit
+                .unused_cut_pieces
+                .iter()
+                .find(|cut_piece| !cut_piece.optional)
+                .unwrap();
+            return Err(best_unit.no_fit_error(cut_piece));
+        }
+        Ok(())
+    }
+
+    /// Returns the solution of the strongest unit of a run that's evolving, or `None` if it isn't
+    /// a solution yet.
+    fn current_solution<B>(evolution: &Evolution<OptimizerUnit<B>>) -> Option<Solution>
+    where
+        B: Bin + Clone + Send + Into<ResultStockPiece>,
+    {
+        let mut unit = evolution.best()?.clone();
+        if !unit.is_complete() {
+            return None;
+        }
+        Self::build_solution(&mut unit).ok()
+    }
+
+    /// Returns the cut pieces, only allowing them to rotate if the rotation policy allows it, with
+    /// the copies of each cut piece combined into groups, and with the cut pieces of each match
+    /// group combined into one. The groups of copies are larger than the size set with
+    /// `set_quantity_group_size` when a population of `MIN_UNITS_IN_MEMORY` layouts wouldn't fit
+    /// in the memory set with `set_max_memory` otherwise.
+    fn rotatable_cut_pieces(&self) -> Vec<CutPieceWithId> {
+        let (grouped, mut cut_pieces): (Vec<CutPieceWithId>, Vec<CutPieceWithId>) = self
+            .cut_pieces
+            .iter()
+            .map(|cut_piece| CutPieceWithId {
+                can_rotate: self.rotate_to_match_pattern.can_rotate(cut_piece),
+                allow_pattern_mismatch: self.pattern_mismatch_penalty.is_some(),
+                ..cut_piece.clone()
+            })
+            .partition(|cut_piece| cut_piece.match_group.is_some());
+
+        let mut groups: BTreeMap<usize, Vec<CutPieceWithId>> = BTreeMap::new();
+        for cut_piece in grouped {
+            if let Some(match_group) = cut_piece.match_group {
+                groups.entry(match_group.id).or_default().push(cut_piece);
             }
         }
-
-        self.add_to_new_bin(cut_piece, rng)
+        let mut next_id = self.cut_pieces.len();
+        let mut group_size = self.quantity_group_size;
+        loop {
+            let mut id = next_id;
+            let grouped = if group_size > 1 {
+                self.group_copies(cut_pieces.clone(), group_size, &mut id)
+            } else {
+                cut_pieces.clone()
+            };
+            if self.fits_in_memory(grouped.len() + groups.len()) || group_size >= cut_pieces.len() {
+                cut_pieces = grouped;
+                next_id = id;
+                break;
+            }
+            group_size *= 2;
+        }
+        let gap = self.cut_width.max();
+        for mut members in groups.into_values() {
+            members.sort_by_key(|cut_piece| {
+                (
+                    cut_piece.match_group.map(|group| group.position),
+                    cut_piece.id,
+                )
+            });
+            cut_pieces.push(match_group_cut_piece(next_id, members, gap));
+            next_id += 1;
+        }
+        cut_pieces
     }
 
-    fn add_to_new_bin<R>(&mut self, cut_piece: &CutPieceWithId, rng: &mut R) -> bool
-    where
-        R: Rng + ?Sized,
-    {
-        let stock_pieces = self
-            .available_stock_pieces
-            .iter_mut()
-            .filter(|stock_piece| {
-                stock_piece.quantity != Some(0) && stock_piece.fits_cut_piece(cut_piece)
-            });
+    /// Returns whether a population of `MIN_UNITS_IN_MEMORY` layouts of `num_cut_pieces` cut
+    /// pieces fits in the memory set with `set_max_memory`.
+    fn fits_in_memory(&self, num_cut_pieces: usize) -> bool {
+        // The population grows by about half while breeding.
+        self.max_memory.is_none_or(|max_memory| {
+            num_cut_pieces.saturating_mul(estimated_cut_piece_size() * MIN_UNITS_IN_MEMORY * 3 / 2)
+                <= max_memory
+        })
+    }
 
-        match stock_pieces.choose(rng) {
-            Some(stock_piece) => {
-                stock_piece.dec_quantity();
+    /// Returns the cut pieces with the copies of each one combined into groups of up to
+    /// `group_size`, which are given IDs from `next_id` on. Each group is only as large as one of
+    /// the stock pieces can fit.
+    fn group_copies(
+        &self,
+        cut_pieces: Vec<CutPieceWithId>,
+        group_size: usize,
+        next_id: &mut usize,
+    ) -> Vec<CutPieceWithId> {
+        let gap = self.cut_width.max();
+        let fits = |copies: &[CutPieceWithId]| {
+            let group = quantity_group_cut_piece(0, copies.to_vec(), gap);
+            self.stock_pieces
+                .iter()
+                .any(|stock_piece| stock_piece.fits_cut_piece(&group, self.cut_width))
+        };
 
-                let mut bin = B::new(
-                    stock_piece.width,
-                    stock_piece.length,
-                    self.blade_width,
-                    stock_piece.pattern_direction,
-                    stock_piece.price,
-                );
-                if !bin.insert_cut_piece_random_heuristic(cut_piece, rng) {
-                    return false;
+        let mut grouped = Vec::with_capacity(cut_pieces.len());
+        for copies in cut_pieces.chunk_by(|a, b| self.is_groupable(a) && is_identical_copy(a, b)) {
+            // The largest group that fits, which is at least 1 so copies that don't fit at all
+            // are still reported as not fitting on their own.
+            let (mut min, mut max) = (1, cmp::min(group_size, copies.len()));
+            while min < max {
+                let size = (min + max).div_ceil(2);
+                if fits(&copies[..size]) {
+                    min = size;
+                } else {
+                    max = size - 1;
+                }
+            }
+            for group in copies.chunks(min) {
+                if let [cut_piece] = group {
+                    grouped.push(cut_piece.clone());
+                } else {
+                    grouped.push(quantity_group_cut_piece(*next_id, group.to_vec(), gap));
+                    *next_id += 1;
                 }
-                self.bins.push(bin);
-                true
             }
-            None => false,
         }
+        grouped
     }
 
-    fn crossover<R>(&self, other: &OptimizerUnit<'a, B>, rng: &mut R) -> OptimizerUnit<'a, B>
-    where
-        R: Rng + ?Sized,
-        B: Clone,
-    {
-        // If there aren't multiple bins we can't do a crossover,
-        // so just return a clone of this unit.
-        if self.bins.len() < 2 && other.bins.len() < 2 {
-            return self.clone();
+    /// Returns the IDs of the match groups whose cut pieces don't all have the same pattern
+    /// direction, stock filter, material, thickness, and size across the grain.
+    fn mismatched_match_groups(&self) -> Vec<usize> {
+        let mut first_cut_pieces: BTreeMap<usize, &CutPieceWithId> = BTreeMap::new();
+        let mut mismatched = BTreeSet::new();
+        for cut_piece in &self.cut_pieces {
+            let Some(match_group) = cut_piece.match_group else {
+                continue;
+            };
+            let first = *first_cut_pieces.entry(match_group.id).or_insert(cut_piece);
+            let across_grain = |cut_piece: &CutPieceWithId| match cut_piece.pattern_direction {
+                PatternDirection::ParallelToWidth => cut_piece.length,
+                _ => cut_piece.width,
+            };
+            if first.pattern_direction != cut_piece.pattern_direction
+                || first.stock_filter != cut_piece.stock_filter
+                || first.material != cut_piece.material
+                || first.thickness != cut_piece.thickness
+                || across_grain(first) != across_grain(cut_piece)
+            {
+                mismatched.insert(match_group.id);
+            }
         }
+        mismatched.into_iter().collect()
+    }
 
-        // Randomly select insertion point and range of bins to inject into the new unit.
-        let cross_dest = rng.gen_range(0..=self.bins.len());
-        let cross_src_start = rng.gen_range(0..other.bins.len());
-        let cross_src_end = rng.gen_range(cross_src_start + 1..=other.bins.len());
-
-        // Create a new unit with the bins of `self` and some of the bins of `other` injected in.
-        let mut new_unit = OptimizerUnit {
-            bins: (self.bins[..cross_dest])
+    /// Returns whether the wider of the cut widths is no larger than any side of the cut pieces,
+    /// and smaller than the shorter side of at least one stock piece.
+    fn is_cut_width_valid(&self) -> bool {
+        let cut_width = self.cut_width.max();
+        let fits_cut_pieces = self
+            .cut_pieces
+            .iter()
+            .all(|cut_piece| cut_width <= cmp::min(cut_piece.width, cut_piece.length));
+        let fits_stock_pieces = self.stock_pieces.is_empty()
+            || self
+                .stock_pieces
                 .iter()
-                .chain((other.bins[cross_src_start..cross_src_end]).iter())
-                .chain((self.bins[cross_dest..]).iter())
-                .cloned()
-                .collect(),
-            possible_stock_pieces: self.possible_stock_pieces,
-            // Make all possible stock pieces available initially. Quantities will be updated below.
-            available_stock_pieces: self.possible_stock_pieces.to_vec(),
-            // Start with no unused cut pieces, and update below.
-            unused_cut_pieces: Default::default(),
-            blade_width: self.blade_width,
-        };
+                .any(|stock_piece| cut_width < cmp::min(stock_piece.width, stock_piece.length));
+        fits_cut_pieces && fits_stock_pieces
+    }
 
-        let mut unused_cut_pieces = self.unused_cut_pieces.clone();
+    /// Returns whether there is at least one stock piece with a quantity that isn't 0.
+    fn has_stock_available(&self) -> bool {
+        self.stock_pieces
+            .iter()
+            .any(|stock_piece| stock_piece.quantity != Some(0))
+    }
 
-        // Update available stock piece quantities based on the injected bins.
-        other.bins[cross_src_start..cross_src_end]
+    /// Returns the number of cut pieces the optimizer lays out, counting each group of copies
+    /// once, including the ones that weren't added because they would go over the maximum.
+    fn num_cut_pieces(&self) -> usize {
+        self.pending_cut_pieces
             .iter()
-            .for_each(|bin| {
-                if let Some(ref mut stock_piece) = new_unit
-                    .available_stock_pieces
-                    .iter_mut()
-                    .find(|sp| bin.matches_stock_piece(sp))
-                {
-                    // Remove the injected cut pieces from the unused set.
-                    for cut_piece in bin.cut_pieces() {
-                        unused_cut_pieces.remove(&cut_piece.into());
-                    }
-                    stock_piece.dec_quantity();
+            .fold(self.num_layout_pieces, |count, cut_piece| {
+                count.saturating_add(self.num_layout_pieces_of(cut_piece))
+            })
+    }
+
+    /// Returns the number of cut pieces the optimizer lays out for the cut pieces that were
+    /// added, counting each group of copies once.
+    fn num_added_layout_pieces(&self) -> usize {
+        self.cut_pieces
+            .chunk_by(|a, b| self.is_groupable(a) && is_identical_copy(a, b))
+            .map(|copies| {
+                if self.is_groupable(&copies[0]) {
+                    copies.len().div_ceil(self.quantity_group_size)
                 } else {
-                    panic!("Attempt to inject invalid bin in crossover operation. This shouldn't happen, and means there is a bug in the code.");
+                    copies.len()
                 }
-            });
+            })
+            .sum()
+    }
 
-        // Remove injected cut pieces from all non-injected bins,
-        // and update the available stock piece quantities.
-        for i in (0..cross_dest)
-            .chain((cross_dest + cross_src_end - cross_src_start)..new_unit.bins.len())
-            .rev()
-        {
-            let bin = &mut new_unit.bins[i];
-            let stock_piece = new_unit
-                .available_stock_pieces
-                .iter_mut()
-                .find(|sp| sp.quantity != Some(0) && bin.matches_stock_piece(sp));
-            let injected_cut_pieces = (other.bins[cross_src_start..cross_src_end])
-                .iter()
-                .flat_map(Bin::cut_pieces);
-            let num_removed_cut_pieces = bin.remove_cut_pieces(injected_cut_pieces);
+    /// Returns whether the copies of the cut piece can be laid out in groups. See
+    /// `set_quantity_group_size`.
+    fn is_groupable(&self, cut_piece: &CutPieceWithId) -> bool {
+        !cut_piece.optional
+            && cut_piece.match_group.is_none()
+            && self
+                .cut_piece_values
+                .get(cut_piece.id)
+                .copied()
+                .flatten()
+                .is_none()
+    }
 
-            if let (0, Some(stock_piece)) = (num_removed_cut_pieces, stock_piece) {
-                // No cut pieces were removed from this bin, so we'll keep it
-                // and decrement the quantity of the available stock piece.
-                stock_piece.dec_quantity();
-            } else {
-                // Either there were no available stock pieces, or there
-                // were cut pieces removed from this bin so we'll remove it
-                // and add its cut pieces to the unused set.
-                for cut_piece in bin.cut_pieces() {
-                    unused_cut_pieces.insert(cut_piece.into());
+    /// Returns the problems with the inputs that can be found without optimizing. Each cut piece
+    /// is only reported once, rather than once for each copy of it.
+    fn input_errors(&self) -> Vec<InputError> {
+        let mut errors: Vec<InputError> = self
+            .stock_pieces
+            .iter()
+            .filter_map(|stock_piece| {
+                if stock_piece.width == 0 || stock_piece.length == 0 {
+                    Some(InputError::ZeroSizeStockPiece(stock_piece.clone()))
+                } else if stock_piece.quantity == Some(0) {
+                    Some(InputError::ZeroQuantityStockPiece(stock_piece.clone()))
+                } else {
+                    None
                 }
-                new_unit.bins.remove(i);
-            }
+            })
+            .collect();
+
+        if !self.is_cut_width_valid() {
+            errors.push(InputError::CutWidthTooLarge(self.cut_width.max()));
         }
 
-        // Try to add all unused cut pieces.
-        unused_cut_pieces.retain(|cut_piece| !new_unit.first_fit_random_heuristics(cut_piece, rng));
-        new_unit.unused_cut_pieces = unused_cut_pieces;
+        if !self.pending_cut_pieces.is_empty() {
+            errors.push(InputError::TooManyCutPieces(self.num_cut_pieces()));
+        }
 
-        // Remove bins that don't have cut pieces.
-        for i in (0..new_unit.bins.len()).rev() {
-            if new_unit.bins[i].cut_pieces().next().is_none() {
-                let bin = &mut new_unit.bins[i];
-                if let Some(ref mut stock_piece) = new_unit
-                    .available_stock_pieces
-                    .iter_mut()
-                    .find(|sp| sp.quantity != Some(0) && bin.matches_stock_piece(sp))
-                {
-                    // Make this stock piece available to use again.
-                    stock_piece.inc_quantity();
-                }
-                new_unit.bins.remove(i);
+        errors.extend(
+            self.mismatched_match_groups()
+                .into_iter()
+                .map(InputError::MismatchedMatchGroup),
+        );
+
+        let mut reported = FnvHashSet::default();
+        for cut_piece in self.rotatable_cut_pieces() {
+            if !reported.insert((
+                cut_piece.external_id,
+                cut_piece.width,
+                cut_piece.length,
+                cut_piece.pattern_direction,
+            )) {
+                continue;
+            }
+
+            if cut_piece.width == 0 || cut_piece.length == 0 {
+                errors.push(InputError::ZeroSizeCutPiece(error_cut_piece(&cut_piece)));
+            } else if self.goal == OptimizeGoal::PlaceAll
+                && !cut_piece.optional
+                && !self.stock_pieces.iter().any(|stock_piece| {
+                    stock_piece.quantity != Some(0)
+                        && stock_piece.fits_cut_piece(&cut_piece, self.cut_width)
+                })
+            {
+                errors.push(InputError::NoStockForCutPiece(error_cut_piece(&cut_piece)));
             }
         }
 
-        new_unit
+        errors
     }
 
-    // Randomly apply a mutation to this unit.
-    fn mutate<R>(&mut self, rng: &mut R)
-    where
-        R: Rng + ?Sized,
-    {
-        if !self.bins.is_empty() && rng.gen_range(0..20) == 1 {
-            self.inversion(rng)
+    /// Sends the event to the progress event callback, if there is one.
+    fn progress_event(&self, event: ProgressEvent) {
+        if let Some(ref callback) = self.progress_event_callback {
+            callback(event);
         }
     }
 
-    // Reverse order of a random range of bins.
-    fn inversion<R>(&mut self, rng: &mut R)
+    fn bin_options(&self) -> BinOptions {
+        BinOptions {
+            blade_width: self.cut_width,
+            diagnostics: self.diagnostics,
+            worst_fit_heuristics: self.worst_fit_heuristics,
+            smallest_y_heuristic: self.smallest_y_heuristic,
+            guillotine_waste_pieces: self.guillotine_waste_pieces,
+            max_guillotine_stages: self.max_guillotine_stages,
+            first_cut_direction: self.first_cut_direction,
+            nested_strip_direction: self.nested_strip_direction,
+        }
+    }
+
+    fn heuristic_profile(&self, mode: OptimizeMode) -> &HeuristicProfile {
+        match mode {
+            OptimizeMode::Guillotine => &self.guillotine_heuristic_profile,
+            OptimizeMode::Nested => &self.nested_heuristic_profile,
+        }
+    }
+
+    fn build_solution<B>(unit: &mut OptimizerUnit<B>) -> Result<Solution>
     where
-        R: Rng + ?Sized,
+        B: Bin + Clone + Send + Into<ResultStockPiece>,
     {
-        let start = rng.gen_range(0..self.bins.len());
-        let end = rng.gen_range(start..self.bins.len());
-        self.bins[start..end].reverse();
+        // Each demand piece must be placed exactly once, so make sure the bookkeeping didn't
+        // place any of them in more than one spot before handing out the layout.
+        let mut placed = FnvHashSet::default();
+        for cut_piece in unit.bins.iter().flat_map(|bin| bin.cut_pieces()) {
+            if !placed.insert(cut_piece.id) {
+                return Err(duplicate_cut_piece_error(cut_piece));
+            }
+        }
+
+        let fitness = unit.fitness();
+        let optimality_gap = unit.optimality_gap();
+        let price = unit.bins.iter().map(|bin| bin.price(true)).sum();
+        let unplaced_cut_pieces = unplaced_cut_pieces(
+            unit.unused_cut_pieces
+                .iter()
+                .filter(|cut_piece| !cut_piece.optional),
+            &unit.context.cut_piece_values,
+        );
+        let (placed_value, unplaced_value) = unit.placed_and_unused_value();
+
+        let used_stock_pieces: Vec<ResultStockPiece> =
+            unit.bins.drain(..).map(Into::into).collect();
+
+        Ok(Solution {
+            fitness,
+            stock_pieces: used_stock_pieces,
+            objective: None,
+            unplaced_cut_pieces,
+            placed_value,
+            unplaced_value,
+            annotations: BTreeMap::new(),
+            optimality_gap,
+            // Set when the solution is sorted.
+            random_seed: 0,
+            price,
+            pass_stats: Vec::new(),
+            // Set when the solution is sorted.
+            min_reusable_waste: (0, 0),
+            result_detail: ResultDetail::Full,
+        })
     }
 }
 
-impl<'a, B> Unit for OptimizerUnit<'a, B>
-where
-    B: Bin + Send + Clone,
-{
-    fn fitness(&self) -> f64 {
-        let fitness = if self.bins.is_empty() {
-            0.0
-        } else {
-            self.bins.iter().fold(0.0, |acc, b| acc + b.fitness()) / self.bins.len() as f64
-        };
+/// How the stock pieces of a solution and their cut pieces are sorted, and how they're mirrored.
+#[derive(Copy, Clone, Debug)]
+struct SolutionOrder {
+    stock_piece_order: StockPieceOrder,
+    cut_piece_order: CutPieceOrder,
+    mirror: Mirror,
+    compact: bool,
+    result_detail: ResultDetail,
+    cut_width: BladeWidth,
+    min_reusable_waste: (usize, usize),
+    // Random seed the solution was found with, which is reported with it.
+    random_seed: u64,
+}
 
-        if self.unused_cut_pieces.is_empty() {
-            fitness
-        } else {
-            // If there are unused cut pieces, the fitness is below 0 because it's not a valid
-            // solution.
-            fitness - 1.0
+impl SolutionOrder {
+    fn sort(&self, solution: &mut Solution) {
+        solution.random_seed = self.random_seed;
+        solution.min_reusable_waste = self.min_reusable_waste;
+        if self.compact {
+            solution.stock_pieces = mem::take(&mut solution.stock_pieces)
+                .into_iter()
+                .map(|stock_piece| stock_piece.compacted(self.cut_width))
+                .collect();
+        }
+        let stock_pieces = &mut solution.stock_pieces;
+        stock_pieces.sort_by_key(|p| cmp::Reverse((p.width, p.length)));
+        match self.stock_piece_order {
+            StockPieceOrder::SizeDescending => (),
+            StockPieceOrder::UtilizationDescending => stock_pieces.sort_by(|a, b| {
+                b.utilization
+                    .partial_cmp(&a.utilization)
+                    .unwrap_or(cmp::Ordering::Equal)
+            }),
+            StockPieceOrder::UtilizationAscending => stock_pieces.sort_by(|a, b| {
+                a.utilization
+                    .partial_cmp(&b.utilization)
+                    .unwrap_or(cmp::Ordering::Equal)
+            }),
+            StockPieceOrder::ExternalId => {
+                stock_pieces.sort_by_key(|p| (p.external_id.is_none(), p.external_id))
+            }
         }
-    }
 
-    fn breed_with<R>(&self, other: &OptimizerUnit<'a, B>, rng: &mut R) -> OptimizerUnit<'a, B>
-    where
-        R: Rng + ?Sized,
-    {
-        let mut new_unit = self.crossover(other, rng);
-        new_unit.mutate(rng);
-        new_unit
+        for (i, stock_piece) in stock_pieces.iter_mut().enumerate() {
+            stock_piece.serial_number = i + 1;
+            stock_piece.mirror(self.mirror, self.cut_width);
+            stock_piece.split_waste(self.min_reusable_waste);
+            stock_piece.sort_cut_pieces(self.cut_piece_order);
+            if self.result_detail == ResultDetail::Full {
+                stock_piece.build_cut_tree(self.cut_width);
+            }
+        }
+
+        // The sheet IDs are made from the layouts, so the details are removed after.
+        solution.assign_sheet_ids();
+        solution.reduce_detail(self.result_detail);
     }
 }
 
-/// Error while optimizing.
-#[derive(Debug)]
-pub enum Error {
-    /// There was no stock piece that could contain this demand piece.
-    NoFitForCutPiece(CutPiece),
+/// A run of the optimizer with one set of stock pieces.
+enum Run<B>
+where
+    B: Bin + Clone + Send,
+{
+    /// The genetic algorithm is evolving layouts of the cut pieces.
+    Evolving {
+        context: Arc<UnitContext<B>>,
+        evolution: Box<Evolution<OptimizerUnit<B>>>,
+
+        // How the heuristics did in the initial population, if diagnostics are enabled.
+        heuristic_stats: Vec<HeuristicStats>,
+    },
+
+    /// The best layout was found without the genetic algorithm.
+    Solved(Solution),
 }
-fn no_fit_for_cut_piece_error(cut_piece: &CutPieceWithId) -> Error {
-    Error::NoFitForCutPiece(CutPiece {
-        quantity: 1,
-        external_id: cut_piece.external_id,
-        width: cut_piece.width,
-        length: cut_piece.length,
-        can_rotate: cut_piece.can_rotate,
-        pattern_direction: cut_piece.pattern_direction,
-    })
+
+/// Which of the solutions found by an optimization to return.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Selection {
+    /// Only the best solution.
+    Best,
+
+    /// The best solution for each objective.
+    Pareto,
+
+    /// Up to this many of the best distinct solutions.
+    TopN(usize),
+
+    /// The best solution of each run, for `Optimizer::recommend_stock`.
+    PerRun,
 }
-type Result<T> = std::result::Result<T, Error>;
 
-/// A valid solution to an optimization.
-#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
-#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
-pub struct Solution {
-    /// Fitness score for this solution.
-    /// Ranges between 0.0 and 1.0 inclusive, with 1.0 being a perfect solution with no waste.
-    pub fitness: f64,
+impl Selection {
+    /// Returns whether every solution of each run is kept as a candidate, not just the best one.
+    fn keeps_all_solutions(self) -> bool {
+        matches!(self, Selection::Pareto | Selection::TopN(_))
+    }
+}
+
+/// An optimization that runs a little at a time. Each set of stock pieces that's tried is
+/// optimized in its own run, one after the other, and the best solution of the runs is kept.
+struct Optimization<'a, B>
+where
+    B: Bin + Clone + Send,
+{
+    optimizer: &'a Optimizer,
+
+    mode: OptimizeMode,
+
+    // Which of the solutions that are found to return.
+    selection: Selection,
+
+    // Cut pieces, only allowed to rotate if the rotation policy allows it.
+    cut_pieces: Vec<CutPieceWithId>,
+
+    // Stock sizes that are each tried in their own run, after the run with mixed stock sizes if
+    // it's allowed.
+    stock_sizes: Vec<(usize, usize)>,
+
+    num_runs: usize,
+
+    // Number of runs that have finished or were skipped.
+    completed_runs: usize,
+
+    // When the optimization should be done, if there's a time limit.
+    deadline: Option<Instant>,
+
+    // The run in progress, with its index.
+    run: Option<(usize, Run<B>)>,
+
+    // The best result of the runs that are done, or `None` if no run found a solution or a
+    // problem that should be reported instead of a missing fit.
+    best_result: Option<Result<Solution>>,
+
+    // Every solution that was found, when looking for the best solution for each objective.
+    candidates: Vec<Solution>,
+
+    // The best fitness found so far by any run.
+    best_fitness: f64,
+
+    // Fraction of the optimization that's done.
+    progress: f64,
+
+    // Whether the progress callback asked to stop the optimization.
+    cancelled: bool,
+
+    // Called with each solution that's better than the ones before it.
+    solution_callback: Option<&'a dyn Fn(&Solution)>,
+
+    // Solution whose layout the runs start from, if any.
+    warm_start: Option<&'a Solution>,
+
+    // How the heuristics did in each run that evolved layouts, if diagnostics are enabled.
+    pass_stats: Vec<PassStats>,
+
+    // The solutions, once the optimization is done.
+    result: Option<Result<Vec<Solution>>>,
+}
+
+impl<'a, B> Optimization<'a, B>
+where
+    B: Bin + Clone + Send + Into<ResultStockPiece>,
+{
+    fn new(optimizer: &'a Optimizer, mode: OptimizeMode, selection: Selection) -> Self {
+        // Stock pieces that must be used are used with every stock size, so they only get a size
+        // of their own if there are no others.
+        let size_set: FnvHashSet<(usize, usize)> = optimizer
+            .stock_pieces
+            .iter()
+            .filter(|sp| !sp.must_use)
+            .map(|sp| (sp.width, sp.length))
+            .collect();
+        let size_set = if size_set.is_empty() {
+            optimizer
+                .stock_pieces
+                .iter()
+                .map(|sp| (sp.width, sp.length))
+                .collect()
+        } else {
+            size_set
+        };
+        // Try the largest stock sizes first, so the runs are always in the same order.
+        let mut stock_sizes: Vec<(usize, usize)> = size_set.into_iter().collect();
+        stock_sizes.sort_by_key(|&(width, length)| {
+            cmp::Reverse((width as u64 * length as u64, width, length))
+        });
+        let num_runs = stock_sizes.len()
+            + if optimizer.allow_mixed_stock_sizes {
+                1
+            } else {
+                0
+            };
+
+        let mut optimization = Self {
+            optimizer,
+            mode,
+            selection,
+            cut_pieces: optimizer.rotatable_cut_pieces(),
+            stock_sizes,
+            num_runs,
+            completed_runs: 0,
+            deadline: optimizer
+                .time_limit
+                .map(|time_limit| Instant::now() + time_limit),
+            run: None,
+            best_result: None,
+            candidates: Vec::new(),
+            best_fitness: f64::NEG_INFINITY,
+            progress: 0.0,
+            cancelled: false,
+            solution_callback: None,
+            warm_start: None,
+            pass_stats: Vec::new(),
+            result: None,
+        };
+
+        if let Some(ref snapshot) = optimizer.solution_snapshot {
+            snapshot.clear();
+        }
 
-    /// The stock pieces that were used for this solution, each containing the demand piece layout.
-    pub stock_pieces: Vec<ResultStockPiece>,
+        if !optimizer.pending_cut_pieces.is_empty() {
+            optimization.finish_with(Err(Error::TooManyCutPieces(optimizer.num_cut_pieces())));
+        } else if optimizer.cut_pieces.is_empty() {
+            // If there are no cut pieces, there's nothing to optimize.
+            optimization.finish_with(Ok(vec![Solution {
+                fitness: 1.0,
+                stock_pieces: Vec::new(),
+                objective: None,
+                unplaced_cut_pieces: Vec::new(),
+                placed_value: 0,
+                unplaced_value: 0,
+                annotations: BTreeMap::new(),
+                price: 0,
+                optimality_gap: 0.0,
+                random_seed: optimizer.random_seed(),
+                pass_stats: Vec::new(),
+                min_reusable_waste: optimizer.min_reusable_waste,
+                result_detail: optimizer.result_detail,
+            }]));
+        } else if let Err(error) = optimizer.check_optimizable::<B>(mode) {
+            optimization.finish_with(Err(error));
+        }
 
-    #[cfg_attr(feature = "serialize", serde(skip))]
-    price: usize,
-}
+        optimization
+    }
 
-/// Optimizer for optimizing rectangular cut pieces from rectangular
-/// stock pieces.
-pub struct Optimizer {
-    stock_pieces: Vec<StockPiece>,
-    cut_pieces: Vec<CutPieceWithId>,
-    cut_width: usize,
-    random_seed: u64,
-    allow_mixed_stock_sizes: bool,
-}
+    /// Sets the callback that's called with each solution that's better than the ones before it.
+    fn with_solution_callback(mut self, solution_callback: &'a dyn Fn(&Solution)) -> Self {
+        self.solution_callback = Some(solution_callback);
+        self
+    }
 
-impl Default for Optimizer {
-    fn default() -> Self {
-        Self {
-            stock_pieces: Default::default(),
-            cut_pieces: Default::default(),
-            cut_width: Default::default(),
-            random_seed: Default::default(),
-            allow_mixed_stock_sizes: true,
-        }
+    /// Sets the solution whose layout the runs start from.
+    fn with_warm_start(mut self, solution: &'a Solution) -> Self {
+        self.warm_start = Some(solution);
+        self
     }
-}
 
-impl Optimizer {
-    /// Create a new optimizer.
-    pub fn new() -> Self {
-        Default::default()
+    /// Returns whether the optimization is done.
+    fn is_done(&self) -> bool {
+        self.result.is_some()
     }
 
-    /// Add a stock piece that the optimizer can use to optimize cut pieces.
-    /// If the same stock piece is added multiple times, the quantities will be
-    /// summed up. If any have a `None` quantity, the quantity on other equivalent
-    /// pieces will be ignored.
-    pub fn add_stock_piece(&mut self, stock_piece: StockPiece) -> &mut Self {
-        let mut existing_stock_piece = self.stock_pieces.iter_mut().find(|sp| {
-            sp.width == stock_piece.width
-                && sp.length == stock_piece.length
-                && sp.pattern_direction == stock_piece.pattern_direction
-                && sp.price == stock_piece.price
-        });
+    /// Runs the rest of the optimization, and returns its solutions.
+    fn run<F, R>(mut self, progress_callback: &F) -> Result<Vec<Solution>>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        while !self.is_done() {
+            self.step(progress_callback);
+        }
+        self.result.unwrap()
+    }
 
-        if let Some(ref mut existing_stock_piece) = existing_stock_piece {
-            match (&mut existing_stock_piece.quantity, stock_piece.quantity) {
-                (Some(ref mut existing_quantity), Some(quantity)) => {
-                    // If there is already a stock piece that is the same except the quantity, add
-                    // to the quantity.
-                    *existing_quantity += quantity;
+    /// Does the next bit of work, which is either a generation of the run in progress, or
+    /// starting the next run.
+    fn step<F, R>(&mut self, progress_callback: &F)
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        if self.is_done() {
+            return;
+        }
+
+        match self.run.take() {
+            Some((
+                i,
+                Run::Evolving {
+                    context,
+                    mut evolution,
+                    heuristic_stats,
+                },
+            )) => match evolution.step() {
+                Some((progress, fitness)) => {
+                    self.report_solution(fitness, || Optimizer::current_solution(&evolution));
+                    self.update_snapshot(fitness, || {
+                        let mut unit = evolution.best().filter(|unit| unit.is_complete())?.clone();
+                        Some(move || Optimizer::build_solution(&mut unit).ok())
+                    });
+                    self.report_epoch(i, progress, fitness, progress_callback);
+                    self.run = Some((
+                        i,
+                        Run::Evolving {
+                            context,
+                            evolution,
+                            heuristic_stats,
+                        },
+                    ));
                 }
-                _ => {
-                    // `None` quantity means infinite, so if either is `None` we want it to be
-                    // `None`.
-                    existing_stock_piece.quantity = None;
+                None => {
+                    let result = Optimizer::finish_run(
+                        &context,
+                        *evolution,
+                        self.selection.keeps_all_solutions(),
+                    );
+                    self.record_pass_stats(i, heuristic_stats, &result);
+                    self.finish_run(i, result);
                 }
+            },
+            Some((i, Run::Solved(solution))) => {
+                self.report_solution(solution.fitness, || Some(solution.clone()));
+                self.update_snapshot(solution.fitness, || {
+                    let solution = solution.clone();
+                    Some(move || Some(solution))
+                });
+                self.report_epoch(i, 1.0, solution.fitness, progress_callback);
+                self.finish_run(i, Ok(vec![solution]));
             }
-        } else {
-            // A stock piece like this hasn't yet been added so let's do it.
-            self.stock_pieces.push(stock_piece);
+            None if self.completed_runs == self.num_runs => {
+                let result = self.solutions();
+                self.finish_with(result);
+            }
+            None => self.start_run(progress_callback),
         }
 
-        self
+        if self.cancelled && !self.is_done() {
+            self.cancel();
+        }
     }
 
-    /// Add a stock pieces that the optimizer can use to optimize cut pieces.
-    /// If the same stock piece is added multiple times, the quantities will be
-    /// summed up. If any have a `None` quantity, the quantity on other equivalent
-    /// pieces will be ignored.
-    pub fn add_stock_pieces<I>(&mut self, stock_pieces: I) -> &mut Self
+    /// Starts the next run, or skips it if it can't find a better solution than the best one so
+    /// far.
+    fn start_run<F, R>(&mut self, progress_callback: &F)
     where
-        I: IntoIterator<Item = StockPiece>,
+        F: Fn(f64) -> R,
+        R: ProgressControl,
     {
-        stock_pieces.into_iter().for_each(|sp| {
-            self.add_stock_piece(sp);
+        let i = self.completed_runs;
+        let mixed_runs = if self.optimizer.allow_mixed_stock_sizes {
+            1
+        } else {
+            0
+        };
+
+        let stock_pieces: Vec<StockPiece> = if i < mixed_runs {
+            // Optimize with all stock sizes.
+            self.optimizer.stock_pieces.clone()
+        } else {
+            // Optimize each stock size separately and see if any have better result than
+            // when optimizing with all stock sizes, along with the stock pieces that must be used.
+            let (width, length) = self.stock_sizes[i - mixed_runs];
+            self.optimizer
+                .stock_pieces
+                .iter()
+                .filter(|sp| sp.must_use || (sp.width == width && sp.length == length))
+                .cloned()
+                .collect()
+        };
+
+        // Skip stock sizes that can't be cheaper than the best solution so far, even if the
+        // cut pieces fit in as few stock pieces as their area allows. Only the best solution is
+        // kept by price, and the other selections need every stock size, such as for the
+        // solutions that are best at another objective.
+        let minimize_price = self.optimizer.fitness_objective == FitnessObjective::MinimizePrice
+            && self.optimizer.goal == OptimizeGoal::PlaceAll
+            && self.selection == Selection::Best;
+        if let (true, Some(Ok(ref best_solution))) =
+            (i >= mixed_runs && minimize_price, &self.best_result)
+        {
+            let cut_pieces: Vec<&CutPieceWithId> = self.cut_pieces.iter().collect();
+            let min_price = OptimizerUnit::<B>::min_stock_pieces(&stock_pieces, &cut_pieces)
+                * stock_pieces.iter().map(|sp| sp.price).min().unwrap_or(0);
+            if best_solution.fitness >= 0.0 && min_price > best_solution.price {
+                self.completed_runs += 1;
+                self.report_progress((i + 1) as f64 / self.num_runs as f64, progress_callback);
+                return;
+            }
+        }
+
+        // Split the time that's left evenly between the runs that are left.
+        let run_deadline = self.deadline.map(|deadline| {
+            let now = Instant::now();
+            now + deadline.saturating_duration_since(now) / (self.num_runs - i) as u32
         });
-        self
-    }
 
-    /// Add a desired cut piece that you need cut from a stock piece.
-    pub fn add_cut_piece(&mut self, cut_piece: CutPiece) -> &mut Self {
-        for _ in 0..cut_piece.quantity {
-            let cut_piece = CutPieceWithId {
-                id: self.cut_pieces.len(),
-                external_id: cut_piece.external_id,
-                width: cut_piece.width,
-                length: cut_piece.length,
-                pattern_direction: cut_piece.pattern_direction,
-                can_rotate: cut_piece.can_rotate,
-            };
+        self.optimizer.progress_event(ProgressEvent::PhaseStarted {
+            phase: i,
+            phases: self.num_runs,
+        });
+        audit::log(&self.optimizer.audit_log_callback, || {
+            AuditEntry::RunStarted { phase: i }
+        });
+        match self.optimizer.start_run::<B>(
+            self.mode,
+            &stock_pieces,
+            &self.cut_pieces,
+            run_deadline,
+            self.warm_start,
+        ) {
+            Ok(run) => self.run = Some((i, run)),
+            Err(error) => self.finish_run(i, Err(error)),
+        }
+    }
 
-            self.cut_pieces.push(cut_piece);
+    /// Records how the heuristics did in run `i`, with the stock pieces of its best solution
+    /// that each heuristic placed cut pieces on. Nothing is recorded if diagnostics are disabled.
+    fn record_pass_stats(
+        &mut self,
+        i: usize,
+        mut heuristics: Vec<HeuristicStats>,
+        result: &Result<Vec<Solution>>,
+    ) {
+        let Ok(solutions) = result else {
+            return;
+        };
+        if !self.optimizer.diagnostics {
+            return;
         }
 
-        self
+        let best_solution = &solutions[0];
+        for stats in &mut heuristics {
+            stats.winning_stock_pieces = best_solution
+                .stock_pieces
+                .iter()
+                .filter(|stock_piece| {
+                    stock_piece
+                        .diagnostics
+                        .as_ref()
+                        .is_some_and(|diagnostics| diagnostics.heuristics.contains(&stats.name))
+                })
+                .count();
+        }
+        self.pass_stats.push(PassStats {
+            phase: i,
+            best_fitness: best_solution.fitness,
+            heuristics,
+        });
     }
 
-    /// Add desired cut pieces that you need cut from a stock piece.
-    pub fn add_cut_pieces<I>(&mut self, cut_pieces: I) -> &mut Self
-    where
-        I: IntoIterator<Item = CutPiece>,
-    {
-        cut_pieces.into_iter().for_each(|dp| {
-            self.add_cut_piece(dp);
+    /// Keeps the result of the run if it's better than the best result so far.
+    fn finish_run(&mut self, i: usize, result: Result<Vec<Solution>>) {
+        self.completed_runs += 1;
+
+        let result = result.map(|mut solutions| {
+            let best_solution = solutions[0].clone();
+            self.report_solution(best_solution.fitness, || Some(best_solution.clone()));
+            self.update_snapshot(best_solution.fitness, || {
+                let best_solution = best_solution.clone();
+                Some(move || Some(best_solution))
+            });
+            self.report_fitness(best_solution.fitness);
+            if self.selection.keeps_all_solutions() {
+                self.candidates.append(&mut solutions);
+            } else if self.selection == Selection::PerRun {
+                self.candidates.push(best_solution.clone());
+            }
+            best_solution
         });
-        self
+
+        let allow_mixed_stock_sizes = self.optimizer.allow_mixed_stock_sizes;
+        if allow_mixed_stock_sizes && i == 0 {
+            self.best_result = Some(result);
+            return;
+        }
+
+        self.best_result = match (result, self.best_result.take()) {
+            (Ok(solution), Some(Ok(best_solution))) => {
+                // When minimizing price, use the lower-priced solution, but if the prices are the
+                // same, use the solution with the higher fitness score. For other objectives, the
+                // fitness score already measures the objective.
+                let fitness_ordering = solution
+                    .fitness
+                    .partial_cmp(&best_solution.fitness)
+                    .unwrap_or(cmp::Ordering::Equal);
+                let ordering = if solution.fitness < 0.0
+                    || best_solution.fitness < 0.0
+                    || self.optimizer.fitness_objective != FitnessObjective::MinimizePrice
+                    || self.optimizer.goal == OptimizeGoal::MaximizeValue
+                {
+                    fitness_ordering
+                } else {
+                    best_solution
+                        .price
+                        .cmp(&solution.price)
+                        .then(fitness_ordering)
+                };
+                // Break ties the same way every time, instead of by the order of the runs.
+                let is_better = ordering
+                    .then_with(|| best_solution.tie_break_key().cmp(&solution.tie_break_key()))
+                    == cmp::Ordering::Greater;
+                Some(Ok(if is_better { solution } else { best_solution }))
+            }
+            (Ok(solution), _) => Some(Ok(solution)),
+            // If no run finds a solution, report a bug in the optimizer rather than a missing
+            // fit.
+            (
+                Err(error @ (Error::Internal(_) | Error::DuplicateCutPiece(_))),
+                None | Some(Err(Error::NoFitForCutPiece(..))),
+            ) => Some(Err(error)),
+            // Without mixed stock sizes, report which cut piece didn't fit a stock size.
+            (
+                Err(error @ Error::NoFitForCutPiece(..)),
+                None | Some(Err(Error::NoFitForCutPiece(..))),
+            ) if !allow_mixed_stock_sizes => Some(Err(error)),
+            (Err(_), best_result) => best_result,
+        };
     }
 
-    /// Set the width of the cut to use between cut pieces. This could
-    /// represent blade or kerf thickness.
-    pub fn set_cut_width(&mut self, cut_width: usize) -> &mut Self {
-        self.cut_width = cut_width;
-        self
+    /// Returns the solutions once every run is done.
+    fn solutions(&mut self) -> Result<Vec<Solution>> {
+        let best_result = self.best_result.take().unwrap_or_else(|| {
+            // No run found a solution. Note: it's safe to assume `cut_pieces` isn't empty
+            // because that's checked when the optimization is created.
+            let cut_piece = &self.optimizer.cut_pieces[0];
+            Err(no_fit_for_cut_piece_error(
+                cut_piece,
+                PlacementSummary {
+                    placed: 0,
+                    quantity: self
+                        .optimizer
+                        .cut_pieces
+                        .iter()
+                        .filter(|other| is_copy_of(other, cut_piece))
+                        .count(),
+                    stock_exhausted: false,
+                },
+            ))
+        });
+
+        let mut solutions = match self.selection {
+            Selection::Pareto if !self.optimizer.objectives.is_empty() => {
+                best_result?;
+                self.optimizer.pareto_front(mem::take(&mut self.candidates))
+            }
+            Selection::PerRun => {
+                best_result?;
+                mem::take(&mut self.candidates)
+            }
+            _ => vec![best_result?],
+        };
+
+        // Solutions are compared by their cut pieces, so a summary keeps them until the
+        // solutions are compared.
+        let result_detail = self.optimizer.result_detail;
+        let order = SolutionOrder {
+            result_detail: cmp::min(result_detail, ResultDetail::PiecesOnly),
+            ..self.optimizer.solution_order()
+        };
+        for solution in &mut solutions {
+            order.sort(solution);
+            solution.pass_stats = self.pass_stats.clone();
+        }
+
+        if let Selection::TopN(n) = self.selection {
+            let mut candidates = mem::take(&mut self.candidates);
+            candidates.sort_by(|a, b| {
+                a.price
+                    .cmp(&b.price)
+                    .then_with(|| {
+                        b.fitness
+                            .partial_cmp(&a.fitness)
+                            .unwrap_or(cmp::Ordering::Equal)
+                    })
+                    .then_with(|| a.tie_break_key().cmp(&b.tie_break_key()))
+            });
+            for mut candidate in candidates {
+                if solutions.len() >= n {
+                    break;
+                }
+                order.sort(&mut candidate);
+                candidate.pass_stats = self.pass_stats.clone();
+                if !solutions
+                    .iter()
+                    .any(|solution| solution.has_same_layout(&candidate))
+                {
+                    solutions.push(candidate);
+                }
+            }
+            solutions.truncate(n);
+        }
+
+        for solution in &mut solutions {
+            solution.reduce_detail(result_detail);
+        }
+        Ok(solutions)
     }
 
-    /// Set the random seed used by the genetic algorithms in the optimizer. Using
-    /// the same random seed will give you the same result for the same input.
-    pub fn set_random_seed(&mut self, seed: u64) -> &mut Self {
-        self.random_seed = seed;
-        self
+    /// Stops the optimization early, with the best solution found so far. The run in progress is
+    /// stopped without running the rest of its generations, and its best solution is used too.
+    fn cancel(&mut self) {
+        match self.run.take() {
+            Some((
+                i,
+                Run::Evolving {
+                    context,
+                    mut evolution,
+                    heuristic_stats,
+                },
+            )) => {
+                evolution.stop();
+                let result = Optimizer::finish_run(
+                    &context,
+                    *evolution,
+                    self.selection.keeps_all_solutions(),
+                );
+                self.record_pass_stats(i, heuristic_stats, &result);
+                self.finish_run(i, result);
+            }
+            Some((i, Run::Solved(solution))) => self.finish_run(i, Ok(vec![solution])),
+            None => (),
+        }
+
+        let result = if let Some(Ok(_)) = self.best_result {
+            self.solutions()
+        } else {
+            Err(Error::Cancelled)
+        };
+        self.finish_with(result);
     }
 
-    /// Set whether the optimizer should allow mixed sized stock pieces in the results.
-    /// If set to false, and multiple stock sizes are given, only one stock size will be used in
-    /// the results.
-    pub fn allow_mixed_stock_sizes(&mut self, allow: bool) -> &mut Self {
-        self.allow_mixed_stock_sizes = allow;
-        self
+    /// Finishes the optimization with the result.
+    fn finish_with(&mut self, result: Result<Vec<Solution>>) {
+        self.result = Some(result);
+        self.optimizer.progress_event(ProgressEvent::Finished);
     }
 
-    /// Optimize in a way where each cut piece can be cut out using only guillotine cuts,
-    /// where each cut extends from one side to the other.
-    ///
-    /// This method is suitable for cutting with a panel saw.
-    pub fn optimize_guillotine<F>(&self, progress_callback: F) -> Result<Solution>
+    /// Reports the progress of run `i`, and the best fitness it has found so far.
+    fn report_epoch<F, R>(&mut self, i: usize, progress: f64, fitness: f64, progress_callback: &F)
     where
-        F: Fn(f64),
+        F: Fn(f64) -> R,
+        R: ProgressControl,
     {
-        self.optimize::<GuillotineBin, F>(progress_callback)
+        self.report_progress(
+            (i as f64 + progress) / self.num_runs as f64,
+            progress_callback,
+        );
+        self.optimizer
+            .progress_event(ProgressEvent::EpochCompleted { best: fitness });
+        audit::log(&self.optimizer.audit_log_callback, || {
+            AuditEntry::EpochCompleted { best: fitness }
+        });
+        self.report_fitness(fitness);
     }
 
-    /// Optimize without the requirement of guillotine cuts. Cuts can start and stop in the middle
-    /// of the stock piece.
-    ///
-    /// This method is suitable for cutting on a CNC.
-    pub fn optimize_nested<F>(&self, progress_callback: F) -> Result<Solution>
+    fn report_progress<F, R>(&mut self, progress: f64, progress_callback: &F)
     where
-        F: Fn(f64),
+        F: Fn(f64) -> R,
+        R: ProgressControl,
     {
-        self.optimize::<MaxRectsBin, F>(progress_callback)
+        self.progress = progress;
+        if progress_callback(progress).should_stop() {
+            self.cancelled = true;
+        }
     }
 
-    fn optimize<B, F>(&self, progress_callback: F) -> Result<Solution>
+    /// Calls the solution callback, if there is one, with the solution built by `solution` if
+    /// `fitness` is higher than the best fitness so far. The solution is only built when it's
+    /// reported.
+    fn report_solution<S>(&self, fitness: f64, solution: S)
     where
-        B: Bin + Clone + Send + Into<ResultStockPiece>,
-        F: Fn(f64),
+        S: FnOnce() -> Option<Solution>,
     {
-        // If there are no cut pieces, there's nothing to optimize.
-        if self.cut_pieces.is_empty() {
-            return Ok(Solution {
-                fitness: 1.0,
-                stock_pieces: Vec::new(),
-                price: 0,
-            });
+        let Some(callback) = self.solution_callback else {
+            return;
+        };
+        if fitness > self.best_fitness {
+            if let Some(mut solution) = solution() {
+                self.optimizer.sort_solution(&mut solution);
+                callback(&solution);
+            }
         }
+    }
 
-        let size_set: FnvHashSet<(usize, usize)> = self
-            .stock_pieces
-            .iter()
-            .map(|sp| (sp.width, sp.length))
-            .collect();
-
-        let num_runs = size_set.len() + if self.allow_mixed_stock_sizes { 1 } else { 0 };
-        let callback = |progress| {
-            progress_callback(progress / num_runs as f64);
+    /// Updates the solution snapshot, if there is one, if `fitness` is higher than the best
+    /// fitness so far. `prepare` copies what the solution is built from, and the solution is only
+    /// built when the snapshot is read, so the optimization doesn't wait for it.
+    fn update_snapshot<P, S>(&self, fitness: f64, prepare: P)
+    where
+        P: FnOnce() -> Option<S>,
+        S: FnOnce() -> Option<Solution> + Send + 'static,
+    {
+        let Some(ref snapshot) = self.optimizer.solution_snapshot else {
+            return;
         };
+        if fitness > self.best_fitness {
+            if let Some(build) = prepare() {
+                let order = self.optimizer.solution_order();
+                snapshot.update(fitness, move || {
+                    let mut solution = build()?;
+                    order.sort(&mut solution);
+                    Some(solution)
+                });
+            }
+        }
+    }
 
-        let mut best_result = if self.allow_mixed_stock_sizes {
-            // Optimize with all stock sizes
-            self.optimize_with_stock_pieces::<B, _>(&self.stock_pieces.clone(), &callback)
-        } else {
-            // We're not allowing mixed sizes so just give an error result
-            // here. Each stock size will be optimized separately below.
-            // Note: it's safe to assume `self.cut_pieces` isn't empty because
-            // that's checked at the beginning of this function.
-            Err(no_fit_for_cut_piece_error(&self.cut_pieces[0]))
-        };
+    fn report_fitness(&mut self, fitness: f64) {
+        if fitness > self.best_fitness {
+            self.best_fitness = fitness;
+            self.optimizer
+                .progress_event(ProgressEvent::NewBestSolution { fitness });
+        }
+    }
+}
 
-        // Optimize each stock size separately and see if any have better result than
-        // when optimizing with all stock sizes.
-        for (i, (width, length)) in size_set.iter().enumerate() {
-            let stock_pieces: Vec<StockPiece> = self
-                .stock_pieces
-                .iter()
-                .filter(|sp| sp.width == *width && sp.length == *length)
-                .cloned()
-                .collect();
+/// An optimization that runs a little at a time, from `Optimizer::start_session`.
+pub struct OptimizerSession<'a> {
+    optimization: SessionOptimization<'a>,
+}
 
-            let completed_runs = i + 1;
-            if let Ok(solution) =
-                self.optimize_with_stock_pieces::<B, _>(&stock_pieces, &|progress| {
-                    progress_callback((completed_runs as f64 + progress) / num_runs as f64);
-                })
-            {
-                match best_result {
-                    Ok(ref best_solution) => {
-                        // Use the lower-priced solution, but if the prices are the same, use the
-                        // solution with the higher fitness score.
-                        if solution.fitness < 0.0 || best_solution.fitness < 0.0 {
-                            if solution.fitness > best_solution.fitness {
-                                best_result = Ok(solution);
-                            }
-                        } else if solution.price < best_solution.price
-                            || (solution.price == best_solution.price
-                                && solution.fitness > best_solution.fitness)
-                        {
-                            best_result = Ok(solution);
-                        }
-                    }
-                    Err(_) => best_result = Ok(solution),
-                }
-            }
-        }
+enum SessionOptimization<'a> {
+    Guillotine(Optimization<'a, GuillotineBin>),
+    Nested(Optimization<'a, MaxRectsBin>),
+}
 
-        if let Ok(ref mut solution) = &mut best_result {
-            solution
-                .stock_pieces
-                .sort_by_key(|p| cmp::Reverse((p.width, p.length)));
-        };
+/// Result of `OptimizerSession::step`.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StepResult {
+    /// The optimization isn't done yet.
+    Running {
+        /// Fraction of the optimization that's done, between 0.0 and 1.0.
+        progress: f64,
+    },
+
+    /// The optimization is done, and its solution can be taken with `OptimizerSession::finish`.
+    Done,
+}
 
-        best_result
+impl OptimizerSession<'_> {
+    /// Run the optimization for about `max_time`. It always runs at least one generation of the
+    /// genetic algorithm, which can take longer than `max_time` for large jobs, and it stops at
+    /// the end of the generation that runs past `max_time`. The time is measured with
+    /// `std::time::Instant`, which panics on WebAssembly in a browser, where `step_with_clock`
+    /// or `step_generations` can be used instead.
+    pub fn step(&mut self, max_time: Duration) -> StepResult {
+        let started = Instant::now();
+        self.step_with_clock(max_time, || started.elapsed())
     }
 
-    fn optimize_with_stock_pieces<B, F>(
-        &self,
-        stock_pieces: &[StockPiece],
-        progress_callback: &F,
-    ) -> Result<Solution>
+    /// Run the optimization for about `max_time`, like `step`, with the time measured by `now`,
+    /// which returns the time since any fixed point, such as from `performance.now()` in a
+    /// browser.
+    pub fn step_with_clock<F>(&mut self, max_time: Duration, mut now: F) -> StepResult
     where
-        B: Bin + Clone + Send + Into<ResultStockPiece>,
-        F: Fn(f64),
+        F: FnMut() -> Duration,
     {
-        let cut_pieces: Vec<&CutPieceWithId> = self.cut_pieces.iter().collect();
-
-        let units: Vec<OptimizerUnit<B>> = OptimizerUnit::generate_initial_units(
-            stock_pieces,
-            cut_pieces,
-            self.cut_width,
-            self.random_seed,
-        )?;
+        let until = now() + max_time;
+        self.step_until(|_| now() >= until)
+    }
 
-        let population_size = units.len();
-        let mut result_units = Population::new(units)
-            .set_size(population_size)
-            .set_rand_seed(self.random_seed)
-            .set_breed_factor(0.5)
-            .set_survival_factor(0.6)
-            .epochs(100, progress_callback)
-            .finish();
+    /// Run up to `generations` generations of the genetic algorithm, and at least one, without
+    /// measuring the time. Starting and finishing each set of stock pieces the optimization tries
+    /// also count as a generation.
+    pub fn step_generations(&mut self, generations: u32) -> StepResult {
+        self.step_until(|steps| steps >= generations)
+    }
 
-        let best_unit = &mut result_units[0];
-        if !best_unit.unused_cut_pieces.is_empty() {
-            return Err(no_fit_for_cut_piece_error(
-                best_unit.unused_cut_pieces.iter().next().unwrap(),
-            ));
+    /// Run the optimization a generation at a time until `stop` returns true for the number of
+    /// generations that have been run.
+    fn step_until<F>(&mut self, stop: F) -> StepResult
+    where
+        F: FnMut(u32) -> bool,
+    {
+        match self.optimization {
+            SessionOptimization::Guillotine(ref mut optimization) => {
+                Self::step_optimization(optimization, stop)
+            }
+            SessionOptimization::Nested(ref mut optimization) => {
+                Self::step_optimization(optimization, stop)
+            }
         }
+    }
 
-        let fitness = best_unit.fitness();
-        let price = best_unit.bins.iter().map(|bin| bin.price()).sum();
-
-        let used_stock_pieces: Vec<ResultStockPiece> =
-            best_unit.bins.drain(..).map(Into::into).collect();
+    /// Run the rest of the optimization, if it isn't done, and return the best solution.
+    pub fn finish(self) -> Result<Solution> {
+        let result = match self.optimization {
+            SessionOptimization::Guillotine(optimization) => optimization.run(&|_| {}),
+            SessionOptimization::Nested(optimization) => optimization.run(&|_| {}),
+        };
+        result.map(|mut solutions| solutions.remove(0))
+    }
 
-        Ok(Solution {
-            fitness,
-            stock_pieces: used_stock_pieces,
-            price,
-        })
+    fn step_optimization<B, F>(optimization: &mut Optimization<B>, mut stop: F) -> StepResult
+    where
+        B: Bin + Clone + Send + Into<ResultStockPiece>,
+        F: FnMut(u32) -> bool,
+    {
+        let mut steps = 0;
+        loop {
+            optimization.step(&|_| {});
+            steps += 1;
+            if optimization.is_done() {
+                return StepResult::Done;
+            }
+            if stop(steps) {
+                return StepResult::Running {
+                    progress: optimization.progress,
+                };
+            }
+        }
     }
 }
@@ -1,25 +1,60 @@
-/// Implementation of the Maximal Rectangles Algorithms for bin packing.
-/// [A Thousand Ways to Pack the Bin](http://pds25.egloos.com/pds/201504/21/98/RectangleBinPack.pdf)
+//! Implementation of the Maximal Rectangles Algorithms for bin packing.
+//! [A Thousand Ways to Pack the Bin](http://pds25.egloos.com/pds/201504/21/98/RectangleBinPack.pdf)
+//!
+//! The heuristics in this module are the ones `Optimizer::optimize_nested` tries. Each one is a
+//! combination of a `FreeRectChoiceHeuristic` and a `RotateCutPieceHeuristic`, and its name is
+//! their names separated by a space, such as `"BottomLeftRule PreferUpright"`, which is the name
+//! used by `HeuristicProfile::Custom` and `StockPieceDiagnostics::heuristics`.
+
 use super::*;
-use crate::guillotine::RotateCutPieceHeuristic;
+pub use crate::guillotine::RotateCutPieceHeuristic;
 
 use rand::distributions::{Distribution, Standard};
 use rand::prelude::*;
-use smallvec::{smallvec, SmallVec};
+use smallvec::SmallVec;
 
 use std::borrow::Borrow;
 use std::cmp;
 
 /// Heuristics for deciding which of the free rectangles to place the demand piece in.
-#[derive(Copy, Clone)]
-pub(crate) enum FreeRectChoiceHeuristic {
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum FreeRectChoiceHeuristic {
+    /// The free rectangle with the least left over along its shorter side.
     BestShortSideFit,
+
+    /// The free rectangle with the least left over along its longer side.
     BestLongSideFit,
+
+    /// The free rectangle with the least area left over.
     BestAreaFit,
+
+    /// The position closest to the bottom, and then the left, of the stock piece.
     BottomLeftRule,
+
+    /// The position where the demand piece touches the most of the edges of the stock piece and
+    /// the other demand pieces.
     ContactPointRule,
 }
 
+impl FreeRectChoiceHeuristic {
+    /// All of the free rectangle choice heuristics.
+    pub const ALL: [FreeRectChoiceHeuristic; 5] = [
+        FreeRectChoiceHeuristic::BestShortSideFit,
+        FreeRectChoiceHeuristic::BestLongSideFit,
+        FreeRectChoiceHeuristic::BestAreaFit,
+        FreeRectChoiceHeuristic::BottomLeftRule,
+        FreeRectChoiceHeuristic::ContactPointRule,
+    ];
+}
+
+impl fmt::Display for FreeRectChoiceHeuristic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 impl Distribution<FreeRectChoiceHeuristic> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> FreeRectChoiceHeuristic {
         match rng.gen_range(0..5) {
@@ -36,73 +71,162 @@ impl Distribution<FreeRectChoiceHeuristic> for Standard {
 pub(crate) struct MaxRectsBin {
     width: usize,
     length: usize,
-    blade_width: usize,
+    blade_width: BladeWidth,
     pattern_direction: PatternDirection,
     cut_pieces: SmallVec<[UsedCutPiece; 8]>,
     free_rects: SmallVec<[Rect; 8]>,
     price: usize,
+    area_price: Option<AreaPrice>,
+    external_id: Option<usize>,
+    seams: Vec<Seam>,
+    trim: Trim,
+    min_leftover_area: Option<u64>,
+    grade: Option<char>,
+    material: Option<usize>,
+    thickness: Option<usize>,
+    source: Option<OffcutSource>,
+    fixed_pieces: Vec<FixedPiece>,
+    nominal_size: Option<(usize, usize)>,
+    must_use: bool,
+    guillotine_waste_pieces: bool,
+    // Direction of the cuts that split this bin into strips, if it's split into strips.
+    strip_direction: Option<CutDirection>,
+    // Where the part of this bin that isn't split into strips yet starts.
+    strips_end: usize,
+    diagnostics: Option<BinDiagnostics>,
+}
+
+/// Diagnostics collected while inserting cut pieces into a `MaxRectsBin`.
+#[derive(Clone, Debug, Default)]
+struct BinDiagnostics {
+    heuristics: SmallVec<[<MaxRectsBin as Bin>::Heuristic; 4]>,
+    rejected_placements: usize,
 }
 
 impl Bin for MaxRectsBin {
     type Heuristic = (FreeRectChoiceHeuristic, RotateCutPieceHeuristic);
 
-    fn new(
-        width: usize,
-        length: usize,
-        blade_width: usize,
-        pattern_direction: PatternDirection,
-        price: usize,
-    ) -> Self {
-        // We start with a single big free rectangle that spans the whole bin.
-        let free_rect = Rect {
-            x: 0,
-            y: 0,
-            width,
-            length,
-        };
-
-        let free_rects = smallvec![free_rect];
-
-        MaxRectsBin {
-            width,
-            length,
+    fn new(stock_piece: &StockPiece, options: BinOptions) -> Self {
+        // We start with a single big free rectangle that spans the whole bin, unless it's split
+        // up by seams. The maximal free rectangles around the fixed pieces are split from it
+        // below.
+        let free_rects = free_rects_between_seams(
+            stock_piece.width,
+            stock_piece.length,
+            &stock_piece.seams,
+            stock_piece.trim,
+            options.blade_width,
+        )
+        .into();
+
+        let mut bin = MaxRectsBin {
+            width: stock_piece.width,
+            length: stock_piece.length,
             free_rects,
-            blade_width,
-            pattern_direction,
+            blade_width: options.blade_width,
+            pattern_direction: stock_piece.pattern_direction,
             cut_pieces: Default::default(),
-            price,
+            price: stock_piece.price,
+            area_price: stock_piece.area_price,
+            external_id: stock_piece.external_id,
+            seams: stock_piece.seams.clone(),
+            trim: stock_piece.trim,
+            min_leftover_area: stock_piece.min_leftover_area,
+            grade: stock_piece.grade,
+            material: stock_piece.material,
+            thickness: stock_piece.thickness,
+            source: stock_piece.source,
+            fixed_pieces: stock_piece.fixed_pieces.clone(),
+            nominal_size: stock_piece.nominal_size,
+            must_use: stock_piece.must_use,
+            guillotine_waste_pieces: options.guillotine_waste_pieces,
+            strip_direction: options.nested_strip_direction,
+            strips_end: 0,
+            diagnostics: options.diagnostics.then(Default::default),
+        };
+
+        for fixed_piece in &stock_piece.fixed_pieces {
+            let rect = fixed_piece.into();
+            for i in (0..bin.free_rects.len()).rev() {
+                bin.split_free_rect(i, &rect);
+            }
+            bin.prune_free_rects();
         }
+
+        bin
     }
 
-    fn fitness(&self) -> f64 {
+    fn fitness(&self, include_optional: bool) -> f64 {
+        // Only the area inside the trim can be used, so the fitness is measured against it, with
+        // each side of the trim counted separately.
+        let (x_start, y_start) = (self.trim.left, self.trim.bottom);
+        let x_end = self.width.saturating_sub(self.trim.right);
+        let y_end = self.length.saturating_sub(self.trim.top);
+        let usable_area =
+            x_end.saturating_sub(x_start) as f64 * y_end.saturating_sub(y_start) as f64;
+
         // We don't want cut loss from the blade width to penalize the fitness
         // so we calculate the used area including the cut loss.
-        let half_blade_width = self.blade_width as f64 / 2.0;
-        let used_area = self.cut_pieces.iter().fold(0.0, |acc, p| {
+        let half_rip_width = self.blade_width.rip as f64 / 2.0;
+        let half_crosscut_width = self.blade_width.crosscut as f64 / 2.0;
+        let counted = |p: &&UsedCutPiece| include_optional || !p.optional;
+        let used_area = self.cut_pieces.iter().filter(counted).fold(0.0, |acc, p| {
             let rect = &p.rect;
             let width: f64 = rect.width as f64
-                + f64::min(rect.x as f64, half_blade_width)
+                + f64::min(rect.x.saturating_sub(x_start) as f64, half_rip_width)
                 + f64::min(
-                    self.width as f64 - rect.width as f64 - rect.x as f64,
-                    half_blade_width,
+                    x_end.saturating_sub(rect.x + rect.width) as f64,
+                    half_rip_width,
                 );
 
             let length: f64 = rect.length as f64
-                + f64::min(rect.y as f64, half_blade_width)
+                + f64::min(rect.y.saturating_sub(y_start) as f64, half_crosscut_width)
                 + f64::min(
-                    self.length as f64 - rect.length as f64 - rect.y as f64,
-                    half_blade_width,
+                    y_end.saturating_sub(rect.y + rect.length) as f64,
+                    half_crosscut_width,
                 );
 
             acc + width * length
         });
 
-        (used_area / (self.width as f64 * self.length as f64))
-            .powf(2.0 + self.free_rects.len() as f64 * 0.01)
+        // The cut pieces that aren't counted would each leave a free rectangle in their place.
+        let num_free_rects =
+            self.free_rects.len() + self.cut_pieces.iter().filter(|p| !counted(p)).count();
+        (used_area / f64::max(usable_area, 1.0)).powf(2.0 + num_free_rects as f64 * 0.01)
     }
 
-    fn price(&self) -> usize {
+    fn price(&self, include_optional: bool) -> usize {
+        let used_area = self
+            .cut_pieces
+            .iter()
+            .filter(|cut_piece| include_optional || !cut_piece.optional)
+            .map(|cut_piece| cut_piece.rect.width as u64 * cut_piece.rect.length as u64)
+            .sum();
         self.price
+            + self
+                .area_price
+                .map_or(0, |area_price| area_price.price_of(used_area))
+    }
+
+    fn area(&self) -> u64 {
+        self.width as u64 * self.length as u64
+    }
+
+    fn cut_length(&self, include_optional: bool) -> u64 {
+        let rects: Vec<Rect> = self
+            .cut_pieces
+            .iter()
+            .filter(|p| include_optional || !p.optional)
+            .map(|p| p.rect)
+            .collect();
+        cuts::nested_cut_length(
+            &rects,
+            self.width,
+            self.length,
+            &self.seams,
+            self.trim,
+            self.blade_width,
+        )
     }
 
     fn remove_cut_pieces<I>(&mut self, cut_pieces: I) -> usize
@@ -126,7 +250,11 @@ impl Bin for MaxRectsBin {
         self.cut_pieces.iter()
     }
 
-    fn possible_heuristics() -> Vec<Self::Heuristic> {
+    fn pattern_direction(&self) -> PatternDirection {
+        self.pattern_direction
+    }
+
+    fn possible_heuristics(_options: BinOptions) -> Vec<Self::Heuristic> {
         vec![
             (
                 FreeRectChoiceHeuristic::BestShortSideFit,
@@ -171,23 +299,72 @@ impl Bin for MaxRectsBin {
         ]
     }
 
+    fn heuristic_name(heuristic: &Self::Heuristic) -> String {
+        format!("{} {}", heuristic.0, heuristic.1)
+    }
+
+    fn in_named_profile(heuristic: &Self::Heuristic, profile: &HeuristicProfile) -> bool {
+        match profile {
+            HeuristicProfile::Cnc => matches!(
+                heuristic.0,
+                FreeRectChoiceHeuristic::ContactPointRule | FreeRectChoiceHeuristic::BottomLeftRule
+            ),
+            HeuristicProfile::All | HeuristicProfile::PanelSaw | HeuristicProfile::Custom(_) => {
+                true
+            }
+        }
+    }
+
     fn insert_cut_piece_with_heuristic(
         &mut self,
         cut_piece: &CutPieceWithId,
         heuristic: &Self::Heuristic,
     ) -> bool {
-        self.insert_with_heuristics(cut_piece, heuristic.0, heuristic.1)
+        if !cut_piece.allows_stock(self.external_id, self.grade, self.material, self.thickness) {
+            return false;
+        }
+
+        let used_area = self
+            .cut_pieces
+            .iter()
+            .map(|p| p.rect.width as u64 * p.rect.length as u64)
+            .sum::<u64>()
+            + fixed_area(&self.fixed_pieces);
+        if !leaves_min_leftover_area(
+            self.width,
+            self.length,
+            self.trim,
+            self.min_leftover_area,
+            used_area,
+            cut_piece,
+        ) {
+            return false;
+        }
+
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.rejected_placements += Rect::count_rejected_placements(
+                &self.free_rects,
+                self.pattern_direction,
+                cut_piece,
+            );
+        }
+
+        let inserted = self.insert_with_heuristics(cut_piece, heuristic.0, heuristic.1);
+
+        if let (true, Some(ref mut diagnostics)) = (inserted, &mut self.diagnostics) {
+            if !diagnostics.heuristics.contains(heuristic) {
+                diagnostics.heuristics.push(*heuristic);
+            }
+        }
+
+        inserted
     }
 
-    fn insert_cut_piece_random_heuristic<R>(
-        &mut self,
-        cut_piece: &CutPieceWithId,
-        rng: &mut R,
-    ) -> bool
+    fn random_heuristic<R>(&self, rng: &mut R) -> Self::Heuristic
     where
         R: Rng + ?Sized,
     {
-        self.insert_cut_piece_with_heuristic(cut_piece, &rng.gen())
+        rng.gen()
     }
 
     fn matches_stock_piece(&self, stock_piece: &StockPiece) -> bool {
@@ -195,6 +372,18 @@ impl Bin for MaxRectsBin {
             && self.length == stock_piece.length
             && self.pattern_direction == stock_piece.pattern_direction
             && self.price == stock_piece.price
+            && self.area_price == stock_piece.area_price
+            && self.external_id == stock_piece.external_id
+            && self.seams == stock_piece.seams
+            && self.trim == stock_piece.trim
+            && self.min_leftover_area == stock_piece.min_leftover_area
+            && self.grade == stock_piece.grade
+            && self.material == stock_piece.material
+            && self.thickness == stock_piece.thickness
+            && self.source == stock_piece.source
+            && self.fixed_pieces == stock_piece.fixed_pieces
+            && self.nominal_size == stock_piece.nominal_size
+            && self.must_use == stock_piece.must_use
     }
 }
 
@@ -216,6 +405,7 @@ impl MaxRectsBin {
             }
 
             self.prune_free_rects();
+            self.end_strip(&best_rect);
 
             let pattern_direction = if is_rotated {
                 cut_piece.pattern_direction.rotated()
@@ -229,6 +419,17 @@ impl MaxRectsBin {
                 can_rotate: cut_piece.can_rotate,
                 pattern_direction,
                 is_rotated,
+                tag: cut_piece.tag.clone(),
+                max_edge_distance: cut_piece.max_edge_distance,
+                stock_filter: cut_piece.stock_filter.clone(),
+                min_grade: cut_piece.min_grade,
+                edge_banding: cut_piece.edge_banding,
+                match_group: cut_piece.match_group,
+                members: cut_piece.members.clone(),
+                material: cut_piece.material,
+                thickness: cut_piece.thickness,
+                allow_pattern_mismatch: cut_piece.allow_pattern_mismatch,
+                optional: cut_piece.optional,
             });
 
             true
@@ -237,6 +438,9 @@ impl MaxRectsBin {
         }
     }
 
+    /// Finds where to place the cut piece. Ties in score are broken by the lowest `y`, then the
+    /// lowest `x`, then the preferred orientation, so the placement doesn't depend on the order
+    /// of `free_rects`.
     fn find_placement_for_cut_piece(
         &self,
         cut_piece: &CutPieceWithId,
@@ -268,32 +472,47 @@ impl MaxRectsBin {
         prefer_rotated: bool,
     ) -> Option<(Rect, bool)> {
         let mut best_rect = Rect::default();
-        let mut best_y = std::usize::MAX;
-        let mut best_x = std::usize::MAX;
+        let mut best_key = (usize::MAX, usize::MAX, usize::MAX, true);
         let mut best_fit = Fit::None;
 
         for free_rect in &self.free_rects {
-            let fit = free_rect.fit_cut_piece(self.pattern_direction, cut_piece, prefer_rotated);
+            let fit = free_rect.fit_cut_piece_in_stock(
+                self.width,
+                self.length,
+                self.pattern_direction,
+                cut_piece,
+                prefer_rotated,
+            );
             if fit.is_upright() {
                 let top_side_y = free_rect.y + cut_piece.length;
-                if top_side_y < best_y || (top_side_y == best_y && free_rect.x < best_x) {
+                let key = (
+                    top_side_y,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.width;
                     best_rect.length = cut_piece.length;
-                    best_y = top_side_y;
-                    best_x = free_rect.x;
+                    best_key = key;
                     best_fit = fit;
                 }
             } else if fit.is_rotated() {
                 let top_side_y = free_rect.y + cut_piece.width;
-                if top_side_y < best_y || (top_side_y == best_y && free_rect.x < best_x) {
+                let key = (
+                    top_side_y,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.length;
                     best_rect.length = cut_piece.width;
-                    best_y = top_side_y;
-                    best_x = free_rect.x;
+                    best_key = key;
                     best_fit = fit;
                 }
             }
@@ -312,12 +531,17 @@ impl MaxRectsBin {
         prefer_rotated: bool,
     ) -> Option<(Rect, bool)> {
         let mut best_rect = Rect::default();
-        let mut best_short_side_fit = std::usize::MAX;
-        let mut best_long_side_fit = std::usize::MAX;
+        let mut best_key = (usize::MAX, usize::MAX, usize::MAX, usize::MAX, true);
         let mut best_fit = Fit::None;
 
         for free_rect in &self.free_rects {
-            let fit = free_rect.fit_cut_piece(self.pattern_direction, cut_piece, prefer_rotated);
+            let fit = free_rect.fit_cut_piece_in_stock(
+                self.width,
+                self.length,
+                self.pattern_direction,
+                cut_piece,
+                prefer_rotated,
+            );
             if fit.is_upright() {
                 let leftover_horiz =
                     (free_rect.width as isize - cut_piece.width as isize).unsigned_abs();
@@ -326,15 +550,19 @@ impl MaxRectsBin {
                 let short_side_fit = cmp::min(leftover_horiz, leftover_vert);
                 let long_side_fit = cmp::max(leftover_horiz, leftover_vert);
 
-                if short_side_fit < best_short_side_fit
-                    || (short_side_fit == best_short_side_fit && long_side_fit < best_long_side_fit)
-                {
+                let key = (
+                    short_side_fit,
+                    long_side_fit,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.width;
                     best_rect.length = cut_piece.length;
-                    best_short_side_fit = short_side_fit;
-                    best_long_side_fit = long_side_fit;
+                    best_key = key;
                     best_fit = fit;
                 }
             } else if fit.is_rotated() {
@@ -345,15 +573,19 @@ impl MaxRectsBin {
                 let short_side_fit = cmp::min(leftover_horiz, leftover_vert);
                 let long_side_fit = cmp::max(leftover_horiz, leftover_vert);
 
-                if short_side_fit < best_short_side_fit
-                    || (short_side_fit == best_short_side_fit && long_side_fit < best_long_side_fit)
-                {
+                let key = (
+                    short_side_fit,
+                    long_side_fit,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.length;
                     best_rect.length = cut_piece.width;
-                    best_short_side_fit = short_side_fit;
-                    best_long_side_fit = long_side_fit;
+                    best_key = key;
                     best_fit = fit;
                 }
             }
@@ -372,12 +604,17 @@ impl MaxRectsBin {
         prefer_rotated: bool,
     ) -> Option<(Rect, bool)> {
         let mut best_rect = Rect::default();
-        let mut best_short_side_fit = std::usize::MAX;
-        let mut best_long_side_fit = std::usize::MAX;
+        let mut best_key = (usize::MAX, usize::MAX, usize::MAX, usize::MAX, true);
         let mut best_fit = Fit::None;
 
         for free_rect in &self.free_rects {
-            let fit = free_rect.fit_cut_piece(self.pattern_direction, cut_piece, prefer_rotated);
+            let fit = free_rect.fit_cut_piece_in_stock(
+                self.width,
+                self.length,
+                self.pattern_direction,
+                cut_piece,
+                prefer_rotated,
+            );
             if fit.is_upright() {
                 let leftover_horiz =
                     (free_rect.width as isize - cut_piece.width as isize).unsigned_abs();
@@ -386,15 +623,19 @@ impl MaxRectsBin {
                 let short_side_fit = cmp::min(leftover_horiz, leftover_vert);
                 let long_side_fit = cmp::max(leftover_horiz, leftover_vert);
 
-                if long_side_fit < best_long_side_fit
-                    || (long_side_fit == best_long_side_fit && short_side_fit < best_short_side_fit)
-                {
+                let key = (
+                    long_side_fit,
+                    short_side_fit,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.width;
                     best_rect.length = cut_piece.length;
-                    best_short_side_fit = short_side_fit;
-                    best_long_side_fit = long_side_fit;
+                    best_key = key;
                     best_fit = fit;
                 }
             } else if fit.is_rotated() {
@@ -405,15 +646,19 @@ impl MaxRectsBin {
                 let short_side_fit = cmp::min(leftover_horiz, leftover_vert);
                 let long_side_fit = cmp::max(leftover_horiz, leftover_vert);
 
-                if long_side_fit < best_long_side_fit
-                    || (long_side_fit == best_long_side_fit && short_side_fit < best_short_side_fit)
-                {
+                let key = (
+                    long_side_fit,
+                    short_side_fit,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.length;
                     best_rect.length = cut_piece.width;
-                    best_short_side_fit = short_side_fit;
-                    best_long_side_fit = long_side_fit;
+                    best_key = key;
                     best_fit = fit;
                 }
             }
@@ -432,8 +677,7 @@ impl MaxRectsBin {
         prefer_rotated: bool,
     ) -> Option<(Rect, bool)> {
         let mut best_rect = Rect::default();
-        let mut best_area_fit = std::u64::MAX;
-        let mut best_short_side_fit = std::u64::MAX;
+        let mut best_key = (u64::MAX, u64::MAX, usize::MAX, usize::MAX, true);
         let mut best_fit = Fit::None;
 
         for free_rect in &self.free_rects {
@@ -446,7 +690,13 @@ impl MaxRectsBin {
 
             let area_fit = free_rect_area - cut_piece_area;
 
-            let fit = free_rect.fit_cut_piece(self.pattern_direction, cut_piece, prefer_rotated);
+            let fit = free_rect.fit_cut_piece_in_stock(
+                self.width,
+                self.length,
+                self.pattern_direction,
+                cut_piece,
+                prefer_rotated,
+            );
             if fit.is_upright() {
                 let leftover_horiz =
                     (free_rect.width as i64 - cut_piece.width as i64).unsigned_abs();
@@ -454,15 +704,19 @@ impl MaxRectsBin {
                     (free_rect.length as i64 - cut_piece.length as i64).unsigned_abs();
                 let short_side_fit = cmp::min(leftover_horiz, leftover_vert);
 
-                if area_fit < best_area_fit
-                    || (area_fit == best_area_fit && short_side_fit < best_short_side_fit)
-                {
+                let key = (
+                    area_fit,
+                    short_side_fit,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.width;
                     best_rect.length = cut_piece.length;
-                    best_area_fit = area_fit;
-                    best_short_side_fit = short_side_fit;
+                    best_key = key;
                     best_fit = fit;
                 }
             } else if fit.is_rotated() {
@@ -472,15 +726,19 @@ impl MaxRectsBin {
                     (free_rect.length as i64 - cut_piece.width as i64).unsigned_abs();
                 let short_side_fit = cmp::min(leftover_horiz, leftover_vert);
 
-                if area_fit < best_area_fit
-                    || (area_fit == best_area_fit && short_side_fit < best_short_side_fit)
-                {
+                let key = (
+                    area_fit,
+                    short_side_fit,
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.length;
                     best_rect.length = cut_piece.width;
-                    best_area_fit = area_fit;
-                    best_short_side_fit = short_side_fit;
+                    best_key = key;
                     best_fit = fit;
                 }
             }
@@ -499,11 +757,17 @@ impl MaxRectsBin {
         prefer_rotated: bool,
     ) -> Option<(Rect, bool)> {
         let mut best_rect = Rect::default();
-        let mut best_contact_score = 0;
+        let mut best_key = (cmp::Reverse(0), usize::MAX, usize::MAX, true);
         let mut best_fit = Fit::None;
 
         for free_rect in &self.free_rects {
-            let fit = free_rect.fit_cut_piece(self.pattern_direction, cut_piece, prefer_rotated);
+            let fit = free_rect.fit_cut_piece_in_stock(
+                self.width,
+                self.length,
+                self.pattern_direction,
+                cut_piece,
+                prefer_rotated,
+            );
             if fit.is_upright() {
                 let score = self.contact_point_score(
                     free_rect.x,
@@ -511,12 +775,18 @@ impl MaxRectsBin {
                     cut_piece.width,
                     cut_piece.length,
                 );
-                if score > best_contact_score || best_fit.is_none() {
+                let key = (
+                    cmp::Reverse(score),
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key || best_fit.is_none() {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.width;
                     best_rect.length = cut_piece.length;
-                    best_contact_score = score;
+                    best_key = key;
                     best_fit = fit;
                 }
             } else if fit.is_rotated() {
@@ -526,12 +796,18 @@ impl MaxRectsBin {
                     cut_piece.length,
                     cut_piece.width,
                 );
-                if score > best_contact_score || best_fit.is_none() {
+                let key = (
+                    cmp::Reverse(score),
+                    free_rect.y,
+                    free_rect.x,
+                    fit.is_rotated() != prefer_rotated,
+                );
+                if key < best_key || best_fit.is_none() {
                     best_rect.x = free_rect.x;
                     best_rect.y = free_rect.y;
                     best_rect.width = cut_piece.length;
                     best_rect.length = cut_piece.width;
-                    best_contact_score = score;
+                    best_key = key;
                     best_fit = fit;
                 }
             }
@@ -569,35 +845,73 @@ impl MaxRectsBin {
         score
     }
 
+    /// If the bin is split into strips and the cut piece at `rect` reaches past the strips, makes
+    /// a new strip that ends just past it, with a cut across the whole bin.
+    fn end_strip(&mut self, rect: &Rect) {
+        let Some(direction) = self.strip_direction else {
+            return;
+        };
+        let (end, blade_width) = match direction {
+            CutDirection::ParallelToLength => (rect.x + rect.width, self.blade_width.rip),
+            CutDirection::ParallelToWidth => (rect.y + rect.length, self.blade_width.crosscut),
+        };
+        if end <= self.strips_end {
+            return;
+        }
+        self.strips_end = end + blade_width;
+
+        // Split the free rectangles along the cut, leaving out the kerf.
+        let mut free_rects = SmallVec::new();
+        for free_rect in self.free_rects.drain(..) {
+            let (start, size) = match direction {
+                CutDirection::ParallelToLength => (free_rect.x, free_rect.width),
+                CutDirection::ParallelToWidth => (free_rect.y, free_rect.length),
+            };
+            let parts = [
+                (start, cmp::min(start + size, end)),
+                (cmp::max(start, end + blade_width), start + size),
+            ];
+            for (part_start, part_end) in parts {
+                if part_start < part_end {
+                    free_rects.push(match direction {
+                        CutDirection::ParallelToLength => Rect {
+                            x: part_start,
+                            width: part_end - part_start,
+                            ..free_rect
+                        },
+                        CutDirection::ParallelToWidth => Rect {
+                            y: part_start,
+                            length: part_end - part_start,
+                            ..free_rect
+                        },
+                    });
+                }
+            }
+        }
+        self.free_rects = free_rects;
+    }
+
     fn split_free_rect(&mut self, free_rect_index: usize, rect: &Rect) {
         let free_rect = self.free_rects[free_rect_index];
 
         // Account for blade width.
         let rect = {
-            let x = if rect.x >= self.blade_width {
-                rect.x - self.blade_width
-            } else {
-                0
-            };
-            let y = if rect.y >= self.blade_width {
-                rect.y - self.blade_width
-            } else {
-                0
-            };
-            let mut width = rect.width + rect.x - x + self.blade_width;
-            if x + width > self.width {
-                width -= x + width - self.width;
-            }
-            let mut length = rect.length + rect.y - y + self.blade_width;
-            if y + length > self.length {
-                length -= y + length - self.length;
-            }
+            let x = rect.x.saturating_sub(self.blade_width.rip);
+            let y = rect.y.saturating_sub(self.blade_width.crosscut);
+            let x_end = cmp::min(
+                (rect.x + rect.width).saturating_add(self.blade_width.rip),
+                self.width,
+            );
+            let y_end = cmp::min(
+                (rect.y + rect.length).saturating_add(self.blade_width.crosscut),
+                self.length,
+            );
 
             Rect {
                 x,
                 y,
-                width,
-                length,
+                width: x_end.saturating_sub(x),
+                length: y_end.saturating_sub(y),
             }
         };
 
@@ -690,18 +1004,142 @@ impl MaxRectsBin {
 
 impl From<MaxRectsBin> for ResultStockPiece {
     fn from(mut bin: MaxRectsBin) -> Self {
-        bin.make_free_rects_disjoint();
-        Self {
-            width: bin.width,
-            length: bin.length,
-            pattern_direction: bin.pattern_direction,
-            cut_pieces: bin.cut_pieces.iter().map(Into::into).collect(),
-            waste_pieces: bin.free_rects.into_vec(),
-            price: bin.price,
+        let fitness = bin.fitness(true);
+        let price = bin.price(true);
+        let waste_pieces = if bin.guillotine_waste_pieces {
+            let rects: Vec<Rect> = bin
+                .cut_pieces
+                .iter()
+                .map(|cp| cp.rect)
+                .chain(bin.fixed_pieces.iter().map(Into::into))
+                .collect();
+            cuts::guillotine_waste(
+                &rects,
+                bin.width,
+                bin.length,
+                &bin.seams,
+                bin.trim,
+                bin.blade_width,
+            )
+        } else {
+            bin.make_free_rects_disjoint();
+            bin.free_rects.into_vec()
+        };
+        let stock_piece = Self::new(
+            bin.external_id,
+            bin.width,
+            bin.length,
+            bin.pattern_direction,
+            bin.cut_pieces
+                .iter()
+                .flat_map(|cut_piece| cut_piece.result_cut_pieces(bin.blade_width.max()))
+                .collect(),
+            waste_pieces,
+            price,
+        )
+        .with_fitness(fitness)
+        .with_seams(bin.seams)
+        .with_trim(bin.trim)
+        .with_grade(bin.grade)
+        .with_material(bin.material)
+        .with_thickness(bin.thickness)
+        .with_fixed_pieces(bin.fixed_pieces)
+        .with_nominal_size(bin.nominal_size)
+        .with_source(bin.source)
+        .with_waste_areas()
+        .with_maximal_free_rects(bin.blade_width)
+        .with_nested_cut_length(bin.blade_width);
+
+        match bin.diagnostics {
+            Some(diagnostics) => stock_piece.with_diagnostics(
+                diagnostics
+                    .heuristics
+                    .iter()
+                    .map(MaxRectsBin::heuristic_name)
+                    .collect(),
+                diagnostics.rejected_placements,
+            ),
+            None => stock_piece,
         }
     }
 }
 
+/// Returns the maximal free rectangles of a stock piece with the `occupied` rectangles removed,
+/// leaving room for cuts of `blade_width` around each of them.
+pub(crate) fn maximal_free_rects(
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+    occupied: &[Rect],
+) -> Vec<Rect> {
+    occupied_bin(width, length, seams, trim, blade_width, occupied)
+        .free_rects
+        .into_vec()
+}
+
+/// Returns the free space of a stock piece with the `occupied` rectangles removed, leaving room
+/// for cuts of `blade_width` around each of them, as rectangles that don't overlap each other.
+pub(crate) fn disjoint_free_rects(
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+    occupied: &[Rect],
+) -> Vec<Rect> {
+    let mut bin = occupied_bin(width, length, seams, trim, blade_width, occupied);
+    bin.make_free_rects_disjoint();
+    bin.free_rects.into_vec()
+}
+
+/// Returns a bin for a stock piece with the `occupied` rectangles removed from its free
+/// rectangles.
+fn occupied_bin(
+    width: usize,
+    length: usize,
+    seams: &[Seam],
+    trim: Trim,
+    blade_width: BladeWidth,
+    occupied: &[Rect],
+) -> MaxRectsBin {
+    let mut bin = MaxRectsBin::new(
+        &StockPiece {
+            width,
+            length,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: seams.to_vec(),
+            trim,
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        },
+        BinOptions {
+            blade_width,
+            ..Default::default()
+        },
+    );
+
+    for rect in occupied {
+        for i in (0..bin.free_rects.len()).rev() {
+            bin.split_free_rect(i, rect);
+        }
+        bin.prune_free_rects();
+    }
+
+    bin
+}
+
 /// Returns 0 if the two intervals i1 and i2 are disjoint, or the length of their overlap otherwise.
 fn common_interval_length(start1: usize, end1: usize, start2: usize, end2: usize) -> usize {
     if end1 < start2 || end2 < start1 {
@@ -725,6 +1163,17 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             CutPieceWithId {
                 id: 1,
@@ -733,6 +1182,17 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             CutPieceWithId {
                 id: 2,
@@ -741,6 +1201,17 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             CutPieceWithId {
                 id: 3,
@@ -749,12 +1220,53 @@ mod tests {
                 length: 10,
                 pattern_direction: PatternDirection::None,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
         ];
 
-        let heuristic = MaxRectsBin::possible_heuristics()[0];
+        let heuristic = MaxRectsBin::possible_heuristics(Default::default())[0];
 
-        let mut bin = MaxRectsBin::new(48, 96, 1, PatternDirection::None, 0);
+        let mut bin = MaxRectsBin::new(
+            &StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: None,
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            BinOptions {
+                blade_width: BladeWidth::uniform(1),
+                diagnostics: false,
+                worst_fit_heuristics: false,
+                smallest_y_heuristic: false,
+                guillotine_waste_pieces: false,
+                max_guillotine_stages: None,
+                first_cut_direction: None,
+                nested_strip_direction: None,
+            },
+        );
         cut_pieces.iter().for_each(|cut_piece| {
             bin.insert_cut_piece_with_heuristic(cut_piece, &heuristic);
         });
@@ -769,6 +1281,17 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 is_rotated: false,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
             UsedCutPiece {
                 id: 3,
@@ -777,6 +1300,17 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 is_rotated: false,
                 can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                members: None,
+                allow_pattern_mismatch: false,
+                optional: false,
+                material: None,
+                thickness: None,
             },
         ];
 
@@ -792,11 +1326,27 @@ mod tests {
         let bin = MaxRectsBin {
             width: 48,
             length: 96,
-            blade_width: 1,
+            blade_width: BladeWidth::uniform(1),
             pattern_direction: PatternDirection::None,
             cut_pieces: Default::default(),
             free_rects: Default::default(),
             price: 0,
+            external_id: None,
+            diagnostics: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            guillotine_waste_pieces: false,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            strip_direction: None,
+            strips_end: 0,
+            material: None,
+            thickness: None,
+            area_price: None,
+            must_use: false,
         };
 
         let stock_piece = StockPiece {
@@ -805,6 +1355,18 @@ mod tests {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(20),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         };
 
         assert!(bin.matches_stock_piece(&stock_piece));
@@ -815,11 +1377,27 @@ mod tests {
         let bin = MaxRectsBin {
             width: 48,
             length: 96,
-            blade_width: 1,
+            blade_width: BladeWidth::uniform(1),
             pattern_direction: PatternDirection::None,
             cut_pieces: Default::default(),
             free_rects: Default::default(),
             price: 0,
+            external_id: None,
+            diagnostics: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            guillotine_waste_pieces: false,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            strip_direction: None,
+            strips_end: 0,
+            material: None,
+            thickness: None,
+            area_price: None,
+            must_use: false,
         };
 
         let stock_pieces = &[
@@ -829,6 +1407,18 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 price: 0,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
             },
             StockPiece {
                 width: 48,
@@ -836,6 +1426,18 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 price: 0,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
             },
             StockPiece {
                 width: 48,
@@ -843,6 +1445,18 @@ mod tests {
                 pattern_direction: PatternDirection::ParallelToLength,
                 price: 0,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
             },
             StockPiece {
                 width: 48,
@@ -850,6 +1464,56 @@ mod tests {
                 pattern_direction: PatternDirection::None,
                 price: 10,
                 quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: Some(20),
+                external_id: Some(1),
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: Some(20),
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: true,
+                area_price: None,
             },
         ];
 
@@ -857,4 +1521,316 @@ mod tests {
             .iter()
             .for_each(|stock_piece| assert!(!bin.matches_stock_piece(stock_piece)))
     }
+
+    #[test]
+    fn maximal_free_rects_huge_blade_width() {
+        let occupied = [Rect {
+            x: 10,
+            y: 10,
+            width: 10,
+            length: 10,
+        }];
+
+        // The cuts around the occupied rect cover the whole stock piece.
+        assert!(maximal_free_rects(
+            48,
+            96,
+            &[],
+            Trim::default(),
+            BladeWidth::uniform(usize::MAX),
+            &occupied
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn placement_ties_ignore_free_rect_order() {
+        let cut_piece = CutPieceWithId {
+            id: 0,
+            external_id: None,
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            members: None,
+            allow_pattern_mismatch: false,
+            optional: false,
+            material: None,
+            thickness: None,
+        };
+        let free_rects = [
+            Rect {
+                x: 30,
+                y: 30,
+                width: 12,
+                length: 12,
+            },
+            Rect {
+                x: 30,
+                y: 0,
+                width: 12,
+                length: 12,
+            },
+            Rect {
+                x: 0,
+                y: 30,
+                width: 12,
+                length: 12,
+            },
+        ];
+
+        for rect_choice in [
+            FreeRectChoiceHeuristic::BestShortSideFit,
+            FreeRectChoiceHeuristic::BestLongSideFit,
+            FreeRectChoiceHeuristic::BestAreaFit,
+            FreeRectChoiceHeuristic::BottomLeftRule,
+            FreeRectChoiceHeuristic::ContactPointRule,
+        ] {
+            let mut bin = MaxRectsBin::new(
+                &StockPiece {
+                    width: 100,
+                    length: 100,
+                    pattern_direction: PatternDirection::None,
+                    price: 0,
+                    quantity: None,
+                    external_id: None,
+                    seams: Vec::new(),
+                    trim: Trim::default(),
+                    min_leftover_area: None,
+                    source: None,
+                    grade: None,
+                    fixed_pieces: Vec::new(),
+                    nominal_size: None,
+                    material: None,
+                    thickness: None,
+                    must_use: false,
+                    area_price: None,
+                },
+                Default::default(),
+            );
+            bin.free_rects = free_rects.iter().copied().collect();
+            let forward = bin.find_placement_for_cut_piece(&cut_piece, rect_choice, false);
+            bin.free_rects = free_rects.iter().rev().copied().collect();
+            let reversed = bin.find_placement_for_cut_piece(&cut_piece, rect_choice, false);
+
+            assert_eq!(forward, reversed);
+            if rect_choice != FreeRectChoiceHeuristic::ContactPointRule {
+                let (rect, _) = forward.unwrap();
+                assert_eq!((rect.x, rect.y), (30, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn bottom_left_ties_break_by_lowest_y() {
+        let cut_piece = CutPieceWithId {
+            id: 0,
+            external_id: None,
+            width: 10,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            members: None,
+            allow_pattern_mismatch: false,
+            optional: false,
+            material: None,
+            thickness: None,
+        };
+        // The top of the cut piece is at 20 in both, upright on the right or rotated on the left.
+        let free_rects = [
+            Rect {
+                x: 50,
+                y: 0,
+                width: 10,
+                length: 20,
+            },
+            Rect {
+                x: 0,
+                y: 10,
+                width: 20,
+                length: 10,
+            },
+        ];
+
+        let mut bin = MaxRectsBin::new(
+            &StockPiece {
+                width: 100,
+                length: 100,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: None,
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            Default::default(),
+        );
+        for prefer_rotated in [false, true] {
+            for free_rects in [free_rects, [free_rects[1], free_rects[0]]] {
+                bin.free_rects = free_rects.iter().copied().collect();
+                let (rect, rotated) = bin
+                    .find_placement_for_cut_piece(
+                        &cut_piece,
+                        FreeRectChoiceHeuristic::BottomLeftRule,
+                        prefer_rotated,
+                    )
+                    .unwrap();
+                assert_eq!((rect.x, rect.y, rotated), (50, 0, false));
+            }
+        }
+    }
+
+    #[test]
+    fn nested_strips() {
+        let cut_piece = |id, width, length| CutPieceWithId {
+            id,
+            external_id: None,
+            width,
+            length,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            members: None,
+            allow_pattern_mismatch: false,
+            optional: false,
+            material: None,
+            thickness: None,
+        };
+        let mut bin = MaxRectsBin::new(
+            &StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: None,
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            BinOptions {
+                blade_width: BladeWidth::uniform(1),
+                nested_strip_direction: Some(CutDirection::ParallelToWidth),
+                ..Default::default()
+            },
+        );
+
+        let heuristic = (
+            FreeRectChoiceHeuristic::BottomLeftRule,
+            RotateCutPieceHeuristic::PreferUpright,
+        );
+        assert!(bin.insert_cut_piece_with_heuristic(&cut_piece(0, 10, 20), &heuristic));
+        assert!(bin.insert_cut_piece_with_heuristic(&cut_piece(1, 30, 30), &heuristic));
+        assert!(bin.insert_cut_piece_with_heuristic(&cut_piece(2, 30, 10), &heuristic));
+
+        // The 30 by 30 piece doesn't fit in the strip of the first piece, so it starts a new
+        // strip, and the 30 by 10 piece fits next to the first piece.
+        let rects: Vec<Rect> = bin.cut_pieces().map(|cut_piece| cut_piece.rect).collect();
+        assert_eq!((rects[1].x, rects[1].y), (0, 21));
+        assert_eq!((rects[2].x, rects[2].y), (11, 0));
+
+        // No free rectangle crosses the cuts between the strips.
+        for free_rect in &bin.free_rects {
+            for cut in [20, 51] {
+                assert!(free_rect.y + free_rect.length <= cut || free_rect.y > cut);
+            }
+        }
+    }
+
+    #[test]
+    fn fitness_inside_trim() {
+        let cut_piece = CutPieceWithId {
+            id: 0,
+            external_id: None,
+            width: 44,
+            length: 90,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            members: None,
+            allow_pattern_mismatch: false,
+            optional: false,
+            material: None,
+            thickness: None,
+        };
+        let mut bin = MaxRectsBin::new(
+            &StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: None,
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim {
+                    left: 4,
+                    bottom: 6,
+                    ..Default::default()
+                },
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            },
+            BinOptions {
+                blade_width: BladeWidth::uniform(1),
+                ..Default::default()
+            },
+        );
+
+        // The cut piece fills the area inside the trim, which only has margins on two sides.
+        assert!(bin.insert_cut_piece_with_heuristic(
+            &cut_piece,
+            &(
+                FreeRectChoiceHeuristic::BottomLeftRule,
+                RotateCutPieceHeuristic::PreferUpright,
+            ),
+        ));
+        assert_eq!((bin.cut_pieces[0].rect.x, bin.cut_pieces[0].rect.y), (4, 6));
+        assert_eq!(bin.fitness(true), 1.0);
+    }
 }
@@ -0,0 +1,361 @@
+//! The stages of an optimization, for running them one at a time with custom steps in between.
+//!
+//! The optimize methods of `Optimizer` run these stages for each set of stock pieces they try.
+//! `Prepared::new` checks the input and prepares what the layouts share,
+//! `Prepared::initial_population` generates the initial layouts with the heuristics,
+//! `Population::evolve` evolves them with the genetic algorithm, `Population::select` picks the
+//! best one, and `Layout::build_solution` turns it into a `Solution`. Between the stages, a
+//! custom step can look at the layouts, or add the layout of a solution it improved, such as with
+//! a local search, to the population with `Population::add_solution`. `Population::evolve_until`
+//! evolves the layouts with a custom rule for when to stop instead.
+//!
+//! The stages optimize the cut pieces with all of the stock pieces of the optimizer together. The
+//! optimize methods also try each stock size on its own, unless mixed stock sizes aren't allowed,
+//! and keep the best of the solutions, which can be done by running the stages with an optimizer
+//! for each stock size.
+
+use crate::genetic::unit::Unit;
+use crate::guillotine::GuillotineBin;
+use crate::maxrects::MaxRectsBin;
+use crate::{
+    new_rng, Bin, CutPieceWithId, Error, OptimizeMode, Optimizer, OptimizerUnit, Result,
+    ResultStockPiece, Solution, UnitContext,
+};
+
+use std::mem;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub use crate::genetic::population::EpochStats;
+
+/// An optimization that's ready to generate its initial layouts, from `Prepared::new`.
+pub struct Prepared<'a> {
+    optimizer: &'a Optimizer,
+
+    // Cut pieces, only allowed to rotate if the rotation policy allows it.
+    cut_pieces: Vec<CutPieceWithId>,
+
+    // When the evolution should stop, if the optimizer has a time limit.
+    deadline: Option<Instant>,
+
+    context: Context,
+}
+
+enum Context {
+    Guillotine(Arc<UnitContext<GuillotineBin>>),
+    Nested(Arc<UnitContext<MaxRectsBin>>),
+}
+
+impl<'a> Prepared<'a> {
+    /// Prepares an optimization of the cut pieces of `optimizer` in `mode`, with its stock pieces
+    /// and options. Fails with the same errors as the optimize methods when the input can't be
+    /// optimized. The time limit of the optimizer starts now, and applies to
+    /// `Population::evolve`.
+    pub fn new(optimizer: &'a Optimizer, mode: OptimizeMode) -> Result<Self> {
+        let cut_pieces = optimizer.rotatable_cut_pieces();
+        let cut_piece_refs: Vec<&CutPieceWithId> = cut_pieces.iter().collect();
+        let context = match mode {
+            OptimizeMode::Guillotine => {
+                optimizer.check_optimizable::<GuillotineBin>(mode)?;
+                Context::Guillotine(optimizer.unit_context(
+                    mode,
+                    &optimizer.stock_pieces,
+                    &cut_piece_refs,
+                ))
+            }
+            OptimizeMode::Nested => {
+                optimizer.check_optimizable::<MaxRectsBin>(mode)?;
+                Context::Nested(optimizer.unit_context(
+                    mode,
+                    &optimizer.stock_pieces,
+                    &cut_piece_refs,
+                ))
+            }
+        };
+
+        Ok(Self {
+            optimizer,
+            cut_pieces,
+            deadline: optimizer
+                .time_limit
+                .map(|time_limit| Instant::now() + time_limit),
+            context,
+        })
+    }
+
+    /// Generates the initial population of layouts, by placing the cut pieces with each of the
+    /// heuristics, in order of size and shuffled, and with random heuristics.
+    pub fn initial_population(&self) -> Result<Population<'a>> {
+        let units = match &self.context {
+            Context::Guillotine(context) => {
+                Units::Guillotine(context.clone(), self.initial_units(context)?)
+            }
+            Context::Nested(context) => {
+                Units::Nested(context.clone(), self.initial_units(context)?)
+            }
+        };
+        Ok(Population {
+            optimizer: self.optimizer,
+            cut_pieces: self.cut_pieces.clone(),
+            deadline: self.deadline,
+            measure_elapsed: false,
+            units,
+        })
+    }
+
+    fn initial_units<B>(&self, context: &Arc<UnitContext<B>>) -> Result<Vec<OptimizerUnit<B>>>
+    where
+        B: Bin + Clone + Send,
+    {
+        let mut rng = new_rng(&self.optimizer.rng_factory, self.optimizer.random_seed());
+        let units = OptimizerUnit::generate_initial_units(
+            context,
+            self.cut_pieces.iter().collect(),
+            &mut rng,
+        )?;
+        Ok(units.into_iter().map(|(_, unit)| unit).collect())
+    }
+}
+
+/// The layouts of an optimization, from `Prepared::initial_population`.
+pub struct Population<'a> {
+    optimizer: &'a Optimizer,
+    cut_pieces: Vec<CutPieceWithId>,
+    deadline: Option<Instant>,
+    measure_elapsed: bool,
+    units: Units,
+}
+
+enum Units {
+    Guillotine(
+        Arc<UnitContext<GuillotineBin>>,
+        Vec<OptimizerUnit<GuillotineBin>>,
+    ),
+    Nested(
+        Arc<UnitContext<MaxRectsBin>>,
+        Vec<OptimizerUnit<MaxRectsBin>>,
+    ),
+}
+
+impl<'a> Population<'a> {
+    /// Returns the number of layouts in this population.
+    pub fn len(&self) -> usize {
+        match &self.units {
+            Units::Guillotine(_, units) => units.len(),
+            Units::Nested(_, units) => units.len(),
+        }
+    }
+
+    /// Returns whether this population has no layouts.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the fitness of each layout in this population, in order. After `evolve`, the
+    /// layouts are ordered from the fittest.
+    pub fn fitnesses(&self) -> Vec<f64> {
+        match &self.units {
+            Units::Guillotine(_, units) => units.iter().map(Unit::fitness).collect(),
+            Units::Nested(_, units) => units.iter().map(Unit::fitness).collect(),
+        }
+    }
+
+    /// Evolves the layouts with the genetic algorithm for up to `generations` generations, like
+    /// the optimize methods do with 100 generations. Evolution stops early when a layout can't be
+    /// improved, or at the time limit of the optimizer. The population keeps its size.
+    pub fn evolve(&mut self, generations: u32) -> &mut Self {
+        match &mut self.units {
+            Units::Guillotine(_, units) => {
+                Self::evolve_units(self.optimizer, units, generations, self.deadline)
+            }
+            Units::Nested(_, units) => {
+                Self::evolve_units(self.optimizer, units, generations, self.deadline)
+            }
+        }
+        self
+    }
+
+    fn evolve_units<B>(
+        optimizer: &Optimizer,
+        units: &mut Vec<OptimizerUnit<B>>,
+        generations: u32,
+        deadline: Option<Instant>,
+    ) where
+        B: Bin + Clone + Send,
+    {
+        if units.is_empty() {
+            return;
+        }
+        *units = optimizer
+            .evolution(mem::take(units), generations, deadline)
+            .finish();
+    }
+
+    /// Evolves the layouts with the genetic algorithm until `stop` returns true for the
+    /// statistics of a generation, which makes it the last one, such as when the fitness reaches
+    /// a target or stops improving. Like `evolve`, evolution also stops when a layout can't be
+    /// improved, or at the time limit of the optimizer. The population keeps its size.
+    pub fn evolve_until<F>(&mut self, stop: F) -> &mut Self
+    where
+        F: FnMut(&EpochStats) -> bool + Send + 'static,
+    {
+        match &mut self.units {
+            Units::Guillotine(_, units) => Self::evolve_units_until(
+                self.optimizer,
+                units,
+                stop,
+                self.deadline,
+                self.measure_elapsed,
+            ),
+            Units::Nested(_, units) => Self::evolve_units_until(
+                self.optimizer,
+                units,
+                stop,
+                self.deadline,
+                self.measure_elapsed,
+            ),
+        }
+        self
+    }
+
+    fn evolve_units_until<B, F>(
+        optimizer: &Optimizer,
+        units: &mut Vec<OptimizerUnit<B>>,
+        mut stop: F,
+        deadline: Option<Instant>,
+        measure_elapsed: bool,
+    ) where
+        B: Bin + Clone + Send,
+        F: FnMut(&EpochStats) -> bool + Send + 'static,
+    {
+        if units.is_empty() {
+            return;
+        }
+        *units = optimizer
+            .population(mem::take(units), deadline)
+            .set_measure_elapsed(measure_elapsed)
+            .epochs_until(move |stats| {
+                stop(stats) || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            })
+            .finish();
+    }
+
+    /// Sets whether `evolve_until` measures the time since evolution started for
+    /// `EpochStats::elapsed`, which is `None` otherwise. The clock isn't read unless it's
+    /// measured or the optimizer has a time limit, since it isn't available on every platform,
+    /// such as WebAssembly in a browser. Defaults to `false`.
+    pub fn set_measure_elapsed(&mut self, measure_elapsed: bool) -> &mut Self {
+        self.measure_elapsed = measure_elapsed;
+        self
+    }
+
+    /// Adds layouts that keep the layout of `solution` as much as they can, like
+    /// `Optimizer::optimize_guillotine_from` starts from, such as for a solution that a custom
+    /// step improved. There's a layout for each of the first `count` heuristics.
+    pub fn add_solution(&mut self, solution: &Solution, count: usize) -> &mut Self {
+        let cut_pieces: Vec<&CutPieceWithId> = self.cut_pieces.iter().collect();
+        let mut rng = new_rng(&self.optimizer.rng_factory, self.optimizer.random_seed());
+        match &mut self.units {
+            Units::Guillotine(context, units) => {
+                for heuristic in context.heuristics.iter().take(count) {
+                    units.push(OptimizerUnit::from_solution(
+                        context,
+                        &cut_pieces,
+                        solution,
+                        heuristic,
+                        &mut rng,
+                    ));
+                }
+            }
+            Units::Nested(context, units) => {
+                for heuristic in context.heuristics.iter().take(count) {
+                    units.push(OptimizerUnit::from_solution(
+                        context,
+                        &cut_pieces,
+                        solution,
+                        heuristic,
+                        &mut rng,
+                    ));
+                }
+            }
+        }
+        self
+    }
+
+    /// Returns the fittest layout, which is the first one if there are several. Fails with
+    /// `Error::NoFitForCutPiece` if it doesn't place every cut piece that's needed.
+    pub fn select(&self) -> Result<Layout<'a>> {
+        let unit = match &self.units {
+            Units::Guillotine(context, units) => {
+                LayoutUnit::Guillotine(Self::select_unit(context, units)?)
+            }
+            Units::Nested(context, units) => LayoutUnit::Nested(Self::select_unit(context, units)?),
+        };
+        Ok(Layout {
+            optimizer: self.optimizer,
+            unit,
+        })
+    }
+
+    fn select_unit<B>(
+        context: &UnitContext<B>,
+        units: &[OptimizerUnit<B>],
+    ) -> Result<OptimizerUnit<B>>
+    where
+        B: Bin + Clone + Send,
+    {
+        let mut best: Option<(&OptimizerUnit<B>, f64)> = None;
+        for unit in units {
+            let fitness = unit.fitness();
+            if best.is_none_or(|(_, best_fitness)| fitness > best_fitness) {
+                best = Some((unit, fitness));
+            }
+        }
+        let Some((best, _)) = best else {
+            return Err(Error::Internal("the population has no layouts".to_string()));
+        };
+
+        Optimizer::check_best_unit(context, std::slice::from_ref(best))?;
+        Ok(best.clone())
+    }
+}
+
+/// A layout of the cut pieces on stock pieces, from `Population::select`.
+#[derive(Clone)]
+pub struct Layout<'a> {
+    optimizer: &'a Optimizer,
+    unit: LayoutUnit,
+}
+
+#[derive(Clone)]
+enum LayoutUnit {
+    Guillotine(OptimizerUnit<GuillotineBin>),
+    Nested(OptimizerUnit<MaxRectsBin>),
+}
+
+impl Layout<'_> {
+    /// Returns the fitness of this layout.
+    pub fn fitness(&self) -> f64 {
+        match &self.unit {
+            LayoutUnit::Guillotine(unit) => unit.fitness(),
+            LayoutUnit::Nested(unit) => unit.fitness(),
+        }
+    }
+
+    /// Builds the solution of this layout, sorted like the solution of the optimize methods.
+    pub fn build_solution(&self) -> Result<Solution> {
+        let mut solution = match self.unit.clone() {
+            LayoutUnit::Guillotine(unit) => Self::unit_solution(unit)?,
+            LayoutUnit::Nested(unit) => Self::unit_solution(unit)?,
+        };
+        self.optimizer.sort_solution(&mut solution);
+        Ok(solution)
+    }
+
+    fn unit_solution<B>(mut unit: OptimizerUnit<B>) -> Result<Solution>
+    where
+        B: Bin + Clone + Send + Into<ResultStockPiece>,
+    {
+        Optimizer::build_solution(&mut unit)
+    }
+}
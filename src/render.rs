@@ -0,0 +1,471 @@
+//! Rendering of solutions as SVG diagrams, enabled by the `render` feature.
+//!
+//! `Solution::to_svg` draws each stock piece of a solution, one below the other, with its cut
+//! pieces labeled with their IDs and sizes, a marker on the ones that were rotated, and its waste
+//! pieces shaded. The elements have classes (`stock-piece`, `cut-piece`, `waste-piece`,
+//! `rotated`, and `label`), so their look can be changed with CSS.
+//!
+//! `Solution::render_text` draws the stock pieces as grids of characters instead, for printing
+//! in a console while debugging, or pasting into a bug report.
+
+use crate::{ResultCutPiece, ResultStockPiece, Solution};
+
+use std::cmp;
+use std::fmt::Write;
+
+// Characters that mark the cut pieces in `Solution::render_text`, in order.
+const PIECE_MARKS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Options for rendering a solution with `Solution::to_svg`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    /// Size of a unit of the stock and cut pieces in the SVG.
+    pub scale: f64,
+
+    /// Space around and between the stock pieces in the SVG.
+    pub margin: f64,
+
+    /// Size of the font of the labels in the SVG.
+    pub font_size: f64,
+
+    /// Whether to label the cut pieces with their external IDs.
+    pub show_ids: bool,
+
+    /// Whether to label the cut pieces with their width and length.
+    pub show_dimensions: bool,
+
+    /// Whether to mark the cut pieces that were rotated.
+    pub show_rotation: bool,
+
+    /// Whether to shade the waste pieces.
+    pub shade_waste: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            scale: 4.0,
+            margin: 20.0,
+            font_size: 12.0,
+            show_ids: true,
+            show_dimensions: true,
+            show_rotation: true,
+            shade_waste: true,
+        }
+    }
+}
+
+impl Solution {
+    /// Returns an SVG document with a diagram of each stock piece of this solution, labeled with
+    /// its sheet ID and size, as set up by `options`.
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let header_height = options.font_size * 1.5;
+        let width = self
+            .stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.width as f64 * options.scale)
+            .fold(0.0, f64::max)
+            + options.margin * 2.0;
+        let height = self
+            .stock_pieces
+            .iter()
+            .map(|stock_piece| {
+                header_height + stock_piece.length as f64 * options.scale + options.margin
+            })
+            .sum::<f64>()
+            + options.margin;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" "#,
+                r#"viewBox="0 0 {width} {height}">"#,
+            ),
+            width = width,
+            height = height,
+        );
+        if options.shade_waste {
+            svg.push_str(concat!(
+                r#"<defs><pattern id="waste" patternUnits="userSpaceOnUse" width="8" height="8">"#,
+                r##"<rect width="8" height="8" fill="#eeeeee"/>"##,
+                r##"<path d="M0,8 L8,0" stroke="#999999" stroke-width="1"/>"##,
+                "</pattern></defs>\n",
+            ));
+        }
+
+        let mut y = options.margin;
+        for stock_piece in &self.stock_pieces {
+            render_stock_piece(
+                &mut svg,
+                stock_piece,
+                options.margin,
+                y + header_height,
+                options,
+            );
+            y += header_height + stock_piece.length as f64 * options.scale + options.margin;
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Returns a picture of each stock piece of this solution as a grid of characters that's
+    /// `cols` characters wide, one below the other. The grid is half as many rows as it would be
+    /// in scale, since characters are about twice as tall as they're wide. Each stock piece is
+    /// labeled with its sheet ID and size, and its cut pieces are drawn with the letters and
+    /// digits in a legend below it, which gives their IDs and sizes. A cut piece too small to
+    /// cover the center of a character is drawn at the character its center is in. The rest of
+    /// the stock piece is drawn with `.`.
+    pub fn render_text(&self, cols: usize) -> String {
+        let mut text = String::new();
+        for (i, stock_piece) in self.stock_pieces.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            render_stock_piece_text(&mut text, stock_piece, cmp::max(cols, 1));
+        }
+        text
+    }
+}
+
+fn render_stock_piece(
+    svg: &mut String,
+    stock_piece: &ResultStockPiece,
+    x: f64,
+    y: f64,
+    options: &SvgOptions,
+) {
+    let scale = options.scale;
+    let _ = writeln!(
+        svg,
+        r#"<g class="stock-piece" transform="translate({x},{y})">"#
+    );
+    let _ = writeln!(
+        svg,
+        concat!(
+            r#"<text class="label" x="0" y="{}" font-family="sans-serif" font-size="{}">"#,
+            "{} ({} x {})</text>",
+        ),
+        -options.font_size * 0.5,
+        options.font_size,
+        escape(&stock_piece.sheet_id),
+        stock_piece.width,
+        stock_piece.length,
+    );
+    let _ = writeln!(
+        svg,
+        r##"<rect x="0" y="0" width="{}" height="{}" fill="#ffffff" stroke="#000000"/>"##,
+        stock_piece.width as f64 * scale,
+        stock_piece.length as f64 * scale,
+    );
+
+    if options.shade_waste {
+        for waste_piece in &stock_piece.waste_pieces {
+            let _ = writeln!(
+                svg,
+                concat!(
+                    r#"<rect class="waste-piece" x="{}" y="{}" width="{}" height="{}" "#,
+                    r#"fill="url(#waste)"/>"#,
+                ),
+                waste_piece.x as f64 * scale,
+                waste_piece.y as f64 * scale,
+                waste_piece.width as f64 * scale,
+                waste_piece.length as f64 * scale,
+            );
+        }
+    }
+
+    for cut_piece in &stock_piece.cut_pieces {
+        render_cut_piece(svg, cut_piece, options);
+    }
+
+    svg.push_str("</g>\n");
+}
+
+fn render_cut_piece(svg: &mut String, cut_piece: &ResultCutPiece, options: &SvgOptions) {
+    let scale = options.scale;
+    let x = cut_piece.x as f64 * scale;
+    let y = cut_piece.y as f64 * scale;
+    let width = cut_piece.width as f64 * scale;
+    let length = cut_piece.length as f64 * scale;
+
+    svg.push_str(r#"<g class="cut-piece">"#);
+    let _ = write!(
+        svg,
+        concat!(
+            r#"<rect x="{x}" y="{y}" width="{width}" height="{length}" "#,
+            r##"fill="#cfe2f3" stroke="#000000"/>"##,
+        ),
+        x = x,
+        y = y,
+        width = width,
+        length = length,
+    );
+
+    if options.show_rotation && cut_piece.is_rotated {
+        // A triangle in the top left corner marks a rotated cut piece.
+        let size = options.font_size.min(width / 2.0).min(length / 2.0);
+        let _ = write!(
+            svg,
+            r##"<path class="rotated" d="M{x},{y} h{size} L{x},{} Z" fill="#e06666"/>"##,
+            y + size,
+        );
+    }
+
+    let mut lines = Vec::with_capacity(2);
+    if options.show_ids {
+        if let Some(external_id) = cut_piece.external_id {
+            lines.push(external_id.to_string());
+        }
+    }
+    if options.show_dimensions {
+        lines.push(format!("{} x {}", cut_piece.width, cut_piece.length));
+    }
+    let center_x = x + width / 2.0;
+    let top = y + length / 2.0 - options.font_size * 1.2 * (lines.len() as f64 - 1.0) / 2.0;
+    for (i, line) in lines.iter().enumerate() {
+        let _ = write!(
+            svg,
+            concat!(
+                r#"<text class="label" x="{}" y="{}" font-family="sans-serif" font-size="{}" "#,
+                r#"text-anchor="middle" dominant-baseline="middle">{}</text>"#,
+            ),
+            center_x,
+            top + options.font_size * 1.2 * i as f64,
+            options.font_size,
+            line,
+        );
+    }
+
+    svg.push_str("</g>\n");
+}
+
+fn render_stock_piece_text(text: &mut String, stock_piece: &ResultStockPiece, cols: usize) {
+    let _ = writeln!(
+        text,
+        "{} ({} x {})",
+        stock_piece.sheet_id, stock_piece.width, stock_piece.length
+    );
+    if stock_piece.width == 0 || stock_piece.length == 0 {
+        return;
+    }
+
+    let col_width = stock_piece.width as f64 / cols as f64;
+    let rows = cmp::max(
+        (stock_piece.length as f64 / col_width / 2.0).round() as usize,
+        1,
+    );
+    let row_length = stock_piece.length as f64 / rows as f64;
+
+    let mut grid = vec![vec![b'.'; cols]; rows];
+    for (i, cut_piece) in stock_piece.cut_pieces.iter().enumerate() {
+        let mark = PIECE_MARKS[i % PIECE_MARKS.len()];
+        let (left, right) = cell_span(cut_piece.x, cut_piece.width, col_width, cols);
+        let (top, bottom) = cell_span(cut_piece.y, cut_piece.length, row_length, rows);
+        for row in &mut grid[top..bottom] {
+            row[left..right].fill(mark);
+        }
+    }
+
+    for row in grid {
+        text.push_str(&String::from_utf8_lossy(&row));
+        text.push('\n');
+    }
+    for (i, cut_piece) in stock_piece.cut_pieces.iter().enumerate() {
+        let _ = write!(
+            text,
+            "{}: {} x {}",
+            PIECE_MARKS[i % PIECE_MARKS.len()] as char,
+            cut_piece.width,
+            cut_piece.length
+        );
+        if let Some(external_id) = cut_piece.external_id {
+            let _ = write!(text, " (ID {})", external_id);
+        }
+        if cut_piece.is_rotated {
+            text.push_str(" rotated");
+        }
+        text.push('\n');
+    }
+}
+
+/// Returns the range of the cells of `cell_size` that have their centers between `start` and
+/// `start + size`, or the cell the center of that span is in if there are none.
+fn cell_span(start: usize, size: usize, cell_size: f64, cells: usize) -> (usize, usize) {
+    let first = ((start as f64 / cell_size - 0.5).ceil().max(0.0) as usize).min(cells);
+    let end = (((start + size) as f64 / cell_size - 0.5).ceil().max(0.0) as usize).min(cells);
+    if first < end {
+        (first, end)
+    } else {
+        let center = ((start as f64 + size as f64 / 2.0) / cell_size) as usize;
+        let center = center.min(cells - 1);
+        (center, center + 1)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CutPiece, EdgeBanding, Optimizer, PatternDirection, StockFilter, StockPiece, Trim,
+    };
+
+    fn solution() -> Solution {
+        let cut_piece = |external_id, width, length| CutPiece {
+            quantity: 1,
+            external_id: Some(external_id),
+            width,
+            length,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: EdgeBanding::NONE,
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        };
+
+        Optimizer::new()
+            .add_stock_piece(StockPiece {
+                width: 48,
+                length: 96,
+                pattern_direction: PatternDirection::None,
+                price: 0,
+                quantity: None,
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::uniform(0),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
+            })
+            .add_cut_piece(cut_piece(1, 30, 20))
+            .add_cut_piece(cut_piece(2, 90, 10))
+            .set_random_seed(1)
+            .optimize_guillotine(|_| {})
+            .unwrap()
+    }
+
+    #[test]
+    fn to_svg() {
+        let solution = solution();
+        let svg = solution.to_svg(&SvgOptions::default());
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches(r#"class="stock-piece""#).count(), 1);
+        assert_eq!(svg.matches(r#"class="cut-piece""#).count(), 2);
+        assert!(svg.contains(">1</text>"));
+        assert!(svg.contains(">2</text>"));
+        for cut_piece in &solution.stock_pieces[0].cut_pieces {
+            assert!(svg.contains(&format!(
+                ">{} x {}</text>",
+                cut_piece.width, cut_piece.length
+            )));
+        }
+        // The long cut piece only fits rotated.
+        assert_eq!(
+            svg.matches(r#"class="rotated""#).count(),
+            solution.stock_pieces[0]
+                .cut_pieces
+                .iter()
+                .filter(|cut_piece| cut_piece.is_rotated)
+                .count()
+        );
+        assert!(svg.contains(">10 x 90</text>"));
+        assert_eq!(
+            svg.matches(r#"class="waste-piece""#).count(),
+            solution.stock_pieces[0].waste_pieces.len()
+        );
+        assert!(svg.contains(&format!(
+            ">{} (48 x 96)</text>",
+            solution.stock_pieces[0].sheet_id
+        )));
+    }
+
+    #[test]
+    fn to_svg_without_labels() {
+        let svg = solution().to_svg(&SvgOptions {
+            show_ids: false,
+            show_dimensions: false,
+            show_rotation: false,
+            shade_waste: false,
+            ..Default::default()
+        });
+
+        assert_eq!(svg.matches(r#"class="cut-piece""#).count(), 2);
+        assert_eq!(svg.matches(r#"class="label""#).count(), 1);
+        assert!(!svg.contains(r#"class="rotated""#));
+        assert!(!svg.contains(r#"class="waste-piece""#));
+        assert!(!svg.contains("<pattern"));
+    }
+
+    #[test]
+    fn render_text() {
+        let solution = solution();
+        let text = solution.render_text(24);
+        let lines: Vec<&str> = text.lines().collect();
+        let stock_piece = &solution.stock_pieces[0];
+
+        // Each character is 2 wide and 4 long.
+        assert_eq!(lines.len(), 1 + 24 + 2);
+        assert_eq!(lines[0], format!("{} (48 x 96)", stock_piece.sheet_id));
+        assert!(lines[1..25].iter().all(|line| line.len() == 24));
+        for (i, cut_piece) in stock_piece.cut_pieces.iter().enumerate() {
+            let mark = (b'A' + i as u8) as char;
+            let cells = text.matches(mark).count();
+            let area = cut_piece.width * cut_piece.length;
+            assert!(cells * 8 >= area / 2 && cells * 8 <= area * 2);
+            let legend = format!(
+                "{}: {} x {} (ID {}){}",
+                mark,
+                cut_piece.width,
+                cut_piece.length,
+                cut_piece.external_id.unwrap(),
+                if cut_piece.is_rotated { " rotated" } else { "" }
+            );
+            assert!(lines[25..].contains(&legend.as_str()));
+        }
+        assert!(text.contains('.'));
+    }
+
+    #[test]
+    fn cell_spans() {
+        // The cells with centers at 1, 3, and 5.
+        assert_eq!(cell_span(1, 5, 2.0, 10), (0, 3));
+        // The cell with the center of a span that doesn't cover the center of a cell.
+        assert_eq!(cell_span(1, 1, 4.0, 10), (0, 1));
+        assert_eq!(cell_span(38, 2, 4.0, 10), (9, 10));
+    }
+
+    #[test]
+    fn escape_text() {
+        assert_eq!(escape(r#"<a & "b">"#), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
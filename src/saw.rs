@@ -0,0 +1,348 @@
+//! Export of guillotine layouts as programs for beam saws, enabled by the `saw-export` feature.
+//!
+//! A `SawProgramFormat` writes the cut trees of the stock pieces of a solution, from
+//! `ResultStockPiece::cut_tree`, in the format of a saw vendor. `Ptx` writes the CSV-based PTX
+//! format that Homag and other beam saws read, and other formats can be added by implementing
+//! `SawProgramFormat`.
+
+use crate::{CutDirection, CutNode, Rect, ResultStockPiece, Solution};
+
+use std::io;
+
+/// Writes the program for cutting the stock pieces of a solution in the format of a saw vendor.
+pub trait SawProgramFormat {
+    /// Writes the program for cutting the stock pieces of `solution` to `writer`. Fails with
+    /// `io::ErrorKind::InvalidInput` if a stock piece has no cut tree, because it can't be cut up
+    /// with guillotine cuts, or the solution doesn't include the details of its layout.
+    fn write_program(&self, solution: &Solution, writer: &mut dyn io::Write) -> io::Result<()>;
+}
+
+/// Writes programs in the PTX format, the CSV-based cutting list format that Homag and other beam
+/// saws read. The program has a `HEADER` and a `JOBS` record, a `BRDS` record for each distinct
+/// stock piece, a `PRTS` record for each distinct cut piece, and a `PTNS` record for each stock
+/// piece that's used, with the `CUTS` records of its cut tree. Fields that the optimizer has no
+/// value for are left empty.
+///
+/// The `FUNCTION` of each cut is its phase, starting at 1 for the cuts that split the area of the
+/// stock piece within its trim, and its `DIMENSION` is the size of the strip it cuts off. Cuts in
+/// the same direction that follow each other in the cut tree, such as the cuts along the seams
+/// and the cuts between the strips next to them, are in the same phase. A strip that's a cut
+/// piece has the `PART_INDEX` of the cut piece, and the other strips, including fixed pieces and
+/// waste, have a `PART_INDEX` of 0 and are followed by the cuts of the next phase that split them
+/// up. The trim isn't cut in the program.
+#[derive(Clone, Debug, Default)]
+pub struct Ptx {
+    /// Name of the job.
+    pub job_name: String,
+
+    /// Whether the dimensions are in inches, instead of millimeters.
+    pub inches: bool,
+}
+
+/// Index of the job in the programs written by `Ptx`, which only have one job.
+const JOB_INDEX: usize = 1;
+
+impl SawProgramFormat for Ptx {
+    fn write_program(&self, solution: &Solution, writer: &mut dyn io::Write) -> io::Result<()> {
+        // Stock pieces and cut pieces are identified by their external ID and size.
+        let mut boards: Vec<(Option<usize>, usize, usize, usize)> = Vec::new();
+        let mut parts: Vec<(Option<usize>, usize, usize, usize)> = Vec::new();
+        let mut patterns = Vec::with_capacity(solution.stock_pieces.len());
+        for stock_piece in &solution.stock_pieces {
+            let Some(tree) = &stock_piece.cut_tree else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "stock piece {} has no cut tree, because it can't be cut with guillotine \
+                         cuts or the solution doesn't include its layout",
+                        stock_piece.serial_number
+                    ),
+                ));
+            };
+
+            let board = count(
+                &mut boards,
+                (
+                    stock_piece.external_id,
+                    stock_piece.width,
+                    stock_piece.length,
+                ),
+            );
+            let part_indices: Vec<usize> = stock_piece
+                .cut_pieces
+                .iter()
+                .map(|cut_piece| {
+                    let (width, length) = if cut_piece.is_rotated {
+                        (cut_piece.length, cut_piece.width)
+                    } else {
+                        (cut_piece.width, cut_piece.length)
+                    };
+                    count(&mut parts, (cut_piece.external_id, width, length))
+                })
+                .collect();
+            patterns.push((board, stock_piece, tree, part_indices));
+        }
+
+        writeln!(
+            writer,
+            "HEADER,1.07,{},{}",
+            field(&self.job_name),
+            if self.inches { 1 } else { 0 }
+        )?;
+        writeln!(writer, "JOBS,{},{}", JOB_INDEX, field(&self.job_name))?;
+        for (i, &(external_id, width, length, quantity)) in boards.iter().enumerate() {
+            writeln!(
+                writer,
+                "BRDS,{},{},{},,{},{},,{}",
+                JOB_INDEX,
+                i + 1,
+                code(external_id),
+                length,
+                width,
+                quantity
+            )?;
+        }
+        for (i, &(external_id, width, length, quantity)) in parts.iter().enumerate() {
+            writeln!(
+                writer,
+                "PRTS,{},{},{},,{},{},{}",
+                JOB_INDEX,
+                i + 1,
+                code(external_id),
+                length,
+                width,
+                quantity
+            )?;
+        }
+        for (i, &(board, stock_piece, tree, ref part_indices)) in patterns.iter().enumerate() {
+            let pattern = i + 1;
+            writeln!(writer, "PTNS,{},{},{},0,1", JOB_INDEX, pattern, board)?;
+
+            let mut cuts = PtxCuts {
+                writer: &mut *writer,
+                stock_piece,
+                pattern,
+                part_indices,
+                cut_index: 0,
+            };
+            match tree {
+                CutNode::Cut { direction, .. } => {
+                    for strip in strips(tree, *direction) {
+                        cuts.write(strip, *direction, 1)?;
+                    }
+                }
+                // The whole area within the trim is a cut piece, a fixed piece, or waste.
+                _ => cuts.write(tree, CutDirection::ParallelToLength, 1)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the `CUTS` records of the cut tree of a pattern.
+struct PtxCuts<'a> {
+    writer: &'a mut dyn io::Write,
+    stock_piece: &'a ResultStockPiece,
+    pattern: usize,
+    part_indices: &'a [usize],
+    cut_index: usize,
+}
+
+impl PtxCuts<'_> {
+    /// Writes the cut that cuts off `strip` in `direction`, then the cuts that split it up.
+    fn write(&mut self, strip: &CutNode, direction: CutDirection, phase: usize) -> io::Result<()> {
+        let extent = extent(strip, self.stock_piece);
+        let dimension = match direction {
+            CutDirection::ParallelToLength => extent.width,
+            CutDirection::ParallelToWidth => extent.length,
+        };
+        let (part, quantity) = match strip {
+            CutNode::CutPiece(i) => (self.part_indices[*i], 1),
+            _ => (0, 0),
+        };
+
+        self.cut_index += 1;
+        writeln!(
+            self.writer,
+            "CUTS,{},{},{},{},{},{},1,{},{}",
+            JOB_INDEX,
+            self.pattern,
+            self.cut_index,
+            self.cut_index,
+            phase,
+            dimension,
+            part,
+            quantity
+        )?;
+
+        if let CutNode::Cut { direction, .. } = strip {
+            for child in strips(strip, *direction) {
+                self.write(child, *direction, phase + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the strips that the cuts in `direction` at the top of `node` split it into, in order.
+fn strips(node: &CutNode, direction: CutDirection) -> Vec<&CutNode> {
+    match node {
+        CutNode::Cut {
+            direction: cut_direction,
+            first,
+            second,
+            ..
+        } if *cut_direction == direction => {
+            let mut first = strips(first, direction);
+            first.extend(strips(second, direction));
+            first
+        }
+        _ => vec![node],
+    }
+}
+
+/// Returns the region of `stock_piece` that `node` covers, which is the bounding box of its cut
+/// pieces, fixed pieces, and waste.
+fn extent(node: &CutNode, stock_piece: &ResultStockPiece) -> Rect {
+    match node {
+        CutNode::Cut { first, second, .. } => {
+            let first = extent(first, stock_piece);
+            let second = extent(second, stock_piece);
+            let x = first.x.min(second.x);
+            let y = first.y.min(second.y);
+            Rect {
+                x,
+                y,
+                width: (first.x + first.width).max(second.x + second.width) - x,
+                length: (first.y + first.length).max(second.y + second.length) - y,
+            }
+        }
+        CutNode::CutPiece(i) => (&stock_piece.cut_pieces[*i]).into(),
+        CutNode::FixedPiece(i) => (&stock_piece.fixed_pieces[*i]).into(),
+        CutNode::Waste(waste_piece) => waste_piece.into(),
+    }
+}
+
+/// Counts one more of the item with the external ID and size in `items`, and returns its index,
+/// starting at 1.
+fn count(
+    items: &mut Vec<(Option<usize>, usize, usize, usize)>,
+    (external_id, width, length): (Option<usize>, usize, usize),
+) -> usize {
+    match items
+        .iter()
+        .position(|item| (item.0, item.1, item.2) == (external_id, width, length))
+    {
+        Some(i) => {
+            items[i].3 += 1;
+            i + 1
+        }
+        None => {
+            items.push((external_id, width, length, 1));
+            items.len()
+        }
+    }
+}
+
+/// Returns the code of a board or part, which is its external ID.
+fn code(external_id: Option<usize>) -> String {
+    external_id.map(|id| id.to_string()).unwrap_or_default()
+}
+
+/// Returns the text without the characters that would end a field or record.
+fn field(text: &str) -> String {
+    text.replace([',', '\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{result_solution, result_stock_piece};
+    use crate::{BladeWidth, Seam, Trim};
+
+    fn stock_piece(cut_pieces: &[(usize, usize, usize, usize)]) -> ResultStockPiece {
+        let mut stock_piece = result_stock_piece(Some(7), 48, 96, cut_pieces);
+        stock_piece.build_cut_tree(BladeWidth::uniform(1));
+        stock_piece
+    }
+
+    #[test]
+    fn ptx_program() {
+        // A strip along the length that's a cut piece, and one that's cut into two cut pieces
+        // and the waste at its end.
+        let stock_piece = stock_piece(&[(21, 41, 27, 40), (0, 0, 20, 96), (21, 0, 27, 40)]);
+        let solution = result_solution(vec![stock_piece]);
+        let ptx = Ptx {
+            job_name: "Kitchen, upper".to_string(),
+            inches: false,
+        };
+
+        let mut program = Vec::new();
+        ptx.write_program(&solution, &mut program).unwrap();
+        assert_eq!(
+            String::from_utf8(program).unwrap(),
+            "HEADER,1.07,Kitchen  upper,0\n\
+             JOBS,1,Kitchen  upper\n\
+             BRDS,1,1,7,,96,48,,1\n\
+             PRTS,1,1,0,,40,27,1\n\
+             PRTS,1,2,1,,96,20,1\n\
+             PRTS,1,3,2,,40,27,1\n\
+             PTNS,1,1,1,0,1\n\
+             CUTS,1,1,1,1,1,20,1,2,1\n\
+             CUTS,1,1,2,2,1,27,1,0,0\n\
+             CUTS,1,1,3,3,2,40,1,3,1\n\
+             CUTS,1,1,4,4,2,40,1,1,1\n\
+             CUTS,1,1,5,5,2,14,1,0,0\n"
+        );
+    }
+
+    #[test]
+    fn ptx_program_not_guillotine() {
+        // A pinwheel layout can't be separated with guillotine cuts.
+        let stock_piece = stock_piece(&[
+            (0, 0, 30, 10),
+            (30, 0, 10, 30),
+            (10, 30, 30, 10),
+            (0, 10, 10, 30),
+        ]);
+        assert_eq!(stock_piece.cut_tree, None);
+        let solution = result_solution(vec![stock_piece]);
+
+        let error = Ptx::default()
+            .write_program(&solution, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn ptx_program_seams_and_trim() {
+        // The cut along the seam and the cut between the strips next to it are in the first
+        // phase, within the trim.
+        let mut stock_piece = stock_piece(&[(2, 2, 20, 92), (23, 2, 10, 40)]);
+        stock_piece.seams = vec![Seam::ParallelToLength(22)];
+        stock_piece.trim = Trim::uniform(2);
+        stock_piece.build_cut_tree(BladeWidth::uniform(1));
+        let solution = result_solution(vec![stock_piece]);
+
+        let mut program = Vec::new();
+        Ptx::default()
+            .write_program(&solution, &mut program)
+            .unwrap();
+        let cuts: Vec<String> = String::from_utf8(program)
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with("CUTS"))
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            cuts,
+            [
+                "CUTS,1,1,1,1,1,20,1,1,1",
+                "CUTS,1,1,2,2,1,10,1,0,0",
+                "CUTS,1,1,3,3,2,40,1,2,1",
+                "CUTS,1,1,4,4,2,51,1,0,0",
+                "CUTS,1,1,5,5,1,12,1,0,0",
+            ]
+        );
+    }
+}
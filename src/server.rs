@@ -0,0 +1,252 @@
+//! Handlers for running the optimizer as an HTTP service with [axum], enabled by the `server`
+//! feature. The request bodies are a JSON `Problem`, and the responses are a JSON `Solution`, in
+//! the same form as with the `serialize` feature. The routes are versioned, so the JSON can change
+//! in later versions of the API without breaking clients of this one.
+//!
+//! The optimizer runs on the blocking thread pool of the tokio runtime. It's cancelled when the
+//! client disconnects, so an abandoned request doesn't keep optimizing.
+
+use crate::{Error, InputError, OptimizeMode, Problem, Result, Solution};
+
+use axum::extract::Json;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::Stream;
+
+use std::convert::Infallible;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Version of the API served by `router`, which is the first segment of its routes.
+pub const API_VERSION: u32 = 1;
+
+/// Returns a router with the handlers in this module:
+///
+/// * `POST /v1/optimize/guillotine`: `optimize_guillotine`
+/// * `POST /v1/optimize/nested`: `optimize_nested`
+/// * `POST /v1/optimize/guillotine/events`: `optimize_guillotine_events`
+/// * `POST /v1/optimize/nested/events`: `optimize_nested_events`
+pub fn router() -> Router {
+    Router::new()
+        .route("/v1/optimize/guillotine", post(optimize_guillotine))
+        .route("/v1/optimize/nested", post(optimize_nested))
+        .route(
+            "/v1/optimize/guillotine/events",
+            post(optimize_guillotine_events),
+        )
+        .route("/v1/optimize/nested/events", post(optimize_nested_events))
+}
+
+/// Optimizes the problem with guillotine cuts, and responds with the solution. See
+/// `Optimizer::optimize_guillotine`.
+pub async fn optimize_guillotine(Json(problem): Json<Problem>) -> Result<Json<Solution>> {
+    optimize(problem, OptimizeMode::Guillotine).await.map(Json)
+}
+
+/// Optimizes the problem without the requirement of guillotine cuts, and responds with the
+/// solution. See `Optimizer::optimize_nested`.
+pub async fn optimize_nested(Json(problem): Json<Problem>) -> Result<Json<Solution>> {
+    optimize(problem, OptimizeMode::Nested).await.map(Json)
+}
+
+/// Optimizes the problem with guillotine cuts, and responds with a stream of server-sent events.
+/// See `events` for the events that are sent.
+pub async fn optimize_guillotine_events(
+    Json(problem): Json<Problem>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    events(problem, OptimizeMode::Guillotine)
+}
+
+/// Optimizes the problem without the requirement of guillotine cuts, and responds with a stream
+/// of server-sent events. See `events` for the events that are sent.
+pub async fn optimize_nested_events(
+    Json(problem): Json<Problem>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    events(problem, OptimizeMode::Nested)
+}
+
+/// Runs the optimization, and returns its solution. The optimization is cancelled if the returned
+/// future is dropped before it's done.
+async fn optimize(problem: Problem, mode: OptimizeMode) -> Result<Solution> {
+    // Cancels the optimization when it's dropped, such as when the client disconnects and axum
+    // drops the future of the handler.
+    struct CancelOnDrop(Arc<AtomicBool>);
+
+    impl Drop for CancelOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _guard = CancelOnDrop(cancelled.clone());
+    run_blocking(problem, mode, move |_| {
+        if cancelled.load(Ordering::Relaxed) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })
+    .await
+}
+
+/// Runs the optimization, and returns a stream of server-sent events for it:
+///
+/// * `progress`: The fraction of the optimization that's done, between 0.0 and 1.0.
+/// * `solution`: The JSON solution, once the optimization is done.
+/// * `error`: The JSON error, if the optimization failed.
+///
+/// The last event is always either `solution` or `error`. The optimization is cancelled if the
+/// stream is dropped before it's done.
+fn events(
+    problem: Problem,
+    mode: OptimizeMode,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    let progress_sender = sender.clone();
+    tokio::spawn(async move {
+        let result = run_blocking(problem, mode, move |progress| {
+            let event = Event::default()
+                .event("progress")
+                .data(progress.to_string());
+            match progress_sender.send(Ok(event)) {
+                Ok(()) => ControlFlow::Continue(()),
+                // The client disconnected.
+                Err(_) => ControlFlow::Break(()),
+            }
+        })
+        .await;
+
+        let event = match result {
+            Ok(solution) => Event::default()
+                .event("solution")
+                .data(json!(solution).to_string()),
+            Err(error) => Event::default()
+                .event("error")
+                .data(error_body(&error).to_string()),
+        };
+        let _ = sender.send(Ok(event));
+    });
+
+    Sse::new(UnboundedReceiverStream::new(receiver)).keep_alive(KeepAlive::default())
+}
+
+/// Runs the optimization on the blocking thread pool.
+async fn run_blocking<F>(
+    problem: Problem,
+    mode: OptimizeMode,
+    progress_callback: F,
+) -> Result<Solution>
+where
+    F: Fn(f64) -> ControlFlow<()> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || match mode {
+        OptimizeMode::Guillotine => problem.optimize_guillotine(progress_callback),
+        OptimizeMode::Nested => problem.optimize_nested(progress_callback),
+    })
+    .await
+    .unwrap_or_else(|error| Err(Error::Internal(format!("optimizer task failed: {}", error))))
+}
+
+/// Returns the JSON body of the error response, which has the name of the error and the details
+/// needed to show it to the user.
+fn error_body(error: &Error) -> serde_json::Value {
+    match error {
+        Error::NoFitForCutPiece(cut_piece, summary) => json!({
+            "error": "noFitForCutPiece",
+            "cutPiece": cut_piece,
+            "placed": summary.placed,
+            "quantity": summary.quantity,
+            "stockExhausted": summary.stock_exhausted,
+        }),
+        Error::Internal(message) => json!({
+            "error": "internal",
+            "message": message,
+        }),
+        Error::DuplicateCutPiece(cut_piece) => json!({
+            "error": "duplicateCutPiece",
+            "cutPiece": cut_piece,
+        }),
+        Error::EmptyHeuristicProfile(mode) => json!({
+            "error": "emptyHeuristicProfile",
+            "mode": mode,
+        }),
+        Error::CutWidthTooLarge(cut_width) => json!({
+            "error": "cutWidthTooLarge",
+            "cutWidth": cut_width,
+        }),
+        Error::Cancelled => json!({ "error": "cancelled" }),
+        Error::TooManyCutPieces(count) => json!({
+            "error": "tooManyCutPieces",
+            "count": count,
+        }),
+        Error::NoStockAvailable(zero_quantity) => json!({
+            "error": "noStockAvailable",
+            "zeroQuantity": zero_quantity,
+        }),
+        Error::InvalidInput(errors) => json!({
+            "error": "invalidInput",
+            "problems": errors.iter().map(input_error_body).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+/// Returns the JSON for a problem with the inputs, in the form of `error_body`.
+fn input_error_body(error: &InputError) -> serde_json::Value {
+    match error {
+        InputError::ZeroSizeStockPiece(stock_piece) => json!({
+            "error": "zeroSizeStockPiece",
+            "stockPiece": stock_piece,
+        }),
+        InputError::ZeroSizeCutPiece(cut_piece) => json!({
+            "error": "zeroSizeCutPiece",
+            "cutPiece": cut_piece,
+        }),
+        InputError::CutWidthTooLarge(cut_width) => json!({
+            "error": "cutWidthTooLarge",
+            "cutWidth": cut_width,
+        }),
+        InputError::NoStockForCutPiece(cut_piece) => json!({
+            "error": "noStockForCutPiece",
+            "cutPiece": cut_piece,
+        }),
+        InputError::TooManyCutPieces(count) => json!({
+            "error": "tooManyCutPieces",
+            "count": count,
+        }),
+        InputError::ZeroQuantityStockPiece(stock_piece) => json!({
+            "error": "zeroQuantityStockPiece",
+            "stockPiece": stock_piece,
+        }),
+        InputError::MismatchedMatchGroup(id) => json!({
+            "error": "mismatchedMatchGroup",
+            "matchGroup": id,
+        }),
+    }
+}
+
+/// Responds with the JSON body from `error_body`. Errors caused by the problem are
+/// `422 Unprocessable Entity`, and bugs in the optimizer are `500 Internal Server Error`.
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Error::NoFitForCutPiece(..)
+            | Error::EmptyHeuristicProfile(_)
+            | Error::CutWidthTooLarge(_)
+            | Error::InvalidInput(_)
+            | Error::TooManyCutPieces(_)
+            | Error::NoStockAvailable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Internal(_) | Error::DuplicateCutPiece(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Cancelled => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (status, Json(error_body(&self))).into_response()
+    }
+}
@@ -0,0 +1,342 @@
+//! A corpus of cases that have been hard for the optimizer, enabled by the `testing` feature, for
+//! checking that an `Optimizer` configured by other code still solves them well enough. The cases
+//! are plain `Problem`s, with the most stock pieces a solution may use to pass.
+//!
+//! ```no_run
+//! // In the tests of a crate that wraps the optimizer.
+//! cut_optimizer_2d::testing::assert_corpus(|optimizer| {
+//!     optimizer.set_random_seed(1);
+//! });
+//! ```
+
+use crate::{
+    CutPiece, Error, OptimizeMode, Optimizer, PatternDirection, Problem, Solution, StockPiece,
+};
+
+use std::fmt;
+
+/// A case of the corpus.
+#[derive(Clone, Debug)]
+pub struct CorpusCase {
+    /// Name of the case, which tells where it came from.
+    pub name: &'static str,
+
+    /// The kind of cuts to optimize the case with.
+    pub mode: OptimizeMode,
+
+    /// The inputs and options of the case.
+    pub problem: Problem,
+
+    /// Most stock pieces that a solution can use to pass.
+    pub max_stock_pieces: usize,
+}
+
+impl CorpusCase {
+    /// Returns the total quantity of the cut pieces of the case.
+    pub fn num_cut_pieces(&self) -> usize {
+        self.problem.cut_pieces.iter().map(|p| p.quantity).sum()
+    }
+
+    /// Returns an optimizer for the case, configured by `configure` after the case's inputs and
+    /// options are set.
+    pub fn optimizer<F>(&self, configure: F) -> Optimizer
+    where
+        F: FnOnce(&mut Optimizer),
+    {
+        let mut optimizer = Optimizer::from_problem(self.problem.clone());
+        configure(&mut optimizer);
+        optimizer
+    }
+
+    /// Optimizes the case with the optimizer configured by `configure`, and checks the solution.
+    pub fn run<F>(&self, configure: F) -> CaseReport
+    where
+        F: FnOnce(&mut Optimizer),
+    {
+        let optimizer = self.optimizer(configure);
+        let result = match self.mode {
+            OptimizeMode::Guillotine => optimizer.optimize_guillotine(|_| {}),
+            OptimizeMode::Nested => optimizer.optimize_nested(|_| {}),
+        };
+
+        let (solution, failure) = match result {
+            Ok(solution) => {
+                let failure = self.check(&solution).err();
+                (Some(solution), failure)
+            }
+            Err(error) => (None, Some(CaseFailure::Error(error))),
+        };
+
+        CaseReport {
+            name: self.name,
+            mode: self.mode,
+            solution,
+            failure,
+        }
+    }
+
+    /// Checks that the solution places every cut piece of the case inside its stock pieces
+    /// without overlapping, and doesn't use too many stock pieces.
+    pub fn check(&self, solution: &Solution) -> Result<(), CaseFailure> {
+        let expected = self.num_cut_pieces();
+        let placed = solution
+            .stock_pieces
+            .iter()
+            .map(|sp| sp.cut_pieces.len())
+            .sum();
+        if placed != expected {
+            return Err(CaseFailure::MissingCutPieces { placed, expected });
+        }
+
+        for (i, stock_piece) in solution.stock_pieces.iter().enumerate() {
+            let cut_pieces = &stock_piece.cut_pieces;
+            let outside = cut_pieces.iter().any(|cp| {
+                cp.x + cp.width > stock_piece.width || cp.y + cp.length > stock_piece.length
+            });
+            let overlapping = cut_pieces.iter().enumerate().any(|(j, a)| {
+                cut_pieces[j + 1..].iter().any(|b| {
+                    a.x < b.x + b.width
+                        && b.x < a.x + a.width
+                        && a.y < b.y + b.length
+                        && b.y < a.y + a.length
+                })
+            });
+            if outside || overlapping {
+                return Err(CaseFailure::InvalidLayout { stock_piece: i });
+            }
+        }
+
+        let used = solution.stock_pieces.len();
+        if used > self.max_stock_pieces {
+            return Err(CaseFailure::TooManyStockPieces {
+                used,
+                max: self.max_stock_pieces,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Why a case of the corpus failed.
+#[derive(Debug)]
+pub enum CaseFailure {
+    /// The optimizer returned an error instead of a solution.
+    Error(Error),
+
+    /// The solution didn't place every cut piece.
+    MissingCutPieces {
+        /// Number of cut pieces in the solution.
+        placed: usize,
+
+        /// Total quantity of the cut pieces of the case.
+        expected: usize,
+    },
+
+    /// A cut piece of the stock piece at this index of the solution is outside the stock piece,
+    /// or overlaps another cut piece.
+    InvalidLayout {
+        /// Index of the stock piece in the solution.
+        stock_piece: usize,
+    },
+
+    /// The solution used more stock pieces than the case allows.
+    TooManyStockPieces {
+        /// Number of stock pieces in the solution.
+        used: usize,
+
+        /// Most stock pieces the case allows.
+        max: usize,
+    },
+}
+
+impl fmt::Display for CaseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaseFailure::Error(error) => write!(f, "optimizer returned an error: {error:?}"),
+            CaseFailure::MissingCutPieces { placed, expected } => {
+                write!(f, "placed {placed} of {expected} cut pieces")
+            }
+            CaseFailure::InvalidLayout { stock_piece } => {
+                write!(f, "invalid layout on stock piece {stock_piece}")
+            }
+            CaseFailure::TooManyStockPieces { used, max } => {
+                write!(f, "used {used} stock pieces, but at most {max} are allowed")
+            }
+        }
+    }
+}
+
+/// Result of running a case of the corpus.
+#[derive(Debug)]
+pub struct CaseReport {
+    /// Name of the case.
+    pub name: &'static str,
+
+    /// The kind of cuts the case was optimized with.
+    pub mode: OptimizeMode,
+
+    /// The solution, if the optimizer found one.
+    pub solution: Option<Solution>,
+
+    /// Why the case failed, or `None` if it passed.
+    pub failure: Option<CaseFailure>,
+}
+
+impl CaseReport {
+    /// Returns whether the case passed.
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Returns the cases of the corpus.
+pub fn corpus() -> Vec<CorpusCase> {
+    let sheet = |width, length, pattern_direction, quantity| StockPiece {
+        width,
+        length,
+        pattern_direction,
+        price: 0,
+        quantity,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Default::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let cut_piece = |width, length, pattern_direction, quantity| CutPiece {
+        quantity,
+        external_id: Some(1),
+        width,
+        length,
+        can_rotate: false,
+        pattern_direction,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: Default::default(),
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+    let problem = |stock_piece, cut_pieces, cut_width| Problem {
+        stock_pieces: vec![stock_piece],
+        cut_pieces,
+        cut_width,
+        random_seed: 1,
+        ..Default::default()
+    };
+
+    let plywood = |quantity| {
+        sheet(
+            1220,
+            2440,
+            PatternDirection::ParallelToLength,
+            Some(quantity),
+        )
+    };
+    let panel = sheet(48, 96, PatternDirection::None, None);
+
+    let mut cases = Vec::new();
+    for mode in [OptimizeMode::Guillotine, OptimizeMode::Nested] {
+        cases.extend([
+            // From GitHub issue 12.
+            CorpusCase {
+                name: "pighetti_github_issue_12",
+                mode,
+                problem: problem(
+                    plywood(1),
+                    vec![
+                        cut_piece(150, 775, PatternDirection::ParallelToLength, 12),
+                        cut_piece(100, 450, PatternDirection::ParallelToLength, 25),
+                    ],
+                    2,
+                ),
+                max_stock_pieces: 1,
+            },
+            // From GitHub issue 16.
+            CorpusCase {
+                name: "pighetti_github_issue_16",
+                mode,
+                problem: problem(
+                    plywood(2),
+                    vec![cut_piece(465, 814, PatternDirection::ParallelToLength, 6)],
+                    2,
+                ),
+                max_stock_pieces: 2,
+            },
+            CorpusCase {
+                name: "32_cut_pieces_on_1_stock_piece",
+                mode,
+                problem: problem(
+                    panel.clone(),
+                    vec![cut_piece(10, 10, PatternDirection::None, 32)],
+                    1,
+                ),
+                max_stock_pieces: 1,
+            },
+            // The cut pieces only fit on one stock piece because there's no cut width.
+            CorpusCase {
+                name: "32_cut_pieces_zero_cut_width",
+                mode,
+                problem: problem(
+                    panel.clone(),
+                    vec![cut_piece(12, 12, PatternDirection::None, 32)],
+                    0,
+                ),
+                max_stock_pieces: 1,
+            },
+            CorpusCase {
+                name: "64_cut_pieces_on_2_stock_pieces",
+                mode,
+                problem: problem(
+                    panel.clone(),
+                    vec![cut_piece(10, 10, PatternDirection::None, 64)],
+                    1,
+                ),
+                max_stock_pieces: 2,
+            },
+        ]);
+    }
+    cases
+}
+
+/// Runs every case of the corpus with the optimizer configured by `configure`, after the case's
+/// inputs and options are set.
+pub fn run_corpus<F>(configure: F) -> Vec<CaseReport>
+where
+    F: Fn(&mut Optimizer),
+{
+    corpus().iter().map(|case| case.run(&configure)).collect()
+}
+
+/// Runs every case of the corpus like `run_corpus`, and panics with the failures if any case
+/// fails.
+pub fn assert_corpus<F>(configure: F)
+where
+    F: Fn(&mut Optimizer),
+{
+    let failures: Vec<String> = run_corpus(configure)
+        .into_iter()
+        .filter_map(|report| {
+            report
+                .failure
+                .map(|failure| format!("{} ({:?}): {}", report.name, report.mode, failure))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "corpus cases failed:\n{}",
+        failures.join("\n")
+    );
+}
@@ -7,6 +7,18 @@ static STOCK_PIECES: &[StockPiece] = &[
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::uniform(0),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     },
     StockPiece {
         width: 48,
@@ -14,6 +26,18 @@ static STOCK_PIECES: &[StockPiece] = &[
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::uniform(0),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     },
 ];
 
@@ -25,6 +49,16 @@ static CUT_PIECES: &[CutPiece] = &[
         length: 30,
         pattern_direction: PatternDirection::None,
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: EdgeBanding::NONE,
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     },
     CutPiece {
         quantity: 1,
@@ -33,6 +67,16 @@ static CUT_PIECES: &[CutPiece] = &[
         length: 30,
         pattern_direction: PatternDirection::None,
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: EdgeBanding::NONE,
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     },
     CutPiece {
         quantity: 1,
@@ -41,6 +85,16 @@ static CUT_PIECES: &[CutPiece] = &[
         length: 30,
         pattern_direction: PatternDirection::None,
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: EdgeBanding::NONE,
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     },
     CutPiece {
         quantity: 1,
@@ -49,6 +103,16 @@ static CUT_PIECES: &[CutPiece] = &[
         length: 30,
         pattern_direction: PatternDirection::None,
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: EdgeBanding::NONE,
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     },
 ];
 
@@ -67,8 +131,15 @@ fn sanity_check_solution(solution: &Solution, num_cut_pieces: usize) {
     );
 
     for stock_piece in stock_pieces {
+        for waste_piece in &stock_piece.waste_pieces {
+            assert_eq!(stock_piece.pattern_direction, waste_piece.pattern_direction);
+        }
+
         for cut_piece in &stock_piece.cut_pieces {
-            assert_eq!(stock_piece.pattern_direction, cut_piece.pattern_direction);
+            assert_eq!(
+                stock_piece.pattern_direction == cut_piece.pattern_direction,
+                !cut_piece.pattern_mismatch
+            );
             let stock_piece_area = stock_piece.width * stock_piece.length;
             let cut_piece_area = stock_piece
                 .cut_pieces
@@ -89,9 +160,21 @@ fn sanity_check_solution(solution: &Solution, num_cut_pieces: usize) {
             .cut_pieces
             .iter()
             .map(|cp| cp.into())
-            .chain(stock_piece.waste_pieces.iter().cloned())
+            .chain(stock_piece.waste_pieces.iter().map(|wp| wp.into()))
             .collect();
 
+        // Assert that the maximal free rects don't overlap any cut pieces.
+        for free_rect in &stock_piece.maximal_free_rects {
+            for cut_piece in &stock_piece.cut_pieces {
+                assert!(
+                    free_rect.x >= cut_piece.x + cut_piece.width
+                        || cut_piece.x >= free_rect.x + free_rect.width
+                        || free_rect.y >= cut_piece.y + cut_piece.length
+                        || cut_piece.y >= free_rect.y + free_rect.length
+                );
+            }
+        }
+
         // Assert that all cut pieces and waste pieces are disjoint.
         for i in (0..rects.len()).rev() {
             for j in (i + 1..rects.len()).rev() {
@@ -102,6 +185,64 @@ fn sanity_check_solution(solution: &Solution, num_cut_pieces: usize) {
     }
 }
 
+/// Returns a stock piece of a solution with cut pieces at the given `(x, y, width, length)`,
+/// numbered by their external IDs from 0, for the tests of the exporters.
+#[cfg(any(feature = "gcode-export", feature = "saw-export"))]
+pub(crate) fn result_stock_piece(
+    external_id: Option<usize>,
+    width: usize,
+    length: usize,
+    cut_pieces: &[(usize, usize, usize, usize)],
+) -> ResultStockPiece {
+    let cut_pieces = cut_pieces
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y, width, length))| ResultCutPiece {
+            external_id: Some(i),
+            x,
+            y,
+            width,
+            length,
+            pattern_direction: PatternDirection::None,
+            is_rotated: false,
+            rotated_to_match_pattern: false,
+            tag: None,
+            edge_banding: Default::default(),
+            pattern_direction_effective: PatternDirection::None,
+            pattern_mismatch: false,
+        })
+        .collect();
+    ResultStockPiece::new(
+        external_id,
+        width,
+        length,
+        PatternDirection::None,
+        cut_pieces,
+        Vec::new(),
+        0,
+    )
+}
+
+/// Returns a solution with the stock pieces, for the tests of the exporters.
+#[cfg(any(feature = "gcode-export", feature = "saw-export"))]
+pub(crate) fn result_solution(stock_pieces: Vec<ResultStockPiece>) -> Solution {
+    Solution {
+        fitness: 1.0,
+        stock_pieces,
+        objective: None,
+        unplaced_cut_pieces: Vec::new(),
+        placed_value: 0,
+        unplaced_value: 0,
+        annotations: Default::default(),
+        price: 0,
+        optimality_gap: 0.0,
+        random_seed: 0,
+        pass_stats: Vec::new(),
+        min_reusable_waste: (0, 0),
+        result_detail: Default::default(),
+    }
+}
+
 #[test]
 fn guillotine() {
     let solution = Optimizer::new()
@@ -124,6 +265,18 @@ fn guillotine_rotate() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -132,6 +285,16 @@ fn guillotine_rotate() {
             length: 10,
             pattern_direction: PatternDirection::None,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -154,6 +317,11 @@ fn guillotine_rotate() {
             length: 11,
             pattern_direction: PatternDirection::None,
             is_rotated: true,
+            rotated_to_match_pattern: false,
+            tag: None,
+            edge_banding: Default::default(),
+            pattern_direction_effective: PatternDirection::None,
+            pattern_mismatch: false,
         }
     );
 }
@@ -167,6 +335,18 @@ fn guillotine_rotate_pattern() {
             pattern_direction: PatternDirection::ParallelToWidth,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -175,6 +355,16 @@ fn guillotine_rotate_pattern() {
             length: 10,
             pattern_direction: PatternDirection::ParallelToLength,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -197,6 +387,11 @@ fn guillotine_rotate_pattern() {
             length: 11,
             pattern_direction: PatternDirection::ParallelToWidth,
             is_rotated: true,
+            rotated_to_match_pattern: true,
+            tag: None,
+            edge_banding: Default::default(),
+            pattern_direction_effective: PatternDirection::ParallelToLength,
+            pattern_mismatch: false,
         }
     );
 }
@@ -210,6 +405,18 @@ fn guillotine_non_fitting_cut_piece_can_rotate() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -218,13 +425,23 @@ fn guillotine_non_fitting_cut_piece_can_rotate() {
             length: 10,
             pattern_direction: PatternDirection::None,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_guillotine(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -238,6 +455,18 @@ fn guillotine_non_fitting_cut_piece_no_rotate() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -246,13 +475,23 @@ fn guillotine_non_fitting_cut_piece_no_rotate() {
             length: 10,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_guillotine(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -266,6 +505,18 @@ fn guillotine_non_fitting_cut_piece_no_rotate_pattern() {
             pattern_direction: PatternDirection::ParallelToWidth,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -274,13 +525,23 @@ fn guillotine_non_fitting_cut_piece_no_rotate_pattern() {
             length: 10,
             pattern_direction: PatternDirection::ParallelToLength,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_guillotine(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -294,6 +555,18 @@ fn guillotine_non_fitting_cut_piece_mismatched_pattern() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -302,13 +575,23 @@ fn guillotine_non_fitting_cut_piece_mismatched_pattern() {
             length: 10,
             pattern_direction: PatternDirection::ParallelToWidth,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_guillotine(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -324,6 +607,16 @@ fn guillotine_no_allow_mixed_stock_sizes() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -332,6 +625,16 @@ fn guillotine_no_allow_mixed_stock_sizes() {
             length: 120,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -358,6 +661,18 @@ fn guillotine_different_stock_piece_prices() {
             pattern_direction: PatternDirection::None,
             price: 1,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -366,6 +681,18 @@ fn guillotine_different_stock_piece_prices() {
             // Maker the 48x120 stock piece more expensive than (2) 48x96 pieces.
             price: 3,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -374,6 +701,16 @@ fn guillotine_different_stock_piece_prices() {
             length: 50,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -382,6 +719,16 @@ fn guillotine_different_stock_piece_prices() {
             length: 50,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -408,6 +755,18 @@ fn guillotine_same_stock_piece_prices() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -415,6 +774,18 @@ fn guillotine_same_stock_piece_prices() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -423,6 +794,16 @@ fn guillotine_same_stock_piece_prices() {
             length: 50,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -431,6 +812,16 @@ fn guillotine_same_stock_piece_prices() {
             length: 50,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -453,6 +844,18 @@ fn guillotine_stock_quantity_too_low() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -461,6 +864,16 @@ fn guillotine_stock_quantity_too_low() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -481,6 +894,18 @@ fn guillotine_stock_quantity_1() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -489,6 +914,16 @@ fn guillotine_stock_quantity_1() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -507,6 +942,18 @@ fn guillotine_stock_quantity_2() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(2),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -515,6 +962,16 @@ fn guillotine_stock_quantity_2() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -533,6 +990,18 @@ fn guillotine_stock_quantity_multiple() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(2),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 64,
@@ -540,6 +1009,18 @@ fn guillotine_stock_quantity_multiple() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -548,6 +1029,16 @@ fn guillotine_stock_quantity_multiple() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -556,6 +1047,16 @@ fn guillotine_stock_quantity_multiple() {
             length: 192,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(0)
         .set_random_seed(1)
@@ -574,6 +1075,18 @@ fn guillotine_one_stock_piece_several_cut_pieces() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -582,6 +1095,16 @@ fn guillotine_one_stock_piece_several_cut_pieces() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -590,6 +1113,16 @@ fn guillotine_one_stock_piece_several_cut_pieces() {
             length: 10,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 4,
@@ -598,6 +1131,16 @@ fn guillotine_one_stock_piece_several_cut_pieces() {
             length: 20,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -606,6 +1149,16 @@ fn guillotine_one_stock_piece_several_cut_pieces() {
             length: 36,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(0)
         .set_random_seed(1)
@@ -624,6 +1177,18 @@ fn guillotine_stock_duplicate_cut_piece() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 64,
@@ -631,6 +1196,18 @@ fn guillotine_stock_duplicate_cut_piece() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -639,6 +1216,16 @@ fn guillotine_stock_duplicate_cut_piece() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -657,6 +1244,18 @@ fn guillotine_32_cut_pieces_on_1_stock_piece() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 32;
@@ -668,6 +1267,16 @@ fn guillotine_32_cut_pieces_on_1_stock_piece() {
         length: 10,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -693,6 +1302,18 @@ fn guillotine_32_cut_pieces_on_2_stock_piece_zero_cut_width() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 32;
@@ -704,6 +1325,16 @@ fn guillotine_32_cut_pieces_on_2_stock_piece_zero_cut_width() {
         length: 12,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -729,6 +1360,18 @@ fn guillotine_32_cut_pieces_on_2_stock_piece() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 32;
@@ -740,6 +1383,16 @@ fn guillotine_32_cut_pieces_on_2_stock_piece() {
         length: 12,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -763,6 +1416,18 @@ fn guillotine_64_cut_pieces_on_2_stock_pieces() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 64;
@@ -774,6 +1439,16 @@ fn guillotine_64_cut_pieces_on_2_stock_pieces() {
         length: 10,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -799,6 +1474,18 @@ fn guillotine_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToWidth,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -806,6 +1493,18 @@ fn guillotine_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToLength,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -813,6 +1512,18 @@ fn guillotine_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToWidth,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -820,6 +1531,18 @@ fn guillotine_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToLength,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let mut rng: StdRng = SeedableRng::seed_from_u64(1);
@@ -837,6 +1560,16 @@ fn guillotine_random_cut_pieces() {
             PatternDirection::ParallelToLength
         },
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -875,6 +1608,18 @@ fn nested_rotate() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -883,6 +1628,16 @@ fn nested_rotate() {
             length: 10,
             pattern_direction: PatternDirection::None,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -905,6 +1660,11 @@ fn nested_rotate() {
             length: 11,
             pattern_direction: PatternDirection::None,
             is_rotated: true,
+            rotated_to_match_pattern: false,
+            tag: None,
+            edge_banding: Default::default(),
+            pattern_direction_effective: PatternDirection::None,
+            pattern_mismatch: false,
         }
     );
 }
@@ -918,6 +1678,18 @@ fn nested_rotate_pattern() {
             pattern_direction: PatternDirection::ParallelToWidth,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -926,6 +1698,16 @@ fn nested_rotate_pattern() {
             length: 10,
             pattern_direction: PatternDirection::ParallelToLength,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -948,6 +1730,11 @@ fn nested_rotate_pattern() {
             length: 11,
             pattern_direction: PatternDirection::ParallelToWidth,
             is_rotated: true,
+            rotated_to_match_pattern: true,
+            tag: None,
+            edge_banding: Default::default(),
+            pattern_direction_effective: PatternDirection::ParallelToLength,
+            pattern_mismatch: false,
         }
     );
 }
@@ -961,6 +1748,18 @@ fn nested_non_fitting_cut_piece_can_rotate() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -969,13 +1768,23 @@ fn nested_non_fitting_cut_piece_can_rotate() {
             length: 10,
             pattern_direction: PatternDirection::None,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_nested(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -989,6 +1798,18 @@ fn nested_non_fitting_cut_piece_no_rotate() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -997,13 +1818,23 @@ fn nested_non_fitting_cut_piece_no_rotate() {
             length: 10,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_nested(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -1017,6 +1848,18 @@ fn nested_non_fitting_cut_piece_no_rotate_pattern() {
             pattern_direction: PatternDirection::ParallelToWidth,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -1025,13 +1868,23 @@ fn nested_non_fitting_cut_piece_no_rotate_pattern() {
             length: 10,
             pattern_direction: PatternDirection::ParallelToLength,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_nested(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -1045,6 +1898,18 @@ fn nested_non_fitting_cut_piece_mismatched_pattern() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -1053,13 +1918,23 @@ fn nested_non_fitting_cut_piece_mismatched_pattern() {
             length: 10,
             pattern_direction: PatternDirection::ParallelToWidth,
             can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
         .optimize_nested(|_| {});
 
     assert!(
-        matches!(result, Err(Error::NoFitForCutPiece(_))),
+        matches!(result, Err(Error::NoFitForCutPiece(..))),
         "should have returned Error::NoFitForCutPiece"
     )
 }
@@ -1075,6 +1950,16 @@ fn nested_no_allow_mixed_stock_sizes() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -1083,6 +1968,16 @@ fn nested_no_allow_mixed_stock_sizes() {
             length: 120,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1109,6 +2004,18 @@ fn nested_different_stock_piece_prices() {
             pattern_direction: PatternDirection::None,
             price: 1,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -1117,6 +2024,18 @@ fn nested_different_stock_piece_prices() {
             // Maker the 48x120 stock piece more expensive than (2) 48x96 pieces.
             price: 3,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -1125,6 +2044,16 @@ fn nested_different_stock_piece_prices() {
             length: 50,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1151,6 +2080,18 @@ fn nested_same_stock_piece_prices() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -1158,6 +2099,18 @@ fn nested_same_stock_piece_prices() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -1166,6 +2119,16 @@ fn nested_same_stock_piece_prices() {
             length: 50,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1188,6 +2151,18 @@ fn nested_stock_quantity_too_low() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -1196,6 +2171,16 @@ fn nested_stock_quantity_too_low() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1216,6 +2201,18 @@ fn nested_stock_quantity_1() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 1,
@@ -1224,6 +2221,16 @@ fn nested_stock_quantity_1() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1242,6 +2249,18 @@ fn nested_stock_quantity_2() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(2),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_cut_piece(CutPiece {
             quantity: 2,
@@ -1250,6 +2269,16 @@ fn nested_stock_quantity_2() {
             length: 96,
             pattern_direction: PatternDirection::None,
             can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1268,6 +2297,18 @@ fn nested_32_cut_pieces_on_1_stock_piece() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 32;
@@ -1279,6 +2320,16 @@ fn nested_32_cut_pieces_on_1_stock_piece() {
         length: 10,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -1304,6 +2355,18 @@ fn nested_32_cut_pieces_on_2_stock_piece_zero_cut_width() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 32;
@@ -1315,6 +2378,16 @@ fn nested_32_cut_pieces_on_2_stock_piece_zero_cut_width() {
         length: 12,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -1340,6 +2413,18 @@ fn nested_32_cut_pieces_on_2_stock_piece() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 32;
@@ -1351,6 +2436,16 @@ fn nested_32_cut_pieces_on_2_stock_piece() {
         length: 12,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -1374,6 +2469,18 @@ fn nested_64_cut_pieces_on_2_stock_pieces() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let num_cut_pieces = 64;
@@ -1385,6 +2492,16 @@ fn nested_64_cut_pieces_on_2_stock_pieces() {
         length: 10,
         pattern_direction: PatternDirection::None,
         can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -1410,6 +2527,18 @@ fn nested_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToWidth,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -1417,6 +2546,18 @@ fn nested_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToLength,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -1424,6 +2565,18 @@ fn nested_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToWidth,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
     optimizer.add_stock_piece(StockPiece {
         width: 48,
@@ -1431,6 +2584,18 @@ fn nested_random_cut_pieces() {
         pattern_direction: PatternDirection::ParallelToLength,
         price: 0,
         quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     });
 
     let mut rng: StdRng = SeedableRng::seed_from_u64(1);
@@ -1448,6 +2613,16 @@ fn nested_random_cut_pieces() {
             PatternDirection::ParallelToLength
         },
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let solution = optimizer
@@ -1469,6 +2644,18 @@ fn add_equivalent_stock_pieces_sums_quantities() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(3),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -1476,6 +2663,18 @@ fn add_equivalent_stock_pieces_sums_quantities() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(6),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         });
 
     assert_eq!(optimizer.stock_pieces.len(), 1);
@@ -1492,6 +2691,18 @@ fn add_equivalent_stock_pieces_with_none() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -1499,12 +2710,63 @@ fn add_equivalent_stock_pieces_with_none() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: Some(6),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         });
 
     assert_eq!(optimizer.stock_pieces.len(), 1);
     assert_eq!(optimizer.stock_pieces[0].quantity, None);
 }
 
+#[test]
+fn add_stock_pieces_with_different_seams() {
+    let stock_piece = StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity: Some(3),
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece.clone())
+        .add_stock_piece(StockPiece {
+            seams: vec![Seam::ParallelToLength(24)],
+            ..stock_piece
+        });
+
+    assert_eq!(optimizer.stock_pieces.len(), 2);
+    assert_eq!(optimizer.stock_pieces[0].seams, Vec::new());
+    assert_eq!(optimizer.stock_pieces[0].quantity, Some(3));
+    assert_eq!(
+        optimizer.stock_pieces[1].seams,
+        vec![Seam::ParallelToLength(24)]
+    );
+    assert_eq!(optimizer.stock_pieces[1].quantity, Some(3));
+}
+
 #[test]
 fn stock_pieces_dec_quantity() {
     let mut stock_piece = StockPiece {
@@ -1513,6 +2775,18 @@ fn stock_pieces_dec_quantity() {
         pattern_direction: PatternDirection::None,
         price: 0,
         quantity: Some(10),
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     };
 
     stock_piece.dec_quantity();
@@ -1535,6 +2809,18 @@ fn guillotine_rotate_cut_pieces() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -1542,6 +2828,18 @@ fn guillotine_rotate_cut_pieces() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1554,6 +2852,16 @@ fn guillotine_rotate_cut_pieces() {
         length: 24,
         pattern_direction: PatternDirection::None,
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let result = optimizer.optimize_guillotine(|_| {});
@@ -1575,6 +2883,18 @@ fn nested_rotate_cut_pieces() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .add_stock_piece(StockPiece {
             width: 48,
@@ -1582,6 +2902,18 @@ fn nested_rotate_cut_pieces() {
             pattern_direction: PatternDirection::None,
             price: 0,
             quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
         })
         .set_cut_width(1)
         .set_random_seed(1)
@@ -1594,6 +2926,16 @@ fn nested_rotate_cut_pieces() {
         length: 24,
         pattern_direction: PatternDirection::None,
         can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     });
 
     let result = optimizer.optimize_guillotine(|_| {});
@@ -1615,6 +2957,18 @@ fn pighetti_github_issue_12() {
         width: 1220,
         pattern_direction: PatternDirection::ParallelToLength,
         price: 130,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     };
 
     let cut_piece_a = CutPiece {
@@ -1624,6 +2978,16 @@ fn pighetti_github_issue_12() {
         width: 150,
         can_rotate: false,
         pattern_direction: PatternDirection::ParallelToLength,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     };
 
     let cut_piece_b = CutPiece {
@@ -1633,6 +2997,16 @@ fn pighetti_github_issue_12() {
         width: 100,
         can_rotate: false,
         pattern_direction: PatternDirection::ParallelToLength,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     };
 
     optimizer.add_stock_piece(plywood);
@@ -1658,6 +3032,18 @@ fn pighetti_github_issue_16() {
         width: 1220,
         pattern_direction: PatternDirection::ParallelToLength,
         price: 130,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
     };
 
     let cut_piece_a = CutPiece {
@@ -1667,6 +3053,16 @@ fn pighetti_github_issue_16() {
         width: 465,
         can_rotate: false,
         pattern_direction: PatternDirection::ParallelToLength,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
     };
 
     let mut optimizer = Optimizer::new();
@@ -1684,6 +3080,22 @@ fn pighetti_github_issue_16() {
     }
 }
 
+#[test]
+#[cfg(feature = "testing")]
+fn regression_corpus() {
+    testing::assert_corpus(|_| {});
+
+    // A case fails when its solution uses more stock pieces than it allows.
+    let mut case = testing::corpus().remove(0);
+    case.max_stock_pieces = 0;
+    let report = case.run(|_| {});
+    assert!(!report.passed());
+    assert!(matches!(
+        report.failure,
+        Some(testing::CaseFailure::TooManyStockPieces { used: 1, max: 0 })
+    ));
+}
+
 #[test]
 fn deterministic_solutions() {
     // Run the same optimization multiple times with the same random seed and
@@ -1696,6 +3108,18 @@ fn deterministic_solutions() {
                 width: 1220,
                 pattern_direction: PatternDirection::ParallelToLength,
                 price: 130,
+                external_id: None,
+                seams: Vec::new(),
+                trim: Trim::default(),
+                min_leftover_area: None,
+                source: None,
+                grade: None,
+                fixed_pieces: Vec::new(),
+                nominal_size: None,
+                material: None,
+                thickness: None,
+                must_use: false,
+                area_price: None,
             };
 
             let cut_piece_a = CutPiece {
@@ -1705,6 +3129,16 @@ fn deterministic_solutions() {
                 width: 465,
                 can_rotate: false,
                 pattern_direction: PatternDirection::ParallelToLength,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: StockFilter::Any,
+                value: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                material: None,
+                thickness: None,
+                max_quantity: None,
             };
 
             let mut optimizer = Optimizer::new();
@@ -1744,3 +3178,6066 @@ fn deterministic_solutions() {
             })
     });
 }
+
+#[test]
+fn result_stock_piece_waste_cost() {
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 10,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            price: 100,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 1);
+
+    assert_eq!(solution.stock_pieces.len(), 1);
+    let stock_piece = &solution.stock_pieces[0];
+    assert_eq!(stock_piece.price, 100);
+    // Half of the stock piece is unused, so half of the price is wasted.
+    assert_eq!(stock_piece.waste_cost, 50.0);
+}
+
+#[test]
+fn stock_piece_order_external_id() {
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: Some(1),
+            external_id: Some(2),
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: Some(1),
+            external_id: Some(1),
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            external_id: None,
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_stock_piece_order(StockPieceOrder::ExternalId)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 2);
+
+    let external_ids: Vec<Option<usize>> = solution
+        .stock_pieces
+        .iter()
+        .map(|sp| sp.external_id)
+        .collect();
+    assert_eq!(external_ids, vec![Some(1), Some(2)]);
+}
+
+#[test]
+fn cut_piece_order_y_then_x() {
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 20,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 4,
+            external_id: None,
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_random_seed(1)
+        .set_cut_piece_order(CutPieceOrder::YThenX)
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 4);
+
+    assert_eq!(solution.stock_pieces.len(), 1);
+    let locations: Vec<(usize, usize)> = solution.stock_pieces[0]
+        .cut_pieces
+        .iter()
+        .map(|cp| (cp.x, cp.y))
+        .collect();
+    assert_eq!(locations, vec![(0, 0), (10, 0), (0, 10), (10, 10)]);
+}
+
+#[test]
+fn problem_optimize_guillotine() {
+    let problem = Problem {
+        stock_pieces: STOCK_PIECES.to_vec(),
+        cut_pieces: CUT_PIECES.to_vec(),
+        cut_width: 1,
+        random_seed: 1,
+        ..Default::default()
+    };
+
+    let solution = problem.optimize_guillotine(|_| {}).unwrap();
+    sanity_check_solution(&solution, CUT_PIECES.len());
+
+    let optimizer_solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    assert_eq!(solution.fitness, optimizer_solution.fitness);
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn server_optimize_guillotine() {
+    use axum::extract::Json;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let problem = Problem {
+        stock_pieces: STOCK_PIECES.to_vec(),
+        cut_pieces: CUT_PIECES.to_vec(),
+        cut_width: 1,
+        random_seed: 1,
+        ..Default::default()
+    };
+
+    let Json(solution) = runtime
+        .block_on(server::optimize_guillotine(Json(problem.clone())))
+        .unwrap();
+    assert_eq!(
+        solution.fitness,
+        problem.optimize_guillotine(|_| {}).unwrap().fitness
+    );
+
+    let problem = Problem {
+        cut_width: 1000,
+        ..problem
+    };
+    let error = runtime
+        .block_on(server::optimize_guillotine(Json(problem)))
+        .unwrap_err();
+    assert_eq!(
+        error.into_response().status(),
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+}
+
+#[test]
+fn optimizer_builder_valid() {
+    let mut builder = OptimizerBuilder::new();
+    builder
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+    let solution = builder
+        .build()
+        .unwrap()
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    sanity_check_solution(&solution, CUT_PIECES.len());
+}
+
+#[test]
+fn optimizer_builder_reports_every_problem() {
+    let cut_piece = |external_id, width, pattern_direction| CutPiece {
+        quantity: 2,
+        external_id: Some(external_id),
+        width,
+        length: 30,
+        pattern_direction,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut builder = OptimizerBuilder::new();
+    builder
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_stock_piece(StockPiece {
+            width: 0,
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(cut_piece(1, 10, PatternDirection::None))
+        .add_cut_piece(cut_piece(2, 0, PatternDirection::None))
+        .add_cut_piece(cut_piece(3, 10, PatternDirection::ParallelToWidth))
+        .set_cut_width(48);
+
+    let Err(Error::InvalidInput(errors)) = builder.build() else {
+        panic!("expected invalid input");
+    };
+    assert_eq!(errors.len(), 4);
+    assert!(matches!(errors[0], InputError::ZeroSizeStockPiece(ref p) if p.width == 0));
+    assert!(matches!(errors[1], InputError::CutWidthTooLarge(48)));
+    assert!(matches!(errors[2], InputError::ZeroSizeCutPiece(ref p) if p.external_id == Some(2)));
+    assert!(matches!(errors[3], InputError::NoStockForCutPiece(ref p) if p.external_id == Some(3)));
+}
+
+#[test]
+fn max_cut_pieces() {
+    let cut_piece = CutPiece {
+        quantity: 10_000_000,
+        external_id: Some(1),
+        width: 1,
+        length: 1,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .set_max_cut_pieces(100_000)
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .add_cut_piece(cut_piece.clone());
+    let num_cut_pieces = CUT_PIECES.len() + 10_000_000;
+    assert!(matches!(
+        optimizer.optimize_guillotine(|_| {}),
+        Err(Error::TooManyCutPieces(n)) if n == num_cut_pieces
+    ));
+    assert_eq!(optimizer.estimate().unplaced_cut_pieces, 10_000_000);
+
+    // Raising the maximum adds the cut pieces that were held back.
+    optimizer.set_max_cut_pieces(num_cut_pieces);
+    assert!(optimizer.pending_cut_pieces.is_empty());
+    assert_eq!(optimizer.cut_pieces.len(), num_cut_pieces);
+
+    let mut builder = OptimizerBuilder::new();
+    builder
+        .set_max_cut_pieces(CUT_PIECES.len())
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            ..cut_piece
+        });
+    let Err(Error::InvalidInput(errors)) = builder.build() else {
+        panic!("expected invalid input");
+    };
+    assert!(matches!(errors[..], [InputError::TooManyCutPieces(n)] if n == CUT_PIECES.len() + 1));
+}
+
+#[test]
+fn no_stock_available() {
+    let stock_piece = |width, quantity| StockPiece {
+        width,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer.add_cut_pieces(CUT_PIECES.to_vec());
+    assert!(matches!(
+        optimizer.optimize_guillotine(|_| {}),
+        Err(Error::NoStockAvailable(0))
+    ));
+
+    // Stock pieces with a quantity of 0 are kept, but don't count as available.
+    optimizer
+        .add_stock_piece(stock_piece(48, Some(0)))
+        .add_stock_piece(stock_piece(48, Some(0)))
+        .add_stock_piece(stock_piece(24, Some(0)));
+    assert_eq!(optimizer.stock_pieces.len(), 2);
+    assert!(matches!(
+        optimizer.optimize_guillotine(|_| {}),
+        Err(Error::NoStockAvailable(2))
+    ));
+    assert!(matches!(
+        optimizer.optimize_nested(|_| {}),
+        Err(Error::NoStockAvailable(2))
+    ));
+
+    // Without stock, a partial solution leaves every cut piece out.
+    let solution = optimizer
+        .set_allow_partial(true)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    assert!(solution.stock_pieces.is_empty());
+    assert_eq!(
+        solution
+            .unplaced_cut_pieces
+            .iter()
+            .map(|cut_piece| cut_piece.quantity)
+            .sum::<usize>(),
+        CUT_PIECES.len()
+    );
+
+    let mut builder = OptimizerBuilder::new();
+    builder
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_stock_piece(stock_piece(24, Some(0)))
+        .add_cut_pieces(CUT_PIECES.to_vec());
+    let Err(Error::InvalidInput(errors)) = builder.build() else {
+        panic!("expected invalid input");
+    };
+    assert!(matches!(
+        errors[..],
+        [InputError::ZeroQuantityStockPiece(ref p)] if p.width == 24
+    ));
+}
+
+#[test]
+fn cut_piece_tags() {
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            external_id: Some(1),
+            width: 10,
+            length: 30,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: Some("kitchen".to_string()),
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(2),
+            width: 20,
+            length: 30,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 3);
+
+    for cut_piece in solution.stock_pieces.iter().flat_map(|sp| &sp.cut_pieces) {
+        match cut_piece.external_id {
+            Some(1) => assert_eq!(cut_piece.tag.as_deref(), Some("kitchen")),
+            _ => assert_eq!(cut_piece.tag, None),
+        }
+    }
+}
+
+#[test]
+fn guillotine_diagnostics() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            width: 10,
+            length: 21,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            external_id: None,
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    sanity_check_solution(&solution, 2);
+    assert!(solution.stock_pieces[0].diagnostics.is_none());
+
+    let solution = optimizer
+        .set_diagnostics(true)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    sanity_check_solution(&solution, 2);
+    assert_eq!(solution.stock_pieces.len(), 1);
+
+    let diagnostics = solution.stock_pieces[0].diagnostics.as_ref().unwrap();
+    assert!(!diagnostics.heuristics.is_empty());
+    assert_eq!(diagnostics.rejected_placements, 0);
+    // One cut across the width of the stock piece.
+    assert_eq!(diagnostics.kerf_area, 10);
+}
+
+#[test]
+fn nested_diagnostics() {
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 10,
+            length: 20,
+            pattern_direction: PatternDirection::ParallelToLength,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::ParallelToLength,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(2),
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::ParallelToWidth,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_random_seed(1)
+        .set_diagnostics(true)
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 2);
+    assert_eq!(solution.stock_pieces.len(), 1);
+
+    let diagnostics = solution.stock_pieces[0].diagnostics.as_ref().unwrap();
+    assert!(!diagnostics.heuristics.is_empty());
+    assert_eq!(diagnostics.kerf_area, 0);
+}
+
+#[test]
+fn single_cut_piece_uses_cheapest_stock_piece() {
+    let stock_pieces = [
+        StockPiece {
+            width: 48,
+            length: 120,
+            pattern_direction: PatternDirection::None,
+            price: 3,
+            quantity: None,
+            external_id: Some(1),
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        },
+        StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 2,
+            quantity: None,
+            external_id: Some(2),
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        },
+        StockPiece {
+            width: 24,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 1,
+            quantity: Some(0),
+            external_id: Some(3),
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        },
+    ];
+
+    let cut_piece = CutPiece {
+        quantity: 1,
+        external_id: Some(1),
+        width: 90,
+        length: 20,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    for solution in [
+        Optimizer::new()
+            .add_stock_pieces(stock_pieces.clone())
+            .add_cut_piece(cut_piece.clone())
+            .set_cut_width(1)
+            .optimize_guillotine(|_| {})
+            .unwrap(),
+        Optimizer::new()
+            .add_stock_pieces(stock_pieces)
+            .add_cut_piece(cut_piece.clone())
+            .set_cut_width(1)
+            .optimize_nested(|_| {})
+            .unwrap(),
+    ] {
+        sanity_check_solution(&solution, 1);
+        assert_eq!(solution.stock_pieces.len(), 1);
+        assert_eq!(solution.stock_pieces[0].external_id, Some(2));
+        assert!(solution.stock_pieces[0].cut_pieces[0].is_rotated);
+    }
+}
+
+#[test]
+fn few_cut_pieces_use_cheapest_stock_pieces() {
+    let stock_piece = |external_id, length, price| StockPiece {
+        width: 48,
+        length,
+        pattern_direction: PatternDirection::None,
+        price,
+        quantity: None,
+        external_id: Some(external_id),
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let cut_piece = |external_id, size, quantity| CutPiece {
+        quantity,
+        external_id: Some(external_id),
+        width: size,
+        length: size,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    // Each cut piece on its own small stock piece is cheaper than the large stock piece.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces([stock_piece(1, 96, 10), stock_piece(2, 48, 3)])
+        .add_cut_pieces([cut_piece(1, 40, 2), cut_piece(2, 20, 1)])
+        .set_cut_width(1)
+        .set_diagnostics(true);
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 3);
+        assert_eq!(solution.price, 9);
+        assert!(solution
+            .stock_pieces
+            .iter()
+            .all(|stock_piece| stock_piece.external_id == Some(2)));
+        // The layouts weren't evolved.
+        assert!(solution.pass_stats.is_empty());
+    }
+}
+
+#[test]
+fn rotate_to_match_pattern_policies() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            width: 10,
+            length: 11,
+            pattern_direction: PatternDirection::ParallelToWidth,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 11,
+            length: 10,
+            pattern_direction: PatternDirection::ParallelToLength,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    assert!(optimizer.optimize_guillotine(|_| {}).is_err());
+
+    let solution = optimizer
+        .set_rotate_to_match_pattern(RotateToMatchPattern::Always)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    sanity_check_solution(&solution, 1);
+    let cut_piece = &solution.stock_pieces[0].cut_pieces[0];
+    assert!(cut_piece.is_rotated);
+    assert!(cut_piece.rotated_to_match_pattern);
+
+    let result = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 10,
+            length: 11,
+            pattern_direction: PatternDirection::ParallelToWidth,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 11,
+            length: 10,
+            pattern_direction: PatternDirection::ParallelToLength,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_rotate_to_match_pattern(RotateToMatchPattern::Never)
+        .optimize_nested(|_| {});
+    assert!(result.is_err());
+}
+
+#[test]
+fn maximal_free_rects() {
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 20,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: None,
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 1);
+
+    let mut rects: Vec<(usize, usize, usize, usize)> = solution.stock_pieces[0]
+        .maximal_free_rects
+        .iter()
+        .map(|r| (r.x, r.y, r.width, r.length))
+        .collect();
+    rects.sort();
+    assert_eq!(rects, vec![(0, 11, 20, 9), (11, 0, 9, 20)]);
+
+    // The free rectangles can be used as stock for a follow-up job.
+    for free_rect in &solution.stock_pieces[0].maximal_free_rects {
+        let stock_piece = free_rect.to_stock_piece(0, Some(1));
+        assert_eq!(
+            (stock_piece.width, stock_piece.length),
+            (free_rect.width, free_rect.length)
+        );
+    }
+
+    // Both free rectangles have the same area, so the one with the lowest `y` is the largest.
+    assert_eq!(
+        solution.stock_pieces[0].largest_empty_rect(),
+        Some(WastePiece {
+            x: 11,
+            y: 0,
+            width: 9,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+        })
+    );
+}
+
+#[test]
+fn largest_empty_rect() {
+    let stock_piece = StockPiece {
+        width: 40,
+        length: 40,
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = CutPiece {
+        quantity: 1,
+        external_id: None,
+        width: 30,
+        length: 10,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    for solution in [
+        Optimizer::new()
+            .add_stock_piece(stock_piece.clone())
+            .add_cut_piece(cut_piece.clone())
+            .set_cut_width(1)
+            .optimize_guillotine(|_| {})
+            .unwrap(),
+        Optimizer::new()
+            .add_stock_piece(stock_piece.clone())
+            .add_cut_piece(cut_piece.clone())
+            .set_cut_width(1)
+            .optimize_nested(|_| {})
+            .unwrap(),
+    ] {
+        sanity_check_solution(&solution, 1);
+        let stock_piece = &solution.stock_pieces[0];
+        let largest = stock_piece.largest_empty_rect().unwrap();
+
+        // The largest empty rectangle spans the whole stock piece beside the cut piece, even if
+        // the waste pieces were split by the cuts.
+        assert_eq!(largest.width as u64 * largest.length as u64, 40 * 29);
+        let largest_waste_area = stock_piece
+            .waste_pieces
+            .iter()
+            .map(|wp| wp.width as u64 * wp.length as u64)
+            .max()
+            .unwrap();
+        assert!(largest.width as u64 * largest.length as u64 >= largest_waste_area);
+        for cut_piece in &stock_piece.cut_pieces {
+            assert!(
+                largest.x >= cut_piece.x + cut_piece.width
+                    || cut_piece.x >= largest.x + largest.width
+                    || largest.y >= cut_piece.y + cut_piece.length
+                    || cut_piece.y >= largest.y + largest.length
+            );
+        }
+    }
+
+    // There's no free space left on a stock piece that's used up.
+    let solution = Optimizer::new()
+        .add_stock_piece(stock_piece.clone())
+        .add_cut_piece(CutPiece {
+            width: 40,
+            length: 40,
+            ..cut_piece
+        })
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    assert_eq!(solution.stock_pieces[0].largest_empty_rect(), None);
+}
+
+#[test]
+fn optimize_batch() {
+    let problems: Vec<Problem> = (1..=4)
+        .map(|seed| Problem {
+            stock_pieces: STOCK_PIECES.to_vec(),
+            cut_pieces: CUT_PIECES.to_vec(),
+            cut_width: 1,
+            random_seed: seed,
+            ..Default::default()
+        })
+        .collect();
+
+    let results = Optimizer::optimize_batch(problems.clone(), OptimizeMode::Nested, 2);
+    assert_eq!(results.len(), problems.len());
+
+    for (problem, result) in problems.iter().zip(results) {
+        let solution = result.unwrap();
+        sanity_check_solution(&solution, CUT_PIECES.len());
+        assert_eq!(
+            solution.fitness,
+            problem.optimize_nested(|_| {}).unwrap().fitness
+        );
+    }
+}
+
+#[test]
+fn batch_job_panic_is_an_error() {
+    let results = batch::run(4, 2, |i| {
+        if i == 2 {
+            panic!("job {i} failed");
+        }
+        Ok(i)
+    });
+    assert_eq!(results.len(), 4);
+    for (i, result) in results.into_iter().enumerate() {
+        match result {
+            Err(Error::Internal(message)) => {
+                assert_eq!(i, 2);
+                assert_eq!(message, "batch optimization panicked: job 2 failed");
+            }
+            result => assert_eq!(result.unwrap(), i),
+        }
+    }
+
+    // A job can run a batch of its own without waiting on the workers.
+    let results = batch::run(2, 2, |i| {
+        let inner = batch::run(2, 2, move |j| Ok(i * 2 + j));
+        inner.into_iter().sum::<Result<usize>>()
+    });
+    assert_eq!(
+        results.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+        [1, 5]
+    );
+}
+
+#[test]
+fn batch_workers_are_bounded() {
+    let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = Arc::new(Mutex::new(FnvHashSet::default()));
+    for _ in 0..4 {
+        let job_threads = threads.clone();
+        let results = batch::run(64, 64, move |i| {
+            job_threads.lock().unwrap().insert(thread::current().id());
+            thread::sleep(Duration::from_millis(1));
+            Ok(i)
+        });
+        assert!(results
+            .into_iter()
+            .enumerate()
+            .all(|(i, result)| result.unwrap() == i));
+    }
+
+    // The jobs run on the calling thread and the workers, which are kept for each batch.
+    assert!(threads.lock().unwrap().len() <= max_threads + 1);
+}
+
+#[test]
+fn crossover_invalid_bin_is_an_error() {
+    let stock_piece = StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let other_stock_piece = StockPiece {
+        width: 48,
+        length: 120,
+        ..stock_piece.clone()
+    };
+
+    let context = Arc::new(UnitContext {
+        possible_stock_pieces: vec![stock_piece.clone()],
+        bin_options: Default::default(),
+        heuristics: GuillotineBin::possible_heuristics(Default::default()),
+        restricted_heuristics: false,
+        max_units: usize::MAX,
+        min_stock_pieces: 1,
+        early_stop_utilization: None,
+        internal_error: OnceLock::new(),
+        audit_log_callback: None,
+        fitness_objective: FitnessObjective::default(),
+        goal: OptimizeGoal::PlaceAll,
+        cut_piece_values: Vec::new(),
+        separations: Default::default(),
+        prefer_banded_edges_outward: false,
+        pattern_mismatch_penalty: None,
+        max_stock_pieces: None,
+    });
+
+    let unit = OptimizerUnit {
+        bins: vec![GuillotineBin::new(&stock_piece, Default::default())],
+        context: context.clone(),
+        available_stock_pieces: vec![stock_piece.clone()],
+        unused_cut_pieces: Default::default(),
+    };
+
+    // Bins for a stock piece that isn't one of the possible stock pieces.
+    let other = OptimizerUnit {
+        bins: vec![
+            GuillotineBin::new(&other_stock_piece, Default::default()),
+            GuillotineBin::new(&other_stock_piece, Default::default()),
+        ],
+        ..unit.clone()
+    };
+
+    let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+    assert!(unit.crossover(&other, &mut rng).is_err());
+
+    unit.breed_with(&other, &mut rng);
+    assert!(context.internal_error.get().is_some());
+}
+
+#[test]
+fn duplicate_cut_piece_is_an_error() {
+    let stock_piece = StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let cut_piece = CutPieceWithId {
+        id: 0,
+        external_id: Some(1),
+        width: 10,
+        length: 10,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        members: None,
+        allow_pattern_mismatch: false,
+        optional: false,
+        material: None,
+        thickness: None,
+    };
+
+    let context = Arc::new(UnitContext {
+        possible_stock_pieces: vec![stock_piece.clone()],
+        bin_options: Default::default(),
+        heuristics: GuillotineBin::possible_heuristics(Default::default()),
+        restricted_heuristics: false,
+        max_units: usize::MAX,
+        min_stock_pieces: 1,
+        early_stop_utilization: None,
+        internal_error: OnceLock::new(),
+        audit_log_callback: None,
+        fitness_objective: FitnessObjective::default(),
+        goal: OptimizeGoal::PlaceAll,
+        cut_piece_values: Vec::new(),
+        separations: Default::default(),
+        prefer_banded_edges_outward: false,
+        pattern_mismatch_penalty: None,
+        max_stock_pieces: None,
+    });
+
+    let heuristic = GuillotineBin::possible_heuristics(Default::default())[0];
+    let mut bins = vec![
+        GuillotineBin::new(&stock_piece, Default::default()),
+        GuillotineBin::new(&stock_piece, Default::default()),
+    ];
+    for bin in &mut bins {
+        assert!(bin.insert_cut_piece_with_heuristic(&cut_piece, &heuristic));
+    }
+
+    let mut unit = OptimizerUnit {
+        bins,
+        context: context.clone(),
+        available_stock_pieces: vec![],
+        unused_cut_pieces: Default::default(),
+    };
+
+    let result = Optimizer::build_solution(&mut unit);
+    assert!(
+        matches!(
+            result,
+            Err(Error::DuplicateCutPiece(ref cut_piece)) if cut_piece.external_id == Some(1)
+        ),
+        "should have returned Error::DuplicateCutPiece"
+    );
+}
+
+#[test]
+fn guillotine_worst_fit_heuristics() {
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_worst_fit_heuristics(true)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+}
+
+#[test]
+fn guillotine_smallest_y_heuristic() {
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_smallest_y_heuristic(true)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+}
+
+#[test]
+fn stock_piece_fitness_and_utilization() {
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    for stock_piece in &solution.stock_pieces {
+        assert!((0.0..=1.0).contains(&stock_piece.fitness));
+
+        let used_area: usize = stock_piece
+            .cut_pieces
+            .iter()
+            .map(|cp| cp.width * cp.length)
+            .sum();
+        let utilization = used_area as f64 / (stock_piece.width * stock_piece.length) as f64;
+        assert!((stock_piece.utilization - utilization).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn guillotine_panel_saw_heuristic_profile() {
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_diagnostics(true)
+        .set_heuristic_profile(OptimizeMode::Guillotine, HeuristicProfile::PanelSaw)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+    for stock_piece in &solution.stock_pieces {
+        let diagnostics = stock_piece.diagnostics.as_ref().unwrap();
+        for heuristic in &diagnostics.heuristics {
+            assert!(
+                heuristic.contains("ShorterLeftoverAxis") || heuristic.contains("MaximizeArea"),
+                "unexpected heuristic {}",
+                heuristic
+            );
+        }
+    }
+}
+
+#[test]
+fn nested_custom_heuristic_profile() {
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_diagnostics(true)
+        .set_heuristic_profile(
+            OptimizeMode::Nested,
+            HeuristicProfile::Custom(vec!["ContactPointRule PreferUpright".to_string()]),
+        )
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+    for stock_piece in &solution.stock_pieces {
+        let diagnostics = stock_piece.diagnostics.as_ref().unwrap();
+        assert_eq!(
+            diagnostics.heuristics,
+            vec!["ContactPointRule PreferUpright"]
+        );
+    }
+}
+
+#[test]
+fn empty_heuristic_profile() {
+    let result = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_heuristic_profile(
+            OptimizeMode::Guillotine,
+            HeuristicProfile::Custom(Vec::new()),
+        )
+        .optimize_guillotine(|_| {});
+
+    assert!(
+        matches!(
+            result,
+            Err(Error::EmptyHeuristicProfile(OptimizeMode::Guillotine))
+        ),
+        "should have returned Error::EmptyHeuristicProfile"
+    );
+}
+
+#[test]
+fn either_pattern_direction() {
+    let cut_piece = |pattern_direction| CutPiece {
+        quantity: 2,
+        external_id: Some(1),
+        width: 10,
+        length: 30,
+        pattern_direction,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    for stock_pattern_direction in [
+        PatternDirection::None,
+        PatternDirection::ParallelToWidth,
+        PatternDirection::ParallelToLength,
+    ] {
+        let mut optimizer = Optimizer::new();
+        optimizer
+            .add_stock_piece(StockPiece {
+                pattern_direction: stock_pattern_direction,
+                ..STOCK_PIECES[0].clone()
+            })
+            .add_cut_piece(cut_piece(PatternDirection::Either))
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .set_rotate_to_match_pattern(RotateToMatchPattern::Always);
+
+        for solution in [
+            optimizer.optimize_guillotine(|_| {}).unwrap(),
+            optimizer.optimize_nested(|_| {}).unwrap(),
+        ] {
+            sanity_check_solution(&solution, 2);
+            for cut_piece in &solution.stock_pieces[0].cut_pieces {
+                assert_eq!(cut_piece.pattern_direction, stock_pattern_direction);
+                assert!(!cut_piece.rotated_to_match_pattern);
+                let effective = if cut_piece.is_rotated {
+                    stock_pattern_direction.rotated()
+                } else {
+                    stock_pattern_direction
+                };
+                assert_eq!(cut_piece.pattern_direction_effective, effective);
+            }
+        }
+    }
+
+    // Without a pattern, the cut piece can't be cut from patterned stock.
+    let result = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            pattern_direction: PatternDirection::ParallelToLength,
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(cut_piece(PatternDirection::None))
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn pattern_direction_effective() {
+    let cut_piece = CutPiece {
+        quantity: 1,
+        external_id: Some(1),
+        width: 10,
+        length: 30,
+        pattern_direction: PatternDirection::ParallelToLength,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            pattern_direction: PatternDirection::ParallelToWidth,
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(cut_piece)
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_rotate_to_match_pattern(RotateToMatchPattern::Always);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        let cut_piece = &solution.stock_pieces[0].cut_pieces[0];
+        assert!(cut_piece.is_rotated);
+        assert_eq!(
+            cut_piece.pattern_direction,
+            PatternDirection::ParallelToWidth
+        );
+        assert_eq!(
+            cut_piece.pattern_direction_effective,
+            PatternDirection::ParallelToLength
+        );
+    }
+}
+
+#[test]
+fn pattern_mismatch_penalty() {
+    let cut_piece = |pattern_direction| CutPiece {
+        quantity: 1,
+        external_id: Some(1),
+        width: 10,
+        length: 30,
+        pattern_direction,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            pattern_direction: PatternDirection::ParallelToLength,
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(cut_piece(PatternDirection::ParallelToWidth))
+        .add_cut_piece(CutPiece {
+            external_id: Some(2),
+            ..cut_piece(PatternDirection::ParallelToLength)
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_rotate_to_match_pattern(RotateToMatchPattern::Never);
+
+    let result = optimizer.optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+
+    optimizer.set_pattern_mismatch_penalty(0.5);
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 2);
+        for cut_piece in &solution.stock_pieces[0].cut_pieces {
+            // Only the cut piece that can't go with the pattern goes against it.
+            assert_eq!(cut_piece.pattern_mismatch, cut_piece.external_id == Some(1));
+            assert!(!cut_piece.is_rotated);
+        }
+    }
+}
+
+#[test]
+fn waste_pieces_keep_pattern_direction() {
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::ParallelToLength,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            external_id: Some(1),
+            width: 20,
+            length: 30,
+            pattern_direction: PatternDirection::ParallelToLength,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 2);
+
+    let waste_pieces = &solution.stock_pieces[0].waste_pieces;
+    assert!(!waste_pieces.is_empty());
+    for waste_piece in waste_pieces {
+        let stock_piece = waste_piece.to_stock_piece(0, Some(1));
+        assert_eq!(
+            stock_piece.pattern_direction,
+            PatternDirection::ParallelToLength
+        );
+        assert_eq!(stock_piece.width, waste_piece.width);
+        assert_eq!(stock_piece.length, waste_piece.length);
+    }
+}
+
+#[test]
+fn guillotine_pareto() {
+    let objectives = vec![Objective::Price, Objective::Waste, Objective::Cuts];
+    let solutions = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_objectives(objectives.clone())
+        .optimize_guillotine_pareto(|_| {})
+        .unwrap();
+
+    assert!(!solutions.is_empty());
+    assert!(solutions.len() <= objectives.len());
+    assert_eq!(solutions[0].objective, Some(Objective::Price));
+    for solution in &solutions {
+        sanity_check_solution(solution, CUT_PIECES.len());
+        assert!(objectives.contains(&solution.objective.unwrap()));
+    }
+}
+
+#[test]
+fn pareto_keeps_stock_sizes_that_cost_more() {
+    let stock_piece = |width, length, price| StockPiece {
+        width,
+        length,
+        price,
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = CutPiece {
+        quantity: 2,
+        width: 10,
+        length: 10,
+        can_rotate: false,
+        ..CUT_PIECES[0].clone()
+    };
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece(100, 100, 1))
+        .add_stock_piece(stock_piece(10, 21, 5))
+        .add_cut_piece(cut_piece)
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .allow_mixed_stock_sizes(false)
+        .set_fitness_objective(FitnessObjective::MinimizePrice)
+        .set_objectives(vec![Objective::Price, Objective::Waste]);
+
+    // The cheaper stock size is tried first, but the one that costs more wastes less.
+    let solutions = optimizer.optimize_guillotine_pareto(|_| {}).unwrap();
+    assert_eq!(solutions.len(), 2);
+    assert_eq!(solutions[0].objective, Some(Objective::Price));
+    assert_eq!(solutions[0].stock_pieces[0].width, 100);
+    assert_eq!(solutions[1].objective, Some(Objective::Waste));
+    assert_eq!(solutions[1].stock_pieces[0].width, 10);
+
+    // Only the cheapest solution is kept otherwise, so the other stock size is skipped.
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.stock_pieces[0].width, 100);
+}
+
+#[test]
+fn nested_pareto_without_objectives() {
+    let solutions = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_nested_pareto(|_| {})
+        .unwrap();
+
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(solutions[0].objective, None);
+    sanity_check_solution(&solutions[0], CUT_PIECES.len());
+}
+
+#[test]
+fn guillotine_max_memory() {
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_max_memory(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+}
+
+#[test]
+fn max_memory_groups_copies() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(CutPiece {
+            quantity: 200,
+            external_id: Some(1),
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1);
+    assert_eq!(optimizer.rotatable_cut_pieces().len(), 200);
+
+    // Room for 10 layouts of 30 cut pieces, with the population growing by half while breeding,
+    // which takes groups of 8, the most that fit along the length of the stock piece.
+    optimizer.set_max_memory(estimated_cut_piece_size() * 30 * MIN_UNITS_IN_MEMORY * 3 / 2);
+    let cut_pieces = optimizer.rotatable_cut_pieces();
+    assert_eq!(cut_pieces.len(), 25);
+    assert!(cut_pieces
+        .iter()
+        .all(|cut_piece| (cut_piece.width, cut_piece.length) == (10, 87)));
+    let solution = optimizer.optimize_nested(|_| {}).unwrap();
+    sanity_check_solution(&solution, 200);
+}
+
+#[test]
+fn initial_units_limited_by_max_units() {
+    let cut_pieces: Vec<CutPieceWithId> = CUT_PIECES
+        .iter()
+        .enumerate()
+        .map(|(id, cut_piece)| CutPieceWithId {
+            id,
+            external_id: cut_piece.external_id,
+            width: cut_piece.width,
+            length: cut_piece.length,
+            pattern_direction: cut_piece.pattern_direction,
+            can_rotate: cut_piece.can_rotate,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            members: None,
+            allow_pattern_mismatch: false,
+            optional: false,
+            material: None,
+            thickness: None,
+        })
+        .collect();
+
+    let context = Arc::new(UnitContext {
+        possible_stock_pieces: STOCK_PIECES.to_vec(),
+        bin_options: Default::default(),
+        heuristics: MaxRectsBin::possible_heuristics(Default::default()),
+        restricted_heuristics: false,
+        max_units: 5,
+        min_stock_pieces: 1,
+        early_stop_utilization: None,
+        internal_error: OnceLock::new(),
+        audit_log_callback: None,
+        fitness_objective: FitnessObjective::default(),
+        goal: OptimizeGoal::PlaceAll,
+        cut_piece_values: Vec::new(),
+        separations: Default::default(),
+        prefer_banded_edges_outward: false,
+        pattern_mismatch_penalty: None,
+        max_stock_pieces: None,
+    });
+
+    let units: Vec<(Option<usize>, OptimizerUnit<MaxRectsBin>)> =
+        OptimizerUnit::generate_initial_units(
+            &context,
+            cut_pieces.iter().collect(),
+            &mut StdRng::seed_from_u64(1),
+        )
+        .unwrap();
+    assert_eq!(units.len(), 5);
+}
+
+fn edge_distance(stock_piece: &ResultStockPiece, cut_piece: &ResultCutPiece) -> usize {
+    cmp::min(
+        cmp::min(
+            cut_piece.x,
+            stock_piece.width - (cut_piece.x + cut_piece.width),
+        ),
+        cmp::min(
+            cut_piece.y,
+            stock_piece.length - (cut_piece.y + cut_piece.length),
+        ),
+    )
+}
+
+fn max_edge_distance_optimizer() -> Optimizer {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 4,
+            external_id: Some(1),
+            width: 4,
+            length: 90,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: Some(6),
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 4,
+            external_id: Some(2),
+            width: 10,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1);
+    optimizer
+}
+
+#[test]
+fn guillotine_max_edge_distance() {
+    let solution = max_edge_distance_optimizer()
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 8);
+    for stock_piece in &solution.stock_pieces {
+        for cut_piece in &stock_piece.cut_pieces {
+            if cut_piece.external_id == Some(1) {
+                assert!(edge_distance(stock_piece, cut_piece) <= 6);
+            }
+        }
+    }
+}
+
+#[test]
+fn nested_max_edge_distance() {
+    let solution = max_edge_distance_optimizer()
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 8);
+    for stock_piece in &solution.stock_pieces {
+        for cut_piece in &stock_piece.cut_pieces {
+            if cut_piece.external_id == Some(1) {
+                assert!(edge_distance(stock_piece, cut_piece) <= 6);
+            }
+        }
+    }
+}
+
+fn crosses_seam(seams: &[Seam], rect: Rect, blade_width: usize) -> bool {
+    seams.iter().any(|seam| match *seam {
+        Seam::ParallelToLength(x) => rect.x < x + blade_width && x < rect.x + rect.width,
+        Seam::ParallelToWidth(y) => rect.y < y + blade_width && y < rect.y + rect.length,
+    })
+}
+
+fn seams_optimizer() -> Optimizer {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: vec![Seam::ParallelToWidth(48), Seam::ParallelToLength(30)],
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 6,
+            external_id: Some(1),
+            width: 20,
+            length: 30,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 6,
+            external_id: Some(2),
+            width: 10,
+            length: 40,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1);
+    optimizer
+}
+
+#[test]
+fn guillotine_seams() {
+    let solution = seams_optimizer().optimize_guillotine(|_| {}).unwrap();
+
+    sanity_check_solution(&solution, 12);
+    for stock_piece in &solution.stock_pieces {
+        assert_eq!(stock_piece.seams.len(), 2);
+        for cut_piece in &stock_piece.cut_pieces {
+            assert!(!crosses_seam(&stock_piece.seams, cut_piece.into(), 1));
+        }
+        for waste_piece in &stock_piece.waste_pieces {
+            assert!(!crosses_seam(&stock_piece.seams, waste_piece.into(), 1));
+        }
+    }
+}
+
+#[test]
+fn nested_seams() {
+    let solution = seams_optimizer().optimize_nested(|_| {}).unwrap();
+
+    sanity_check_solution(&solution, 12);
+    for stock_piece in &solution.stock_pieces {
+        for cut_piece in &stock_piece.cut_pieces {
+            assert!(!crosses_seam(&stock_piece.seams, cut_piece.into(), 1));
+        }
+    }
+}
+
+#[test]
+fn nested_guillotine_waste_pieces() {
+    let solution = seams_optimizer()
+        .set_guillotine_waste_pieces(true)
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 12);
+    for stock_piece in &solution.stock_pieces {
+        let rects: Vec<Rect> = stock_piece
+            .cut_pieces
+            .iter()
+            .map(Into::into)
+            .chain(stock_piece.waste_pieces.iter().map(Into::into))
+            .collect();
+
+        // Assert that no cut pieces or waste pieces overlap.
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                assert!(
+                    a.x >= b.x + b.width
+                        || b.x >= a.x + a.width
+                        || a.y >= b.y + b.length
+                        || b.y >= a.y + a.length
+                );
+            }
+        }
+
+        for waste_piece in &stock_piece.waste_pieces {
+            assert!(!crosses_seam(&stock_piece.seams, waste_piece.into(), 1));
+        }
+    }
+}
+
+#[test]
+fn cut_piece_too_big_for_seams() {
+    let result = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: vec![Seam::ParallelToWidth(48)],
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 40,
+            length: 60,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .optimize_guillotine(|_| {});
+
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn trim() {
+    let trim = Trim {
+        left: 2,
+        right: 1,
+        bottom: 3,
+        top: 0,
+    };
+    let cut_piece = |width, length, quantity| CutPiece {
+        quantity,
+        external_id: Some(1),
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+    let stock_piece = StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim,
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece.clone())
+        .add_cut_piece(cut_piece(20, 30, 6))
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 6);
+        for stock_piece in &solution.stock_pieces {
+            assert_eq!((stock_piece.width, stock_piece.length), (48, 96));
+            assert_eq!(stock_piece.trim, trim);
+            let rects = stock_piece
+                .cut_pieces
+                .iter()
+                .map(Rect::from)
+                .chain(stock_piece.waste_pieces.iter().map(Rect::from));
+            for rect in rects {
+                assert!(rect.x >= 2 && rect.x + rect.width <= 47);
+                assert!(rect.y >= 3 && rect.y + rect.length <= 96);
+            }
+        }
+    }
+
+    // The cut piece would fit on the stock piece if it wasn't trimmed.
+    let result = Optimizer::new()
+        .add_stock_piece(stock_piece)
+        .add_cut_piece(cut_piece(46, 93, 1))
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn min_leftover_area() {
+    let stock_piece = StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        // At most 92% utilization.
+        min_leftover_area: Some(48 * 96 * 8 / 100),
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let cut_piece = |width, length, quantity| CutPiece {
+        quantity,
+        external_id: Some(1),
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    // Three of the cut pieces would fit on each stock piece, but only two leave enough area.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece.clone())
+        .add_cut_piece(cut_piece(48, 30, 6))
+        .set_random_seed(1);
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 6);
+        assert_eq!(solution.stock_pieces.len(), 3);
+        for stock_piece in &solution.stock_pieces {
+            assert!(stock_piece.utilization <= 0.92);
+        }
+    }
+
+    // The trim isn't left over, so three of the cut pieces would leave enough area on the whole
+    // stock piece, but only two leave enough within the trim.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            width: 50,
+            length: 100,
+            trim: Trim::uniform(1),
+            min_leftover_area: Some(500),
+            ..stock_piece.clone()
+        })
+        .add_cut_piece(cut_piece(48, 30, 6))
+        .set_random_seed(1);
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 6);
+        assert_eq!(solution.stock_pieces.len(), 3);
+    }
+
+    let result = Optimizer::new()
+        .add_stock_piece(stock_piece)
+        .add_cut_piece(cut_piece(48, 96, 1))
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn max_guillotine_stages() {
+    let stages = |stock_piece: &ResultStockPiece| {
+        let rects: Vec<Rect> = stock_piece
+            .cut_pieces
+            .iter()
+            .map(|p| Rect {
+                x: p.x,
+                y: p.y,
+                width: p.width,
+                length: p.length,
+            })
+            .collect();
+        cuts::guillotine_stages(
+            &rects,
+            stock_piece.width,
+            stock_piece.length,
+            &stock_piece.seams,
+            stock_piece.trim,
+            BladeWidth::uniform(1),
+        )
+    };
+
+    let cut_piece = |width, length| CutPiece {
+        quantity: 3,
+        external_id: None,
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    // Without a limit, these take five stages to cut.
+    let cut_pieces = [
+        cut_piece(10, 30),
+        cut_piece(17, 12),
+        cut_piece(25, 21),
+        cut_piece(8, 41),
+        cut_piece(13, 13),
+    ];
+    for max_stages in [2, 3] {
+        let solution = Optimizer::new()
+            .add_stock_pieces(STOCK_PIECES.to_vec())
+            .add_cut_pieces(cut_pieces.clone())
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .set_max_guillotine_stages(max_stages)
+            .optimize_guillotine(|_| {})
+            .unwrap();
+
+        sanity_check_solution(&solution, 15);
+        for stock_piece in &solution.stock_pieces {
+            assert!(stages(stock_piece) <= max_stages);
+        }
+    }
+
+    // A single stage can only cut strips the full width or length of the stock piece.
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 4,
+            external_id: Some(1),
+            width: 20,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_max_guillotine_stages(1)
+        .optimize_guillotine(|_| {});
+    assert!(matches!(solution, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn cut_widths() {
+    let (rip, crosscut) = (1, 8);
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_widths(rip, crosscut)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, CUT_PIECES.len());
+
+        let mut narrow_gaps = 0;
+        for stock_piece in &solution.stock_pieces {
+            for (i, a) in stock_piece.cut_pieces.iter().enumerate() {
+                for b in &stock_piece.cut_pieces[i + 1..] {
+                    let overlaps_x = a.x < b.x + b.width && b.x < a.x + a.width;
+                    let overlaps_y = a.y < b.y + b.length && b.y < a.y + a.length;
+                    if overlaps_y {
+                        // Side by side, so a rip cut separates them.
+                        let gap = cmp::max(a.x, b.x) - cmp::min(a.x + a.width, b.x + b.width);
+                        assert!(gap >= rip);
+                        if gap < crosscut {
+                            narrow_gaps += 1;
+                        }
+                    } else if overlaps_x {
+                        // One above the other, so a crosscut separates them.
+                        let gap = cmp::max(a.y, b.y) - cmp::min(a.y + a.length, b.y + b.length);
+                        assert!(gap >= crosscut);
+                    }
+                }
+            }
+        }
+        // The rip cuts are narrower than the crosscuts.
+        assert!(narrow_gaps > 0);
+    }
+
+    let result = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_widths(1, 1000)
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::CutWidthTooLarge(1000))));
+}
+
+#[test]
+fn first_cut_direction() {
+    let cut_piece = |width, length| CutPiece {
+        quantity: 4,
+        external_id: None,
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+    // None of the cut pieces span a whole stock piece, so the first cuts always make more than
+    // one strip.
+    let cut_pieces = [
+        cut_piece(10, 15),
+        cut_piece(12, 8),
+        cut_piece(14, 20),
+        cut_piece(7, 18),
+    ];
+
+    for direction in [
+        CutDirection::ParallelToLength,
+        CutDirection::ParallelToWidth,
+    ] {
+        let solution = Optimizer::new()
+            .add_stock_pieces(STOCK_PIECES.to_vec())
+            .add_cut_pieces(cut_pieces.clone())
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .set_first_cut_direction(direction)
+            .optimize_guillotine(|_| {})
+            .unwrap();
+
+        sanity_check_solution(&solution, 16);
+        for stock_piece in &solution.stock_pieces {
+            let rects: Vec<Rect> = stock_piece
+                .cut_pieces
+                .iter()
+                .map(|p| Rect {
+                    x: p.x,
+                    y: p.y,
+                    width: p.width,
+                    length: p.length,
+                })
+                .collect();
+            let mut indices: Vec<usize> = (0..rects.len()).collect();
+            let strips = cuts::group_spans(
+                &rects,
+                &mut indices,
+                direction == CutDirection::ParallelToLength,
+            );
+            assert!(rects.len() == 1 || strips.len() > 1);
+        }
+    }
+}
+
+fn annotated_solution() -> Solution {
+    let mut solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    solution
+        .annotate("job", "1234")
+        .annotate("customer", "Acme");
+    for stock_piece in &mut solution.stock_pieces {
+        let operator = format!("operator {}", stock_piece.serial_number);
+        stock_piece
+            .annotate("machine", "panel saw")
+            .annotate("operator", operator);
+    }
+    solution
+}
+
+#[test]
+fn annotations() {
+    let mut solution = annotated_solution();
+    assert_eq!(solution.annotation("job"), Some("1234"));
+    assert_eq!(solution.annotation("customer"), Some("Acme"));
+    assert_eq!(solution.annotation("machine"), None);
+
+    solution.annotate("job", "1235");
+    assert_eq!(solution.annotation("job"), Some("1235"));
+    assert_eq!(solution.annotations.len(), 2);
+
+    for stock_piece in &solution.stock_pieces {
+        assert_eq!(stock_piece.annotation("machine"), Some("panel saw"));
+        assert_eq!(
+            stock_piece.annotation("operator"),
+            Some(format!("operator {}", stock_piece.serial_number).as_str())
+        );
+        assert_eq!(stock_piece.annotation("job"), None);
+    }
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn annotations_serialization() {
+    let solution = annotated_solution();
+    let json = serde_json::to_string(&solution).unwrap();
+    let deserialized: Solution = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized.annotations, solution.annotations);
+    assert_eq!(deserialized.stock_pieces.len(), solution.stock_pieces.len());
+    for (deserialized, stock_piece) in deserialized.stock_pieces.iter().zip(&solution.stock_pieces)
+    {
+        assert_eq!(deserialized.annotations, stock_piece.annotations);
+    }
+
+    // Solutions without annotations don't serialize them.
+    let mut solution = solution;
+    solution.annotations.clear();
+    let json = serde_json::to_value(&solution).unwrap();
+    assert!(json.get("annotations").is_none());
+    let deserialized: Solution = serde_json::from_value(json).unwrap();
+    assert!(deserialized.annotations.is_empty());
+}
+
+#[test]
+fn min_reusable_waste() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    // Every waste piece is a reusable offcut by default.
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    for stock_piece in &solution.stock_pieces {
+        assert_eq!(stock_piece.reusable_offcuts, stock_piece.waste_pieces);
+        assert!(stock_piece.scrap.is_empty());
+    }
+
+    let solution = optimizer
+        .set_min_reusable_waste(10, 40)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    let is_reusable = |waste_piece: &WastePiece| {
+        (waste_piece.width >= 10 && waste_piece.length >= 40)
+            || (waste_piece.width >= 40 && waste_piece.length >= 10)
+    };
+    let mut num_scrap = 0;
+    for stock_piece in &solution.stock_pieces {
+        assert_eq!(
+            stock_piece.reusable_offcuts.len() + stock_piece.scrap.len(),
+            stock_piece.waste_pieces.len()
+        );
+        assert!(stock_piece.reusable_offcuts.iter().all(is_reusable));
+        assert!(!stock_piece.scrap.iter().any(is_reusable));
+        num_scrap += stock_piece.scrap.len();
+    }
+    assert!(num_scrap > 0);
+}
+
+#[test]
+fn mirror() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+
+    for mirror in [Mirror::Horizontal, Mirror::Vertical, Mirror::Both] {
+        let mirrored = optimizer
+            .set_mirror(mirror)
+            .optimize_guillotine(|_| {})
+            .unwrap();
+        sanity_check_solution(&mirrored, CUT_PIECES.len());
+
+        let horizontal = mirror != Mirror::Vertical;
+        let vertical = mirror != Mirror::Horizontal;
+        for (stock_piece, mirrored_stock_piece) in
+            solution.stock_pieces.iter().zip(&mirrored.stock_pieces)
+        {
+            for (cut_piece, mirrored_cut_piece) in stock_piece
+                .cut_pieces
+                .iter()
+                .zip(&mirrored_stock_piece.cut_pieces)
+            {
+                let x = if horizontal {
+                    stock_piece.width - cut_piece.x - cut_piece.width
+                } else {
+                    cut_piece.x
+                };
+                let y = if vertical {
+                    stock_piece.length - cut_piece.y - cut_piece.length
+                } else {
+                    cut_piece.y
+                };
+                assert_eq!((mirrored_cut_piece.x, mirrored_cut_piece.y), (x, y));
+            }
+        }
+    }
+
+    // The cut pieces are ordered after they're mirrored.
+    let mirrored = optimizer
+        .set_mirror(Mirror::Horizontal)
+        .set_cut_piece_order(CutPieceOrder::XThenY)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    for stock_piece in &mirrored.stock_pieces {
+        assert!(stock_piece
+            .cut_pieces
+            .windows(2)
+            .all(|pair| (pair[0].x, pair[0].y) <= (pair[1].x, pair[1].y)));
+    }
+}
+
+fn early_stop_optimizer(utilization: f64) -> Optimizer {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 4,
+            external_id: Some(1),
+            width: 10,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_early_stop_utilization(utilization);
+    optimizer
+}
+
+#[test]
+fn guillotine_early_stop() {
+    let progress = std::cell::Cell::new(0.0);
+    let solution = early_stop_optimizer(0.1)
+        .optimize_guillotine(|p| progress.set(p))
+        .unwrap();
+
+    sanity_check_solution(&solution, 4);
+    assert_eq!(solution.stock_pieces.len(), 1);
+    assert!(progress.get() < 1.0);
+}
+
+#[test]
+fn nested_early_stop_utilization_not_reached() {
+    let progress = std::cell::Cell::new(0.0);
+    let solution = early_stop_optimizer(0.9)
+        .optimize_nested(|p| progress.set(p))
+        .unwrap();
+
+    sanity_check_solution(&solution, 4);
+    assert_eq!(progress.get(), 1.0);
+}
+
+#[test]
+fn estimate() {
+    let estimate = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 10,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 8,
+            external_id: Some(1),
+            width: 24,
+            length: 48,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(2),
+            width: 50,
+            length: 100,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .estimate();
+
+    assert_eq!(
+        estimate,
+        Estimate {
+            stock_pieces: 2,
+            utilization: 1.0,
+            price: 20,
+            unplaced_cut_pieces: 1,
+        }
+    );
+}
+
+#[test]
+fn estimate_not_better_than_optimized() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    let estimate = optimizer.estimate();
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+
+    assert_eq!(estimate.unplaced_cut_pieces, 0);
+    assert!(estimate.stock_pieces >= solution.stock_pieces.len());
+    assert!(estimate.utilization > 0.0 && estimate.utilization <= 1.0);
+}
+
+#[test]
+fn estimate_no_cut_pieces() {
+    let estimate = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .estimate();
+
+    assert_eq!(estimate.stock_pieces, 0);
+    assert_eq!(estimate.utilization, 0.0);
+}
+
+fn stock_exhausted_optimizer() -> Optimizer {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: Some(1),
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 6,
+            external_id: Some(1),
+            width: 24,
+            length: 48,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_random_seed(1);
+    optimizer
+}
+
+#[test]
+fn guillotine_no_fit_stock_exhausted() {
+    let result = stock_exhausted_optimizer().optimize_guillotine(|_| {});
+
+    match result {
+        Err(Error::NoFitForCutPiece(cut_piece, summary)) => {
+            assert_eq!(cut_piece.external_id, Some(1));
+            assert_eq!(
+                summary,
+                PlacementSummary {
+                    placed: 4,
+                    quantity: 6,
+                    stock_exhausted: true,
+                }
+            );
+            assert_eq!(summary.to_string(), "4 of 6 placed, stock exhausted");
+        }
+        _ => panic!("should have returned Error::NoFitForCutPiece"),
+    }
+}
+
+#[test]
+fn nested_no_fit_stock_exhausted() {
+    let result = stock_exhausted_optimizer().optimize_nested(|_| {});
+
+    match result {
+        Err(Error::NoFitForCutPiece(_, summary)) => {
+            assert_eq!(summary.to_string(), "4 of 6 placed, stock exhausted");
+        }
+        _ => panic!("should have returned Error::NoFitForCutPiece"),
+    }
+}
+
+#[test]
+fn no_fit_cut_piece_too_big() {
+    let result = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            external_id: Some(1),
+            width: 100,
+            length: 200,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .allow_mixed_stock_sizes(false)
+        .optimize_guillotine(|_| {});
+
+    match result {
+        Err(Error::NoFitForCutPiece(_, summary)) => {
+            assert_eq!(summary.to_string(), "0 of 2 placed");
+            assert!(!summary.stock_exhausted);
+        }
+        _ => panic!("should have returned Error::NoFitForCutPiece"),
+    }
+}
+
+#[test]
+fn guillotine_time_limit() {
+    let progress = std::cell::Cell::new(0.0);
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_time_limit(Duration::ZERO)
+        .optimize_guillotine(|p| progress.set(p))
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+    assert!(progress.get() < 1.0);
+}
+
+#[test]
+fn skip_stock_size_that_cant_be_cheaper() {
+    let progress_calls = std::cell::Cell::new(0);
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 10,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_stock_piece(StockPiece {
+            width: 30,
+            length: 50,
+            pattern_direction: PatternDirection::None,
+            price: 100,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 4,
+            external_id: Some(1),
+            width: 24,
+            length: 48,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_random_seed(1)
+        .optimize_guillotine(|_| progress_calls.set(progress_calls.get() + 1))
+        .unwrap();
+
+    sanity_check_solution(&solution, 4);
+    assert_eq!(solution.stock_pieces.len(), 1);
+    assert_eq!(solution.stock_pieces[0].width, 48);
+    // Laying out the cut pieces on the expensive stock pieces would take many generations.
+    assert!(progress_calls.get() < 10);
+}
+
+#[test]
+fn progress_events() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_progress_event_callback(move |event| sender.send(event).unwrap())
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+
+    let events: Vec<ProgressEvent> = receiver.try_iter().collect();
+    assert_eq!(
+        events.first(),
+        Some(&ProgressEvent::PhaseStarted {
+            phase: 0,
+            phases: 3
+        })
+    );
+    assert_eq!(events.last(), Some(&ProgressEvent::Finished));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, ProgressEvent::EpochCompleted { .. })));
+
+    // Each new best solution is better than the last.
+    let best_fitnesses: Vec<f64> = events
+        .iter()
+        .filter_map(|event| match *event {
+            ProgressEvent::NewBestSolution { fitness } => Some(fitness),
+            _ => None,
+        })
+        .collect();
+    assert!(!best_fitnesses.is_empty());
+    assert!(best_fitnesses.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn cut_piece_assignments() {
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 5,
+            external_id: Some(2),
+            width: 24,
+            length: 48,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 3,
+            external_id: Some(1),
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 8);
+
+    let serial_numbers: Vec<usize> = solution
+        .stock_pieces
+        .iter()
+        .map(|stock_piece| stock_piece.serial_number)
+        .collect();
+    assert_eq!(
+        serial_numbers,
+        (1..=solution.stock_pieces.len()).collect::<Vec<_>>()
+    );
+
+    let assignments = solution.cut_piece_assignments();
+    assert_eq!(assignments.len(), 2);
+    for (assignment, (external_id, quantity)) in assignments.iter().zip([(1, 3), (2, 5)]) {
+        assert_eq!(assignment.external_id, Some(external_id));
+        assert_eq!(
+            assignment
+                .stock_pieces
+                .iter()
+                .map(|count| count.count)
+                .sum::<usize>(),
+            quantity
+        );
+        for count in &assignment.stock_pieces {
+            let stock_piece = &solution.stock_pieces[count.serial_number - 1];
+            assert_eq!(
+                stock_piece
+                    .cut_pieces
+                    .iter()
+                    .filter(|cut_piece| cut_piece.external_id == Some(external_id))
+                    .count(),
+                count.count
+            );
+        }
+    }
+}
+
+#[test]
+fn session_matches_optimize() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for mode in [OptimizeMode::Guillotine, OptimizeMode::Nested] {
+        let mut session = optimizer.start_session(mode);
+        let mut steps = 0;
+        let mut last_progress = 0.0;
+        while let StepResult::Running { progress } = session.step(Duration::ZERO) {
+            assert!(progress >= last_progress && progress <= 1.0);
+            last_progress = progress;
+            steps += 1;
+        }
+        assert!(steps > 1);
+        let solution = session.finish().unwrap();
+
+        let expected = match mode {
+            OptimizeMode::Guillotine => optimizer.optimize_guillotine(|_| {}),
+            OptimizeMode::Nested => optimizer.optimize_nested(|_| {}),
+        }
+        .unwrap();
+
+        sanity_check_solution(&solution, CUT_PIECES.len());
+        assert_eq!(solution.fitness, expected.fitness);
+        assert_eq!(solution.stock_pieces.len(), expected.stock_pieces.len());
+    }
+}
+
+#[test]
+fn session_step_without_clock() {
+    use std::cell::Cell;
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+    let expected = optimizer.optimize_nested(|_| {}).unwrap();
+
+    let mut session = optimizer.start_session(OptimizeMode::Nested);
+    let mut steps = 0;
+    while let StepResult::Running { .. } = session.step_generations(5) {
+        steps += 1;
+    }
+    assert!(steps > 1);
+    assert_eq!(session.finish().unwrap().fitness, expected.fitness);
+
+    // A clock that advances a millisecond each time it's read runs a generation per step.
+    let time = Cell::new(Duration::ZERO);
+    let clock = || {
+        time.set(time.get() + Duration::from_millis(1));
+        time.get()
+    };
+    let mut session = optimizer.start_session(OptimizeMode::Nested);
+    let mut clock_steps = 0;
+    while let StepResult::Running { .. } = session.step_with_clock(Duration::from_millis(1), clock)
+    {
+        clock_steps += 1;
+    }
+    assert!(clock_steps > steps);
+    assert_eq!(session.finish().unwrap().fitness, expected.fitness);
+}
+
+#[test]
+fn cut_width_too_large() {
+    let cut_piece = CutPiece {
+        quantity: 1,
+        external_id: Some(1),
+        width: 10,
+        length: 30,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    // The cut width is wider than the cut piece.
+    let result = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_piece(cut_piece.clone())
+        .set_cut_width(11)
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::CutWidthTooLarge(11))));
+
+    // The cut width is as wide as the stock piece.
+    let result = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 10,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            width: 1,
+            length: 1,
+            ..cut_piece
+        })
+        .set_cut_width(10)
+        .optimize_nested(|_| {});
+    assert!(matches!(result, Err(Error::CutWidthTooLarge(10))));
+}
+
+#[test]
+fn optimize_with_offcuts() {
+    let first_solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            width: 48,
+            length: 96,
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: Some(7),
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: None,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+            area_price: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 48,
+            length: 60,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(2),
+            width: 20,
+            length: 20,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    let offcuts = first_solution.offcuts(10, 10);
+    assert!(!offcuts.is_empty());
+    for offcut in &offcuts {
+        assert!(cmp::min(offcut.width, offcut.length) >= 10);
+        assert_eq!(offcut.external_id, Some(7));
+    }
+
+    let solution = Optimizer::new()
+        .add_stock_pieces(offcuts)
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            external_id: Some(3),
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, 2);
+    for stock_piece in &solution.stock_pieces {
+        // The source leads back to the waste piece this stock piece was cut from.
+        let source = stock_piece.source.unwrap();
+        let original = &first_solution.stock_pieces[source.serial_number - 1];
+        assert_eq!(source.external_id, original.external_id);
+        assert!(original.waste_pieces.iter().any(|waste_piece| {
+            waste_piece.x == source.x
+                && waste_piece.y == source.y
+                && waste_piece.width == stock_piece.width
+                && waste_piece.length == stock_piece.length
+        }));
+    }
+}
+
+#[test]
+fn cancel_from_progress_callback() {
+    let calls = std::cell::Cell::new(0);
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_nested(|_| {
+            calls.set(calls.get() + 1);
+            ControlFlow::Break(())
+        })
+        .unwrap();
+
+    // The optimization stops after the first progress report, with the best solution so far.
+    assert_eq!(calls.get(), 1);
+    sanity_check_solution(&solution, CUT_PIECES.len());
+
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|progress| {
+            if progress < 0.5 {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })
+        .unwrap();
+    sanity_check_solution(&solution, CUT_PIECES.len());
+}
+
+#[test]
+fn per_unit_epoch_seeds_are_reproducible() {
+    let optimize = || {
+        Optimizer::new()
+            .add_stock_pieces(STOCK_PIECES.to_vec())
+            .add_cut_pieces(CUT_PIECES.to_vec())
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .set_epoch_seed_strategy(EpochSeedStrategy::PerUnit)
+            .optimize_guillotine(|_| {})
+            .unwrap()
+    };
+
+    let solution = optimize();
+    sanity_check_solution(&solution, CUT_PIECES.len());
+
+    let other = optimize();
+    assert_eq!(solution.fitness, other.fitness);
+    for (stock_piece, other) in solution.stock_pieces.iter().zip(&other.stock_pieces) {
+        assert_eq!(stock_piece.cut_pieces, other.cut_pieces);
+    }
+}
+
+#[test]
+fn stock_filter() {
+    let stock_piece = |external_id, price| StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price,
+        quantity: None,
+        external_id: Some(external_id),
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let cut_pieces: Vec<CutPiece> = CUT_PIECES
+        .iter()
+        .map(|cut_piece| CutPiece {
+            stock_filter: if cut_piece.external_id.unwrap() % 2 == 0 {
+                // Even cut pieces can only be cut from the full sheets.
+                StockFilter::Only(vec![2])
+            } else {
+                // Odd cut pieces can't be cut from the full sheets.
+                StockFilter::Except(vec![2])
+            },
+            ..cut_piece.clone()
+        })
+        .collect();
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece(1, 1))
+        .add_stock_piece(stock_piece(2, 10))
+        .add_cut_pieces(cut_pieces.clone())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, CUT_PIECES.len());
+        for stock_piece in &solution.stock_pieces {
+            for cut_piece in &stock_piece.cut_pieces {
+                let is_even = cut_piece.external_id.unwrap() % 2 == 0;
+                assert_eq!(is_even, stock_piece.external_id == Some(2));
+            }
+        }
+    }
+
+    // A cut piece that isn't allowed on any of the stock pieces doesn't fit.
+    let result = Optimizer::new()
+        .add_stock_piece(stock_piece(1, 1))
+        .add_cut_piece(CutPiece {
+            stock_filter: StockFilter::Only(vec![3]),
+            value: None,
+            ..cut_pieces[0].clone()
+        })
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn grades() {
+    let stock_piece = |external_id, price, grade| StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price,
+        quantity: None,
+        external_id: Some(external_id),
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let cut_pieces: Vec<CutPiece> = CUT_PIECES
+        .iter()
+        .map(|cut_piece| CutPiece {
+            min_grade: if cut_piece.external_id.unwrap() % 2 == 0 {
+                // Even cut pieces have a visible face.
+                Some('A')
+            } else {
+                // Odd cut pieces are cabinet backs.
+                Some('C')
+            },
+            ..cut_piece.clone()
+        })
+        .collect();
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece(1, 10, Some('A')))
+        .add_stock_piece(stock_piece(2, 5, Some('B')))
+        .add_stock_piece(stock_piece(3, 1, Some('C')))
+        .add_cut_pieces(cut_pieces.clone())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, CUT_PIECES.len());
+        for stock_piece in &solution.stock_pieces {
+            // The faces are only cut from A-grade stock pieces, and the backs never need the
+            // B-grade stock pieces since C-grade ones are cheaper.
+            assert_ne!(stock_piece.grade, Some('B'));
+            for cut_piece in &stock_piece.cut_pieces {
+                if cut_piece.external_id.unwrap() % 2 == 0 {
+                    assert_eq!(stock_piece.grade, Some('A'));
+                }
+            }
+        }
+
+        // Offcuts keep the grade of the stock piece they remain from.
+        for (offcut, stock_piece) in solution.offcuts(0, 0).iter().zip(
+            solution
+                .stock_pieces
+                .iter()
+                .flat_map(|stock_piece| stock_piece.waste_pieces.iter().map(move |_| stock_piece)),
+        ) {
+            assert_eq!(offcut.grade, stock_piece.grade);
+        }
+    }
+
+    // Stock pieces without a grade don't meet a minimum grade.
+    let result = Optimizer::new()
+        .add_stock_piece(stock_piece(1, 1, None))
+        .add_cut_piece(cut_pieces[0].clone())
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn fixed_pieces() {
+    let fixed_piece = FixedPiece {
+        external_id: Some(100),
+        x: 14,
+        y: 30,
+        width: 20,
+        length: 30,
+    };
+    let stock_piece = StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity: Some(1),
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: vec![fixed_piece],
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+    let cut_piece = CutPiece {
+        quantity: 10,
+        external_id: Some(1),
+        width: 12,
+        length: 12,
+        can_rotate: false,
+        pattern_direction: PatternDirection::None,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece.clone())
+        .add_cut_piece(cut_piece.clone())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 10);
+        let stock_piece = &solution.stock_pieces[0];
+        assert_eq!(stock_piece.fixed_pieces, vec![fixed_piece]);
+
+        // The cut pieces and waste pieces are placed around the fixed piece, leaving room for
+        // the cut.
+        let around = Rect {
+            x: fixed_piece.x - 1,
+            y: fixed_piece.y - 1,
+            width: fixed_piece.width + 2,
+            length: fixed_piece.length + 2,
+        };
+        let rects = stock_piece
+            .cut_pieces
+            .iter()
+            .map(Rect::from)
+            .chain(stock_piece.waste_pieces.iter().map(Rect::from))
+            .chain(stock_piece.maximal_free_rects.iter().map(Rect::from));
+        for rect in rects {
+            assert!(
+                rect.x >= around.x + around.width
+                    || around.x >= rect.x + rect.width
+                    || rect.y >= around.y + around.length
+                    || around.y >= rect.y + rect.length
+            );
+        }
+    }
+
+    // A cut piece that only fits where the fixed piece is doesn't fit.
+    let result = Optimizer::new()
+        .add_stock_piece(stock_piece)
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            width: 40,
+            length: 40,
+            ..cut_piece
+        })
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn optimality_gap() {
+    let optimizer = |width, length, quantity| {
+        let mut optimizer = Optimizer::new();
+        optimizer
+            .add_stock_pieces(STOCK_PIECES.to_vec())
+            .add_cut_piece(CutPiece {
+                quantity,
+                external_id: Some(1),
+                width,
+                length,
+                can_rotate: false,
+                pattern_direction: PatternDirection::None,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: StockFilter::Any,
+                value: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                material: None,
+                thickness: None,
+                max_quantity: None,
+            })
+            .set_random_seed(1);
+        optimizer
+    };
+
+    // The cut pieces fill the smallest stock piece, so no solution can use less stock area.
+    let solution = optimizer(12, 12, 32).optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.optimality_gap, 0.0);
+
+    // A single small cut piece still needs a whole stock piece, which the lower bound accounts
+    // for.
+    let solution = optimizer(10, 10, 1).optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.optimality_gap, 0.0);
+
+    // The lower bound counts the cut pieces that need a second stock piece.
+    let solution = optimizer(48, 60, 3).optimize_guillotine(|_| {}).unwrap();
+    let stock_area: f64 = solution
+        .stock_pieces
+        .iter()
+        .map(|sp| (sp.width * sp.length) as f64)
+        .sum();
+    assert_eq!(
+        solution.optimality_gap,
+        (stock_area - 2.0 * 48.0 * 96.0) / stock_area
+    );
+}
+
+#[test]
+fn kerf_and_waste_areas() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            trim: Trim::uniform(2),
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_diagnostics(true)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        for stock_piece in &solution.stock_pieces {
+            // The area within the trim is split between the cut pieces, the cuts and the waste.
+            let used_area: u64 = stock_piece
+                .cut_pieces
+                .iter()
+                .map(|cp| (cp.width * cp.length) as u64)
+                .sum();
+            assert_eq!(
+                used_area + stock_piece.kerf_area + stock_piece.waste_area,
+                44 * 92
+            );
+            assert!(stock_piece.kerf_area > 0);
+            assert_eq!(
+                stock_piece.diagnostics.as_ref().unwrap().kerf_area,
+                stock_piece.kerf_area
+            );
+        }
+        assert_eq!(
+            solution.kerf_area(),
+            solution
+                .stock_pieces
+                .iter()
+                .map(|sp| sp.kerf_area)
+                .sum::<u64>()
+        );
+        assert_eq!(
+            solution.waste_area(),
+            solution
+                .stock_pieces
+                .iter()
+                .map(|sp| sp.waste_area)
+                .sum::<u64>()
+        );
+    }
+}
+
+#[test]
+fn separations() {
+    let cut_piece = |external_id| CutPiece {
+        quantity: 2,
+        external_id: Some(external_id),
+        width: 10,
+        length: 10,
+        can_rotate: false,
+        pattern_direction: PatternDirection::None,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces((1..=3).map(cut_piece))
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .add_separation(2, 1)
+        .add_separation(3, 3);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 6);
+        // The copies of cut piece 3 need a stock piece each.
+        assert!(solution.stock_pieces.len() >= 2);
+        for stock_piece in &solution.stock_pieces {
+            let count = |external_id| {
+                stock_piece
+                    .cut_pieces
+                    .iter()
+                    .filter(|cut_piece| cut_piece.external_id == Some(external_id))
+                    .count()
+            };
+            assert!(count(1) == 0 || count(2) == 0);
+            assert!(count(3) <= 1);
+        }
+    }
+}
+
+#[test]
+fn edge_banding_orientation() {
+    let cut_piece = CutPiece {
+        quantity: 1,
+        external_id: Some(1),
+        width: 40,
+        length: 10,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: EdgeBanding {
+            left: true,
+            top: true,
+            ..Default::default()
+        },
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+    let stock_piece = StockPiece {
+        width: 10,
+        length: 40,
+        ..STOCK_PIECES[0].clone()
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece)
+        .add_cut_piece(cut_piece)
+        .set_random_seed(1);
+
+    // The cut piece only fits rotated, which turns its left edge to the bottom and its top edge
+    // to the left.
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    let result_cut_piece = &solution.stock_pieces[0].cut_pieces[0];
+    assert!(result_cut_piece.is_rotated);
+    assert_eq!(
+        result_cut_piece.edge_banding,
+        EdgeBanding {
+            left: true,
+            bottom: true,
+            ..Default::default()
+        }
+    );
+
+    let solution = optimizer
+        .set_mirror(Mirror::Both)
+        .optimize_nested(|_| {})
+        .unwrap();
+    assert_eq!(
+        solution.stock_pieces[0].cut_pieces[0].edge_banding,
+        EdgeBanding {
+            right: true,
+            top: true,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn prefer_banded_edges_outward() {
+    let cut_piece = CutPiece {
+        quantity: 2,
+        external_id: Some(1),
+        width: 10,
+        length: 20,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: EdgeBanding {
+            right: true,
+            ..Default::default()
+        },
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+    let stock_piece = StockPiece {
+        width: 30,
+        length: 30,
+        ..STOCK_PIECES[0].clone()
+    };
+
+    // Whether a banded edge of `cut_piece` is next to the other cut piece.
+    let faces_cut_piece = |cut_piece: &ResultCutPiece, other: &ResultCutPiece| {
+        let banding = cut_piece.edge_banding;
+        let overlaps_x =
+            other.x < cut_piece.x + cut_piece.width && cut_piece.x < other.x + other.width;
+        let overlaps_y =
+            other.y < cut_piece.y + cut_piece.length && cut_piece.y < other.y + other.length;
+        (banding.left && other.x + other.width == cut_piece.x && overlaps_y)
+            || (banding.right && cut_piece.x + cut_piece.width == other.x && overlaps_y)
+            || (banding.bottom && other.y + other.length == cut_piece.y && overlaps_x)
+            || (banding.top && cut_piece.y + cut_piece.length == other.y && overlaps_x)
+    };
+
+    for seed in 0..4 {
+        let mut optimizer = Optimizer::new();
+        optimizer
+            .add_stock_piece(stock_piece.clone())
+            .add_cut_piece(cut_piece.clone())
+            .set_random_seed(seed)
+            .set_prefer_banded_edges_outward(true);
+
+        for solution in [
+            optimizer.optimize_guillotine(|_| {}).unwrap(),
+            optimizer.optimize_nested(|_| {}).unwrap(),
+        ] {
+            sanity_check_solution(&solution, 2);
+            let cut_pieces = &solution.stock_pieces[0].cut_pieces;
+            assert!(!faces_cut_piece(&cut_pieces[0], &cut_pieces[1]));
+            assert!(!faces_cut_piece(&cut_pieces[1], &cut_pieces[0]));
+        }
+    }
+}
+
+#[test]
+fn nominal_size() {
+    let stock_piece = StockPiece {
+        width: 1250,
+        length: 2500,
+        pattern_direction: PatternDirection::None,
+        price: 0,
+        quantity: None,
+        external_id: Some(1),
+        nominal_size: Some((1220, 2440)),
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = CutPiece {
+        quantity: 2,
+        external_id: Some(1),
+        width: 1250,
+        length: 1250,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece)
+        .add_cut_piece(cut_piece)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 2);
+        // The cut pieces only fit on one stock piece because it's packed at its usable size,
+        // which is bigger than its nominal size.
+        assert_eq!(solution.stock_pieces.len(), 1);
+        let stock_piece = &solution.stock_pieces[0];
+        assert_eq!((stock_piece.width, stock_piece.length), (1250, 2500));
+        assert_eq!(stock_piece.nominal_size, Some((1220, 2440)));
+    }
+}
+
+#[test]
+fn material() {
+    let stock_piece = |material| StockPiece {
+        external_id: material,
+        material,
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = |material| CutPiece {
+        quantity: 2,
+        external_id: material,
+        width: 20,
+        length: 30,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece(Some(1)))
+        .add_stock_piece(stock_piece(Some(2)))
+        .add_cut_piece(cut_piece(Some(1)))
+        .add_cut_piece(cut_piece(Some(2)))
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 4);
+        assert_eq!(solution.stock_pieces.len(), 2);
+        for stock_piece in &solution.stock_pieces {
+            assert_eq!(stock_piece.external_id, stock_piece.material);
+            for cut_piece in &stock_piece.cut_pieces {
+                assert_eq!(cut_piece.external_id, stock_piece.material);
+            }
+        }
+    }
+
+    // There's no stock piece of the material of this cut piece.
+    let result = optimizer
+        .add_cut_piece(cut_piece(None))
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn thickness() {
+    let stock_piece = |external_id, thickness| StockPiece {
+        external_id: Some(external_id),
+        thickness: Some(thickness),
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = |thickness| CutPiece {
+        quantity: 2,
+        external_id: Some(thickness),
+        width: 20,
+        length: 30,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: Some(thickness),
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece(1, 18))
+        .add_stock_piece(stock_piece(2, 12))
+        .add_cut_piece(cut_piece(18))
+        .add_cut_piece(cut_piece(12))
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 4);
+        assert_eq!(solution.stock_pieces.len(), 2);
+        for stock_piece in &solution.stock_pieces {
+            for cut_piece in &stock_piece.cut_pieces {
+                assert_eq!(cut_piece.external_id, stock_piece.thickness);
+            }
+        }
+    }
+
+    // There's no stock piece of the thickness of this cut piece.
+    let result = optimizer
+        .add_cut_piece(cut_piece(6))
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn must_use_stock_pieces() {
+    let optimizer = |must_use| {
+        let mut optimizer = Optimizer::new();
+        optimizer
+            .add_stock_piece(StockPiece {
+                external_id: Some(1),
+                price: 100,
+                ..STOCK_PIECES[0].clone()
+            })
+            .add_stock_piece(StockPiece {
+                width: 30,
+                length: 40,
+                quantity: Some(1),
+                external_id: Some(2),
+                must_use,
+                ..STOCK_PIECES[0].clone()
+            })
+            .add_cut_piece(CutPiece {
+                quantity: 1,
+                external_id: Some(1),
+                width: 40,
+                length: 90,
+                pattern_direction: PatternDirection::None,
+                can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: StockFilter::Any,
+                value: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                material: None,
+                thickness: None,
+                max_quantity: None,
+            })
+            .add_cut_piece(CutPiece {
+                quantity: 1,
+                external_id: Some(2),
+                width: 5,
+                length: 5,
+                pattern_direction: PatternDirection::None,
+                can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: StockFilter::Any,
+                value: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                material: None,
+                thickness: None,
+                max_quantity: None,
+            })
+            .set_cut_width(1)
+            .set_random_seed(1);
+        optimizer
+    };
+    let uses_offcut = |solution: &Solution| {
+        solution
+            .stock_pieces
+            .iter()
+            .any(|stock_piece| stock_piece.external_id == Some(2))
+    };
+
+    // Both cut pieces fit on one full sheet, so the offcut is only used if it has to be.
+    let optimizer_without = optimizer(false);
+    let optimizer_with = optimizer(true);
+    for (solution_without, solution_with) in [
+        (
+            optimizer_without.optimize_guillotine(|_| {}).unwrap(),
+            optimizer_with.optimize_guillotine(|_| {}).unwrap(),
+        ),
+        (
+            optimizer_without.optimize_nested(|_| {}).unwrap(),
+            optimizer_with.optimize_nested(|_| {}).unwrap(),
+        ),
+    ] {
+        sanity_check_solution(&solution_without, 2);
+        sanity_check_solution(&solution_with, 2);
+        assert!(!uses_offcut(&solution_without));
+        assert!(uses_offcut(&solution_with));
+    }
+}
+
+#[test]
+fn must_use_stock_piece_with_identical_stock_piece() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            quantity: Some(1),
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_stock_piece(StockPiece {
+            quantity: Some(1),
+            must_use: true,
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            external_id: Some(1),
+            width: 40,
+            length: 90,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1)
+        .set_random_seed(1);
+    assert_eq!(optimizer.stock_pieces.len(), 2);
+
+    // Each cut piece needs a sheet of its own, so both sheets are used, and neither is used
+    // twice.
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 2);
+        assert_eq!(solution.stock_pieces.len(), 2);
+    }
+}
+
+#[test]
+fn area_price() {
+    let flat_price = StockPiece {
+        external_id: Some(1),
+        price: 100,
+        ..STOCK_PIECES[0].clone()
+    };
+    let area_price = StockPiece {
+        external_id: Some(2),
+        price: 10,
+        area_price: Some(AreaPrice {
+            price: 5,
+            area: 100,
+        }),
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = |width, length| CutPiece {
+        quantity: 1,
+        external_id: None,
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    // A small cut piece is cheaper on the stock piece priced by area: 10 + 600 * 5 / 100 = 40.
+    // A large cut piece is cheaper on the flat priced stock piece: 10 + 3600 * 5 / 100 = 190.
+    for (cut_piece, external_id, price) in [
+        (cut_piece(20, 30), Some(2), 40),
+        (cut_piece(40, 90), Some(1), 100),
+    ] {
+        let mut optimizer = Optimizer::new();
+        optimizer
+            .add_stock_piece(flat_price.clone())
+            .add_stock_piece(area_price.clone())
+            .add_cut_piece(cut_piece)
+            .set_cut_width(1)
+            .set_random_seed(1);
+
+        for solution in [
+            optimizer.optimize_guillotine(|_| {}).unwrap(),
+            optimizer.optimize_nested(|_| {}).unwrap(),
+        ] {
+            sanity_check_solution(&solution, 1);
+            assert_eq!(solution.stock_pieces.len(), 1);
+            assert_eq!(solution.stock_pieces[0].external_id, external_id);
+            assert_eq!(solution.stock_pieces[0].price, price);
+            assert_eq!(solution.price, price);
+        }
+    }
+}
+
+#[test]
+fn recommend_stock() {
+    let stock_piece = |external_id, length, price| StockPiece {
+        external_id: Some(external_id),
+        width: 48,
+        length,
+        price,
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = |quantity, length| CutPiece {
+        quantity,
+        external_id: None,
+        width: 23,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    for mode in [OptimizeMode::Guillotine, OptimizeMode::Nested] {
+        // Two of the smaller stock pieces cost more than one of the larger stock pieces.
+        let mut optimizer = Optimizer::new();
+        optimizer
+            .add_stock_piece(stock_piece(1, 48, 30))
+            .add_stock_piece(stock_piece(2, 96, 50))
+            .add_cut_piece(cut_piece(4, 46))
+            .set_cut_width(1)
+            .set_random_seed(1);
+
+        let recommendation = optimizer.recommend_stock(mode, |_| {}).unwrap();
+        assert_eq!(recommendation.options.len(), 2);
+        let recommended = recommendation.recommended();
+        assert_eq!(
+            recommended.purchases,
+            vec![StockPurchase {
+                external_id: Some(2),
+                width: 48,
+                length: 96,
+                quantity: 1,
+                price: 50,
+            }]
+        );
+        assert_eq!(recommended.price, 50);
+        assert_eq!(recommended.stock_pieces, 1);
+        sanity_check_solution(&recommended.solution, 4);
+        assert_eq!(
+            recommendation.options[1].purchases,
+            vec![StockPurchase {
+                external_id: Some(1),
+                width: 48,
+                length: 48,
+                quantity: 2,
+                price: 60,
+            }]
+        );
+        sanity_check_solution(&recommendation.options[1].solution, 4);
+
+        // Stock sizes the cut pieces don't fit in aren't an option on their own, but they can
+        // still be mixed with the other stock sizes.
+        optimizer.add_cut_piece(cut_piece(1, 60));
+        let recommendation = optimizer.recommend_stock(mode, |_| {}).unwrap();
+        for option in &recommendation.options {
+            sanity_check_solution(&option.solution, 5);
+            assert!(option
+                .purchases
+                .iter()
+                .any(|purchase| purchase.external_id == Some(2)));
+        }
+        assert!(recommendation.recommended().price <= 100);
+
+        // There's no option if the cut pieces don't fit in any stock size.
+        optimizer.add_cut_piece(cut_piece(1, 100));
+        let result = optimizer.recommend_stock(mode, |_| {});
+        assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+    }
+}
+
+#[test]
+fn max_stock_pieces() {
+    let cut_piece = CutPiece {
+        quantity: 6,
+        external_id: None,
+        width: 23,
+        length: 46,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(cut_piece.clone())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_max_stock_pieces(2);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 6);
+        assert_eq!(solution.stock_pieces.len(), 2);
+    }
+
+    // Four cut pieces fit on each stock piece, so one stock piece isn't enough.
+    optimizer.set_max_stock_pieces(1);
+    for result in [
+        optimizer.optimize_guillotine(|_| {}),
+        optimizer.optimize_nested(|_| {}),
+    ] {
+        assert!(matches!(
+            result,
+            Err(Error::NoFitForCutPiece(
+                _,
+                PlacementSummary {
+                    stock_exhausted: true,
+                    ..
+                }
+            ))
+        ));
+    }
+
+    // With partial solutions, the cut pieces that fit on one stock piece are placed.
+    optimizer.set_allow_partial(true);
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 4);
+        assert_eq!(solution.stock_pieces.len(), 1);
+        assert_eq!(solution.unplaced_cut_pieces.len(), 1);
+        assert_eq!(solution.unplaced_cut_pieces[0].quantity, 2);
+    }
+
+    // A single cut piece can't be placed without any stock pieces.
+    let result = Optimizer::new()
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            ..cut_piece
+        })
+        .set_max_stock_pieces(0)
+        .optimize_guillotine(|_| {});
+    assert!(matches!(result, Err(Error::NoFitForCutPiece(..))));
+}
+
+#[test]
+fn equal_solutions_tie_break() {
+    let stock_piece = |length, price| StockPiece {
+        length,
+        price,
+        ..STOCK_PIECES[0].clone()
+    };
+    let cut_piece = CutPiece {
+        quantity: 2,
+        external_id: None,
+        width: 48,
+        length: 48,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    // Two of the smaller stock pieces and one of the larger stock pieces have the same price, and
+    // both are used completely, so the solution with fewer stock pieces is preferred.
+    for (stock_pieces, allow_mixed) in [
+        ([stock_piece(48, 10), stock_piece(96, 20)], true),
+        ([stock_piece(96, 20), stock_piece(48, 10)], true),
+        ([stock_piece(48, 10), stock_piece(96, 20)], false),
+        ([stock_piece(96, 20), stock_piece(48, 10)], false),
+    ] {
+        for seed in 1..=3 {
+            let mut optimizer = Optimizer::new();
+            optimizer
+                .add_stock_pieces(stock_pieces.clone())
+                .add_cut_piece(cut_piece.clone())
+                .allow_mixed_stock_sizes(allow_mixed)
+                .set_random_seed(seed);
+
+            for solution in [
+                optimizer.optimize_guillotine(|_| {}).unwrap(),
+                optimizer.optimize_nested(|_| {}).unwrap(),
+            ] {
+                sanity_check_solution(&solution, 2);
+                assert_eq!(solution.stock_pieces.len(), 1);
+                assert_eq!(solution.stock_pieces[0].length, 96);
+            }
+        }
+    }
+}
+
+#[test]
+fn optional_cut_pieces() {
+    let cut_piece = |external_id, quantity, max_quantity, width, length| CutPiece {
+        quantity,
+        external_id: Some(external_id),
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity,
+    };
+    let count = |solution: &Solution, external_id| {
+        solution
+            .stock_pieces
+            .iter()
+            .flat_map(|stock_piece| &stock_piece.cut_pieces)
+            .filter(|cut_piece| cut_piece.external_id == Some(external_id))
+            .count()
+    };
+
+    // The optional shelf blanks fill the rest of the stock piece, but don't need another one.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(cut_piece(1, 1, None, 48, 48))
+        .add_cut_piece(cut_piece(2, 1, Some(20), 23, 23))
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        assert_eq!(solution.stock_pieces.len(), 1);
+        assert_eq!(count(&solution, 1), 1);
+        assert_eq!(count(&solution, 2), 4);
+        sanity_check_solution(&solution, 5);
+        assert!(solution.unplaced_cut_pieces.is_empty());
+    }
+
+    // Optional cut pieces that don't fit in the space that's left aren't placed, and don't keep
+    // the required cut pieces from being placed.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(cut_piece(1, 2, None, 48, 48))
+        .add_cut_piece(cut_piece(2, 0, Some(5), 40, 40))
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        assert_eq!(solution.stock_pieces.len(), 1);
+        assert_eq!(count(&solution, 1), 2);
+        assert_eq!(count(&solution, 2), 0);
+        sanity_check_solution(&solution, 2);
+        assert!(solution.unplaced_cut_pieces.is_empty());
+    }
+}
+
+#[test]
+fn heuristic_names_from_public_enums() {
+    let heuristic = format!(
+        "{} {} {}",
+        guillotine::FreeRectChoiceHeuristic::BestAreaFit,
+        guillotine::SplitHeuristic::MinimizeArea,
+        guillotine::RotateCutPieceHeuristic::PreferRotated
+    );
+    assert_eq!(heuristic, "BestAreaFit MinimizeArea PreferRotated");
+    assert_eq!(guillotine::FreeRectChoiceHeuristic::ALL.len(), 7);
+    assert_eq!(maxrects::FreeRectChoiceHeuristic::ALL.len(), 5);
+
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_diagnostics(true)
+        .set_heuristic_profile(
+            OptimizeMode::Guillotine,
+            HeuristicProfile::Custom(vec![heuristic.clone()]),
+        )
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+    for stock_piece in &solution.stock_pieces {
+        let diagnostics = stock_piece.diagnostics.as_ref().unwrap();
+        assert_eq!(diagnostics.heuristics, vec![heuristic.clone()]);
+    }
+
+    let heuristic = format!(
+        "{} {}",
+        maxrects::FreeRectChoiceHeuristic::BottomLeftRule,
+        maxrects::RotateCutPieceHeuristic::PreferUpright
+    );
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_diagnostics(true)
+        .set_heuristic_profile(
+            OptimizeMode::Nested,
+            HeuristicProfile::Custom(vec![heuristic.clone()]),
+        )
+        .optimize_nested(|_| {})
+        .unwrap();
+
+    sanity_check_solution(&solution, CUT_PIECES.len());
+    for stock_piece in &solution.stock_pieces {
+        let diagnostics = stock_piece.diagnostics.as_ref().unwrap();
+        assert_eq!(diagnostics.heuristics, vec![heuristic.clone()]);
+    }
+}
+
+#[test]
+fn derive_random_seed() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1);
+
+    // Without a seed, the seed is 0 unless it's derived from the input.
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.random_seed, 0);
+
+    optimizer.set_derive_random_seed(true);
+    assert_eq!(optimizer.random_seed(), optimizer.fingerprint());
+    let derived = optimizer.optimize_guillotine(|_| {}).unwrap();
+    sanity_check_solution(&derived, CUT_PIECES.len());
+    assert_eq!(derived.random_seed, optimizer.fingerprint());
+
+    // The reported seed reproduces the solution.
+    let reproduced = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(derived.random_seed)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    assert_eq!(reproduced.random_seed, derived.random_seed);
+    assert_eq!(reproduced.fingerprint(), derived.fingerprint());
+
+    // A seed that was set is used instead of the derived one.
+    optimizer.set_random_seed(7);
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.random_seed, 7);
+
+    // A different input gets a different seed.
+    let mut other = Optimizer::new();
+    other
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES[1..].to_vec())
+        .set_cut_width(1)
+        .set_derive_random_seed(true);
+    assert_ne!(other.random_seed(), optimizer.fingerprint());
+}
+
+#[test]
+fn compact_solution() {
+    let mut solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_compact(true)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    sanity_check_solution(&solution, CUT_PIECES.len());
+
+    // The optimizer already compacted the layouts, so they don't change.
+    let compacted = solution.clone().compact(1, 1).clone();
+    assert_eq!(compacted.fingerprint(), solution.fingerprint());
+
+    // Move a cut piece away from the corner, leaving a gap that compacting closes.
+    solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES[..1].to_vec())
+        .add_cut_pieces(CUT_PIECES[..1].to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    let sheet_id = solution.stock_pieces[0].sheet_id.clone();
+    let cut_piece = &mut solution.stock_pieces[0].cut_pieces[0];
+    assert_eq!((cut_piece.x, cut_piece.y), (0, 0));
+    cut_piece.x = 10;
+    cut_piece.y = 30;
+
+    solution.compact(1, 1);
+    sanity_check_solution(&solution, 1);
+    let stock_piece = &solution.stock_pieces[0];
+    let cut_piece = &stock_piece.cut_pieces[0];
+    assert_eq!((cut_piece.x, cut_piece.y), (0, 0));
+    assert_eq!(stock_piece.sheet_id, sheet_id);
+    assert_eq!(
+        stock_piece.waste_area,
+        stock_piece
+            .waste_pieces
+            .iter()
+            .map(|waste_piece| waste_piece.width as u64 * waste_piece.length as u64)
+            .sum::<u64>()
+    );
+    assert!(stock_piece
+        .waste_pieces
+        .iter()
+        .all(|waste_piece| waste_piece.x > cut_piece.width || waste_piece.y > cut_piece.length));
+}
+
+/// A xorshift random number generator, for testing `Optimizer::set_rng`.
+struct XorShiftRng(u64);
+
+impl RngCore for XorShiftRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> std::result::Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for XorShiftRng {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        // Xorshift gets stuck at 0.
+        XorShiftRng(u64::from_le_bytes(seed) | 1)
+    }
+}
+
+#[test]
+fn custom_rng() {
+    let optimize = |optimizer: &mut Optimizer| {
+        optimizer
+            .add_stock_pieces(STOCK_PIECES.to_vec())
+            .add_cut_pieces(CUT_PIECES.to_vec())
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .optimize_guillotine(|_| {})
+            .unwrap()
+    };
+
+    // `StdRng` is the default.
+    let solution = optimize(&mut Optimizer::new());
+    let std_rng_solution = optimize(Optimizer::new().set_rng::<StdRng>());
+    assert_eq!(std_rng_solution.fingerprint(), solution.fingerprint());
+
+    let solution = optimize(Optimizer::new().set_rng::<XorShiftRng>());
+    sanity_check_solution(&solution, CUT_PIECES.len());
+    let again = optimize(Optimizer::new().set_rng::<XorShiftRng>());
+    assert_eq!(again.fingerprint(), solution.fingerprint());
+
+    // The factory is used for every random number generator.
+    let seeds = Arc::new(Mutex::new(Vec::new()));
+    let factory_seeds = seeds.clone();
+    let solution = optimize(Optimizer::new().set_rng_factory(move |seed| {
+        factory_seeds.lock().unwrap().push(seed);
+        Box::new(XorShiftRng::seed_from_u64(seed))
+    }));
+    sanity_check_solution(&solution, CUT_PIECES.len());
+    assert!(seeds.lock().unwrap().contains(&1));
+    assert!(seeds.lock().unwrap().len() > 1);
+}
+
+#[test]
+fn stock_piece_cut_tree() {
+    fn collect_cut_pieces(node: &CutNode, cut_pieces: &mut Vec<usize>) {
+        match node {
+            CutNode::Cut { first, second, .. } => {
+                collect_cut_pieces(first, cut_pieces);
+                collect_cut_pieces(second, cut_pieces);
+            }
+            CutNode::CutPiece(i) => cut_pieces.push(*i),
+            CutNode::FixedPiece(_) | CutNode::Waste(_) => (),
+        }
+    }
+
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_cut_piece_order(CutPieceOrder::XThenY)
+        .set_mirror(Mirror::Horizontal)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+
+    for stock_piece in &solution.stock_pieces {
+        // Every cut piece is a leaf of the tree once, by its index after sorting.
+        let cut_tree = stock_piece.cut_tree.as_ref().unwrap();
+        let mut cut_pieces = Vec::new();
+        collect_cut_pieces(cut_tree, &mut cut_pieces);
+        cut_pieces.sort_unstable();
+        assert_eq!(
+            cut_pieces,
+            (0..stock_piece.cut_pieces.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            cut_tree.cuts().is_empty(),
+            stock_piece.cut_pieces.len() == 1
+                && stock_piece.cut_pieces[0].width == stock_piece.width
+                && stock_piece.cut_pieces[0].length == stock_piece.length
+        );
+    }
+}
+
+#[test]
+fn heuristic_pass_stats() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert!(solution.pass_stats.is_empty());
+
+    let solution = optimizer
+        .set_diagnostics(true)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    assert!(!solution.pass_stats.is_empty());
+    for pass in &solution.pass_stats {
+        assert!(!pass.heuristics.is_empty());
+        for stats in &pass.heuristics {
+            assert!(stats.layouts > 0);
+            assert!(stats.mean_fitness <= stats.best_fitness);
+            assert!(stats.best_fitness <= pass.best_fitness);
+        }
+    }
+
+    // The heuristics that placed the cut pieces of the solution were counted for the pass that
+    // found it.
+    let pass = solution
+        .pass_stats
+        .iter()
+        .find(|pass| pass.best_fitness == solution.fitness)
+        .unwrap();
+    for stock_piece in &solution.stock_pieces {
+        for name in &stock_piece.diagnostics.as_ref().unwrap().heuristics {
+            let stats = pass.heuristics.iter().find(|stats| &stats.name == name);
+            assert!(stats.is_some_and(|stats| stats.winning_stock_pieces > 0));
+        }
+    }
+}
+
+#[test]
+fn result_detail() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_diagnostics(true);
+    let full = optimizer.optimize_guillotine_top_n(3, |_| {}).unwrap();
+    assert!(full.len() > 1);
+    assert!(full[0]
+        .stock_pieces
+        .iter()
+        .all(|stock_piece| stock_piece.cut_tree.is_some() && stock_piece.diagnostics.is_some()));
+
+    let pieces_only = optimizer
+        .set_result_detail(ResultDetail::PiecesOnly)
+        .optimize_guillotine_top_n(3, |_| {})
+        .unwrap();
+    let summary = optimizer
+        .set_result_detail(ResultDetail::Summary)
+        .optimize_guillotine_top_n(3, |_| {})
+        .unwrap();
+    assert_eq!(pieces_only.len(), full.len());
+    assert_eq!(summary.len(), full.len());
+
+    for ((full, pieces_only), summary) in full.iter().zip(&pieces_only).zip(&summary) {
+        assert_eq!(pieces_only.fitness, full.fitness);
+        assert_eq!(summary.fitness, full.fitness);
+        for ((full, pieces_only), summary) in full
+            .stock_pieces
+            .iter()
+            .zip(&pieces_only.stock_pieces)
+            .zip(&summary.stock_pieces)
+        {
+            assert_eq!(pieces_only.cut_pieces, full.cut_pieces);
+            assert_eq!(pieces_only.sheet_id, full.sheet_id);
+            assert_eq!(summary.sheet_id, full.sheet_id);
+            assert_eq!(summary.utilization, full.utilization);
+            assert_eq!(summary.waste_area, full.waste_area);
+            assert!(summary.cut_pieces.is_empty());
+
+            for stock_piece in [pieces_only, summary] {
+                assert!(stock_piece.waste_pieces.is_empty());
+                assert!(stock_piece.reusable_offcuts.is_empty());
+                assert!(stock_piece.scrap.is_empty());
+                assert!(stock_piece.maximal_free_rects.is_empty());
+                assert_eq!(stock_piece.cut_tree, None);
+                assert_eq!(stock_piece.diagnostics, None);
+            }
+        }
+    }
+}
+
+#[test]
+fn solution_stats() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    let stats = solution.stats();
+
+    let stock_area: u64 = solution
+        .stock_pieces
+        .iter()
+        .map(|stock_piece| stock_piece.width as u64 * stock_piece.length as u64)
+        .sum();
+    let used_area: u64 = CUT_PIECES
+        .iter()
+        .map(|cut_piece| (cut_piece.width * cut_piece.length * cut_piece.quantity) as u64)
+        .sum();
+    assert_eq!(stats.stock_pieces, solution.stock_pieces.len());
+    assert_eq!(stats.stock_area, stock_area);
+    assert_eq!(stats.used_area, used_area);
+    assert_eq!(stats.waste_area, solution.waste_area());
+    assert_eq!(stats.kerf_area, solution.kerf_area());
+    assert_eq!(stats.cut_length, solution.cut_length());
+    assert_eq!(
+        stats.stock_area,
+        stats.used_area + stats.waste_area + stats.kerf_area
+    );
+    assert_eq!(
+        stats.price,
+        solution
+            .stock_pieces
+            .iter()
+            .map(|sp| sp.price)
+            .sum::<usize>()
+    );
+    assert!(
+        (stats.utilization_percent - used_area as f64 * 100.0 / stock_area as f64).abs() < 1e-9
+    );
+    assert_eq!(stats.sheets.len(), solution.stock_pieces.len());
+    for (sheet, stock_piece) in stats.sheets.iter().zip(&solution.stock_pieces) {
+        assert_eq!(sheet.serial_number, stock_piece.serial_number);
+        assert_eq!(sheet.cut_length, stock_piece.cut_length);
+        assert_eq!(
+            sheet.used_area,
+            stock_piece
+                .cut_pieces
+                .iter()
+                .map(|cut_piece| (cut_piece.width * cut_piece.length) as u64)
+                .sum::<u64>()
+        );
+        assert!((sheet.utilization_percent - stock_piece.utilization * 100.0).abs() < 1e-9);
+    }
+
+    // The stats don't need the layout.
+    let summary = optimizer
+        .set_result_detail(ResultDetail::Summary)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    assert_eq!(summary.stats(), stats);
+}
+
+#[test]
+fn pipeline_stages() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    // The stages give the same solution as optimizing with a single stock size.
+    let prepared = pipeline::Prepared::new(&optimizer, OptimizeMode::Guillotine).unwrap();
+    let mut population = prepared.initial_population().unwrap();
+    assert!(!population.is_empty());
+    population.evolve(100);
+    let fitnesses = population.fitnesses();
+    assert!(fitnesses.windows(2).all(|pair| pair[0] >= pair[1]));
+    let layout = population.select().unwrap();
+    assert_eq!(layout.fitness(), fitnesses[0]);
+    let solution = layout.build_solution().unwrap();
+    let optimized = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert!(solution.has_same_layout(&optimized));
+    assert_eq!(solution.fitness, optimized.fitness);
+
+    // A custom step can add the layout of a solution it improved.
+    let mut compacted = solution.clone();
+    compacted.compact(1, 1);
+    let len = population.len();
+    population.add_solution(&compacted, 2);
+    assert_eq!(population.len(), len + 2);
+    let solution = population.select().unwrap().build_solution().unwrap();
+    sanity_check_solution(&solution, CUT_PIECES.iter().map(|p| p.quantity).sum());
+}
+
+#[test]
+fn pipeline_evolve_until() {
+    use std::sync::{Arc, Mutex};
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+    let prepared = pipeline::Prepared::new(&optimizer, OptimizeMode::Nested).unwrap();
+
+    // The clock is only read when the elapsed time is asked for.
+    let stats = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&stats);
+    let mut population = prepared.initial_population().unwrap();
+    let len = population.len();
+    population.evolve_until(move |stats: &pipeline::EpochStats| {
+        recorded.lock().unwrap().push(*stats);
+        stats.epoch >= 3
+    });
+    assert_eq!(population.len(), len);
+    let stats = stats.lock().unwrap();
+    assert!(stats.len() <= 4);
+    assert!(stats.iter().all(|stats| stats.elapsed.is_none()));
+    assert!(stats
+        .windows(2)
+        .all(|pair| pair[1].best_fitness >= pair[0].best_fitness));
+
+    let elapsed = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&elapsed);
+    prepared
+        .initial_population()
+        .unwrap()
+        .set_measure_elapsed(true)
+        .evolve_until(move |stats| {
+            recorded.lock().unwrap().push(stats.elapsed);
+            true
+        });
+    assert!(elapsed.lock().unwrap().iter().all(Option::is_some));
+}
+
+#[test]
+fn pipeline_errors() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            quantity: Some(1),
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            width: 40,
+            length: 90,
+            ..CUT_PIECES[0].clone()
+        })
+        .set_cut_width(1)
+        .set_random_seed(1);
+    // Only one of the cut pieces fits on the one stock piece there is.
+    let prepared = pipeline::Prepared::new(&optimizer, OptimizeMode::Nested).unwrap();
+    let mut population = prepared.initial_population().unwrap();
+    assert!(matches!(
+        population.evolve(10).select(),
+        Err(Error::NoFitForCutPiece(..))
+    ));
+
+    optimizer.set_heuristic_profile(OptimizeMode::Nested, HeuristicProfile::Custom(Vec::new()));
+    assert!(matches!(
+        pipeline::Prepared::new(&optimizer, OptimizeMode::Nested),
+        Err(Error::EmptyHeuristicProfile(OptimizeMode::Nested))
+    ));
+}
+
+#[test]
+fn solution_to_csv() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(CutPiece {
+            external_id: Some(1),
+            width: 48,
+            length: 30,
+            can_rotate: false,
+            tag: Some("door, \"left\"".to_string()),
+            ..CUT_PIECES[0].clone()
+        })
+        .add_cut_piece(CutPiece {
+            external_id: None,
+            width: 48,
+            length: 20,
+            can_rotate: false,
+            tag: None,
+            ..CUT_PIECES[0].clone()
+        })
+        .set_cut_width(1);
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    let stock_piece = &solution.stock_pieces[0];
+    let csv = solution.to_csv();
+    let sections: Vec<&str> = csv.split("\n\n").collect();
+    assert_eq!(sections.len(), 2);
+
+    let rows: Vec<&str> = sections[0].lines().collect();
+    assert_eq!(
+        rows[0],
+        "sheet,sheet_id,external_id,tag,x,y,width,length,rotated"
+    );
+    assert_eq!(rows.len(), 3);
+    for (row, cut_piece) in rows[1..].iter().zip(&stock_piece.cut_pieces) {
+        let (id, tag) = match cut_piece.external_id {
+            Some(1) => ("1", "\"door, \"\"left\"\"\""),
+            _ => ("", ""),
+        };
+        assert_eq!(
+            *row,
+            format!(
+                "1,{},{},{},0,{},48,{},false",
+                stock_piece.sheet_id, id, tag, cut_piece.y, cut_piece.length
+            )
+        );
+    }
+
+    let rows: Vec<&str> = sections[1].lines().collect();
+    assert_eq!(rows[0], "sheet,sheet_id,x,y,width,length");
+    assert_eq!(rows.len(), 1 + stock_piece.reusable_offcuts.len());
+    for (row, offcut) in rows[1..].iter().zip(&stock_piece.reusable_offcuts) {
+        assert_eq!(
+            *row,
+            format!(
+                "1,{},{},{},{},{}",
+                stock_piece.sheet_id, offcut.x, offcut.y, offcut.width, offcut.length
+            )
+        );
+    }
+    assert!(csv.ends_with('\n'));
+}
+
+#[test]
+fn optimizer_fingerprint_is_portable() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 40,
+            length: 40,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1);
+
+    // Each value is hashed as a little-endian `u64` with 64-bit FNV-1a, so the fingerprint is
+    // the same on every platform.
+    assert_eq!(optimizer.fingerprint(), 0xf831_d9c1_6a09_f7b5);
+
+    let fingerprint = optimizer.fingerprint();
+    optimizer.set_cut_width(2);
+    assert_ne!(optimizer.fingerprint(), fingerprint);
+}
+
+#[test]
+fn compact_keeps_threshold_and_detail() {
+    let optimize = |result_detail| {
+        let mut solution = Optimizer::new()
+            .add_stock_pieces(STOCK_PIECES[..1].to_vec())
+            .add_cut_pieces(CUT_PIECES[..1].to_vec())
+            .set_cut_widths(2, 1)
+            .set_min_reusable_waste(10, 50)
+            .set_result_detail(result_detail)
+            .set_random_seed(1)
+            .optimize_guillotine(|_| {})
+            .unwrap();
+        let cut_piece = &mut solution.stock_pieces[0].cut_pieces[0];
+        cut_piece.x = 10;
+        cut_piece.y = 30;
+        solution.compact(2, 1);
+        sanity_check_solution(&solution, 1);
+        assert_eq!(solution.min_reusable_waste, (10, 50));
+        assert_eq!(solution.result_detail, result_detail);
+        let cut_piece = &solution.stock_pieces[0].cut_pieces[0];
+        assert_eq!((cut_piece.x, cut_piece.y), (0, 0));
+        solution
+    };
+
+    // The waste pieces are split by the threshold the solution was optimized with.
+    let solution = optimize(ResultDetail::Full);
+    let stock_piece = &solution.stock_pieces[0];
+    assert!(stock_piece.cut_tree.is_some());
+    assert!(!stock_piece.waste_pieces.is_empty());
+    let is_reusable = |waste_piece: &WastePiece| {
+        let (width, length) = (waste_piece.width, waste_piece.length);
+        (width >= 10 && length >= 50) || (width >= 50 && length >= 10)
+    };
+    assert!(stock_piece.reusable_offcuts.iter().all(is_reusable));
+    assert!(!stock_piece.scrap.iter().any(is_reusable));
+    assert_eq!(
+        stock_piece.reusable_offcuts.len() + stock_piece.scrap.len(),
+        stock_piece.waste_pieces.len()
+    );
+
+    // The details that were left out of the solution aren't added back.
+    let solution = optimize(ResultDetail::PiecesOnly);
+    let stock_piece = &solution.stock_pieces[0];
+    assert!(stock_piece.waste_pieces.is_empty());
+    assert!(stock_piece.reusable_offcuts.is_empty());
+    assert!(stock_piece.scrap.is_empty());
+    assert!(stock_piece.maximal_free_rects.is_empty());
+    assert!(stock_piece.cut_tree.is_none());
+}
+
+#[test]
+fn quantity_groups() {
+    let cut_piece = |width, length, quantity| CutPiece {
+        quantity,
+        external_id: Some(1),
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    // Each group of copies counts once toward the maximum.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .set_max_cut_pieces(9)
+        .set_quantity_group_size(4)
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_piece(cut_piece(10, 20, 40))
+        .set_cut_width(1);
+    assert!(matches!(
+        optimizer.optimize_guillotine(|_| {}),
+        Err(Error::TooManyCutPieces(10))
+    ));
+    optimizer.set_quantity_group_size(5);
+    assert!(optimizer.pending_cut_pieces.is_empty());
+    assert_eq!(optimizer.rotatable_cut_pieces().len(), 8);
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    sanity_check_solution(&solution, 40);
+    assert_eq!(optimizer.estimate().unplaced_cut_pieces, 0);
+
+    // Groups are only as large as the stock pieces can fit, which is 3 of these along the length.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .set_quantity_group_size(10)
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(cut_piece(40, 30, 10))
+        .set_cut_width(1);
+    let cut_pieces = optimizer.rotatable_cut_pieces();
+    let sizes: Vec<(usize, usize)> = cut_pieces
+        .iter()
+        .map(|cut_piece| (cut_piece.width, cut_piece.length))
+        .collect();
+    assert_eq!(sizes, [(40, 92), (40, 92), (40, 92), (40, 30)]);
+    let solution = optimizer.optimize_nested(|_| {}).unwrap();
+    sanity_check_solution(&solution, 10);
+    assert!(solution
+        .stock_pieces
+        .iter()
+        .flat_map(|stock_piece| &stock_piece.cut_pieces)
+        .all(|cut_piece| (cut_piece.width, cut_piece.length) == (40, 30)
+            && cut_piece.external_id == Some(1)));
+}
+
+#[test]
+fn match_groups() {
+    let cut_piece = |external_id, length, position: Option<usize>| CutPiece {
+        quantity: 1,
+        external_id: Some(external_id),
+        width: 20,
+        length,
+        pattern_direction: PatternDirection::ParallelToLength,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: position.map(|position| MatchGroup { id: 1, position }),
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+    let stock_piece = StockPiece {
+        width: 48,
+        length: 96,
+        pattern_direction: PatternDirection::ParallelToLength,
+        ..STOCK_PIECES[0].clone()
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece.clone())
+        .add_cut_pieces([
+            cut_piece(1, 15, Some(2)),
+            cut_piece(2, 10, Some(0)),
+            cut_piece(3, 8, Some(1)),
+            cut_piece(4, 30, None),
+            cut_piece(5, 25, None),
+        ])
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 5);
+        let mut group: Vec<&ResultCutPiece> = solution
+            .stock_pieces
+            .iter()
+            .flat_map(|stock_piece| &stock_piece.cut_pieces)
+            .filter(|cut_piece| matches!(cut_piece.external_id, Some(1..=3)))
+            .collect();
+        group.sort_by_key(|cut_piece| cut_piece.y);
+
+        // The match group is on one stock piece, in order along the grain with a cut between each
+        // cut piece.
+        assert_eq!(
+            group.iter().map(|cp| cp.external_id).collect::<Vec<_>>(),
+            vec![Some(2), Some(3), Some(1)]
+        );
+        assert!(solution
+            .stock_pieces
+            .iter()
+            .any(|stock_piece| stock_piece.cut_pieces.len() >= 3
+                && group.iter().all(|cp| stock_piece.cut_pieces.contains(cp))));
+        for pair in group.windows(2) {
+            assert_eq!(pair[0].x, pair[1].x);
+            assert_eq!(pair[0].y + pair[0].length + 1, pair[1].y);
+        }
+    }
+
+    // Rotating the match group to match the pattern direction of the stock piece turns it a
+    // quarter turn counterclockwise, so the grain runs from right to left.
+    let solution = Optimizer::new()
+        .add_stock_piece(StockPiece {
+            pattern_direction: PatternDirection::ParallelToWidth,
+            ..stock_piece.clone()
+        })
+        .add_cut_pieces([
+            cut_piece(1, 15, Some(2)),
+            cut_piece(2, 10, Some(0)),
+            cut_piece(3, 8, Some(1)),
+        ])
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    sanity_check_solution(&solution, 3);
+    let mut group: Vec<&ResultCutPiece> = solution.stock_pieces[0].cut_pieces.iter().collect();
+    group.sort_by_key(|cut_piece| cut_piece.x);
+    assert_eq!(
+        group.iter().map(|cp| cp.external_id).collect::<Vec<_>>(),
+        vec![Some(1), Some(3), Some(2)]
+    );
+    for pair in group.windows(2) {
+        assert!(pair[0].is_rotated);
+        assert_eq!(pair[0].y, pair[1].y);
+        assert_eq!(pair[0].x + pair[0].width + 1, pair[1].x);
+    }
+
+    let mut builder = OptimizerBuilder::new();
+    builder.add_stock_piece(stock_piece).add_cut_pieces([
+        cut_piece(1, 15, Some(0)),
+        CutPiece {
+            width: 21,
+            ..cut_piece(2, 10, Some(1))
+        },
+    ]);
+    let Err(Error::InvalidInput(errors)) = builder.build() else {
+        panic!("expected invalid input");
+    };
+    assert!(matches!(errors[..], [InputError::MismatchedMatchGroup(1)]));
+}
+
+#[test]
+fn result_stock_piece_to_stock_piece() {
+    let cut_piece = |external_id, quantity| CutPiece {
+        quantity,
+        external_id: Some(external_id),
+        width: 12,
+        length: 12,
+        can_rotate: false,
+        pattern_direction: PatternDirection::None,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(cut_piece(1, 10))
+        .set_cut_width(1)
+        .set_random_seed(1);
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    let frozen = &solution.stock_pieces[0];
+    let stock_piece = frozen.to_stock_piece(0);
+    assert_eq!(stock_piece.quantity, Some(1));
+    assert_eq!(stock_piece.fixed_pieces.len(), frozen.cut_pieces.len());
+
+    // More cut pieces are placed in the waste of the stock piece, around the ones already on it.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece)
+        .add_cut_piece(cut_piece(2, 4))
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        sanity_check_solution(&solution, 4);
+        let stock_piece = &solution.stock_pieces[0];
+        assert_eq!(
+            stock_piece
+                .fixed_pieces
+                .iter()
+                .map(|fixed_piece| (fixed_piece.x, fixed_piece.y, fixed_piece.external_id))
+                .collect::<Vec<_>>(),
+            frozen
+                .cut_pieces
+                .iter()
+                .map(|cut_piece| (cut_piece.x, cut_piece.y, cut_piece.external_id))
+                .collect::<Vec<_>>()
+        );
+        for cut_piece in &stock_piece.cut_pieces {
+            assert!(stock_piece.fixed_pieces.iter().all(|fixed_piece| {
+                cut_piece.x > fixed_piece.x + fixed_piece.width
+                    || fixed_piece.x > cut_piece.x + cut_piece.width
+                    || cut_piece.y > fixed_piece.y + fixed_piece.length
+                    || fixed_piece.y > cut_piece.y + cut_piece.length
+            }));
+        }
+    }
+}
+
+#[test]
+fn nested_strip_direction() {
+    for direction in [
+        CutDirection::ParallelToLength,
+        CutDirection::ParallelToWidth,
+    ] {
+        let solution = Optimizer::new()
+            .add_stock_pieces(STOCK_PIECES.to_vec())
+            .add_cut_pieces(CUT_PIECES.to_vec())
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .set_nested_strip_direction(direction)
+            .optimize_nested(|_| {})
+            .unwrap();
+        sanity_check_solution(&solution, CUT_PIECES.len());
+    }
+}
+
+#[test]
+fn sheet_ids() {
+    let optimize = |length| {
+        Optimizer::new()
+            .add_stock_piece(STOCK_PIECES[0].clone())
+            .add_cut_piece(CutPiece {
+                quantity: 6,
+                external_id: Some(1),
+                width: 40,
+                length,
+                pattern_direction: PatternDirection::None,
+                can_rotate: false,
+                tag: None,
+                max_edge_distance: None,
+                stock_filter: StockFilter::Any,
+                value: None,
+                min_grade: None,
+                edge_banding: Default::default(),
+                match_group: None,
+                material: None,
+                thickness: None,
+                max_quantity: None,
+            })
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .optimize_guillotine(|_| {})
+            .unwrap()
+    };
+
+    let solution = optimize(40);
+    assert!(solution.stock_pieces.len() > 1);
+    let fingerprint = solution.fingerprint();
+    for stock_piece in &solution.stock_pieces {
+        assert_eq!(
+            stock_piece.sheet_id,
+            format!("{fingerprint:016x}-{}", stock_piece.serial_number)
+        );
+    }
+
+    // The same layout gets the same sheet IDs, and a different one gets different ones.
+    let sheet_ids = |solution: &Solution| {
+        solution
+            .stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.sheet_id.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sheet_ids(&optimize(40)), sheet_ids(&solution));
+    let other = optimize(41);
+    assert_ne!(other.fingerprint(), fingerprint);
+    assert_ne!(sheet_ids(&other)[0], sheet_ids(&solution)[0]);
+
+    // The fingerprint only depends on the values in the layout, so it's the same on every
+    // platform, whatever the size and byte order of `usize`.
+    let mut layout = solution.clone();
+    layout.stock_pieces.truncate(1);
+    let stock_piece = &mut layout.stock_pieces[0];
+    stock_piece.width = 48;
+    stock_piece.length = 96;
+    stock_piece.external_id = None;
+    stock_piece.cut_pieces.truncate(1);
+    let cut_piece = &mut stock_piece.cut_pieces[0];
+    cut_piece.external_id = Some(1);
+    cut_piece.x = 0;
+    cut_piece.y = 41;
+    cut_piece.width = 40;
+    cut_piece.length = 40;
+    cut_piece.is_rotated = false;
+    assert_eq!(layout.fingerprint(), 0xd11f_b3e7_2d63_ca5d);
+}
+
+#[test]
+fn solution_cut_length() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(CutPiece {
+            quantity: 1,
+            external_id: Some(1),
+            width: 10,
+            length: 10,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_cut_width(1);
+
+    // A cut across the whole stock piece, then one across the strip the cut piece is in.
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.stock_pieces[0].cut_length, 96 + 10);
+    assert_eq!(solution.cut_length(), 96 + 10);
+
+    // The two sides of the cut piece that aren't on an edge of the stock piece.
+    let solution = optimizer.optimize_nested(|_| {}).unwrap();
+    assert_eq!(solution.cut_length(), 10 + 10);
+
+    // Strips across the stock piece that are a cut apart are separated by one cut, and the second
+    // one is cut off the rest of the stock piece.
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(STOCK_PIECES[0].clone())
+        .add_cut_piece(CutPiece {
+            quantity: 2,
+            width: 48,
+            length: 10,
+            can_rotate: false,
+            ..CUT_PIECES[0].clone()
+        })
+        .set_cut_width(1);
+    let solution = optimizer.optimize_nested(|_| {}).unwrap();
+    assert_eq!(solution.cut_length(), 48 + 48);
+    assert_eq!(solution.stats().cut_length, solution.cut_length());
+}
+
+#[test]
+fn guillotine_anytime() {
+    use std::cell::RefCell;
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    let num_cut_pieces = CUT_PIECES.iter().map(|p| p.quantity).sum();
+    let fitnesses = RefCell::new(Vec::new());
+    let solution = optimizer
+        .optimize_guillotine_anytime(
+            |_| {},
+            |solution| {
+                sanity_check_solution(solution, num_cut_pieces);
+                fitnesses.borrow_mut().push(solution.fitness);
+            },
+        )
+        .unwrap();
+
+    // Each reported solution is better than the one before it, and the optimization returns the
+    // same solution as without the callback.
+    let fitnesses = fitnesses.into_inner();
+    assert!(!fitnesses.is_empty());
+    assert!(fitnesses.windows(2).all(|w| w[0] < w[1]));
+    let best = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.fitness, best.fitness);
+    assert_eq!(solution.price, best.price);
+}
+
+#[test]
+fn solution_snapshot() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let snapshot = SolutionSnapshot::new();
+    assert!(snapshot.latest().is_none());
+    assert_eq!(snapshot.version(), 0);
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1)
+        .set_solution_snapshot(snapshot.clone());
+
+    let num_cut_pieces = CUT_PIECES.iter().map(|p| p.quantity).sum();
+    let done = AtomicBool::new(false);
+    let solution = thread::scope(|scope| {
+        // Read the snapshot from another thread while the optimization runs.
+        let reader = scope.spawn(|| {
+            let mut fitnesses = Vec::new();
+            while !done.load(Ordering::Acquire) {
+                if let Some(solution) = snapshot.latest() {
+                    sanity_check_solution(&solution, num_cut_pieces);
+                    fitnesses.push(solution.fitness);
+                }
+                thread::yield_now();
+            }
+            fitnesses
+        });
+
+        let solution = optimizer.optimize_nested(|_| {}).unwrap();
+        done.store(true, Ordering::Release);
+
+        // The solutions that are read only get better.
+        let fitnesses = reader.join().unwrap();
+        assert!(fitnesses.windows(2).all(|w| w[0] <= w[1]));
+        solution
+    });
+
+    // The snapshot holds the solution with the highest fitness, which is built when it's read.
+    let latest = snapshot.latest().unwrap();
+    sanity_check_solution(&latest, num_cut_pieces);
+    assert!(snapshot.version() > 0);
+    assert_eq!(Some(latest.fitness), snapshot.fitness());
+    assert!(latest.fitness >= solution.fitness);
+
+    // The snapshot is cleared when the next optimization starts.
+    let _ = Optimizer::new()
+        .set_solution_snapshot(snapshot.clone())
+        .optimize_nested(|_| {});
+    assert!(snapshot.latest().is_none());
+    assert!(snapshot.fitness().is_none());
+}
+
+#[test]
+fn optimize_from_previous_solution() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+    let previous = optimizer.optimize_guillotine(|_| {}).unwrap();
+
+    // Add a cut piece to the job, and start from the previous layout.
+    optimizer.add_cut_piece(CutPiece {
+        quantity: 1,
+        external_id: Some(100),
+        width: 5,
+        length: 5,
+        pattern_direction: PatternDirection::None,
+        can_rotate: true,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: None,
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    });
+    let num_cut_pieces = CUT_PIECES.iter().map(|p| p.quantity).sum::<usize>() + 1;
+
+    let solution = optimizer
+        .optimize_guillotine_from(&previous, |_| {})
+        .unwrap();
+    sanity_check_solution(&solution, num_cut_pieces);
+    assert!(solution.stock_pieces.len() <= previous.stock_pieces.len() + 1);
+
+    let previous = optimizer.optimize_nested(|_| {}).unwrap();
+    let solution = optimizer.optimize_nested_from(&previous, |_| {}).unwrap();
+    sanity_check_solution(&solution, num_cut_pieces);
+    assert!(solution.price <= previous.price);
+}
+
+#[test]
+fn fitness_objectives() {
+    let stock_piece = |width, price| StockPiece {
+        width,
+        length: 96,
+        pattern_direction: PatternDirection::None,
+        price,
+        quantity: None,
+        external_id: None,
+        seams: Vec::new(),
+        trim: Trim::default(),
+        min_leftover_area: None,
+        source: None,
+        grade: None,
+        fixed_pieces: Vec::new(),
+        nominal_size: None,
+        material: None,
+        thickness: None,
+        must_use: false,
+        area_price: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(stock_piece(48, 10))
+        .add_stock_piece(stock_piece(96, 100))
+        .add_cut_piece(CutPiece {
+            quantity: 4,
+            external_id: Some(1),
+            width: 48,
+            length: 48,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_random_seed(1);
+
+    // Two of the cheap stock pieces cost less than one of the large ones.
+    let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+    assert_eq!(solution.price, 20);
+
+    let solution = optimizer
+        .set_fitness_objective(FitnessObjective::MinimizeSheetCount)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    sanity_check_solution(&solution, 4);
+    assert_eq!(solution.stock_pieces.len(), 1);
+    assert_eq!(solution.price, 100);
+
+    for objective in [
+        FitnessObjective::MinimizeWaste,
+        FitnessObjective::MinimizeCutLength,
+        FitnessObjective::Weighted {
+            waste: 1.0,
+            sheet_count: 1.0,
+            price: 2.0,
+            cut_length: 0.5,
+        },
+    ] {
+        let solution = optimizer
+            .set_fitness_objective(objective)
+            .optimize_nested(|_| {})
+            .unwrap();
+        sanity_check_solution(&solution, 4);
+        assert!(solution.fitness > 0.0 && solution.fitness <= 1.0);
+    }
+}
+
+#[test]
+fn maximize_value() {
+    let cut_piece = |external_id, width, length, quantity, value| CutPiece {
+        quantity,
+        external_id: Some(external_id),
+        width,
+        length,
+        pattern_direction: PatternDirection::None,
+        can_rotate: false,
+        tag: None,
+        max_edge_distance: None,
+        stock_filter: StockFilter::Any,
+        value: Some(value),
+        min_grade: None,
+        edge_banding: Default::default(),
+        match_group: None,
+        material: None,
+        thickness: None,
+        max_quantity: None,
+    };
+
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            quantity: Some(1),
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(cut_piece(1, 48, 96, 1, 10))
+        .add_cut_piece(cut_piece(2, 48, 48, 2, 8))
+        .add_cut_piece(cut_piece(3, 100, 100, 1, 100))
+        .set_optimize_goal(OptimizeGoal::MaximizeValue)
+        .set_random_seed(1);
+
+    // The two cut pieces worth 8 are worth more than the one worth 10 that fills the stock
+    // piece on its own, and the one that's worth the most doesn't fit at all.
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        assert_eq!(solution.stock_pieces.len(), 1);
+        let external_ids: Vec<_> = solution.stock_pieces[0]
+            .cut_pieces
+            .iter()
+            .map(|cut_piece| cut_piece.external_id)
+            .collect();
+        assert_eq!(external_ids, vec![Some(2), Some(2)]);
+        assert_eq!(solution.placed_value, 16);
+        assert_eq!(solution.unplaced_value, 110);
+    }
+
+    // Without the goal, the cut pieces that don't fit are an error.
+    assert!(matches!(
+        optimizer
+            .set_optimize_goal(OptimizeGoal::PlaceAll)
+            .optimize_guillotine(|_| {}),
+        Err(Error::NoFitForCutPiece(..))
+    ));
+
+    // A single cut piece that doesn't fit gives an empty solution.
+    let solution = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_piece(cut_piece(3, 100, 100, 1, 100))
+        .set_optimize_goal(OptimizeGoal::MaximizeValue)
+        .optimize_guillotine(|_| {})
+        .unwrap();
+    assert!(solution.stock_pieces.is_empty());
+    assert_eq!(solution.unplaced_cut_pieces.len(), 1);
+    assert_eq!(solution.unplaced_cut_pieces[0].value, Some(100));
+}
+
+#[test]
+fn allow_partial() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_piece(StockPiece {
+            quantity: Some(1),
+            ..STOCK_PIECES[0].clone()
+        })
+        .add_cut_piece(CutPiece {
+            quantity: 5,
+            external_id: Some(1),
+            width: 48,
+            length: 30,
+            pattern_direction: PatternDirection::None,
+            can_rotate: false,
+            tag: None,
+            max_edge_distance: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: Default::default(),
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        })
+        .set_random_seed(1);
+
+    assert!(matches!(
+        optimizer.optimize_guillotine(|_| {}),
+        Err(Error::NoFitForCutPiece(..))
+    ));
+
+    // Only three of the cut pieces fit on the stock piece, and the rest are combined into one
+    // unplaced cut piece.
+    optimizer.set_allow_partial(true);
+    for solution in [
+        optimizer.optimize_guillotine(|_| {}).unwrap(),
+        optimizer.optimize_nested(|_| {}).unwrap(),
+    ] {
+        let placed: usize = solution
+            .stock_pieces
+            .iter()
+            .map(|stock_piece| stock_piece.cut_pieces.len())
+            .sum();
+        let unplaced: usize = solution
+            .unplaced_cut_pieces
+            .iter()
+            .map(|cut_piece| cut_piece.quantity)
+            .sum();
+        assert_eq!(solution.stock_pieces.len(), 1);
+        assert_eq!(placed, 3);
+        assert_eq!(solution.unplaced_cut_pieces.len(), 1);
+        assert_eq!(unplaced, 2);
+    }
+}
+
+#[test]
+fn guillotine_top_n() {
+    let mut optimizer = Optimizer::new();
+    optimizer
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_cut_width(1)
+        .set_random_seed(1);
+
+    let best = optimizer.optimize_guillotine(|_| {}).unwrap();
+    let solutions = optimizer.optimize_guillotine_top_n(3, |_| {}).unwrap();
+    assert!(!solutions.is_empty() && solutions.len() <= 3);
+    assert_eq!(solutions[0].fitness, best.fitness);
+    assert_eq!(solutions[0].price, best.price);
+
+    let num_cut_pieces = CUT_PIECES.iter().map(|p| p.quantity).sum();
+    for (i, solution) in solutions.iter().enumerate() {
+        sanity_check_solution(solution, num_cut_pieces);
+        for other in &solutions[..i] {
+            assert!(!solution.has_same_layout(other));
+        }
+    }
+}
+
+#[test]
+fn nested_top_n_zero() {
+    let solutions = Optimizer::new()
+        .add_stock_pieces(STOCK_PIECES.to_vec())
+        .add_cut_pieces(CUT_PIECES.to_vec())
+        .set_random_seed(1)
+        .optimize_nested_top_n(0, |_| {})
+        .unwrap();
+    assert!(solutions.is_empty());
+}
+
+#[cfg(feature = "audit-log")]
+#[test]
+fn audit_log_is_reproducible() {
+    use std::sync::Mutex;
+
+    let audit_log = || {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let log = entries.clone();
+        Optimizer::new()
+            .add_stock_pieces(STOCK_PIECES.to_vec())
+            .add_cut_pieces(CUT_PIECES.to_vec())
+            .set_cut_width(1)
+            .set_random_seed(1)
+            .set_audit_log_callback(move |entry| log.lock().unwrap().push(entry.to_string()))
+            .optimize_guillotine(|_| {})
+            .unwrap();
+        Arc::try_unwrap(entries).unwrap().into_inner().unwrap()
+    };
+
+    let entries = audit_log();
+    assert_eq!(entries[0], "run 0");
+    assert!(entries.iter().any(|entry| entry.starts_with("heuristic ")));
+    assert!(entries.iter().any(|entry| entry.starts_with("stock ")));
+    assert!(entries.iter().any(|entry| entry.starts_with("epoch ")));
+    assert_eq!(entries, audit_log());
+}
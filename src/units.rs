@@ -0,0 +1,427 @@
+//! Units of length for the sizes given to the optimizer, enabled by the `units` feature.
+//!
+//! The optimizer works with whole numbers, and doesn't know what they measure, so a stock list in
+//! millimeters and a cut list in inches would be optimized together without an error.
+//! `UnitOptimizer` only takes sizes in one `LengthUnit`, such as `Millimeters` or `Inches`, and
+//! converts them to the whole numbers the optimizer works with, so sizes in different units can't
+//! be mixed.
+
+use crate::{
+    CutPiece, EdgeBanding, FitnessObjective, MatchGroup, OptimizeGoal, Optimizer, PatternDirection,
+    ProgressControl, Solution, StockFilter, StockPiece, Trim,
+};
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// A unit of length that sizes can be given to `UnitOptimizer` in.
+pub trait LengthUnit: Copy + fmt::Debug {
+    /// Returns the length as the whole number the optimizer works with.
+    fn to_internal(self) -> usize;
+
+    /// Returns the length of the whole number the optimizer works with, such as the location or
+    /// size of a cut piece in a solution.
+    fn from_internal(value: usize) -> Self;
+}
+
+/// A length in millimeters. The optimizer works with whole millimeters.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Millimeters(pub usize);
+
+impl LengthUnit for Millimeters {
+    fn to_internal(self) -> usize {
+        self.0
+    }
+
+    fn from_internal(value: usize) -> Self {
+        Millimeters(value)
+    }
+}
+
+impl fmt::Display for Millimeters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} mm", self.0)
+    }
+}
+
+/// A length in inches. The optimizer works with hundredths of an inch, so that's what the length
+/// is stored as.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Inches(usize);
+
+impl Inches {
+    /// Returns the length of this many inches, rounded to the nearest hundredth, or an error if
+    /// it's negative, isn't finite, or is too long to store.
+    pub fn new(inches: f64) -> Result<Self, InvalidLength> {
+        let hundredths = (inches * 100.0).round();
+        if !hundredths.is_finite() || hundredths < 0.0 || hundredths > usize::MAX as f64 {
+            return Err(InvalidLength(inches));
+        }
+        Ok(Inches(hundredths as usize))
+    }
+
+    /// Returns the length of this many hundredths of an inch.
+    pub fn from_hundredths(hundredths: usize) -> Self {
+        Inches(hundredths)
+    }
+
+    /// Returns the length in hundredths of an inch.
+    pub fn hundredths(self) -> usize {
+        self.0
+    }
+
+    /// Returns the length in inches.
+    pub fn inches(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+}
+
+impl LengthUnit for Inches {
+    fn to_internal(self) -> usize {
+        self.hundredths()
+    }
+
+    fn from_internal(value: usize) -> Self {
+        Inches::from_hundredths(value)
+    }
+}
+
+impl fmt::Display for Inches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\"", self.inches())
+    }
+}
+
+/// A length that can't be given to the optimizer, because it's negative, isn't finite, or is too
+/// long to store.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InvalidLength(pub f64);
+
+impl fmt::Display for InvalidLength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid length: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidLength {}
+
+/// A stock piece for `UnitOptimizer::add_stock_piece`, without a width and length, which are
+/// given in the unit of the optimizer instead. Sizes in other fields of `StockPiece`, such as the
+/// trim and seams, can't be given in the unit, so they're left out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StockTemplate {
+    /// Pattern direction of the stock piece. See `StockPiece::pattern_direction`.
+    pub pattern_direction: PatternDirection,
+
+    /// Price to use to optimize for price when not all stock pieces are the same price. See
+    /// `StockPiece::price`.
+    pub price: usize,
+
+    /// Quantity of this stock piece available for optimization. `None` means infinite quantity.
+    pub quantity: Option<usize>,
+
+    /// ID to be used by the caller to match up result stock pieces with the original stock piece.
+    pub external_id: Option<usize>,
+
+    /// Grade of the stock piece. See `StockPiece::grade`.
+    pub grade: Option<char>,
+
+    /// Material of the stock piece. See `StockPiece::material`.
+    pub material: Option<usize>,
+
+    /// Thickness of the stock piece. See `StockPiece::thickness`.
+    pub thickness: Option<usize>,
+
+    /// Whether the stock piece must be used up before other stock pieces. See
+    /// `StockPiece::must_use`.
+    pub must_use: bool,
+}
+
+/// A cut piece for `UnitOptimizer::add_cut_piece`, without a width and length, which are given in
+/// the unit of the optimizer instead. The maximum edge distance of `CutPiece` is a size that can't
+/// be given in the unit, so it's left out.
+#[derive(Clone, Debug)]
+pub struct CutTemplate {
+    /// Quantity of this cut piece.
+    pub quantity: usize,
+
+    /// ID to be used by the caller to match up result cut pieces with the original cut piece.
+    pub external_id: Option<usize>,
+
+    /// Pattern direction of this cut piece.
+    pub pattern_direction: PatternDirection,
+
+    /// Whether or not the optimizer is allowed to rotate this piece to make it fit.
+    pub can_rotate: bool,
+
+    /// Arbitrary text to be carried through to the result cut pieces. See `CutPiece::tag`.
+    pub tag: Option<String>,
+
+    /// Which stock pieces this cut piece may be cut from. See `CutPiece::stock_filter`.
+    pub stock_filter: StockFilter,
+
+    /// Value of each copy of this cut piece. See `CutPiece::value`.
+    pub value: Option<usize>,
+
+    /// Lowest grade of stock piece this cut piece may be cut from. See `CutPiece::min_grade`.
+    pub min_grade: Option<char>,
+
+    /// Edges of this cut piece that get edge banding. See `CutPiece::edge_banding`.
+    pub edge_banding: EdgeBanding,
+
+    /// Match group of this cut piece. See `CutPiece::match_group`.
+    pub match_group: Option<MatchGroup>,
+
+    /// Material this cut piece is cut from. See `CutPiece::material`.
+    pub material: Option<usize>,
+
+    /// Thickness of this cut piece. See `CutPiece::thickness`.
+    pub thickness: Option<usize>,
+
+    /// Most copies of this cut piece to cut. See `CutPiece::max_quantity`.
+    pub max_quantity: Option<usize>,
+}
+
+/// Optimizer that takes the sizes of the stock pieces and cut pieces in the unit `U`. The sizes
+/// in the solution it finds are the whole numbers the optimizer works with, which can be turned
+/// back into lengths with `LengthUnit::from_internal`.
+///
+/// Only the options that don't take sizes can be set, besides the cut width, so every size the
+/// optimizer is given is in `U`.
+pub struct UnitOptimizer<U> {
+    optimizer: Optimizer,
+    unit: PhantomData<U>,
+}
+
+impl<U> Default for UnitOptimizer<U>
+where
+    U: LengthUnit,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U> UnitOptimizer<U>
+where
+    U: LengthUnit,
+{
+    /// Create a new optimizer that takes sizes in the unit `U`.
+    pub fn new() -> Self {
+        Self {
+            optimizer: Optimizer::new(),
+            unit: PhantomData,
+        }
+    }
+
+    /// Add a stock piece that the optimizer can use to optimize cut pieces, with its `width` and
+    /// `length` in `U`.
+    pub fn add_stock_piece(
+        &mut self,
+        width: U,
+        length: U,
+        stock_piece: StockTemplate,
+    ) -> &mut Self {
+        self.optimizer.add_stock_piece(StockPiece {
+            width: width.to_internal(),
+            length: length.to_internal(),
+            pattern_direction: stock_piece.pattern_direction,
+            price: stock_piece.price,
+            area_price: None,
+            quantity: stock_piece.quantity,
+            external_id: stock_piece.external_id,
+            seams: Vec::new(),
+            trim: Trim::default(),
+            min_leftover_area: None,
+            source: None,
+            grade: stock_piece.grade,
+            material: stock_piece.material,
+            thickness: stock_piece.thickness,
+            fixed_pieces: Vec::new(),
+            nominal_size: None,
+            must_use: stock_piece.must_use,
+        });
+        self
+    }
+
+    /// Add a desired cut piece that you need cut from a stock piece, with its `width` and
+    /// `length` in `U`.
+    pub fn add_cut_piece(&mut self, width: U, length: U, cut_piece: CutTemplate) -> &mut Self {
+        self.optimizer.add_cut_piece(CutPiece {
+            quantity: cut_piece.quantity,
+            external_id: cut_piece.external_id,
+            width: width.to_internal(),
+            length: length.to_internal(),
+            pattern_direction: cut_piece.pattern_direction,
+            can_rotate: cut_piece.can_rotate,
+            tag: cut_piece.tag,
+            max_edge_distance: None,
+            stock_filter: cut_piece.stock_filter,
+            value: cut_piece.value,
+            min_grade: cut_piece.min_grade,
+            edge_banding: cut_piece.edge_banding,
+            match_group: cut_piece.match_group,
+            material: cut_piece.material,
+            thickness: cut_piece.thickness,
+            max_quantity: cut_piece.max_quantity,
+        });
+        self
+    }
+
+    /// Set the width of the cut to use between cut pieces, in `U`.
+    pub fn set_cut_width(&mut self, cut_width: U) -> &mut Self {
+        self.optimizer.set_cut_width(cut_width.to_internal());
+        self
+    }
+
+    /// Set different widths for the rip cuts and the crosscuts, in `U`. See
+    /// `Optimizer::set_cut_widths`.
+    pub fn set_cut_widths(&mut self, rip: U, crosscut: U) -> &mut Self {
+        self.optimizer
+            .set_cut_widths(rip.to_internal(), crosscut.to_internal());
+        self
+    }
+
+    /// Set the random seed used by the genetic algorithms in the optimizer. See
+    /// `Optimizer::set_random_seed`.
+    pub fn set_random_seed(&mut self, seed: u64) -> &mut Self {
+        self.optimizer.set_random_seed(seed);
+        self
+    }
+
+    /// Set whether the optimizer should allow mixed sized stock pieces in the results. See
+    /// `Optimizer::allow_mixed_stock_sizes`.
+    pub fn allow_mixed_stock_sizes(&mut self, allow: bool) -> &mut Self {
+        self.optimizer.allow_mixed_stock_sizes(allow);
+        self
+    }
+
+    /// Set the most stock pieces a solution can use in total. See
+    /// `Optimizer::set_max_stock_pieces`.
+    pub fn set_max_stock_pieces(&mut self, max: usize) -> &mut Self {
+        self.optimizer.set_max_stock_pieces(max);
+        self
+    }
+
+    /// Set what the fitness of the layouts measures. See `Optimizer::set_fitness_objective`.
+    pub fn set_fitness_objective(&mut self, objective: FitnessObjective) -> &mut Self {
+        self.optimizer.set_fitness_objective(objective);
+        self
+    }
+
+    /// Set whether every cut piece needs to be placed. See `Optimizer::set_optimize_goal`.
+    pub fn set_optimize_goal(&mut self, goal: OptimizeGoal) -> &mut Self {
+        self.optimizer.set_optimize_goal(goal);
+        self
+    }
+
+    /// Set the maximum time the optimizer should spend optimizing. See
+    /// `Optimizer::set_time_limit`.
+    pub fn set_time_limit(&mut self, time_limit: Duration) -> &mut Self {
+        self.optimizer.set_time_limit(time_limit);
+        self
+    }
+
+    /// Optimize with guillotine cuts. See `Optimizer::optimize_guillotine`.
+    pub fn optimize_guillotine<F, R>(&self, progress_callback: F) -> crate::Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimizer.optimize_guillotine(progress_callback)
+    }
+
+    /// Optimize without the requirement of guillotine cuts. See `Optimizer::optimize_nested`.
+    pub fn optimize_nested<F, R>(&self, progress_callback: F) -> crate::Result<Solution>
+    where
+        F: Fn(f64) -> R,
+        R: ProgressControl,
+    {
+        self.optimizer.optimize_nested(progress_callback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock_piece() -> StockTemplate {
+        StockTemplate {
+            pattern_direction: PatternDirection::None,
+            price: 0,
+            quantity: None,
+            external_id: None,
+            grade: None,
+            material: None,
+            thickness: None,
+            must_use: false,
+        }
+    }
+
+    fn cut_piece(external_id: usize) -> CutTemplate {
+        CutTemplate {
+            quantity: 1,
+            external_id: Some(external_id),
+            pattern_direction: PatternDirection::None,
+            can_rotate: true,
+            tag: None,
+            stock_filter: StockFilter::Any,
+            value: None,
+            min_grade: None,
+            edge_banding: EdgeBanding::NONE,
+            match_group: None,
+            material: None,
+            thickness: None,
+            max_quantity: None,
+        }
+    }
+
+    fn inches(inches: f64) -> Inches {
+        Inches::new(inches).unwrap()
+    }
+
+    #[test]
+    fn inches_to_hundredths() {
+        assert_eq!(inches(23.75).to_internal(), 2375);
+        assert_eq!(inches(0.125).to_internal(), 13);
+        assert_eq!(inches(0.0).to_internal(), 0);
+        assert_eq!(Inches::from_internal(4800), inches(48.0));
+        assert_eq!(inches(0.5).to_string(), "0.5\"");
+        assert_eq!(Millimeters(1220).to_string(), "1220 mm");
+    }
+
+    #[test]
+    fn invalid_inches() {
+        assert_eq!(Inches::new(-1.0), Err(InvalidLength(-1.0)));
+        assert!(Inches::new(f64::NAN).is_err());
+        assert_eq!(
+            Inches::new(f64::INFINITY),
+            Err(InvalidLength(f64::INFINITY))
+        );
+        assert!(Inches::new(f64::MAX).is_err());
+    }
+
+    #[test]
+    fn optimize_in_inches() {
+        let mut optimizer = UnitOptimizer::new();
+        optimizer
+            .add_stock_piece(inches(48.0), inches(96.0), stock_piece())
+            .add_cut_piece(inches(23.875), inches(30.5), cut_piece(1))
+            .add_cut_piece(inches(23.875), inches(30.5), cut_piece(2))
+            .set_cut_width(inches(0.125))
+            .set_random_seed(1);
+        let solution = optimizer.optimize_guillotine(|_| {}).unwrap();
+
+        let stock_piece = &solution.stock_pieces[0];
+        assert_eq!(Inches::from_internal(stock_piece.width), inches(48.0));
+        assert_eq!(Inches::from_internal(stock_piece.length), inches(96.0));
+        for cut_piece in &stock_piece.cut_pieces {
+            let size = [
+                Inches::from_internal(cut_piece.width),
+                Inches::from_internal(cut_piece.length),
+            ];
+            assert!(size == [inches(23.88), inches(30.5)] || size == [inches(30.5), inches(23.88)]);
+        }
+    }
+}